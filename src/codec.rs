@@ -63,6 +63,12 @@ pub trait Codec: Send + Sync {
         data: &'input mut [u8],
         ingest_ns: u64,
     ) -> Result<Option<Value<'input>>>;
+
+    /// Restores `data` after `decode` failed on it, so it can be reported as it was received.
+    ///
+    /// Only codecs decoding in place need to implement this.
+    fn restore(&self, _data: &mut [u8]) {}
+
     /// Encodes a Value into a binary
     ///
     /// # Errors
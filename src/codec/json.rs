@@ -90,6 +90,12 @@ impl<S: Sorting> Codec for Json<S> {
         .map(Some)
         .map_err(Error::from)
     }
+    fn restore(&self, data: &mut [u8]) {
+        // strings are unescaped in place, but the input buffer holds a copy of the original input
+        if self.input_buffer.len() == data.len() {
+            data.copy_from_slice(&self.input_buffer);
+        }
+    }
     fn encode(&self, data: &Value) -> Result<Vec<u8>> {
         if S::SORTED {
             Ok(sorted_serialize(data)?.into_bytes())
@@ -136,6 +142,17 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn restore() {
+        let mut codec: Json<Unsorted> = Json::default();
+        // the escaped string is unescaped in place before the error is hit
+        let original = br#"{"snot": "bad\nger", "badger": }"#.to_vec();
+        let mut data = original.clone();
+        assert!(codec.decode(&mut data, 42).is_err());
+        codec.restore(&mut data);
+        assert_eq!(original, data);
+    }
+
     #[test]
     fn test_json_codec() -> Result<()> {
         let seed = literal!({ "snot": "badger" });
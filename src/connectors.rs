@@ -22,8 +22,9 @@ pub(crate) mod sink;
 /// source parts
 pub(crate) mod source;
 
+/// connector utilities
 #[macro_use]
-pub(crate) mod utils;
+pub mod utils;
 
 mod google;
 #[cfg(test)]
@@ -51,7 +52,7 @@ use futures::Future;
 use halfbrown::HashMap;
 use std::{fmt::Display, sync::atomic::Ordering, time::Duration};
 use tremor_common::ids::{ConnectorId, ConnectorIdGen, SourceId};
-use tremor_common::ports::{ERR, IN, OUT};
+use tremor_common::ports::{CONNECTIONS, ERR, IN, OUT};
 use tremor_pipeline::METRICS_CHANNEL;
 use tremor_script::ast::DeployEndpoint;
 use tremor_value::Value;
@@ -113,14 +114,24 @@ impl Addr {
         Ok(())
     }
 
-    fn has_source(&self) -> bool {
+    pub(crate) fn has_source(&self) -> bool {
         self.source.is_some()
     }
 
+    /// the address of the source part of the connector, if it has one
+    pub(crate) fn source(&self) -> Option<&SourceAddr> {
+        self.source.as_ref()
+    }
+
     fn has_sink(&self) -> bool {
         self.sink.is_some()
     }
 
+    /// number of messages queued for the sink part of the connector, not yet handled
+    pub(crate) fn queued(&self) -> usize {
+        self.sink.as_ref().map_or(0, |sink| sink.addr.len())
+    }
+
     /// stops the connector
     ///
     /// # Errors
@@ -354,6 +365,8 @@ impl Context for ConnectorContext {
 pub struct StatusReport {
     /// connector instance url
     pub(crate) alias: String,
+    /// type of the connector
+    pub(crate) connector_type: ConnectorType,
     /// state of the connector
     pub status: State,
     /// current connectivity
@@ -536,6 +549,7 @@ async fn connector_task(
                     if let Err(e) = tx
                         .send(StatusReport {
                             alias: alias.clone(),
+                            connector_type: ctx.connector_type.clone(),
                             status: connector_state,
                             connectivity,
                             pipelines: pipes,
@@ -948,9 +962,9 @@ impl Drainage {
 }
 
 /// describes connectivity state of the connector
-#[derive(Debug, Serialize, Copy, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
-pub(crate) enum Connectivity {
+pub enum Connectivity {
     /// connector is connected
     Connected,
     /// connector is disconnected
@@ -960,7 +974,10 @@ pub(crate) enum Connectivity {
 const IN_PORTS: [Cow<'static, str>; 1] = [IN];
 const IN_PORTS_REF: &[Cow<'static, str>; 1] = &IN_PORTS;
 const OUT_PORTS: [Cow<'static, str>; 2] = [OUT, ERR];
-const OUT_PORTS_REF: &[Cow<'static, str>; 2] = &OUT_PORTS;
+pub(crate) const OUT_PORTS_REF: &[Cow<'static, str>; 2] = &OUT_PORTS;
+const LIFECYCLE_OUT_PORTS: [Cow<'static, str>; 3] = [OUT, ERR, CONNECTIONS];
+/// Output ports of connectors emitting connection lifecycle events
+pub(crate) const LIFECYCLE_OUT_PORTS_REF: &[Cow<'static, str>; 3] = &LIFECYCLE_OUT_PORTS;
 
 /// A Connector connects the tremor runtime to the outside world.
 ///
@@ -1198,6 +1215,9 @@ pub(crate) fn builtin_connector_types() -> Vec<Box<dyn ConnectorBuilder + 'stati
         Box::new(impls::otel::server::Builder::default()),
         Box::new(impls::gbq::writer::Builder::default()),
         Box::new(impls::gpubsub::consumer::Builder::default()),
+        Box::new(impls::sse::client::Builder::default()),
+        Box::new(impls::grpc::client::Builder::default()),
+        Box::new(impls::grpc::server::Builder::default()),
     ]
 }
 
@@ -31,6 +31,8 @@ pub(crate) mod file;
 /// Google Big Query
 pub(crate) mod gbq;
 pub(crate) mod gpubsub;
+/// gRPC
+pub(crate) mod grpc;
 /// HTTP
 pub(crate) mod http;
 /// Kafka consumer and producer
@@ -47,6 +49,8 @@ pub(crate) mod null;
 pub(crate) mod otel;
 /// AWS S3 connectors
 pub(crate) mod s3;
+/// Server-Sent Events
+pub(crate) mod sse;
 /// std streams connector (stdout, stderr, stdin)
 pub(crate) mod stdio;
 /// tcp server and client connector impls
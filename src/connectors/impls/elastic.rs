@@ -37,6 +37,7 @@ use elasticsearch::{
     Bulk, BulkDeleteOperation, BulkOperation, BulkOperations, BulkParts, Elasticsearch,
 };
 use halfbrown::HashMap;
+use reqwest::StatusCode;
 use tremor_common::time::nanotime;
 use tremor_script::utils::sorted_serialize;
 use tremor_value::value::StaticValue;
@@ -140,7 +141,8 @@ impl ConnectorBuilder for Builder {
                         Some(Credentials::ApiKey(id.clone(), api_key.clone()))
                     }
                     // Gcp Auth is handled in sink connect
-                    Auth::Gcp | Auth::None => None,
+                    // OAuth2 tokens expire, they are added to each request in `on_event`
+                    Auth::Gcp | Auth::OAuth2(_) | Auth::None => None,
                 }
             };
             let cert_validation =
@@ -394,6 +396,7 @@ impl Sink for ElasticSink {
             let mut origin_uri = self.origin_uri.clone();
             origin_uri.host = client.cluster_name;
             let default_index = self.config.index.clone();
+            let auth = self.config.auth.clone();
             let task_ctx = ctx.clone();
             async_std::task::Builder::new()
                 .name(format!(
@@ -403,25 +406,47 @@ impl Sink for ElasticSink {
                 ))
                 .spawn::<_, Result<()>>(async move {
                     let r: Result<Value> = (|| async {
-                        // build bulk request (we can't do that in a separate function)
-                        let mut ops = BulkOperations::new();
-                        // per request options - extract from event metadata (ignoring batched)
-                        let event_es_meta = ESMeta::new(event.data.suffix().meta());
-
-                        for (data, meta) in event.value_meta_iter() {
-                            ESMeta::new(meta).insert_op(data, &mut ops)?;
-                        }
+                        let mut auth_header = if let Auth::OAuth2(_) = auth {
+                            auth.header_value().await?
+                        } else {
+                            None
+                        };
+                        let mut retried = false;
+                        let response = loop {
+                            // build bulk request (we can't do that in a separate function)
+                            let mut ops = BulkOperations::new();
+                            // per request options - extract from event metadata (ignoring batched)
+                            let event_es_meta = ESMeta::new(event.data.suffix().meta());
+
+                            for (data, meta) in event.value_meta_iter() {
+                                ESMeta::new(meta).insert_op(data, &mut ops)?;
+                            }
 
-                        let parts = event_es_meta.parts(default_index.as_deref());
+                            let parts = event_es_meta.parts(default_index.as_deref());
 
-                        // apply request scoped options
-                        let bulk =
-                            event_es_meta.apply_to(client.client.bulk(parts).body(vec![ops]))?;
+                            // apply request scoped options
+                            let mut bulk = event_es_meta
+                                .apply_to(client.client.bulk(parts).body(vec![ops]))?;
+                            if let Some(auth_header) = auth_header.as_deref() {
+                                bulk = bulk.header(
+                                    reqwest::header::AUTHORIZATION,
+                                    reqwest::header::HeaderValue::from_str(auth_header)?,
+                                );
+                            }
 
-                        let response = bulk
-                            .send()
-                            .await
-                            .and_then(Response::error_for_status_code)?;
+                            let response = bulk.send().await?;
+                            // retry once with a fresh token if the current one got rejected
+                            if let (false, StatusCode::UNAUTHORIZED, Some(rejected)) =
+                                (retried, response.status_code(), auth_header.as_deref())
+                            {
+                                if let Some(refreshed) = auth.refresh(rejected).await? {
+                                    auth_header = Some(refreshed);
+                                    retried = true;
+                                    continue;
+                                }
+                            }
+                            break response.error_for_status_code()?;
+                        };
                         let value = response.json::<StaticValue>().await?;
                         Ok(value.into_value())
                     })()
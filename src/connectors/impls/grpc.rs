@@ -0,0 +1,510 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! gRPC connectors for arbitrary services
+//!
+//! The messages of the called method are described by a protobuf descriptor
+//! set, as generated by `protoc --include_imports --descriptor_set_out=<file>`,
+//! and mapped to and from tremor values following the protobuf JSON mapping.
+//! Calls are framed as described in
+//! <https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md> and sent
+//! over HTTP/2. Call metadata and status are available as `$grpc` metadata.
+//! Received messages larger than `max_message_size` (4MB by default) fail the
+//! call with `RESOURCE_EXHAUSTED`.
+
+pub(crate) mod client;
+pub(crate) mod server;
+
+use crate::connectors::prelude::*;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor};
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// content type of gRPC requests and responses
+pub(crate) const CONTENT_TYPE: &str = "application/grpc";
+/// every message is prefixed with a compression flag and its length
+const PREFIX_LEN: usize = 5;
+/// the default maximum size of a received message, as in the reference implementations
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+pub(crate) fn default_max_message_size() -> usize {
+    DEFAULT_MAX_MESSAGE_SIZE
+}
+
+pub(crate) struct GrpcDefaults;
+impl Defaults for GrpcDefaults {
+    const SCHEME: &'static str = "http";
+    const HOST: &'static str = "localhost";
+    const PORT: u16 = 50051;
+}
+
+/// gRPC status codes
+pub(crate) mod code {
+    pub(crate) const OK: u32 = 0;
+    pub(crate) const CANCELLED: u32 = 1;
+    pub(crate) const UNKNOWN: u32 = 2;
+    pub(crate) const INVALID_ARGUMENT: u32 = 3;
+    pub(crate) const DEADLINE_EXCEEDED: u32 = 4;
+    pub(crate) const PERMISSION_DENIED: u32 = 7;
+    pub(crate) const RESOURCE_EXHAUSTED: u32 = 8;
+    pub(crate) const UNIMPLEMENTED: u32 = 12;
+    pub(crate) const INTERNAL: u32 = 13;
+    pub(crate) const UNAVAILABLE: u32 = 14;
+    pub(crate) const UNAUTHENTICATED: u32 = 16;
+}
+
+/// The method a connector calls or serves
+#[derive(Debug, Clone)]
+pub(crate) struct Method {
+    descriptor: MethodDescriptor,
+}
+
+impl Method {
+    /// Loads `service`/`method` from the descriptor set at `path`
+    pub(crate) async fn load(path: &Path, service: &str, method: &str) -> Result<Self> {
+        let bytes = async_std::fs::read(path).await?;
+        let pool = DescriptorPool::decode(bytes.as_slice())?;
+        let service_descriptor = pool.get_service_by_name(service).ok_or_else(|| {
+            Error::from(format!(
+                "Service `{service}` not found in descriptor set {}",
+                path.display()
+            ))
+        })?;
+        let descriptor = service_descriptor
+            .methods()
+            .find(|m| m.name() == method)
+            .ok_or_else(|| Error::from(format!("Method `{method}` not found in `{service}`")))?;
+        Ok(Self { descriptor })
+    }
+
+    /// The request path, `/<package>.<service>/<method>`
+    pub(crate) fn path(&self) -> String {
+        format!(
+            "/{}/{}",
+            self.descriptor.parent_service().full_name(),
+            self.descriptor.name()
+        )
+    }
+
+    /// Whether a call carries a stream of requests
+    pub(crate) fn client_streaming(&self) -> bool {
+        self.descriptor.is_client_streaming()
+    }
+
+    /// Whether a call carries a stream of responses
+    pub(crate) fn server_streaming(&self) -> bool {
+        self.descriptor.is_server_streaming()
+    }
+
+    pub(crate) fn encode_request(&self, value: &Value) -> Result<Bytes> {
+        encode(self.descriptor.input(), value)
+    }
+
+    pub(crate) fn decode_request(&self, message: &[u8]) -> Result<Value<'static>> {
+        decode(self.descriptor.input(), message)
+    }
+
+    pub(crate) fn encode_response(&self, value: &Value) -> Result<Bytes> {
+        encode(self.descriptor.output(), value)
+    }
+
+    pub(crate) fn decode_response(&self, message: &[u8]) -> Result<Value<'static>> {
+        decode(self.descriptor.output(), message)
+    }
+}
+
+/// Encodes `value` as a length prefixed message
+fn encode(descriptor: MessageDescriptor, value: &Value) -> Result<Bytes> {
+    let message = DynamicMessage::deserialize(descriptor, value.clone())?;
+    let encoded = message.encode_to_vec();
+    let mut framed = BytesMut::with_capacity(PREFIX_LEN + encoded.len());
+    framed.put_u8(0);
+    framed.put_u32(u32::try_from(encoded.len())?);
+    framed.put_slice(&encoded);
+    Ok(framed.freeze())
+}
+
+fn decode(descriptor: MessageDescriptor, message: &[u8]) -> Result<Value<'static>> {
+    let message = DynamicMessage::decode(descriptor, message)?;
+    Ok(tremor_value::to_value(&message)?)
+}
+
+/// Splits a received body into messages
+#[derive(Debug)]
+pub(crate) struct Deframer {
+    buf: BytesMut,
+    max_message_size: usize,
+}
+
+impl Deframer {
+    pub(crate) fn new(max_message_size: usize) -> Self {
+        Self {
+            buf: BytesMut::new(),
+            max_message_size,
+        }
+    }
+
+    pub(crate) fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// The next complete message, if any, or the status to fail the call with
+    pub(crate) fn message(&mut self) -> std::result::Result<Option<Bytes>, Status> {
+        if self.buf.len() < PREFIX_LEN {
+            return Ok(None);
+        }
+        // we never announce an encoding, so peers must not compress
+        if self.buf[0] != 0 {
+            return Err(Status::new(
+                code::INTERNAL,
+                "Received a compressed gRPC message",
+            ));
+        }
+        let len = u32::from_be_bytes([self.buf[1], self.buf[2], self.buf[3], self.buf[4]]);
+        // checked before the message is buffered
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= self.max_message_size)
+            .ok_or_else(|| {
+                Status::new(
+                    code::RESOURCE_EXHAUSTED,
+                    format!(
+                        "Received message larger than max ({len} vs. {})",
+                        self.max_message_size
+                    ),
+                )
+            })?;
+        if self.buf.len() < PREFIX_LEN + len {
+            return Ok(None);
+        }
+        self.buf.advance(PREFIX_LEN);
+        Ok(Some(self.buf.split_to(len).freeze()))
+    }
+}
+
+/// Status a call ended with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Status {
+    pub(crate) code: u32,
+    pub(crate) message: Option<String>,
+}
+
+impl Status {
+    pub(crate) fn ok() -> Self {
+        Self {
+            code: code::OK,
+            message: None,
+        }
+    }
+
+    pub(crate) fn new(code: u32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: Some(message.into()),
+        }
+    }
+
+    pub(crate) fn is_ok(&self) -> bool {
+        self.code == code::OK
+    }
+
+    /// Status of a response that was rejected on the HTTP level
+    pub(crate) fn from_http(status: hyper::StatusCode) -> Self {
+        let code = match status.as_u16() {
+            400 => code::INTERNAL,
+            401 => code::UNAUTHENTICATED,
+            403 => code::PERMISSION_DENIED,
+            404 => code::UNIMPLEMENTED,
+            429 | 502 | 503 | 504 => code::UNAVAILABLE,
+            _ => code::UNKNOWN,
+        };
+        Self::new(code, format!("HTTP status {status}"))
+    }
+
+    /// Reads the status from response trailers, or the headers of a
+    /// trailers-only response
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let code = headers.get("grpc-status")?.to_str().ok()?.parse().ok()?;
+        let message = headers
+            .get("grpc-message")
+            .and_then(|m| m.to_str().ok())
+            .map(percent_decode);
+        Some(Self { code, message })
+    }
+
+    pub(crate) fn trailers(&self) -> HeaderMap {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from(self.code));
+        if let Some(message) = &self.message {
+            // the encoded message is always a valid header value
+            if let Ok(message) = HeaderValue::from_str(&percent_encode(message)) {
+                trailers.insert("grpc-message", message);
+            }
+        }
+        trailers
+    }
+
+    /// A trailers-only response, ending a call without any messages
+    pub(crate) fn response(&self) -> hyper::Response<hyper::Body> {
+        let mut response = hyper::Response::new(hyper::Body::empty());
+        let headers = response.headers_mut();
+        headers.insert(
+            hyper::header::CONTENT_TYPE,
+            HeaderValue::from_static(CONTENT_TYPE),
+        );
+        headers.extend(self.trailers());
+        response
+    }
+
+    pub(crate) fn value(&self) -> Value<'static> {
+        let mut value = literal!({ "status": self.code });
+        if let Some(message) = &self.message {
+            value.try_insert("message", message.clone());
+        }
+        value
+    }
+}
+
+/// Parses a `grpc-timeout` header value, e.g. `100m` for 100 milliseconds
+pub(crate) fn parse_timeout(value: &str) -> Option<Duration> {
+    // at most 8 digits followed by the unit
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    if !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 3600)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+/// Formats a `grpc-timeout` header value in the most precise unit that fits,
+/// rounding up
+pub(crate) fn format_timeout(timeout: Duration) -> String {
+    let nanos = timeout.as_nanos();
+    for (unit, nanos_per_unit) in [
+        ("n", 1),
+        ("u", 1_000),
+        ("m", 1_000_000),
+        ("S", 1_000_000_000),
+        ("M", 60_000_000_000),
+        ("H", 3_600_000_000_000),
+    ] {
+        let amount = (nanos + nanos_per_unit - 1) / nanos_per_unit;
+        if amount < 100_000_000 {
+            return format!("{amount}{unit}");
+        }
+    }
+    "99999999H".to_string()
+}
+
+/// Headers that are part of the protocol and not of the call metadata
+fn is_reserved(name: &str) -> bool {
+    name.starts_with("grpc-") || matches!(name, "content-type" | "content-length" | "te")
+}
+
+/// The custom metadata of a call
+pub(crate) fn metadata_to_value(headers: &HeaderMap) -> Value<'static> {
+    let mut metadata = Value::object_with_capacity(headers.keys_len());
+    for name in headers.keys().filter(|name| !is_reserved(name.as_str())) {
+        // binary metadata is kept base64 encoded, as it is sent
+        let values: Vec<Value<'static>> = headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .map(|v| Value::from(v.to_string()))
+            .collect();
+        metadata.try_insert(name.to_string(), values);
+    }
+    metadata
+}
+
+/// Adds the custom metadata from `$grpc.metadata` to `headers`
+pub(crate) fn insert_metadata(headers: &mut HeaderMap, metadata: Option<&Value>) -> Result<()> {
+    if let Some(metadata) = metadata.as_object() {
+        for (name, values) in metadata {
+            let name = HeaderName::from_bytes(name.as_bytes())?;
+            if is_reserved(name.as_str()) {
+                continue;
+            }
+            if let Some(values) = values.as_array() {
+                for value in values.iter().filter_map(ValueAccess::as_str) {
+                    headers.append(name.clone(), HeaderValue::from_str(value)?);
+                }
+            } else if let Some(value) = values.as_str() {
+                headers.append(name, HeaderValue::from_str(value)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `grpc-message` is percent encoded
+fn percent_encode(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+    for b in message.bytes() {
+        if (b' '..=b'~').contains(&b) && b != b'%' {
+            encoded.push(char::from(b));
+        } else {
+            let _ = write!(encoded, "%{b:02X}");
+        }
+    }
+    encoded
+}
+
+fn percent_decode(message: &str) -> String {
+    let bytes = message.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(b) = escaped {
+            decoded.push(b);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deframe() {
+        let mut deframer = Deframer::new(3);
+        deframer.push(&[0, 0, 0, 0, 3, 1, 2]);
+        assert_eq!(Ok(None), deframer.message());
+        deframer.push(&[3, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(Ok(Some(Bytes::from_static(&[1, 2, 3]))), deframer.message());
+        assert_eq!(Ok(Some(Bytes::new())), deframer.message());
+        assert_eq!(Ok(None), deframer.message());
+        deframer.push(&[1, 0, 0, 0, 0]);
+        assert_eq!(
+            Err(code::INTERNAL),
+            deframer.message().map_err(|status| status.code)
+        );
+
+        // messages above the limit are rejected as soon as their length is known
+        let mut deframer = Deframer::new(3);
+        deframer.push(&[0, 0, 0, 0, 4]);
+        assert_eq!(
+            Err(code::RESOURCE_EXHAUSTED),
+            deframer.message().map_err(|status| status.code)
+        );
+    }
+
+    #[test]
+    fn timeouts() {
+        assert_eq!(Some(Duration::from_millis(100)), parse_timeout("100m"));
+        assert_eq!(Some(Duration::from_secs(7200)), parse_timeout("2H"));
+        assert_eq!(Some(Duration::from_nanos(1)), parse_timeout("1n"));
+        assert_eq!(None, parse_timeout("100"));
+        assert_eq!(None, parse_timeout("+1S"));
+        assert_eq!(None, parse_timeout("123456789S"));
+        assert_eq!(None, parse_timeout("1s"));
+        assert_eq!("1500000n", format_timeout(Duration::from_micros(1500)));
+        assert_eq!("100000u", format_timeout(Duration::from_millis(100)));
+        assert_eq!("100000m", format_timeout(Duration::from_secs(100)));
+        assert_eq!(
+            Some(Duration::from_secs(100)),
+            parse_timeout(&format_timeout(Duration::from_secs(100)))
+        );
+    }
+
+    #[async_std::test]
+    async fn method_roundtrip() -> Result<()> {
+        let method = Method::load(
+            Path::new("./tests/data/grpc/echo.desc"),
+            "tremor.test.Echo",
+            "ServerStream",
+        )
+        .await?;
+        assert_eq!("/tremor.test.Echo/ServerStream", method.path());
+        assert!(!method.client_streaming());
+        assert!(method.server_streaming());
+        let request = literal!({"message": "snot", "count": 3});
+        let mut deframer = Deframer::new(DEFAULT_MAX_MESSAGE_SIZE);
+        deframer.push(&method.encode_request(&request)?);
+        let message = deframer.message().map_err(|s| format!("{s:?}"))?;
+        assert_eq!(
+            request,
+            method.decode_request(&message.ok_or("no message")?)?
+        );
+        assert!(Method::load(
+            Path::new("./tests/data/grpc/echo.desc"),
+            "tremor.test.Echo",
+            "Snot"
+        )
+        .await
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn status_roundtrip() {
+        let status = Status::new(code::INVALID_ARGUMENT, "snot: 100% badger\nü");
+        let trailers = status.trailers();
+        assert_eq!(
+            Some("snot: 100%25 badger%0A%C3%BC"),
+            trailers.get("grpc-message").and_then(|m| m.to_str().ok())
+        );
+        assert_eq!(Some(status), Status::from_headers(&trailers));
+        assert_eq!(None, Status::from_headers(&HeaderMap::new()));
+        assert_eq!(
+            code::UNIMPLEMENTED,
+            Status::from_http(hyper::StatusCode::NOT_FOUND).code
+        );
+    }
+
+    #[test]
+    fn metadata_roundtrip() -> Result<()> {
+        let metadata = literal!({
+            "x-snot": ["badger", "badger"],
+            "x-token-bin": "AAEC",
+            "grpc-timeout": "1S"
+        });
+        let mut headers = HeaderMap::new();
+        insert_metadata(&mut headers, Some(&metadata))?;
+        headers.insert("te", HeaderValue::from_static("trailers"));
+        assert_eq!(
+            literal!({
+                "x-snot": ["badger", "badger"],
+                "x-token-bin": ["AAEC"]
+            }),
+            metadata_to_value(&headers)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Client calling a single gRPC method
+//!
+//! For methods taking a single request every event is sent as a separate call.
+//! Methods taking a stream of requests share one call, it is opened with the
+//! first event and closed after an event with `$grpc.end` set to `true`.
+//! Responses are emitted via the `out` port, the responses of a server streaming
+//! call form a tremor stream. Calls ending with a non-OK status emit the status
+//! via the `err` port.
+
+use super::{
+    code, default_max_message_size, format_timeout, insert_metadata, metadata_to_value, Deframer,
+    GrpcDefaults, Method, Status,
+};
+use crate::connectors::impls::http::transport::{self, HttpVersion};
+use crate::connectors::prelude::*;
+use crate::connectors::utils::tls::{tls_client_config, TLSClientConfig};
+use crate::errors::err_conector_def;
+use async_std::channel::{bounded, Receiver, Sender};
+use either::Either;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderValue, CONTENT_TYPE, TE};
+use hyper::Body;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+const CONNECTOR_TYPE: &str = "grpc_client";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// URL of the gRPC server
+    #[serde(default = "Default::default")]
+    url: Url<GrpcDefaults>,
+    /// protobuf descriptor set containing the service
+    descriptor: PathBuf,
+    /// fully qualified name of the service, e.g. `helloworld.Greeter`
+    service: String,
+    /// name of the method to call
+    method: String,
+    /// timeout for receiving the response headers of calls with a single
+    /// request in nanoseconds, sent to the server as `grpc-timeout`
+    #[serde(default = "Default::default")]
+    timeout: Option<u64>,
+    /// maximum size of a received response message in bytes
+    #[serde(default = "default_max_message_size")]
+    max_message_size: usize,
+    /// optional tls client config
+    #[serde(with = "either::serde_untagged_optional", default = "Default::default")]
+    tls: Option<Either<TLSClientConfig, bool>>,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        CONNECTOR_TYPE.into()
+    }
+
+    async fn build_cfg(
+        &self,
+        id: &str,
+        _: &ConnectorConfig,
+        config: &Value,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(config)?;
+        let tls_client_config = match config.tls.as_ref() {
+            Some(Either::Right(true)) => {
+                Some(tls_client_config(&TLSClientConfig::default()).await?)
+            }
+            Some(Either::Left(tls_config)) => Some(tls_client_config(tls_config).await?),
+            Some(Either::Right(false)) | None => None,
+        };
+        if config.url.scheme() == "https" && tls_client_config.is_none() {
+            return Err(err_conector_def(
+                id,
+                &format!("missing tls config for {id} with 'https' url. Set 'tls' to 'true' or provide a full tls config."),
+            ));
+        }
+        let method = Method::load(&config.descriptor, &config.service, &config.method)
+            .await
+            .map_err(|e| err_conector_def(id, &e.to_string()))?;
+        let (response_tx, response_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        Ok(Box::new(GrpcClient {
+            config,
+            method: Arc::new(method),
+            tls_client_config,
+            response_tx,
+            response_rx,
+        }))
+    }
+}
+
+struct GrpcClient {
+    config: Config,
+    method: Arc<Method>,
+    tls_client_config: Option<rustls::ClientConfig>,
+    response_tx: Sender<SourceReply>,
+    response_rx: Receiver<SourceReply>,
+}
+
+#[async_trait::async_trait()]
+impl Connector for GrpcClient {
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Structured
+    }
+
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let source = GrpcClientSource {
+            rx: self.response_rx.clone(),
+        };
+        builder.spawn(source, source_context).map(Some)
+    }
+
+    async fn create_sink(
+        &mut self,
+        sink_context: SinkContext,
+        builder: SinkManagerBuilder,
+    ) -> Result<Option<SinkAddr>> {
+        let url = self.config.url.url();
+        let sink = GrpcClientSink {
+            config: self.config.clone(),
+            method: self.method.clone(),
+            tls_client_config: self.tls_client_config.clone(),
+            client: None,
+            call_counter: 1,
+            open_call: None,
+            response_tx: self.response_tx.clone(),
+            origin_uri: EventOriginUri {
+                scheme: CONNECTOR_TYPE.to_string(),
+                host: url.host_str().unwrap_or_default().to_string(),
+                port: url.port_or_known_default(),
+                path: vec![self.method.path()],
+            },
+        };
+        builder.spawn(sink, sink_context).map(Some)
+    }
+}
+
+struct GrpcClientSource {
+    rx: Receiver<SourceReply>,
+}
+
+#[async_trait::async_trait()]
+impl Source for GrpcClientSource {
+    async fn pull_data(&mut self, _pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+        Ok(self.rx.recv().await?)
+    }
+
+    fn is_transactional(&self) -> bool {
+        false
+    }
+
+    /// there is no asynchronous task driving this and is being stopped by the quiescence process
+    fn asynchronous(&self) -> bool {
+        false
+    }
+}
+
+/// A call with a stream of requests that is still open for sending
+struct OpenCall {
+    id: u64,
+    body: hyper::body::Sender,
+}
+
+struct GrpcClientSink {
+    config: Config,
+    method: Arc<Method>,
+    tls_client_config: Option<rustls::ClientConfig>,
+    client: Option<Arc<transport::Client>>,
+    call_counter: u64,
+    open_call: Option<OpenCall>,
+    response_tx: Sender<SourceReply>,
+    origin_uri: EventOriginUri,
+}
+
+impl GrpcClientSink {
+    fn request(&self, metadata: Option<&Value>, body: Body) -> Result<hyper::Request<Body>> {
+        let mut url = self.config.url.url().clone();
+        url.set_path(&self.method.path());
+        let mut request = hyper::Request::post(url.as_str()).body(body)?;
+        let headers = request.headers_mut();
+        insert_metadata(headers, metadata)?;
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(super::CONTENT_TYPE));
+        headers.insert(TE, HeaderValue::from_static("trailers"));
+        Ok(request)
+    }
+
+    fn next_call(&mut self, correlation: Option<&Value>) -> Call {
+        let id = self.call_counter;
+        self.call_counter = self.call_counter.wrapping_add(1).max(1);
+        Call {
+            id,
+            method: self.method.clone(),
+            origin_uri: self.origin_uri.clone(),
+            correlation: correlation.map(Value::clone_static),
+            max_message_size: self.config.max_message_size,
+            tx: self.response_tx.clone(),
+        }
+    }
+
+    /// Sends a single request
+    fn call(
+        &mut self,
+        ctx: &SinkContext,
+        client: Arc<transport::Client>,
+        value: &Value,
+        meta: &Value,
+    ) -> Result<()> {
+        let grpc_meta = meta.get("grpc");
+        let message = self.method.encode_request(value)?;
+        let mut request = self.request(grpc_meta.get("metadata"), Body::from(message))?;
+        let call = self.next_call(meta.get("correlation"));
+        let timeout = self.config.timeout.map(Duration::from_nanos);
+        if let Some(timeout) = timeout {
+            request.headers_mut().insert(
+                "grpc-timeout",
+                HeaderValue::from_str(&format_timeout(timeout))?,
+            );
+        }
+        spawn_task(ctx.clone(), async move {
+            let response = if let Some(timeout) = timeout {
+                async_std::future::timeout(timeout, client.request(request))
+                    .await
+                    .map_err(|_| Status::new(code::DEADLINE_EXCEEDED, "Deadline exceeded"))
+                    .and_then(unavailable)
+            } else {
+                unavailable(client.request(request).await)
+            };
+            call.receive(response).await
+        });
+        Ok(())
+    }
+
+    /// Sends a request on the open call, opening one if necessary
+    async fn stream(
+        &mut self,
+        ctx: &SinkContext,
+        client: Arc<transport::Client>,
+        value: &Value,
+        meta: &Value,
+    ) -> Result<()> {
+        let grpc_meta = meta.get("grpc");
+        let message = self.method.encode_request(value)?;
+        if self.open_call.is_none() {
+            let (body, request_body) = Body::channel();
+            let request = self.request(grpc_meta.get("metadata"), request_body)?;
+            let call = self.next_call(meta.get("correlation"));
+            debug!("{ctx} Opening call {}", call.id);
+            self.open_call = Some(OpenCall { id: call.id, body });
+            spawn_task(ctx.clone(), async move {
+                call.receive(unavailable(client.request(request).await))
+                    .await
+            });
+        }
+        if let Some(open_call) = self.open_call.as_mut() {
+            if let Err(e) = open_call.body.send_data(message).await {
+                self.open_call = None;
+                return Err(e.into());
+            }
+            if grpc_meta.get_bool("end").unwrap_or_default() {
+                // dropping the sender ends the request stream
+                debug!("{ctx} Closing call {}", open_call.id);
+                self.open_call = None;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait()]
+impl Sink for GrpcClientSink {
+    async fn connect(&mut self, _ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
+        // calls with a single request are multiplexed over one HTTP/2 connection
+        self.open_call = None;
+        self.client = Some(Arc::new(transport::Client::new(
+            HttpVersion::Http2,
+            self.tls_client_config.clone(),
+            1,
+            None,
+        )));
+        Ok(true)
+    }
+
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        event: Event,
+        ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+        _start: u64,
+    ) -> Result<SinkReply> {
+        let client = if let Some(client) = self.client.clone() {
+            client
+        } else {
+            error!("{ctx} No gRPC client available.");
+            return Ok(SinkReply::FAIL);
+        };
+        for (value, meta) in event.value_meta_iter() {
+            let res = if self.method.client_streaming() {
+                self.stream(ctx, client.clone(), value, meta).await
+            } else {
+                self.call(ctx, client.clone(), value, meta)
+            };
+            if let Err(e) = res {
+                error!("{ctx} Error sending gRPC request: {e}");
+                return Ok(SinkReply::FAIL);
+            }
+        }
+        Ok(SinkReply::NONE)
+    }
+
+    fn auto_ack(&self) -> bool {
+        true
+    }
+}
+
+/// Maps a failed request to the `UNAVAILABLE` status
+fn unavailable(
+    response: Result<hyper::Response<Body>>,
+) -> std::result::Result<hyper::Response<Body>, Status> {
+    response.map_err(|e| Status::new(code::UNAVAILABLE, e.to_string()))
+}
+
+/// A call in flight, forwarding its responses to the source
+struct Call {
+    id: u64,
+    method: Arc<Method>,
+    origin_uri: EventOriginUri,
+    correlation: Option<Value<'static>>,
+    max_message_size: usize,
+    tx: Sender<SourceReply>,
+}
+
+impl Call {
+    fn stream(&self) -> u64 {
+        if self.method.server_streaming() {
+            self.id
+        } else {
+            DEFAULT_STREAM_ID
+        }
+    }
+
+    fn meta(&self, metadata: Option<Value<'static>>) -> Value<'static> {
+        let mut grpc = literal!({ "call_id": self.id });
+        if let Some(metadata) = metadata {
+            grpc.try_insert("metadata", metadata);
+        }
+        let mut meta = literal!({ "grpc": grpc });
+        if let Some(correlation) = &self.correlation {
+            meta.try_insert("correlation", correlation.clone());
+        }
+        meta
+    }
+
+    async fn receive(
+        self,
+        response: std::result::Result<hyper::Response<Body>, Status>,
+    ) -> Result<()> {
+        let status = match response {
+            Ok(response) => self
+                .read(response)
+                .await
+                .unwrap_or_else(|e| Status::new(code::INTERNAL, e.to_string())),
+            Err(status) => status,
+        };
+        if self.method.server_streaming() {
+            self.tx
+                .send(SourceReply::EndStream {
+                    origin_uri: self.origin_uri.clone(),
+                    stream: self.id,
+                    meta: None,
+                })
+                .await?;
+        }
+        if !status.is_ok() {
+            debug!("gRPC call {} failed: {status:?}", self.id);
+            self.tx
+                .send(SourceReply::Structured {
+                    origin_uri: self.origin_uri.clone(),
+                    payload: EventPayload::from(ValueAndMeta::from_parts(
+                        status.value(),
+                        self.meta(None),
+                    )),
+                    stream: DEFAULT_STREAM_ID,
+                    port: Some(ERR),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Emits all response messages, returns the status the call ended with
+    async fn read(&self, response: hyper::Response<Body>) -> Result<Status> {
+        let (parts, mut body) = response.into_parts();
+        if parts.status != hyper::StatusCode::OK {
+            return Ok(Status::from_http(parts.status));
+        }
+        // trailers-only responses carry the status in the headers
+        if let Some(status) = Status::from_headers(&parts.headers) {
+            return Ok(status);
+        }
+        let metadata = metadata_to_value(&parts.headers);
+        let mut deframer = Deframer::new(self.max_message_size);
+        while let Some(data) = body.data().await {
+            deframer.push(&data?);
+            loop {
+                let message = match deframer.message() {
+                    Ok(Some(message)) => message,
+                    Ok(None) => break,
+                    Err(status) => return Ok(status),
+                };
+                let value = self.method.decode_response(&message)?;
+                let meta = self.meta(Some(metadata.clone()));
+                self.tx
+                    .send(SourceReply::Structured {
+                        origin_uri: self.origin_uri.clone(),
+                        payload: EventPayload::from(ValueAndMeta::from_parts(value, meta)),
+                        stream: self.stream(),
+                        port: None,
+                    })
+                    .await?;
+            }
+        }
+        let trailers = body.trailers().await?;
+        Ok(trailers
+            .as_ref()
+            .and_then(Status::from_headers)
+            .unwrap_or_else(|| Status::new(code::INTERNAL, "Missing grpc-status")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn missing_descriptor() -> Result<()> {
+        let config = literal!({
+            "config": {
+                "url": "localhost:50051",
+                "descriptor": "/snot/badger.desc",
+                "service": "snot.Badger",
+                "method": "Get"
+            },
+        });
+        let config: ConnectorConfig = crate::config::Connector::from_config(
+            "my_grpc_client",
+            ConnectorType(CONNECTOR_TYPE.into()),
+            &config,
+        )?;
+        assert!(Builder::default().build("foo", &config).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,536 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Server serving a single gRPC method
+//!
+//! Every request message becomes an event with the `call_id` and metadata of
+//! its call in `$grpc`, the messages of a call with a stream of requests form a
+//! tremor stream. Responses are routed back to their call via
+//! `$grpc.call_id`. A call is completed with the first response unless the
+//! method returns a stream of responses, then it is completed by a response
+//! with `$grpc.end` set to `true`. A response with a non-zero `$grpc.status`
+//! completes the call with that status and an optional `$grpc.message`.
+//! Calls that are not responded to within the `grpc-timeout` requested by the
+//! client, or the configured `timeout`, fail with `DEADLINE_EXCEEDED`.
+
+use super::{
+    code, default_max_message_size, insert_metadata, metadata_to_value, parse_timeout, Deframer,
+    GrpcDefaults, Method, Status,
+};
+use crate::connectors::impls::http::transport::Executor;
+use crate::connectors::prelude::*;
+use crate::connectors::utils::tls::{TLSServerConfig, TlsServer};
+use crate::errors::err_conector_def;
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::net::{SocketAddr, TcpListener};
+use async_std::task::JoinHandle;
+use dashmap::DashMap;
+use futures::{AsyncRead, AsyncWrite};
+use halfbrown::HashMap;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{server::conn::Http, service::service_fn, Body};
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+const CONNECTOR_TYPE: &str = "grpc_server";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// URL to listen on
+    #[serde(default = "Default::default")]
+    url: Url<GrpcDefaults>,
+    /// protobuf descriptor set containing the service
+    descriptor: PathBuf,
+    /// fully qualified name of the service, e.g. `helloworld.Greeter`
+    service: String,
+    /// name of the method to serve
+    method: String,
+    /// TLS configuration, if required
+    tls: Option<TLSServerConfig>,
+    /// maximum size of a received request message in bytes
+    #[serde(default = "default_max_message_size")]
+    max_message_size: usize,
+    /// time in nanoseconds to wait for the response of a call, if the client
+    /// requests a shorter `grpc-timeout` that one is used
+    #[serde(default = "Default::default")]
+    timeout: Option<u64>,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        CONNECTOR_TYPE.into()
+    }
+
+    async fn build_cfg(
+        &self,
+        id: &str,
+        _: &ConnectorConfig,
+        config: &Value,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(config)?;
+        let method = Method::load(&config.descriptor, &config.service, &config.method)
+            .await
+            .map_err(|e| err_conector_def(id, &e.to_string()))?;
+        Ok(Box::new(GrpcServer {
+            config,
+            method: Arc::new(method),
+            inflight: Arc::default(),
+        }))
+    }
+}
+
+struct GrpcServer {
+    config: Config,
+    method: Arc<Method>,
+    /// calls waiting for their response head
+    inflight: Arc<DashMap<u64, Sender<hyper::Response<Body>>>>,
+}
+
+#[async_trait::async_trait()]
+impl Connector for GrpcServer {
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Structured
+    }
+
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        let source = GrpcServerSource {
+            config: self.config.clone(),
+            method: self.method.clone(),
+            inflight: self.inflight.clone(),
+            server_task: None,
+            tx,
+            rx,
+        };
+        builder.spawn(source, source_context).map(Some)
+    }
+
+    async fn create_sink(
+        &mut self,
+        sink_context: SinkContext,
+        builder: SinkManagerBuilder,
+    ) -> Result<Option<SinkAddr>> {
+        let sink = GrpcServerSink {
+            method: self.method.clone(),
+            inflight: self.inflight.clone(),
+            open: HashMap::new(),
+        };
+        builder.spawn(sink, sink_context).map(Some)
+    }
+}
+
+struct GrpcServerSource {
+    config: Config,
+    method: Arc<Method>,
+    inflight: Arc<DashMap<u64, Sender<hyper::Response<Body>>>>,
+    server_task: Option<JoinHandle<()>>,
+    tx: Sender<SourceReply>,
+    rx: Receiver<SourceReply>,
+}
+
+#[async_trait::async_trait()]
+impl Source for GrpcServerSource {
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        if let Some(server_task) = self.server_task.take() {
+            server_task.cancel().await;
+        }
+        let hostport = format!(
+            "{}:{}",
+            self.config.url.host_or_local(),
+            self.config.url.port_or_dflt()
+        );
+        let tls_server = match self.config.tls.as_ref() {
+            Some(tls_config) => Some(
+                TlsServer::new(tls_config)
+                    .await?
+                    .with_protocols(vec![b"h2".to_vec()]),
+            ),
+            None => None,
+        };
+        let server = Arc::new(Server {
+            path: self.method.path(),
+            method: self.method.clone(),
+            inflight: self.inflight.clone(),
+            call_counter: AtomicU64::new(1),
+            max_message_size: self.config.max_message_size,
+            timeout: self.config.timeout.map(Duration::from_nanos),
+            tx: self.tx.clone(),
+        });
+        let server_ctx = ctx.clone();
+        self.server_task = Some(spawn_task(ctx.clone(), async move {
+            let listener = TcpListener::bind(&hostport).await?;
+            info!(
+                "{server_ctx} Serving {} on {}",
+                server.path,
+                listener.local_addr()?
+            );
+            loop {
+                let (stream, peer) = listener.accept().await?;
+                let server = server.clone();
+                let tls_server = tls_server.clone();
+                async_std::task::spawn(async move {
+                    let res = match tls_server {
+                        Some(tls_server) => {
+                            let (acceptor, _peer_cert) = tls_server.acceptor().await;
+                            match acceptor.accept(stream).await {
+                                Ok(stream) => server.serve(stream, peer).await,
+                                Err(e) => Err(Error::from(e)),
+                            }
+                        }
+                        None => server.serve(stream, peer).await,
+                    };
+                    if let Err(e) = res {
+                        debug!("gRPC connection from {peer} failed: {e}");
+                    }
+                });
+            }
+        }));
+        Ok(true)
+    }
+
+    async fn pull_data(&mut self, _pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+        Ok(self.rx.recv().await?)
+    }
+
+    async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
+        if let Some(server_task) = self.server_task.take() {
+            server_task.cancel().await;
+        }
+        Ok(())
+    }
+
+    fn is_transactional(&self) -> bool {
+        false
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
+
+/// State shared by all connections
+struct Server {
+    path: String,
+    method: Arc<Method>,
+    inflight: Arc<DashMap<u64, Sender<hyper::Response<Body>>>>,
+    call_counter: AtomicU64,
+    max_message_size: usize,
+    timeout: Option<Duration>,
+    tx: Sender<SourceReply>,
+}
+
+impl Server {
+    async fn serve<IO>(self: Arc<Self>, io: IO, peer: SocketAddr) -> Result<()>
+    where
+        IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let service = service_fn(move |request| {
+            let server = self.clone();
+            async move { Ok::<_, Infallible>(server.handle(request, peer).await) }
+        });
+        let mut http = Http::new().with_executor(Executor);
+        http.http2_only(true);
+        http.serve_connection(io.compat(), service).await?;
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        request: hyper::Request<Body>,
+        peer: SocketAddr,
+    ) -> hyper::Response<Body> {
+        if request.uri().path() != self.path {
+            return Status::new(
+                code::UNIMPLEMENTED,
+                format!("Method {} not found", request.uri().path()),
+            )
+            .response();
+        }
+        let is_grpc = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .map_or(false, |ct| ct.starts_with(super::CONTENT_TYPE));
+        if !is_grpc {
+            let mut response = hyper::Response::new(Body::empty());
+            *response.status_mut() = hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE;
+            return response;
+        }
+        let call_id = self.call_counter.fetch_add(1, Ordering::Relaxed);
+        let (response_tx, response_rx) = bounded(1);
+        self.inflight.insert(call_id, response_tx);
+
+        let (parts, body) = request.into_parts();
+        let deadline = parts
+            .headers
+            .get("grpc-timeout")
+            .and_then(|timeout| timeout.to_str().ok())
+            .and_then(parse_timeout)
+            .into_iter()
+            .chain(self.timeout)
+            .min();
+        let call = IncomingCall {
+            id: call_id,
+            method: self.method.clone(),
+            origin_uri: EventOriginUri {
+                scheme: CONNECTOR_TYPE.to_string(),
+                host: peer.ip().to_string(),
+                port: Some(peer.port()),
+                path: vec![self.path.clone()],
+            },
+            meta: literal!({
+                "grpc": {
+                    "call_id": call_id,
+                    "metadata": metadata_to_value(&parts.headers)
+                }
+            }),
+            max_message_size: self.max_message_size,
+            tx: self.tx.clone(),
+        };
+        let inflight = self.inflight.clone();
+        async_std::task::spawn(async move {
+            if let Err(status) = call.read(body).await {
+                debug!("Error reading gRPC call {call_id}: {status:?}");
+                // reject the call, unless a response has been sent already
+                if let Some((_, response_tx)) = inflight.remove(&call_id) {
+                    let _ = response_tx.send(status.response()).await;
+                }
+            }
+        });
+        let response = if let Some(deadline) = deadline {
+            async_std::future::timeout(deadline, response_rx.recv())
+                .await
+                .unwrap_or_else(|_| {
+                    Ok(Status::new(code::DEADLINE_EXCEEDED, "Deadline exceeded").response())
+                })
+        } else {
+            response_rx.recv().await
+        };
+        // the call is done, unless the sink already picked it up
+        self.inflight.remove(&call_id);
+        response.unwrap_or_else(|_| Status::new(code::UNAVAILABLE, "No response").response())
+    }
+}
+
+/// A call received by the server, forwarding its requests to the source
+struct IncomingCall {
+    id: u64,
+    method: Arc<Method>,
+    origin_uri: EventOriginUri,
+    meta: Value<'static>,
+    max_message_size: usize,
+    tx: Sender<SourceReply>,
+}
+
+impl IncomingCall {
+    /// Forwards the requests of the call, returns the status to reject the call
+    /// with if that fails
+    async fn read(self, mut body: Body) -> std::result::Result<(), Status> {
+        let stream = if self.method.client_streaming() {
+            self.id
+        } else {
+            DEFAULT_STREAM_ID
+        };
+        let unavailable = |e: async_std::channel::SendError<SourceReply>| {
+            Status::new(code::UNAVAILABLE, e.to_string())
+        };
+        let mut deframer = Deframer::new(self.max_message_size);
+        while let Some(data) = body.data().await {
+            deframer.push(&data.map_err(|e| Status::new(code::CANCELLED, e.to_string()))?);
+            while let Some(message) = deframer.message()? {
+                let value = self
+                    .method
+                    .decode_request(&message)
+                    .map_err(|e| Status::new(code::INVALID_ARGUMENT, e.to_string()))?;
+                self.tx
+                    .send(SourceReply::Structured {
+                        origin_uri: self.origin_uri.clone(),
+                        payload: EventPayload::from(ValueAndMeta::from_parts(
+                            value,
+                            self.meta.clone(),
+                        )),
+                        stream,
+                        port: None,
+                    })
+                    .await
+                    .map_err(unavailable)?;
+            }
+        }
+        if self.method.client_streaming() {
+            self.tx
+                .send(SourceReply::EndStream {
+                    origin_uri: self.origin_uri,
+                    stream,
+                    meta: Some(self.meta),
+                })
+                .await
+                .map_err(unavailable)?;
+        }
+        Ok(())
+    }
+}
+
+struct GrpcServerSink {
+    method: Arc<Method>,
+    inflight: Arc<DashMap<u64, Sender<hyper::Response<Body>>>>,
+    /// calls whose response has been started
+    open: HashMap<u64, hyper::body::Sender>,
+}
+
+impl GrpcServerSink {
+    /// Sends the response head of a call, returns false if the call is gone
+    async fn start(
+        &mut self,
+        call_id: u64,
+        grpc_meta: Option<&Value<'_>>,
+        status: &Status,
+    ) -> Result<bool> {
+        let response_tx = if let Some((_, response_tx)) = self.inflight.remove(&call_id) {
+            response_tx
+        } else {
+            return Ok(false);
+        };
+        if !status.is_ok() {
+            response_tx.send(status.response()).await?;
+            return Ok(false);
+        }
+        let (body, response_body) = Body::channel();
+        let mut response = hyper::Response::new(response_body);
+        let headers = response.headers_mut();
+        insert_metadata(headers, grpc_meta.get("metadata"))?;
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(super::CONTENT_TYPE));
+        response_tx.send(response).await?;
+        self.open.insert(call_id, body);
+        Ok(true)
+    }
+
+    /// Sends a response message and completes the call if necessary
+    async fn respond(
+        &mut self,
+        value: &Value<'_>,
+        grpc_meta: Option<&Value<'_>>,
+        mut status: Status,
+    ) -> Result<()> {
+        let call_id = grpc_meta
+            .get_u64("call_id")
+            .ok_or("Missing `$grpc.call_id`")?;
+        if !self.open.contains_key(&call_id) && !self.start(call_id, grpc_meta, &status).await? {
+            return Ok(());
+        }
+        let mut end = !status.is_ok()
+            || !self.method.server_streaming()
+            || grpc_meta.get_bool("end").unwrap_or_default();
+        if let Some(body) = self.open.get_mut(&call_id) {
+            if status.is_ok() {
+                match self.method.encode_response(value) {
+                    Ok(message) => body.send_data(message).await?,
+                    Err(e) => {
+                        status = Status::new(code::INTERNAL, e.to_string());
+                        end = true;
+                    }
+                }
+            }
+        }
+        if end {
+            if let Some(mut body) = self.open.remove(&call_id) {
+                body.send_trailers(status.trailers()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait()]
+impl Sink for GrpcServerSink {
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        event: Event,
+        ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+        _start: u64,
+    ) -> Result<SinkReply> {
+        for (value, meta) in event.value_meta_iter() {
+            let grpc_meta = meta.get("grpc");
+            let status = grpc_meta
+                .get_u32("status")
+                .map_or_else(Status::ok, |code| Status {
+                    code,
+                    message: grpc_meta.get_str("message").map(ToString::to_string),
+                });
+            if let Err(e) = self.respond(value, grpc_meta, status).await {
+                warn!("{ctx} Error sending gRPC response: {e}");
+                if let Some(call_id) = grpc_meta.get_u64("call_id") {
+                    self.open.remove(&call_id);
+                }
+            }
+        }
+        Ok(SinkReply::NONE)
+    }
+
+    async fn on_signal(
+        &mut self,
+        _signal: Event,
+        _ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+    ) -> Result<SinkReply> {
+        // clean out calls whose client went away
+        self.inflight.retain(|_call_id, sender| !sender.is_closed());
+        Ok(SinkReply::NONE)
+    }
+
+    fn auto_ack(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn missing_descriptor() -> Result<()> {
+        let config = literal!({
+            "config": {
+                "url": "localhost:50051",
+                "descriptor": "/snot/badger.desc",
+                "service": "snot.Badger",
+                "method": "Get"
+            },
+        });
+        let config: ConnectorConfig = crate::config::Connector::from_config(
+            "my_grpc_server",
+            ConnectorType(CONNECTOR_TYPE.into()),
+            &config,
+        )?;
+        assert!(Builder::default().build("foo", &config).await.is_err());
+        Ok(())
+    }
+}
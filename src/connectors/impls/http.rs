@@ -16,4 +16,5 @@ pub(crate) mod auth;
 pub(crate) mod client;
 pub(crate) mod meta;
 pub(crate) mod server;
+pub(crate) mod transport;
 pub(crate) mod utils;
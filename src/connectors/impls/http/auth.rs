@@ -13,6 +13,15 @@
 // limitations under the License.
 
 use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::sync::Mutex;
+use http_types::{headers, Method, StatusCode, Url};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use tremor_common::time::nanotime;
+use url::form_urlencoded;
 
 use crate::errors::Result;
 
@@ -28,12 +37,16 @@ pub enum Auth {
     ElasticsearchApiKey { id: String, api_key: String },
     #[serde(alias = "gcp")]
     Gcp,
+    #[serde(alias = "oauth2")]
+    OAuth2(OAuth2),
     #[serde(alias = "none")]
     None,
 }
 
 impl Auth {
     /// Prepare a HTTP autheorization header value given the auth strategy
+    ///
+    /// `oauth2` tokens need to be fetched asynchronously, use [`Auth::header_value`] for those.
     pub fn as_header_value(&self) -> Result<Option<String>> {
         match self {
             Auth::Gcp => {
@@ -57,9 +70,31 @@ impl Auth {
                 writer.into_inner(); // release the reference, so header-value is accessible again
                 Ok(Some(header_value))
             }
+            Auth::OAuth2(_) => {
+                Err("OAuth2 tokens can only be fetched asynchronously via `header_value`".into())
+            }
             Auth::None => Ok(None),
         }
     }
+
+    /// Prepare a HTTP authorization header value given the auth strategy,
+    /// fetching a new `oauth2` token if none is cached or it is about to expire
+    pub async fn header_value(&self) -> Result<Option<String>> {
+        match self {
+            Auth::OAuth2(oauth2) => oauth2.header_value().await.map(Some),
+            other => other.as_header_value(),
+        }
+    }
+
+    /// Prepare a new HTTP authorization header value after `rejected` was refused by the server with a 401.
+    ///
+    /// Returns `None` if the auth strategy has no way of getting new credentials, in which case retrying is pointless.
+    pub async fn refresh(&self, rejected: &str) -> Result<Option<String>> {
+        match self {
+            Auth::OAuth2(oauth2) => oauth2.refresh(rejected).await.map(Some),
+            _ => Ok(None),
+        }
+    }
 }
 
 impl Default for Auth {
@@ -68,9 +103,264 @@ impl Default for Auth {
     }
 }
 
+/// `client_assertion_type` for authenticating with a JWT, see RFC 7523
+const JWT_BEARER_ASSERTION_TYPE: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// OAuth2 client credentials grant
+///
+/// Tokens are fetched from the `token_url` on first use and cached until
+/// `refresh_margin` nanoseconds before they expire. Clones of this config
+/// share the cached token.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OAuth2 {
+    /// URL of the token endpoint
+    token_url: String,
+    /// client id
+    client_id: String,
+    /// client secret, sent along in the token request body
+    #[serde(default)]
+    client_secret: Option<String>,
+    /// scopes to request the token for
+    #[serde(default)]
+    scopes: Vec<String>,
+    /// audience to request the token for, required by some providers
+    #[serde(default)]
+    audience: Option<String>,
+    /// authenticate the client with a signed JWT instead of or in addition to the secret
+    #[serde(default)]
+    assertion: Option<JwtAssertion>,
+    /// refresh tokens this many nanoseconds before they expire
+    #[serde(default = "default_refresh_margin")]
+    refresh_margin: u64,
+    /// give up on a token request after this many nanoseconds
+    #[serde(default = "default_token_timeout")]
+    token_timeout: u64,
+    #[serde(skip)]
+    token: Arc<Mutex<Option<Token>>>,
+}
+
+fn default_refresh_margin() -> u64 {
+    // 30s
+    30_000_000_000
+}
+
+fn default_token_timeout() -> u64 {
+    // 10s
+    10_000_000_000
+}
+
+impl PartialEq for OAuth2 {
+    // the cached token is runtime state, not config
+    fn eq(&self, other: &Self) -> bool {
+        self.token_url == other.token_url
+            && self.client_id == other.client_id
+            && self.client_secret == other.client_secret
+            && self.scopes == other.scopes
+            && self.audience == other.audience
+            && self.assertion == other.assertion
+            && self.refresh_margin == other.refresh_margin
+            && self.token_timeout == other.token_timeout
+    }
+}
+
+impl OAuth2 {
+    async fn header_value(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some(cached) = token.as_ref() {
+            if cached.is_valid_at(nanotime() + self.refresh_margin) {
+                return Ok(cached.header.clone());
+            }
+        }
+        let fetched = self.fetch().await?;
+        let header = fetched.header.clone();
+        *token = Some(fetched);
+        Ok(header)
+    }
+
+    async fn refresh(&self, rejected: &str) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some(cached) = token.as_ref() {
+            // another request refreshed the token already
+            if cached.header != rejected && cached.is_valid_at(nanotime() + self.refresh_margin) {
+                return Ok(cached.header.clone());
+            }
+        }
+        let fetched = self.fetch().await?;
+        let header = fetched.header.clone();
+        *token = Some(fetched);
+        Ok(header)
+    }
+
+    async fn form(&self) -> Result<String> {
+        let mut form = form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", "client_credentials");
+        form.append_pair("client_id", &self.client_id);
+        if let Some(client_secret) = &self.client_secret {
+            form.append_pair("client_secret", client_secret);
+        }
+        if let Some(assertion) = &self.assertion {
+            let jwt = assertion.sign(&self.client_id, &self.token_url).await?;
+            form.append_pair("client_assertion_type", JWT_BEARER_ASSERTION_TYPE);
+            form.append_pair("client_assertion", &jwt);
+        }
+        if !self.scopes.is_empty() {
+            form.append_pair("scope", &self.scopes.join(" "));
+        }
+        if let Some(audience) = &self.audience {
+            form.append_pair("audience", audience);
+        }
+        Ok(form.finish())
+    }
+
+    async fn fetch(&self) -> Result<Token> {
+        // requests wait on the token lock meanwhile, a hanging endpoint must not block them
+        async_std::future::timeout(Duration::from_nanos(self.token_timeout), self.request())
+            .await
+            .map_err(|_| format!("OAuth2 token request to {} timed out", self.token_url))?
+    }
+
+    async fn request(&self) -> Result<Token> {
+        let url = Url::parse(&self.token_url)?;
+        let mut response = surf::RequestBuilder::new(Method::Post, url)
+            .header(headers::ACCEPT, "application/json")
+            .content_type("application/x-www-form-urlencoded")
+            .body_string(self.form().await?)
+            .await?;
+        let mut body = response.body_bytes().await?;
+        if response.status() != StatusCode::Ok {
+            return Err(format!(
+                "OAuth2 token request to {} failed with {}: {}",
+                self.token_url,
+                response.status(),
+                String::from_utf8_lossy(&body)
+            )
+            .into());
+        }
+        let token: TokenResponse = simd_json::from_slice(&mut body)?;
+        Ok(Token::new(token, nanotime()))
+    }
+}
+
+/// Successful response of a token endpoint, see RFC 6749 section 5.1
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: Option<String>,
+    /// lifetime of the token in seconds
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// A cached access token
+#[derive(Clone)]
+struct Token {
+    /// the ready authorization header value
+    header: String,
+    /// `None` if the token endpoint didn't tell
+    expires_at: Option<u64>,
+}
+
+impl Token {
+    fn new(response: TokenResponse, now: u64) -> Self {
+        // the token type is case insensitive, but not every server knows
+        let token_type = match response.token_type {
+            Some(token_type) if !token_type.eq_ignore_ascii_case("bearer") => token_type,
+            _ => "Bearer".to_string(),
+        };
+        Self {
+            header: format!("{} {}", token_type, response.access_token),
+            expires_at: response
+                .expires_in
+                .map(|secs| now + secs.saturating_mul(1_000_000_000)),
+        }
+    }
+
+    fn is_valid_at(&self, at: u64) -> bool {
+        self.expires_at.map_or(true, |expires_at| at < expires_at)
+    }
+}
+
+impl std::fmt::Debug for Token {
+    // keep the token itself out of logs
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Token")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// A JWT signed by the client to authenticate towards the token endpoint, see RFC 7523
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct JwtAssertion {
+    /// path to the PEM encoded private key, or to the shared secret for `HS*` algorithms
+    key: PathBuf,
+    /// signing algorithm
+    #[serde(default = "default_algorithm")]
+    algorithm: Algorithm,
+    /// `kid` header of the JWT
+    #[serde(default)]
+    key_id: Option<String>,
+    /// `aud` claim of the JWT, defaults to the token url
+    #[serde(default)]
+    audience: Option<String>,
+    /// lifetime of the JWT in nanoseconds
+    #[serde(default = "default_assertion_lifetime")]
+    lifetime: u64,
+}
+
+fn default_algorithm() -> Algorithm {
+    Algorithm::RS256
+}
+
+fn default_assertion_lifetime() -> u64 {
+    // 5 minutes
+    300_000_000_000
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    sub: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+    jti: String,
+}
+
+impl JwtAssertion {
+    async fn sign(&self, client_id: &str, token_url: &str) -> Result<String> {
+        let key = async_std::fs::read(&self.key).await?;
+        let key = match self.algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                EncodingKey::from_secret(&key)
+            }
+            Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(&key)?,
+            _ => EncodingKey::from_rsa_pem(&key)?,
+        };
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.key_id.clone();
+        let iat = nanotime() / 1_000_000_000;
+        let claims = Claims {
+            iss: client_id,
+            sub: client_id,
+            aud: self.audience.as_deref().unwrap_or(token_url),
+            iat,
+            exp: iat + self.lifetime / 1_000_000_000,
+            jti: uuid::Uuid::new_v4().to_string(),
+        };
+        Ok(jsonwebtoken::encode(&header, &claims, &key)?)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use async_std::prelude::FutureExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tremor_value::literal;
 
     #[test]
     fn header_value_basic() -> Result<()> {
@@ -104,4 +394,134 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn token_expiry() {
+        let token = Token::new(
+            TokenResponse {
+                access_token: "snot".to_string(),
+                token_type: Some("bearer".to_string()),
+                expires_in: Some(60),
+            },
+            1_000,
+        );
+        assert_eq!("Bearer snot", token.header);
+        assert!(token.is_valid_at(1_000 + 59_000_000_000));
+        assert!(!token.is_valid_at(1_000 + 60_000_000_000));
+
+        let token = Token::new(
+            TokenResponse {
+                access_token: "snot".to_string(),
+                token_type: Some("MAC".to_string()),
+                expires_in: None,
+            },
+            1_000,
+        );
+        assert_eq!("MAC snot", token.header);
+        assert!(token.is_valid_at(u64::MAX));
+    }
+
+    #[async_std::test]
+    async fn oauth2_form() -> Result<()> {
+        let auth: Auth = tremor_value::structurize(literal!({
+            "oauth2": {
+                "token_url": "http://localhost/token",
+                "client_id": "badger",
+                "client_secret": "s3cr3t&",
+                "scopes": ["read", "write"]
+            }
+        }))?;
+        if let Auth::OAuth2(oauth2) = &auth {
+            assert_eq!(
+                "grant_type=client_credentials&client_id=badger&client_secret=s3cr3t%26&scope=read+write",
+                oauth2.form().await?
+            );
+        } else {
+            panic!("expected oauth2 auth, got {:?}", auth);
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn oauth2_token_caching() -> Result<()> {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let mut app = tide::with_state(requests.clone());
+        app.at("/token")
+            .post(|mut req: tide::Request<Arc<AtomicUsize>>| async move {
+                let form = req.body_string().await?;
+                let n = req.state().fetch_add(1, Ordering::AcqRel);
+                if !form.starts_with("grant_type=client_credentials&client_id=badger") {
+                    return Ok(tide::Response::new(400));
+                }
+                Ok(tide::Response::builder(200)
+                    .content_type(tide::http::mime::JSON)
+                    .body(format!(
+                        r#"{{"access_token":"token{}","token_type":"bearer","expires_in":3600}}"#,
+                        n
+                    ))
+                    .build())
+            });
+        // bind upfront so the server is ready to accept before the first request
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let token_url = format!("http://{}/token", listener.local_addr()?);
+        let server = async_std::task::spawn(app.listen(listener));
+
+        let auth: Auth = tremor_value::structurize(literal!({
+            "oauth2": {
+                "token_url": token_url,
+                "client_id": "badger",
+                "client_secret": "snot"
+            }
+        }))?;
+        let shared = auth.clone();
+        assert_eq!(
+            Some("Bearer token0".to_string()),
+            auth.header_value().await?
+        );
+        assert_eq!(
+            Some("Bearer token0".to_string()),
+            shared.header_value().await?
+        );
+        assert_eq!(1, requests.load(Ordering::Acquire));
+
+        // a rejected token is replaced, but only once
+        assert_eq!(
+            Some("Bearer token1".to_string()),
+            auth.refresh("Bearer token0").await?
+        );
+        assert_eq!(
+            Some("Bearer token1".to_string()),
+            shared.refresh("Bearer token0").await?
+        );
+        assert_eq!(2, requests.load(Ordering::Acquire));
+
+        assert_eq!(
+            None,
+            Auth::Bearer("snot".to_string())
+                .refresh("Bearer snot")
+                .await?
+        );
+        server.cancel().await;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn oauth2_token_timeout() -> Result<()> {
+        // connections are accepted by the os, but never answered
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let auth: Auth = tremor_value::structurize(literal!({
+            "oauth2": {
+                "token_url": format!("http://{}/token", listener.local_addr()?),
+                "client_id": "badger",
+                "token_timeout": 100_000_000
+            }
+        }))?;
+        let res = auth
+            .header_value()
+            .timeout(Duration::from_secs(5))
+            .await
+            .expect("token request did not time out");
+        assert!(res.is_err());
+        Ok(())
+    }
 }
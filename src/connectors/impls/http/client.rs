@@ -12,24 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod poll;
+
 use std::sync::Arc;
 use std::time::Duration;
 
 use async_std::channel::{bounded, Receiver, Sender};
 use either::Either;
 use halfbrown::HashMap;
-use http_client::h1::H1Client;
-use http_client::HttpClient;
-use http_types::Method;
+use http_types::{headers, Method, Request, Response, StatusCode};
 use tremor_common::time::nanotime;
 
 use super::auth::Auth;
 use super::meta::{extract_request_meta, extract_response_meta, HttpRequestBuilder};
+use super::transport::{self, HttpVersion};
 use super::utils::{Header, RequestId};
 use crate::connectors::sink::concurrency_cap::ConcurrencyCap;
 use crate::connectors::utils::mime::MimeCodecMap;
 use crate::connectors::utils::tls::{tls_client_config, TLSClientConfig};
 use crate::{connectors::prelude::*, errors::err_conector_def};
+use async_std::task::JoinHandle;
+use poll::{PollConfig, Poller, Schedule};
 
 const CONNECTOR_TYPE: &str = "http_client";
 const DEFAULT_CODEC: &str = "json";
@@ -58,9 +61,16 @@ pub(crate) struct Config {
     /// optional tls client config
     #[serde(with = "either::serde_untagged_optional", default = "Default::default")]
     tls: Option<Either<TLSClientConfig, bool>>,
+    /// HTTP versions to speak: `auto` negotiates HTTP/2 via ALPN, `http2` also
+    /// uses h2c for plaintext urls
+    #[serde(default = "Default::default")]
+    http_version: HttpVersion,
     /// MIME mapping to/from tremor codecs
     #[serde(default)]
     custom_codecs: HashMap<String, String>,
+    /// poll `url` on a schedule, following paginated responses
+    #[serde(default = "Default::default")]
+    poll: Option<PollConfig>,
 }
 
 const DEFAULT_CONCURRENCY: usize = 4;
@@ -107,6 +117,12 @@ impl ConnectorBuilder for Builder {
                     &format!("missing tls config for {id} with 'https' url. Set 'tls' to 'true' or provide a full tls config."),
                 ));
         }
+        let schedule = config
+            .poll
+            .as_ref()
+            .map(PollConfig::schedule)
+            .transpose()
+            .map_err(|e| err_conector_def(id, &e.to_string()))?;
         let (response_tx, response_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         let mime_codec_map = Arc::new(MimeCodecMap::with_overwrites(&config.custom_codecs));
 
@@ -121,6 +137,7 @@ impl ConnectorBuilder for Builder {
             tls_client_config,
             mime_codec_map,
             configured_codec,
+            schedule,
         }))
     }
 }
@@ -134,6 +151,7 @@ pub struct Client {
     // this is basically an immutable map, we use arc to share it across tasks (e.g. for each request sending)
     mime_codec_map: Arc<MimeCodecMap>,
     configured_codec: String,
+    schedule: Option<Schedule>,
 }
 
 #[async_trait::async_trait]
@@ -147,8 +165,26 @@ impl Connector for Client {
         source_context: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
+        let poller = self
+            .config
+            .poll
+            .clone()
+            .zip(self.schedule.clone())
+            .map(|(poll, schedule)| {
+                Poller::new(
+                    self.config.clone(),
+                    poll,
+                    schedule,
+                    self.tls_client_config.clone(),
+                    self.mime_codec_map.clone(),
+                    self.configured_codec.clone(),
+                    self.response_tx.clone(),
+                )
+            });
         let source = HttpRequestSource {
             rx: self.response_rx.clone(),
+            poller,
+            poll_task: None,
         };
         builder.spawn(source, source_context).map(Some)
     }
@@ -172,27 +208,46 @@ impl Connector for Client {
 
 struct HttpRequestSource {
     rx: Receiver<SourceReply>,
+    poller: Option<Poller>,
+    poll_task: Option<JoinHandle<()>>,
 }
 
 #[async_trait::async_trait()]
 impl Source for HttpRequestSource {
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        if let Some(poller) = self.poller.as_ref() {
+            if let Some(poll_task) = self.poll_task.take() {
+                poll_task.cancel().await;
+            }
+            self.poll_task = Some(spawn_task(ctx.clone(), poller.clone().run(ctx.clone())));
+        }
+        Ok(true)
+    }
+
     async fn pull_data(&mut self, _pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
         Ok(self.rx.recv().await?)
     }
 
+    async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
+        if let Some(poll_task) = self.poll_task.take() {
+            poll_task.cancel().await;
+        }
+        Ok(())
+    }
+
     fn is_transactional(&self) -> bool {
         false
     }
 
-    /// there is no asynchronous task driving this and is being stopped by the quiescence process
+    /// without polling there is no asynchronous task driving this and it is being stopped by the quiescence process
     fn asynchronous(&self) -> bool {
-        false
+        self.poller.is_some()
     }
 }
 
 struct HttpRequestSink {
     request_counter: u64,
-    client: Option<Arc<H1Client>>,
+    client: Option<Arc<transport::Client>>,
     response_tx: Sender<SourceReply>,
     reply_tx: Sender<AsyncSinkReply>,
     config: Config,
@@ -234,20 +289,50 @@ impl HttpRequestSink {
     }
 }
 
+/// Sends `request`, if it is rejected with a 401 and `auth` is able to provide
+/// fresh credentials, it is retried once with those.
+///
+/// Chunked requests are never retried as their body has been streamed already.
+async fn send(
+    client: &transport::Client,
+    auth: &Auth,
+    mut request: Request,
+    request_is_chunked: bool,
+) -> Result<Response> {
+    let rejected = request
+        .header(headers::AUTHORIZATION)
+        .map(|values| values.last().to_string());
+    let rejected = match rejected {
+        Some(rejected) if !request_is_chunked && matches!(auth, Auth::OAuth2(_)) => rejected,
+        _ => return client.send(request).await,
+    };
+    // keep the body around for a possible retry
+    let body = request.take_body().into_bytes().await?;
+    let mut retry = request.clone();
+    request.set_body(body.clone());
+    let response = client.send(request).await?;
+    if response.status() == StatusCode::Unauthorized {
+        if let Some(auth_header) = auth.refresh(&rejected).await? {
+            debug!("Retrying request with refreshed credentials after a 401");
+            retry.insert_header(headers::AUTHORIZATION, auth_header);
+            retry.set_body(body);
+            return client.send(retry).await;
+        }
+    }
+    Ok(response)
+}
+
 #[async_trait::async_trait()]
 impl Sink for HttpRequestSink {
     async fn connect(&mut self, _ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
         let timeout = self.config.timeout.map(Duration::from_nanos);
-        let tls_config = self.tls_client_config.as_ref().cloned().map(Arc::new);
-        let client_config = http_client::Config::new()
-            .set_http_keep_alive(true) // TODO: make configurable, maybe some people don't want that
-            .set_tcp_no_delay(true)
-            .set_timeout(timeout)
-            .set_max_connections_per_host(self.config.concurrency)
-            .set_tls_config(tls_config);
-
-        let client = H1Client::try_from(client_config)
-            .map_err(|e| format!("Invalid HTTP Client config: {e}."))?;
+        // concurrent requests are capped by `concurrency`, with HTTP/2 they share a connection
+        let client = transport::Client::new(
+            self.config.http_version,
+            self.tls_client_config.clone(),
+            self.config.concurrency,
+            timeout,
+        );
         self.client = Some(Arc::new(client));
 
         Ok(true)
@@ -275,6 +360,7 @@ impl Sink for HttpRequestSink {
                 None
             };
             let mut origin_uri = self.origin_uri.clone();
+            let auth = self.config.auth.clone();
             let ingest_ns = event.ingest_ns;
 
             // take the metadata from the first element of the batch
@@ -293,7 +379,8 @@ impl Sink for HttpRequestSink {
                     &self.codec_map,
                     &self.config,
                     &self.configured_codec,
-                ),
+                )
+                .await,
                 "Error turning event into an HTTP Request",
             )?;
             let configured_codec = self.configured_codec.clone();
@@ -331,7 +418,7 @@ impl Sink for HttpRequestSink {
                         .path_segments()
                         .map(|iter| iter.map(ToString::to_string).collect::<Vec<_>>())
                         .unwrap_or_default();
-                    match client.send(request).await {
+                    match send(&client, &auth, request, request_is_chunked).await {
                         Ok(mut response) => {
                             let response_meta = extract_response_meta(&response);
                             let mut meta = send_ctx.meta(literal!({
@@ -0,0 +1,578 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Polling mode of the `http_client`
+//!
+//! Besides sending requests for the events arriving at its sink, the connector requests `url`
+//! on a schedule, either every `interval` nanoseconds or according to a `cron` expression.
+//! Paginated responses are followed until the last page, emitting every page, or every item
+//! of a page, as an event. The position of the next page is persisted in `state_file`, so a
+//! restarted connector resumes where it stopped.
+//!
+//! ```js
+//! "poll": {
+//!   "interval": 60000000000,
+//!   "pagination": {"type": "cursor", "field": "meta.next_cursor", "param": "cursor"},
+//!   "emit": "item",
+//!   "items": "data",
+//!   "state_file": "/var/lib/tremor/api-poll.json"
+//! }
+//! ```
+
+use super::{send, Config};
+use crate::connectors::impls::http::meta::{extract_request_meta, extract_response_meta};
+use crate::connectors::impls::http::transport;
+use crate::connectors::prelude::*;
+use crate::connectors::utils::mime::MimeCodecMap;
+use async_std::channel::Sender;
+use async_std::path::Path;
+use either::Either;
+use http_types::{headers, Method, Request};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PollConfig {
+    /// poll every `interval` nanoseconds, starting right after connecting
+    #[serde(default = "Default::default")]
+    interval: Option<u64>,
+    /// poll according to a cron expression, like the entries of `crononome`
+    #[serde(default = "Default::default")]
+    cron: Option<String>,
+    /// HTTP method of the poll requests
+    #[serde(default = "default_method")]
+    method: Method,
+    /// how to get to the next page
+    #[serde(default = "Default::default")]
+    pagination: Pagination,
+    /// emit every `page` as one event or every `item` of a page as one event
+    #[serde(default = "Default::default")]
+    emit: Emit,
+    /// dot-separated path to the items of a JSON page, the page itself is the array of items if not set
+    #[serde(default = "Default::default")]
+    items: Option<String>,
+    /// file to persist the position of the next page in
+    #[serde(default = "Default::default")]
+    state_file: Option<String>,
+    /// maximum number of pages fetched in one poll, the next poll continues with the following page
+    #[serde(default = "default_max_pages")]
+    max_pages: usize,
+}
+
+fn default_method() -> Method {
+    Method::Get
+}
+
+fn default_max_pages() -> usize {
+    100
+}
+
+impl PollConfig {
+    /// validates the config and compiles its schedule
+    pub(super) fn schedule(&self) -> Result<Schedule> {
+        if self.max_pages == 0 {
+            return Err("`max_pages` must be greater than 0".into());
+        }
+        match (self.interval, self.cron.as_ref()) {
+            (Some(0), None) => Err("`interval` must be greater than 0".into()),
+            (Some(interval), None) => Ok(Schedule::Interval(Duration::from_nanos(interval))),
+            (None, Some(expr)) => Ok(Schedule::Cron(cron::Schedule::from_str(expr)?)),
+            _ => Err("Exactly one of `interval` or `cron` needs to be set for polling".into()),
+        }
+    }
+}
+
+/// How to find the next page of a response
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Pagination {
+    /// every poll fetches a single page
+    None,
+    /// follow the `rel="next"` url of the `Link` header, the next poll starts over at `url`
+    Link,
+    /// read the cursor of the next page from `field` of the JSON page and send it as query
+    /// parameter `param`, once there is no cursor or item left the next poll resumes from the
+    /// last cursor, fetching the last page again if it came without a new cursor
+    Cursor {
+        /// dot-separated path to the cursor in the JSON page
+        field: String,
+        /// query parameter carrying the cursor
+        param: String,
+    },
+    /// page through with offset and limit query parameters until a page has less than `limit`
+    /// items, the next poll continues after the last item seen
+    Offset {
+        #[serde(default = "default_offset_param")]
+        offset_param: String,
+        #[serde(default = "default_limit_param")]
+        limit_param: String,
+        /// number of items per page
+        limit: u64,
+    },
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+fn default_offset_param() -> String {
+    "offset".to_string()
+}
+
+fn default_limit_param() -> String {
+    "limit".to_string()
+}
+
+/// What becomes an event
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Emit {
+    /// each page, decoded with the configured codec
+    Page,
+    /// each item of a JSON page
+    Item,
+}
+
+impl Default for Emit {
+    fn default() -> Self {
+        Self::Page
+    }
+}
+
+#[derive(Clone)]
+pub(super) enum Schedule {
+    Interval(Duration),
+    Cron(cron::Schedule),
+}
+
+impl Schedule {
+    /// Waits for the next poll, returns `false` if there is none.
+    /// An interval polls right away the first time.
+    async fn wait(&self, first: bool) -> bool {
+        match self {
+            Schedule::Interval(interval) => {
+                if !first {
+                    async_std::task::sleep(*interval).await;
+                }
+                true
+            }
+            Schedule::Cron(schedule) => {
+                if let Some(at) = schedule.upcoming(chrono::Utc).next() {
+                    let wait = (at - chrono::Utc::now()).to_std().unwrap_or_default();
+                    async_std::task::sleep(wait).await;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Position of the next page, this is what is persisted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Position {
+    Url(String),
+    Cursor(String),
+    Offset(u64),
+}
+
+/// Outcome of fetching a page
+enum Next {
+    /// there is another page
+    Page(Position),
+    /// this was the last page, where to start with the next poll
+    Done(Option<Position>),
+}
+
+#[derive(Clone)]
+pub(super) struct Poller {
+    config: Config,
+    poll: PollConfig,
+    schedule: Schedule,
+    tls_client_config: Option<rustls::ClientConfig>,
+    codec_map: Arc<MimeCodecMap>,
+    configured_codec: String,
+    tx: Sender<SourceReply>,
+}
+
+impl Poller {
+    pub(super) fn new(
+        config: Config,
+        poll: PollConfig,
+        schedule: Schedule,
+        tls_client_config: Option<rustls::ClientConfig>,
+        codec_map: Arc<MimeCodecMap>,
+        configured_codec: String,
+        tx: Sender<SourceReply>,
+    ) -> Self {
+        Self {
+            config,
+            poll,
+            schedule,
+            tls_client_config,
+            codec_map,
+            configured_codec,
+            tx,
+        }
+    }
+
+    /// Polls until there is no poll scheduled anymore
+    pub(super) async fn run(self, ctx: SourceContext) -> Result<()> {
+        let client = transport::Client::new(
+            self.config.http_version,
+            self.tls_client_config.clone(),
+            1,
+            self.config.timeout.map(Duration::from_nanos),
+        );
+        let mut position = self.load().await?;
+        let mut first = true;
+        while self.schedule.wait(first).await {
+            first = false;
+            for page in 0..self.poll.max_pages {
+                let next = match self.fetch(&ctx, &client, position.as_ref(), page).await {
+                    Ok(next) => next,
+                    Err(e) => {
+                        // the next poll retries the same page
+                        warn!("{ctx} Error polling {}: {e}", self.config.url);
+                        break;
+                    }
+                };
+                let done = match next {
+                    Next::Page(next) => {
+                        position = Some(next);
+                        false
+                    }
+                    Next::Done(next) => {
+                        position = next;
+                        true
+                    }
+                };
+                if let Err(e) = self.store(position.as_ref()).await {
+                    warn!("{ctx} Error persisting the poll position: {e}");
+                }
+                if done {
+                    break;
+                }
+            }
+        }
+        info!("{ctx} No more polls scheduled.");
+        Ok(())
+    }
+
+    fn url(&self, position: Option<&Position>) -> Result<url::Url> {
+        let mut url = self.config.url.url().clone();
+        match (position, &self.poll.pagination) {
+            (Some(Position::Url(next)), Pagination::Link) => url = url::Url::parse(next)?,
+            (Some(Position::Cursor(cursor)), Pagination::Cursor { param, .. }) => {
+                set_query_param(&mut url, param, cursor);
+            }
+            (
+                position,
+                Pagination::Offset {
+                    offset_param,
+                    limit_param,
+                    limit,
+                },
+            ) => {
+                let offset = offset(position);
+                set_query_param(&mut url, offset_param, &offset.to_string());
+                set_query_param(&mut url, limit_param, &limit.to_string());
+            }
+            // a position of a different pagination, e.g. after a config change
+            _ => (),
+        }
+        Ok(url)
+    }
+
+    async fn request(&self, url: url::Url) -> Result<Request> {
+        let mut request = Request::new(self.poll.method, url);
+        for (name, values) in &self.config.headers {
+            match &values.0 {
+                Either::Left(values) => {
+                    for value in values {
+                        request.append_header(name.as_str(), value.as_str());
+                    }
+                }
+                Either::Right(value) => {
+                    request.append_header(name.as_str(), value.as_str());
+                }
+            }
+        }
+        if let Some(auth_header) = self.config.auth.header_value().await? {
+            request.insert_header(headers::AUTHORIZATION, auth_header);
+        }
+        Ok(request)
+    }
+
+    /// Fetches and emits a single page
+    #[allow(clippy::too_many_lines)]
+    async fn fetch(
+        &self,
+        ctx: &SourceContext,
+        client: &transport::Client,
+        position: Option<&Position>,
+        page: usize,
+    ) -> Result<Next> {
+        let url = self.url(position)?;
+        let request = self.request(url.clone()).await?;
+        let req_meta = extract_request_meta(&request);
+        let mut response = send(client, &self.config.auth, request, false).await?;
+        if !response.status().is_success() {
+            return Err(format!("Unexpected response status {}", response.status()).into());
+        }
+        let next_link = response
+            .header("Link")
+            .and_then(|values| values.iter().find_map(|value| next_link(value.as_str())))
+            .map(|next| url.join(next))
+            .transpose()?;
+        let response_meta = extract_response_meta(&response);
+        let codec_overwrite = response
+            .content_type()
+            .and_then(|mime| self.codec_map.get_codec_name(mime.essence()))
+            .filter(|codec| *codec != &self.configured_codec)
+            .cloned();
+        let data = response.body_bytes().await?;
+
+        let origin_uri = EventOriginUri {
+            scheme: "http_client".to_string(),
+            host: url.host_str().unwrap_or_default().to_string(),
+            port: url.port_or_known_default(),
+            path: url
+                .path_segments()
+                .map(|segments| segments.map(ToString::to_string).collect())
+                .unwrap_or_default(),
+        };
+        let meta = ctx.meta(literal!({
+            "request": req_meta,
+            "response": response_meta,
+            "page": page
+        }));
+
+        // only look into the page if we have to
+        let json = self.poll.emit == Emit::Item
+            || matches!(
+                self.poll.pagination,
+                Pagination::Cursor { .. } | Pagination::Offset { .. }
+            );
+        let (num_items, cursor) = if json {
+            let mut body = data.clone();
+            let page = tremor_value::parse_to_value(&mut body)?.into_static();
+            let items = self
+                .poll
+                .items
+                .as_deref()
+                .map_or(Some(&page), |path| lookup(&page, path))
+                .and_then(Value::as_array);
+            let cursor = match &self.poll.pagination {
+                Pagination::Cursor { field, .. } => lookup(&page, field).and_then(|cursor| {
+                    cursor
+                        .as_str()
+                        .map(ToString::to_string)
+                        .or_else(|| cursor.as_u64().map(|cursor| cursor.to_string()))
+                        .filter(|cursor| !cursor.is_empty())
+                }),
+                _ => None,
+            };
+            if self.poll.emit == Emit::Item {
+                for item in items.ok_or("No items found in the page")? {
+                    self.tx
+                        .send(SourceReply::Structured {
+                            origin_uri: origin_uri.clone(),
+                            payload: (item.clone(), meta.clone()).into(),
+                            stream: DEFAULT_STREAM_ID,
+                            port: None,
+                        })
+                        .await?;
+                }
+            }
+            (items.map(Vec::len), cursor)
+        } else {
+            (None, None)
+        };
+        if self.poll.emit == Emit::Page {
+            self.tx
+                .send(SourceReply::Data {
+                    origin_uri,
+                    data,
+                    meta: Some(meta),
+                    stream: None, // every page is a discrete unit
+                    port: None,
+                    codec_overwrite,
+                })
+                .await?;
+        }
+
+        Ok(match &self.poll.pagination {
+            Pagination::None => Next::Done(None),
+            Pagination::Link => next_link.map_or(Next::Done(None), |next| {
+                Next::Page(Position::Url(next.to_string()))
+            }),
+            Pagination::Cursor { .. } => match (cursor, num_items) {
+                (Some(cursor), num_items) if num_items != Some(0) => {
+                    Next::Page(Position::Cursor(cursor))
+                }
+                (cursor, _) => {
+                    Next::Done(cursor.map(Position::Cursor).or_else(|| position.cloned()))
+                }
+            },
+            Pagination::Offset { limit, .. } => {
+                let num_items = num_items.ok_or("No items found in the page")? as u64;
+                let offset = offset(position) + num_items;
+                if num_items < *limit {
+                    Next::Done(Some(Position::Offset(offset)))
+                } else {
+                    Next::Page(Position::Offset(offset))
+                }
+            }
+        })
+    }
+
+    async fn load(&self) -> Result<Option<Position>> {
+        match self.poll.state_file.as_ref() {
+            Some(path) if Path::new(path).exists().await => {
+                let mut state = async_std::fs::read(path).await?;
+                Ok(simd_json::from_slice(&mut state)?)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Replaces the state file, so it is never left half written
+    async fn store(&self, position: Option<&Position>) -> Result<()> {
+        if let Some(path) = self.poll.state_file.as_ref() {
+            let tmp = format!("{path}.tmp");
+            async_std::fs::write(&tmp, simd_json::to_vec(&position)?).await?;
+            async_std::fs::rename(&tmp, path).await?;
+        }
+        Ok(())
+    }
+}
+
+fn offset(position: Option<&Position>) -> u64 {
+    match position {
+        Some(Position::Offset(offset)) => *offset,
+        _ => 0,
+    }
+}
+
+/// Looks up a dot-separated `path` in `value`
+fn lookup<'value>(value: &'value Value<'static>, path: &str) -> Option<&'value Value<'static>> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+fn set_query_param(url: &mut url::Url, name: &str, value: &str) {
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| key != name)
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(name, value);
+}
+
+/// Target of the `rel="next"` link in a `Link` header value
+fn next_link(links: &str) -> Option<&str> {
+    links.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let target = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+        let is_next = parts
+            .filter_map(|param| param.split_once('='))
+            .any(|(key, value)| {
+                key.trim().eq_ignore_ascii_case("rel")
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .split_ascii_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("next"))
+            });
+        is_next.then(|| target)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_header() {
+        assert_eq!(
+            Some("https://api.example.com/items?page=3"),
+            next_link(
+                r#"<https://api.example.com/items?page=1>; rel="prev", <https://api.example.com/items?page=3>; rel="next""#
+            )
+        );
+        assert_eq!(
+            Some("/items?page=2"),
+            next_link("</items?page=2>; rel=next")
+        );
+        assert_eq!(
+            Some("/b"),
+            next_link(r#"</a>; rel="last", </b>; title="x"; rel="prefetch next""#)
+        );
+        assert_eq!(None, next_link(r#"</a>; rel="last""#));
+        assert_eq!(None, next_link("garbage"));
+    }
+
+    #[test]
+    fn query_params() -> Result<()> {
+        let mut url = url::Url::parse("http://localhost/items?cursor=a&q=snot")?;
+        set_query_param(&mut url, "cursor", "b");
+        assert_eq!("http://localhost/items?q=snot&cursor=b", url.as_str());
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_path() {
+        let page = literal!({"meta": {"next": "abc"}, "data": [1, 2]});
+        assert_eq!(
+            Some("abc"),
+            lookup(&page, "meta.next").and_then(Value::as_str)
+        );
+        assert_eq!(None, lookup(&page, "meta.prev"));
+        assert_eq!(
+            Some(2),
+            lookup(&page, "data")
+                .and_then(Value::as_array)
+                .map(Vec::len)
+        );
+    }
+
+    #[test]
+    fn schedule() -> Result<()> {
+        let poll = |raw: Value<'static>| -> Result<Schedule> {
+            tremor_value::structurize::<PollConfig>(raw)?.schedule()
+        };
+        assert!(matches!(
+            poll(literal!({"interval": 1_000_000_000}))?,
+            Schedule::Interval(_)
+        ));
+        assert!(matches!(
+            poll(literal!({"cron": "0 * * * * * *"}))?,
+            Schedule::Cron(_)
+        ));
+        assert!(poll(literal!({})).is_err());
+        assert!(poll(literal!({"interval": 0})).is_err());
+        assert!(poll(literal!({"interval": 1, "cron": "0 * * * * * *"})).is_err());
+        assert!(poll(literal!({"interval": 1, "max_pages": 0})).is_err());
+        Ok(())
+    }
+}
@@ -45,7 +45,7 @@ pub(crate) struct HttpRequestBuilder {
 
 // TODO: do some deduplication with SinkResponse
 impl HttpRequestBuilder {
-    pub(super) fn new(
+    pub(super) async fn new(
         request_id: RequestId,
         meta: Option<&Value>,
         codec_map: &MimeCodecMap,
@@ -142,7 +142,7 @@ impl HttpRequestBuilder {
             }
         }
         // handle AUTH
-        if let Some(auth_header) = config.auth.as_header_value()? {
+        if let Some(auth_header) = config.auth.header_value().await? {
             request.insert_header(headers::AUTHORIZATION, auth_header);
         }
 
@@ -344,7 +344,8 @@ mod test {
         let configured_codec = "json";
 
         let mut b =
-            HttpRequestBuilder::new(request_id, meta, &codec_map, &config, configured_codec)?;
+            HttpRequestBuilder::new(request_id, meta, &codec_map, &config, configured_codec)
+                .await?;
 
         let r = b.finalize(&mut s).await?.unwrap();
         assert_eq!(r.header("pie").unwrap().iter().count(), 1);
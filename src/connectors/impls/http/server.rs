@@ -12,30 +12,37 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod validation;
+
 use crate::connectors::{
+    impls::sse,
     prelude::*,
-    utils::{mime::MimeCodecMap, tls::TLSServerConfig},
+    utils::{
+        mime::MimeCodecMap,
+        tls::{TLSServerConfig, TlsServer},
+    },
 };
 use crate::{connectors::spawn_task, errors::err_conector_def};
 use async_std::channel::unbounded;
+use async_std::io::ReadExt;
 use async_std::{
     channel::{bounded, Receiver, Sender},
+    net::TcpListener,
     task::JoinHandle,
 };
 use dashmap::DashMap;
 use halfbrown::{Entry, HashMap};
 use http_types::headers::{self, HeaderValue, HeaderValues};
-use http_types::{mime::BYTE_STREAM, Mime, StatusCode};
+use http_types::{mime::BYTE_STREAM, Method, Mime, StatusCode};
 use simd_json::ValueAccess;
 use std::{str::FromStr, sync::Arc};
-use tide::{
-    listener::{Listener, ToListener},
-    Response,
-};
-use tide_rustls::TlsListener;
+use tide::Response;
 use tremor_common::ids::Id;
+use tremor_common::time::nanotime;
+use validation::{Rejection, SignatureConfig, Validator};
 
 use super::meta::{extract_request_meta, BodyData};
+use super::transport::{self, HttpVersion, PeerTls};
 use super::utils::{FixedBodyReader, RequestId, StreamingBodyReader};
 
 #[derive(Deserialize, Debug, Clone)]
@@ -46,10 +53,26 @@ pub(crate) struct Config {
     url: Url,
     /// TLS configuration, if required
     tls: Option<TLSServerConfig>,
+    /// HTTP versions to accept: `auto` accepts HTTP/1.1 and HTTP/2, negotiated
+    /// via ALPN or, without TLS, by the connection preface (h2c)
+    #[serde(default = "Default::default")]
+    http_version: HttpVersion,
     /// custom codecs mapping from mime_type to custom codec name
     /// e.g. for handling `application/json` with the `binary` codec, if desired
     #[serde(default)]
     custom_codecs: HashMap<String, String>,
+    /// methods requests may use, any method if empty
+    #[serde(default = "Default::default")]
+    allowed_methods: Vec<Method>,
+    /// glob patterns of the paths requests may target, any path if empty
+    #[serde(default = "Default::default")]
+    allowed_paths: Vec<String>,
+    /// maximum size of request bodies in bytes
+    #[serde(default = "Default::default")]
+    max_body_size: Option<u64>,
+    /// HMAC signature verification of request bodies, e.g. for webhooks
+    #[serde(default = "Default::default")]
+    signature: Option<SignatureConfig>,
 }
 
 impl ConfigImpl for Config {}
@@ -93,6 +116,13 @@ impl ConnectorBuilder for Builder {
             .map_or_else(|| HttpServer::DEFAULT_CODEC.to_string(), |c| c.name.clone());
         let inflight = Arc::default();
         let codec_map = MimeCodecMap::with_overwrites(&config.custom_codecs);
+        let validator = Validator::new(
+            &config.allowed_methods,
+            &config.allowed_paths,
+            config.max_body_size,
+            config.signature.as_ref(),
+        )
+        .map_err(|e| err_conector_def(id, &e.to_string()))?;
 
         Ok(Box::new(HttpServer {
             config,
@@ -101,6 +131,7 @@ impl ConnectorBuilder for Builder {
             inflight,
             configured_codec,
             codec_map,
+            validator: Arc::new(validator),
         }))
     }
 }
@@ -113,6 +144,7 @@ pub(crate) struct HttpServer {
     inflight: Arc<DashMap<RequestId, Sender<Response>>>,
     configured_codec: String,
     codec_map: MimeCodecMap,
+    validator: Arc<Validator>,
 }
 
 impl HttpServer {
@@ -145,8 +177,10 @@ impl Connector for HttpServer {
             origin_uri: self.origin_uri.clone(),
             server_task: None,
             tls_server_config: self.tls_server_config.clone(),
+            http_version: self.config.http_version,
             configured_codec: self.configured_codec.clone(),
             codec_map: self.codec_map.clone(),
+            validator: self.validator.clone(),
         };
         builder.spawn(source, source_context).map(Some)
     }
@@ -174,8 +208,10 @@ struct HttpServerSource {
     request_tx: Sender<RawRequestData>,
     server_task: Option<JoinHandle<()>>,
     tls_server_config: Option<TLSServerConfig>,
+    http_version: HttpVersion,
     configured_codec: String,
     codec_map: MimeCodecMap,
+    validator: Arc<Validator>,
 }
 
 #[async_trait::async_trait()]
@@ -209,40 +245,31 @@ impl Source for HttpServerSource {
         let tx = self.request_tx.clone();
 
         let ctx = ctx.clone();
-        let tls_server_config = self.tls_server_config.clone();
+        let tls_server = match self.tls_server_config.as_ref() {
+            Some(tls_config) => Some(TlsServer::new(tls_config).await?),
+            None => None,
+        };
+        let http_version = self.http_version;
+        let validator = self.validator.clone();
 
         // Server task - this is the main receive loop for http server instances
         self.server_task = Some(spawn_task(ctx.clone(), async move {
-            if let Some(tls_server_config) = tls_server_config {
-                let mut endpoint = tide::Server::with_state(HttpServerState::new(tx, ctx.clone()));
-                endpoint.at("/").all(handle_request);
-                endpoint.at("/*").all(handle_request);
-
-                let mut listener = TlsListener::build()
-                    .addrs(&hostport)
-                    .cert(&tls_server_config.cert)
-                    .key(&tls_server_config.key)
-                    .finish()?;
-                listener.bind(endpoint).await?;
-                if let Some(info) = listener.info().into_iter().next() {
-                    info!(
-                        "{ctx} Listening for HTTPS requests on {}",
-                        info.connection()
-                    );
-                }
-                listener.accept().await?;
+            let mut endpoint =
+                tide::Server::with_state(HttpServerState::new(tx, ctx.clone(), validator));
+            endpoint.at("/").all(handle_request);
+            endpoint.at("/*").all(handle_request);
+
+            let listener = TcpListener::bind(&hostport).await?;
+            let scheme = if tls_server.is_some() {
+                "HTTPS"
             } else {
-                let mut endpoint = tide::Server::with_state(HttpServerState::new(tx, ctx.clone()));
-                endpoint.at("/").all(handle_request);
-                endpoint.at("/*").all(handle_request);
-                let mut listener = (&hostport).to_listener()?;
-                listener.bind(endpoint).await?;
-                if let Some(info) = listener.info().into_iter().next() {
-                    info!("{ctx} Listening for HTTP requests on {}", info.connection());
-                }
-                listener.accept().await?;
+                "HTTP"
             };
-            Ok(())
+            info!(
+                "{ctx} Listening for {scheme} requests on {}",
+                listener.local_addr()?
+            );
+            transport::serve(listener, tls_server, http_version, endpoint).await
         }));
 
         Ok(true)
@@ -309,14 +336,53 @@ impl Source for HttpServerSource {
     fn asynchronous(&self) -> bool {
         true
     }
+
+    fn metrics(&mut self, timestamp: u64, ctx: &SourceContext) -> Vec<EventPayload> {
+        if self.validator.is_active() {
+            vec![self.validator.metrics(ctx.alias(), timestamp)]
+        } else {
+            vec![]
+        }
+    }
 }
 
 struct HttpServerSink {
     inflight: Arc<DashMap<RequestId, Sender<Response>>>,
+    /// responses that are kept open to push server-sent events
+    event_streams: HashMap<RequestId, EventStream>,
     codec_map: MimeCodecMap,
     configured_codec: String,
 }
 
+/// An open `text/event-stream` response
+struct EventStream {
+    tx: Sender<Vec<u8>>,
+    codec_overwrite: Option<String>,
+}
+
+impl EventStream {
+    /// Encodes `value` as a single server-sent event and pushes it to the client
+    async fn send<'event>(
+        &self,
+        request_id: RequestId,
+        value: &'event Value<'event>,
+        sse_meta: Option<&'event Value<'event>>,
+        ingest_ns: u64,
+        serializer: &mut EventSerializer,
+    ) -> Result<()> {
+        let data = serializer
+            .serialize_for_stream_with_codec(
+                value,
+                ingest_ns,
+                request_id.get(),
+                self.codec_overwrite.as_ref(),
+            )?
+            .concat();
+        self.tx.send(sse::encode(&data, sse_meta)).await?;
+        Ok(())
+    }
+}
+
 impl HttpServerSink {
     const ERROR_MSG_EXTRACT_VALUE: &'static str = "Error turning Event into HTTP response";
     const ERROR_MSG_APPEND_RESPONSE: &'static str = "Error appending batched data to HTTP response";
@@ -328,10 +394,40 @@ impl HttpServerSink {
     ) -> Self {
         Self {
             inflight,
+            event_streams: HashMap::new(),
             codec_map,
             configured_codec,
         }
     }
+
+    /// Sends an event to the open event stream with the given id, the stream is
+    /// closed if the client went away
+    async fn send_to_stream<'event>(
+        &mut self,
+        rid: RequestId,
+        value: &'event Value<'event>,
+        sse_meta: Option<&'event Value<'event>>,
+        ingest_ns: u64,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+    ) {
+        if let Some(stream) = self.event_streams.get(&rid) {
+            if let Err(e) = stream
+                .send(rid, value, sse_meta, ingest_ns, serializer)
+                .await
+            {
+                debug!("{ctx} Closing event stream for request_id {rid}: {e}");
+                self.close_stream(rid, serializer);
+            }
+        }
+    }
+
+    fn close_stream(&mut self, rid: RequestId, serializer: &mut EventSerializer) {
+        if let Some(stream) = self.event_streams.remove(&rid) {
+            stream.tx.close();
+            serializer.drop_stream(rid.get());
+        }
+    }
 }
 
 #[async_trait::async_trait()]
@@ -356,8 +452,27 @@ impl Sink for HttpServerSink {
         // - update SinkResponse for each element of the batch
         // - send response immediately in case of chunked encoding
         let mut response_map = HashMap::new();
+        let mut streamed = false;
         for (value, meta) in event.value_meta_iter() {
             let http_meta = ctx.extract_meta(meta);
+            let sse_meta = meta.get("sse");
+
+            // events for a request that is answered with an event stream are pushed right away
+            let stream_rid = http_meta
+                .get_u64("request_id")
+                .or_else(|| {
+                    min_pull_id
+                        .zip(max_pull_id)
+                        .filter(|(min, max)| min == max)
+                        .map(|(min, _)| min)
+                })
+                .map(RequestId::new);
+            if let Some(rid) = stream_rid.filter(|rid| self.event_streams.contains_key(rid)) {
+                self.send_to_stream(rid, value, sse_meta, ingest_ns, ctx, serializer)
+                    .await;
+                streamed = true;
+                continue;
+            }
 
             // first try to extract request_id from event batch element metadata
             if let Some(rid) = http_meta.get_u64("request_id").map(RequestId::new) {
@@ -370,6 +485,7 @@ impl Sink for HttpServerSink {
                                     rid,
                                     sender,
                                     http_meta,
+                                    sse_meta,
                                     &self.codec_map,
                                     &self.configured_codec,
                                 )
@@ -378,7 +494,9 @@ impl Sink for HttpServerSink {
                             )?;
 
                             ctx.bail_err(
-                                response.append(value, ingest_ns, serializer).await,
+                                response
+                                    .append(value, sse_meta, ingest_ns, serializer)
+                                    .await,
                                 Self::ERROR_MSG_APPEND_RESPONSE,
                             )?;
                             k.insert(response);
@@ -391,7 +509,9 @@ impl Sink for HttpServerSink {
                     }
                     Entry::Occupied(mut o) => {
                         ctx.bail_err(
-                            o.get_mut().append(value, ingest_ns, serializer).await,
+                            o.get_mut()
+                                .append(value, sse_meta, ingest_ns, serializer)
+                                .await,
                             Self::ERROR_MSG_APPEND_RESPONSE,
                         )?;
                     }
@@ -412,6 +532,7 @@ impl Sink for HttpServerSink {
                                             rid,
                                             sender,
                                             http_meta,
+                                            sse_meta,
                                             &self.codec_map,
                                             &self.configured_codec,
                                         )
@@ -420,7 +541,9 @@ impl Sink for HttpServerSink {
                                     )?;
 
                                     ctx.bail_err(
-                                        response.append(value, ingest_ns, serializer).await,
+                                        response
+                                            .append(value, sse_meta, ingest_ns, serializer)
+                                            .await,
                                         Self::ERROR_MSG_APPEND_RESPONSE,
                                     )?;
                                     k.insert(response);
@@ -431,7 +554,9 @@ impl Sink for HttpServerSink {
                             }
                             Entry::Occupied(mut o) => {
                                 ctx.bail_err(
-                                    o.get_mut().append(value, ingest_ns, serializer).await,
+                                    o.get_mut()
+                                        .append(value, sse_meta, ingest_ns, serializer)
+                                        .await,
                                     Self::ERROR_MSG_APPEND_RESPONSE,
                                 )?;
                             }
@@ -449,6 +574,7 @@ impl Sink for HttpServerSink {
                                                 rid,
                                                 sender,
                                                 http_meta,
+                                                sse_meta,
                                                 &self.codec_map,
                                                 &self.configured_codec,
                                             )
@@ -457,7 +583,9 @@ impl Sink for HttpServerSink {
                                         )?;
 
                                         ctx.bail_err(
-                                            response.append(value, ingest_ns, serializer).await,
+                                            response
+                                                .append(value, sse_meta, ingest_ns, serializer)
+                                                .await,
                                             Self::ERROR_MSG_APPEND_RESPONSE,
                                         )?;
                                         k.insert(response);
@@ -468,13 +596,25 @@ impl Sink for HttpServerSink {
                                 }
                                 Entry::Occupied(mut o) => {
                                     ctx.bail_err(
-                                        o.get_mut().append(value, ingest_ns, serializer).await,
+                                        o.get_mut()
+                                            .append(value, sse_meta, ingest_ns, serializer)
+                                            .await,
                                         Self::ERROR_MSG_APPEND_RESPONSE,
                                     )?;
                                 }
                             }
                         }
                     }
+                    None if sse_meta.get_bool("broadcast").unwrap_or_default() => {
+                        // only explicitly marked server-sent events without a request
+                        // are broadcast to all open event streams
+                        let rids: Vec<RequestId> = self.event_streams.keys().copied().collect();
+                        for rid in rids {
+                            self.send_to_stream(rid, value, sse_meta, ingest_ns, ctx, serializer)
+                                .await;
+                        }
+                        streamed = true;
+                    }
                     None => {
                         // unroutable
                         warn!("{ctx} Unable to extract request_id from event. Dropping response.");
@@ -483,16 +623,19 @@ impl Sink for HttpServerSink {
             }
         }
 
-        if response_map.is_empty() {
+        if response_map.is_empty() && !streamed {
             error!("{ctx} No request context found for event.");
             return Ok(SinkReply::FAIL);
         }
         for (rid, response) in response_map {
             debug!("{ctx} Sending response for request_id {rid}");
-            ctx.swallow_err(
-                response.finalize(serializer).await,
-                &format!("Error sending response for request_id {rid}"),
-            );
+            match response.finalize(serializer).await {
+                Ok(Some(event_stream)) => {
+                    self.event_streams.insert(rid, event_stream);
+                }
+                Ok(None) => (),
+                Err(e) => error!("{ctx} Error sending response for request_id {rid}: {e}"),
+            }
         }
         Ok(SinkReply::NONE)
     }
@@ -501,10 +644,19 @@ impl Sink for HttpServerSink {
         &mut self,
         _signal: Event,
         _ctx: &SinkContext,
-        _serializer: &mut EventSerializer,
+        serializer: &mut EventSerializer,
     ) -> Result<SinkReply> {
         // clean out closed channels
         self.inflight.retain(|_key, sender| !sender.is_closed());
+        let closed: Vec<RequestId> = self
+            .event_streams
+            .iter()
+            .filter(|(_rid, stream)| stream.tx.is_closed())
+            .map(|(rid, _stream)| *rid)
+            .collect();
+        for rid in closed {
+            self.close_stream(rid, serializer);
+        }
         Ok(SinkReply::NONE)
     }
 
@@ -519,6 +671,8 @@ struct SinkResponse {
     body_data: BodyData,
     tx: Sender<Response>,
     codec_overwrite: Option<String>,
+    /// the response is a `text/event-stream`, every value is sent as a separate event
+    event_stream: bool,
 }
 
 impl SinkResponse {
@@ -526,6 +680,7 @@ impl SinkResponse {
         request_id: RequestId,
         tx: Sender<Response>,
         http_meta: Option<&Value<'event>>,
+        sse_meta: Option<&Value<'event>>,
         codec_map: &MimeCodecMap,
         configured_codec: &String,
     ) -> Result<Self> {
@@ -578,6 +733,11 @@ impl SinkResponse {
             .map_or(false, |te| te.as_str() == "chunked");
 
         let header_content_type = res.content_type();
+        // respond with an event stream if explicitly requested via content-type
+        // or if the response carries `$sse` metadata and no other content-type
+        let event_stream = header_content_type
+            .as_ref()
+            .map_or(sse_meta.is_some(), |mime| mime.essence() == sse::MIME_TYPE);
 
         let codec_overwrite = header_content_type
             .as_ref()
@@ -605,12 +765,15 @@ impl SinkResponse {
 
         // set content-type if not explicitly set in the response headers meta
         // either from the configured or overwritten codec
-        if res.content_type().is_none() {
+        if event_stream {
+            res.set_content_type(Mime::from_str(sse::MIME_TYPE)?);
+            res.insert_header(headers::CACHE_CONTROL, "no-cache");
+        } else if res.content_type().is_none() {
             if let Some(ct) = content_type {
                 res.set_content_type(ct);
             }
         }
-        let (body_data, res) = if chunked {
+        let (body_data, res) = if chunked || event_stream {
             let (chunk_tx, chunk_rx) = unbounded();
             let streaming_reader = StreamingBodyReader::new(chunk_rx);
             res.set_body(tide::Body::from_reader(streaming_reader, None));
@@ -628,12 +791,14 @@ impl SinkResponse {
             body_data,
             tx,
             codec_overwrite,
+            event_stream,
         })
     }
 
     async fn append<'event>(
         &mut self,
         value: &'event Value<'event>,
+        sse_meta: Option<&'event Value<'event>>,
         ingest_ns: u64,
         serializer: &mut EventSerializer,
     ) -> Result<()> {
@@ -643,7 +808,12 @@ impl SinkResponse {
             self.request_id.get(),
             self.codec_overwrite.as_ref(),
         )?;
-        self.append_data(chunks).await
+        if self.event_stream {
+            self.append_data(vec![sse::encode(&chunks.concat(), sse_meta)])
+                .await
+        } else {
+            self.append_data(chunks).await
+        }
     }
 
     async fn append_data(&mut self, mut chunks: Vec<Vec<u8>>) -> Result<()> {
@@ -660,7 +830,18 @@ impl SinkResponse {
 
     /// Consume self and finalize and send the response.
     /// In the chunked case we have already sent it before.
-    async fn finalize(mut self, serializer: &mut EventSerializer) -> Result<()> {
+    /// Event streams are kept open and returned.
+    async fn finalize(mut self, serializer: &mut EventSerializer) -> Result<Option<EventStream>> {
+        if self.event_stream {
+            self.tx.close();
+            return Ok(match self.body_data {
+                BodyData::Chunked(tx) => Some(EventStream {
+                    tx,
+                    codec_overwrite: self.codec_overwrite,
+                }),
+                BodyData::Data(_) => None,
+            });
+        }
         // finalize the stream
         let rest = serializer.finish_stream(self.request_id.get())?;
         if !rest.is_empty() {
@@ -686,7 +867,7 @@ impl SinkResponse {
         }
         // close the channel. we are done here
         self.tx.close();
-        Ok(())
+        Ok(None)
     }
 }
 
@@ -694,11 +875,12 @@ impl SinkResponse {
 struct HttpServerState {
     tx: Sender<RawRequestData>,
     ctx: SourceContext,
+    validator: Arc<Validator>,
 }
 
 impl HttpServerState {
-    fn new(tx: Sender<RawRequestData>, ctx: SourceContext) -> Self {
-        Self { tx, ctx }
+    fn new(tx: Sender<RawRequestData>, ctx: SourceContext, validator: Arc<Validator>) -> Self {
+        Self { tx, ctx, validator }
     }
 }
 
@@ -724,10 +906,52 @@ async fn handle_request(mut req: tide::Request<HttpServerState>) -> tide::Result
         result
     }
 }
+/// Answers a request that failed validation, it never reaches the pipeline
+fn reject(req: &tide::Request<HttpServerState>, rejection: &Rejection) -> tide::Response {
+    let state = req.state();
+    debug!(
+        "{ctx} Rejecting HTTP request {} {}: {rejection:?}",
+        req.method(),
+        req.url().path(),
+        ctx = state.ctx
+    );
+    let mut response = tide::Response::new(rejection.status());
+    if *rejection == Rejection::Method {
+        let allowed: Vec<String> = state
+            .validator
+            .allowed_methods()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        response.insert_header(headers::ALLOW, allowed.join(", "));
+    }
+    response
+}
+
 async fn _handle_request(req: &mut tide::Request<HttpServerState>) -> tide::Result<tide::Response> {
-    let request_meta = extract_request_meta(req.as_ref());
+    let validator = req.state().validator.clone();
+    if let Err(rejection) = validator.check_head(req.method(), req.url().path(), req.len()) {
+        return Ok(reject(req, &rejection));
+    }
+    let mut request_meta = extract_request_meta(req.as_ref());
+    if let Some(PeerTls(peer_tls)) = req.ext::<PeerTls>() {
+        request_meta.try_insert("peer", literal!({ "tls": peer_tls.clone() }));
+    }
     let content_type = req.content_type().map(|mime| mime.essence().to_string());
-    let data = req.body_bytes().await?;
+    let data = if let Some(max_body_size) = validator.max_body_size() {
+        // read one byte more to detect oversized bodies without a content-length
+        let mut data = Vec::new();
+        req.take_body()
+            .take(max_body_size.saturating_add(1))
+            .read_to_end(&mut data)
+            .await?;
+        data
+    } else {
+        req.body_bytes().await?
+    };
+    if let Err(rejection) = validator.check_body(req.as_ref(), &data, nanotime()) {
+        return Ok(reject(req, &rejection));
+    }
 
     // Dispatch
     let (response_tx, response_rx) = bounded(1);
@@ -0,0 +1,498 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validation of incoming requests of the `http_server`
+//!
+//! Requests with a method or path that is not allowed, with a body exceeding `max_body_size`
+//! or without a valid HMAC signature are answered right away and never reach the pipeline.
+//!
+//! Signatures are computed over `payload`, in which `{body}` is replaced by the raw request body
+//! and `{timestamp}` by the timestamp of the request. Common webhook providers are covered like this:
+//!
+//! ```js
+//! // GitHub
+//! "signature": {"header": "X-Hub-Signature-256", "prefix": "sha256=", "secret": "..."}
+//! // Slack
+//! "signature": {
+//!   "header": "X-Slack-Signature", "prefix": "v0=", "secret": "...",
+//!   "timestamp_header": "X-Slack-Request-Timestamp", "payload": "v0:{timestamp}:{body}"
+//! }
+//! // Stripe
+//! "signature": {
+//!   "header": "Stripe-Signature", "signature_key": "v1", "secret": "...",
+//!   "timestamp_key": "t", "payload": "{timestamp}.{body}"
+//! }
+//! ```
+
+use crate::connectors::prelude::*;
+use crate::connectors::utils::metrics::make_metrics_payload;
+use beef::Cow;
+use halfbrown::HashMap;
+use http_types::{headers::Headers, Method, StatusCode};
+use ring::hmac;
+use std::sync::atomic::AtomicU64;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SignatureConfig {
+    /// header carrying the signature
+    header: String,
+    /// hash function of the HMAC
+    #[serde(default = "Default::default")]
+    algorithm: Algorithm,
+    /// shared secret
+    secret: String,
+    /// encoding of the signature in the header
+    #[serde(default = "Default::default")]
+    encoding: Encoding,
+    /// prefix of the signature in the header, e.g. `sha256=`
+    #[serde(default = "Default::default")]
+    prefix: String,
+    /// for headers made of comma-separated `key=value` pairs, the key of the signatures
+    #[serde(default = "Default::default")]
+    signature_key: Option<String>,
+    /// header carrying the request timestamp as unix seconds
+    #[serde(default = "Default::default")]
+    timestamp_header: Option<String>,
+    /// key of the request timestamp in a signature header made of `key=value` pairs
+    #[serde(default = "Default::default")]
+    timestamp_key: Option<String>,
+    /// maximum difference between the request timestamp and now in nanoseconds
+    #[serde(default = "default_tolerance")]
+    tolerance: u64,
+    /// the signed payload
+    #[serde(default = "default_payload")]
+    payload: String,
+}
+
+fn default_tolerance() -> u64 {
+    // 5 minutes
+    300_000_000_000
+}
+
+fn default_payload() -> String {
+    "{body}".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Default for Algorithm {
+    fn default() -> Self {
+        Self::Sha256
+    }
+}
+
+impl Algorithm {
+    fn hmac(self) -> hmac::Algorithm {
+        match self {
+            Algorithm::Sha1 => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            Algorithm::Sha256 => hmac::HMAC_SHA256,
+            Algorithm::Sha512 => hmac::HMAC_SHA512,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Encoding {
+    Hex,
+    Base64,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self::Hex
+    }
+}
+
+/// Why a request was rejected
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Rejection {
+    Method,
+    Path,
+    Size,
+    Signature(&'static str),
+}
+
+impl Rejection {
+    pub(super) fn status(&self) -> StatusCode {
+        match self {
+            Rejection::Method => StatusCode::MethodNotAllowed,
+            Rejection::Path => StatusCode::NotFound,
+            Rejection::Size => StatusCode::PayloadTooLarge,
+            Rejection::Signature(_) => StatusCode::Unauthorized,
+        }
+    }
+}
+
+/// `*` and `?` never match a `/`, so `/hooks/*` does not allow `/hooks/github/nested`
+const PATH_MATCH_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Default)]
+struct Counts {
+    accepted: AtomicU64,
+    method: AtomicU64,
+    path: AtomicU64,
+    size: AtomicU64,
+    signature: AtomicU64,
+}
+
+/// Checks incoming requests against the configured rules
+pub(super) struct Validator {
+    allowed_methods: Vec<Method>,
+    allowed_paths: Vec<glob::Pattern>,
+    max_body_size: Option<u64>,
+    signature: Option<(SignatureConfig, hmac::Key)>,
+    counts: Counts,
+}
+
+impl Validator {
+    pub(super) fn new(
+        allowed_methods: &[Method],
+        allowed_paths: &[String],
+        max_body_size: Option<u64>,
+        signature: Option<&SignatureConfig>,
+    ) -> Result<Self> {
+        let allowed_paths = allowed_paths
+            .iter()
+            .map(|path| glob::Pattern::new(path))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let signature = signature
+            .map(|config| -> Result<_> {
+                if config.signature_key.is_some() && !config.prefix.is_empty() {
+                    return Err("`prefix` and `signature_key` are mutually exclusive".into());
+                }
+                if config.timestamp_key.is_some() && config.signature_key.is_none() {
+                    return Err("`timestamp_key` requires `signature_key`".into());
+                }
+                let key = hmac::Key::new(config.algorithm.hmac(), config.secret.as_bytes());
+                Ok((config.clone(), key))
+            })
+            .transpose()?;
+        Ok(Self {
+            allowed_methods: allowed_methods.to_vec(),
+            allowed_paths,
+            max_body_size,
+            signature,
+            counts: Counts::default(),
+        })
+    }
+
+    /// if there is anything to check at all
+    pub(super) fn is_active(&self) -> bool {
+        !self.allowed_methods.is_empty()
+            || !self.allowed_paths.is_empty()
+            || self.max_body_size.is_some()
+            || self.signature.is_some()
+    }
+
+    pub(super) fn allowed_methods(&self) -> &[Method] {
+        &self.allowed_methods
+    }
+
+    pub(super) fn max_body_size(&self) -> Option<u64> {
+        self.max_body_size
+    }
+
+    /// Checks everything that is known before reading the body
+    pub(super) fn check_head(
+        &self,
+        method: Method,
+        path: &str,
+        content_length: Option<usize>,
+    ) -> std::result::Result<(), Rejection> {
+        if !self.allowed_methods.is_empty() && !self.allowed_methods.contains(&method) {
+            return Err(self.reject(Rejection::Method));
+        }
+        if !self.allowed_paths.is_empty()
+            && !self
+                .allowed_paths
+                .iter()
+                .any(|pattern| pattern.matches_with(path, PATH_MATCH_OPTIONS))
+        {
+            return Err(self.reject(Rejection::Path));
+        }
+        if let Some((max, len)) = self.max_body_size.zip(content_length) {
+            if len as u64 > max {
+                return Err(self.reject(Rejection::Size));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the body, which is read up to `max_body_size + 1` bytes, and its signature
+    pub(super) fn check_body(
+        &self,
+        headers: &Headers,
+        body: &[u8],
+        now_ns: u64,
+    ) -> std::result::Result<(), Rejection> {
+        if let Some(max) = self.max_body_size {
+            if body.len() as u64 > max {
+                return Err(self.reject(Rejection::Size));
+            }
+        }
+        if let Some((config, key)) = self.signature.as_ref() {
+            verify(config, key, headers, body, now_ns).map_err(|r| self.reject(r))?;
+        }
+        self.counts.accepted.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn reject(&self, rejection: Rejection) -> Rejection {
+        let count = match rejection {
+            Rejection::Method => &self.counts.method,
+            Rejection::Path => &self.counts.path,
+            Rejection::Size => &self.counts.size,
+            Rejection::Signature(_) => &self.counts.signature,
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        rejection
+    }
+
+    /// Counts of accepted and rejected requests
+    pub(super) fn metrics(&self, alias: &str, timestamp: u64) -> EventPayload {
+        let mut fields = HashMap::with_capacity(5);
+        for (name, count) in [
+            ("accepted", &self.counts.accepted),
+            ("rejected_method", &self.counts.method),
+            ("rejected_path", &self.counts.path),
+            ("rejected_size", &self.counts.size),
+            ("rejected_signature", &self.counts.signature),
+        ] {
+            fields.insert(
+                Cow::const_str(name),
+                Value::from(count.load(Ordering::Relaxed)),
+            );
+        }
+        let mut tags = HashMap::with_capacity(1);
+        tags.insert(Cow::const_str("connector"), Value::from(alias.to_string()));
+        make_metrics_payload("http_server_requests", fields, tags, timestamp)
+    }
+}
+
+fn header<'headers>(headers: &'headers Headers, name: &str) -> Option<&'headers str> {
+    headers.get(name).map(|values| values.last().as_str())
+}
+
+fn verify(
+    config: &SignatureConfig,
+    key: &hmac::Key,
+    headers: &Headers,
+    body: &[u8],
+    now_ns: u64,
+) -> std::result::Result<(), Rejection> {
+    let value = header(headers, &config.header).ok_or(Rejection::Signature("missing signature"))?;
+    let (signatures, timestamp) = if let Some(signature_key) = config.signature_key.as_ref() {
+        let pairs: Vec<(&str, &str)> = value
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .collect();
+        let signatures: Vec<&str> = pairs
+            .iter()
+            .filter(|(k, _)| *k == signature_key.as_str())
+            .map(|(_, v)| *v)
+            .collect();
+        let timestamp = config.timestamp_key.as_ref().and_then(|timestamp_key| {
+            pairs
+                .iter()
+                .find(|(k, _)| *k == timestamp_key.as_str())
+                .map(|(_, v)| *v)
+        });
+        (signatures, timestamp)
+    } else {
+        let signature = value
+            .trim()
+            .strip_prefix(config.prefix.as_str())
+            .ok_or(Rejection::Signature("missing signature prefix"))?;
+        (vec![signature], None)
+    };
+    let timestamp = match (
+        config.timestamp_header.as_ref(),
+        config.timestamp_key.as_ref(),
+    ) {
+        (Some(timestamp_header), _) => Some(
+            header(headers, timestamp_header).ok_or(Rejection::Signature("missing timestamp"))?,
+        ),
+        (None, Some(_)) => Some(timestamp.ok_or(Rejection::Signature("missing timestamp"))?),
+        (None, None) => None,
+    };
+    if let Some(timestamp) = timestamp {
+        let secs: u64 = timestamp
+            .parse()
+            .map_err(|_| Rejection::Signature("invalid timestamp"))?;
+        let then_ns = secs.saturating_mul(1_000_000_000);
+        let skew = if then_ns > now_ns {
+            then_ns - now_ns
+        } else {
+            now_ns - then_ns
+        };
+        if skew > config.tolerance {
+            return Err(Rejection::Signature("timestamp outside of tolerance"));
+        }
+    }
+
+    let payload = signed_payload(&config.payload, timestamp.unwrap_or_default(), body);
+    let valid = signatures.iter().any(|signature| {
+        let decoded = match config.encoding {
+            Encoding::Hex => hex::decode(signature).ok(),
+            Encoding::Base64 => base64::decode(signature).ok(),
+        };
+        // verified in constant time
+        decoded.map_or(false, |decoded| {
+            hmac::verify(key, &payload, &decoded).is_ok()
+        })
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(Rejection::Signature("invalid signature"))
+    }
+}
+
+/// Fills in `{timestamp}` and `{body}` of the payload template
+fn signed_payload(template: &str, timestamp: &str, body: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(template.len() + body.len());
+    for (i, part) in template.split("{body}").enumerate() {
+        if i > 0 {
+            payload.extend_from_slice(body);
+        }
+        payload.extend_from_slice(part.replace("{timestamp}", timestamp).as_bytes());
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_660_000_000_000_000_000;
+
+    fn validator(signature: Value<'static>) -> Result<Validator> {
+        let config: SignatureConfig = tremor_value::structurize(signature)?;
+        Validator::new(
+            &[Method::Post],
+            &["/hooks/*".to_string()],
+            Some(16),
+            Some(&config),
+        )
+    }
+
+    fn request(headers: &[(&str, String)]) -> http_types::Request {
+        let mut request = http_types::Request::new(Method::Post, "http://localhost/hooks/test");
+        for (name, value) in headers {
+            request.insert_header(*name, value.as_str());
+        }
+        request
+    }
+
+    fn sign(payload: &[u8]) -> Result<String> {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"snot");
+        Ok(hex::encode(hmac::sign(&key, payload)))
+    }
+
+    #[test]
+    fn head() -> Result<()> {
+        let validator = validator(literal!({"header": "X-Signature", "secret": "snot"}))?;
+        assert_eq!(
+            Ok(()),
+            validator.check_head(Method::Post, "/hooks/github", Some(4))
+        );
+        assert_eq!(
+            Err(Rejection::Method),
+            validator.check_head(Method::Get, "/hooks/github", None)
+        );
+        assert_eq!(
+            Err(Rejection::Path),
+            validator.check_head(Method::Post, "/admin", None)
+        );
+        assert_eq!(
+            Err(Rejection::Path),
+            validator.check_head(Method::Post, "/hooks/github/nested", None)
+        );
+        assert_eq!(
+            Err(Rejection::Size),
+            validator.check_head(Method::Post, "/hooks/github", Some(17))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn prefixed_signature() -> Result<()> {
+        let validator = validator(literal!({
+            "header": "X-Hub-Signature-256", "prefix": "sha256=", "secret": "snot"
+        }))?;
+        let signed = request(&[(
+            "X-Hub-Signature-256",
+            format!("sha256={}", sign(b"badger")?),
+        )]);
+        let unsigned = request(&[]);
+        assert_eq!(
+            Ok(()),
+            validator.check_body(signed.as_ref(), b"badger", NOW)
+        );
+        assert!(validator.check_body(signed.as_ref(), b"snot", NOW).is_err());
+        assert!(validator
+            .check_body(unsigned.as_ref(), b"badger", NOW)
+            .is_err());
+        assert_eq!(
+            Err(Rejection::Size),
+            validator.check_body(signed.as_ref(), &[0; 17], NOW)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn timestamped_signature() -> Result<()> {
+        let validator = validator(literal!({
+            "header": "Stripe-Signature",
+            "signature_key": "v1",
+            "timestamp_key": "t",
+            "payload": "{timestamp}.{body}",
+            "secret": "snot"
+        }))?;
+        let t = NOW / 1_000_000_000;
+        let signature = sign(format!("{t}.badger").as_bytes())?;
+        let signed = request(&[("Stripe-Signature", format!("t={t},v1=00,v1={signature}"))]);
+        assert_eq!(
+            Ok(()),
+            validator.check_body(signed.as_ref(), b"badger", NOW)
+        );
+        assert_eq!(
+            Err(Rejection::Signature("timestamp outside of tolerance")),
+            validator.check_body(signed.as_ref(), b"badger", NOW + 301_000_000_000)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn payload_template() {
+        assert_eq!(
+            b"v0:123:snot".to_vec(),
+            signed_payload("v0:{timestamp}:{body}", "123", b"snot")
+        );
+        assert_eq!(b"snot".to_vec(), signed_payload("{body}", "", b"snot"));
+    }
+}
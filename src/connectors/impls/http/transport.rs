@@ -0,0 +1,433 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP/1.1 and HTTP/2 transport for the http connectors
+//!
+//! The connectors build and inspect requests and responses as `http_types`
+//! values, this module moves them over the wire with hyper. With TLS the
+//! protocol is negotiated via ALPN, plaintext connections use HTTP/1.1 unless
+//! HTTP/2 is enforced, in which case h2c with prior knowledge is used.
+
+use crate::connectors::utils::tls::{system_client_config, TlsServer};
+use crate::errors::{Error, Result};
+use async_std::net::{SocketAddr, TcpListener};
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, TryStreamExt};
+use http_types::{
+    headers::{HeaderValue, Headers, CONTENT_LENGTH},
+    Method, StatusCode, Url, Version,
+};
+use hyper::{client::HttpConnector, server::conn::Http, service::service_fn, Body};
+use hyper_rustls::HttpsConnector;
+use std::{convert::Infallible, future::Future, str::FromStr, sync::Arc, time::Duration};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tremor_value::Value;
+
+const ALPN_H2: &[u8] = b"h2";
+const ALPN_HTTP1: &[u8] = b"http/1.1";
+/// size of the chunks a body is streamed in
+const CHUNK_SIZE: usize = 8192;
+
+/// The HTTP versions a connector speaks
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HttpVersion {
+    /// HTTP/2 if negotiated via ALPN, HTTP/1.1 otherwise
+    Auto,
+    /// HTTP/1.1 only
+    Http1,
+    /// HTTP/2 only, plaintext connections use h2c with prior knowledge
+    Http2,
+}
+
+impl Default for HttpVersion {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl HttpVersion {
+    /// the protocols to offer during the TLS handshake, in order of preference
+    fn alpn_protocols(self) -> Vec<Vec<u8>> {
+        match self {
+            Self::Auto => vec![ALPN_H2.to_vec(), ALPN_HTTP1.to_vec()],
+            Self::Http1 => vec![ALPN_HTTP1.to_vec()],
+            Self::Http2 => vec![ALPN_H2.to_vec()],
+        }
+    }
+}
+
+/// Runs the background tasks of hyper connections on async-std
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Executor;
+
+impl<F> hyper::rt::Executor<F> for Executor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        async_std::task::spawn(fut);
+    }
+}
+
+/// An HTTP client multiplexing concurrent requests over HTTP/2 connections
+/// where possible
+pub(crate) struct Client {
+    inner: hyper::Client<HttpsConnector<HttpConnector>>,
+    timeout: Option<Duration>,
+}
+
+impl Client {
+    pub(crate) fn new(
+        version: HttpVersion,
+        tls_config: Option<rustls::ClientConfig>,
+        max_idle_per_host: usize,
+        timeout: Option<Duration>,
+    ) -> Self {
+        let mut http = HttpConnector::new();
+        // https is handled by the `HttpsConnector`
+        http.enforce_http(false);
+        http.set_nodelay(true);
+        // without an explicit tls config https urls, e.g. from redirects or
+        // request metadata, are verified against the system root certificates
+        let mut tls_config = tls_config.unwrap_or_else(system_client_config);
+        tls_config.set_protocols(&version.alpn_protocols());
+        let inner = hyper::Client::builder()
+            .executor(Executor)
+            .pool_max_idle_per_host(max_idle_per_host)
+            .http2_only(version == HttpVersion::Http2)
+            .build(HttpsConnector::from((http, tls_config)));
+        Self { inner, timeout }
+    }
+
+    /// Sends the request and waits for the response head, the body is
+    /// streamed afterwards
+    pub(crate) async fn send(&self, request: http_types::Request) -> Result<http_types::Response> {
+        from_hyper_response(self.request(to_hyper_request(request)?).await?)
+    }
+
+    /// Sends a hyper request and waits for the response head, for connectors
+    /// that need access to the raw body, e.g. its trailers
+    pub(crate) async fn request(
+        &self,
+        request: hyper::Request<Body>,
+    ) -> Result<hyper::Response<Body>> {
+        Ok(if let Some(timeout) = self.timeout {
+            async_std::future::timeout(timeout, self.inner.request(request)).await??
+        } else {
+            self.inner.request(request).await?
+        })
+    }
+}
+
+/// Metadata of the verified client certificate of the connection a request was
+/// received on, stored in the request extensions
+#[derive(Clone)]
+pub(crate) struct PeerTls(pub(crate) Value<'static>);
+
+/// Serves `app` on `listener`, with TLS if a `tls_server` is given, until the
+/// returned future is dropped
+pub(crate) async fn serve<State>(
+    listener: TcpListener,
+    tls_server: Option<TlsServer>,
+    version: HttpVersion,
+    app: tide::Server<State>,
+) -> Result<()>
+where
+    State: Clone + Send + Sync + 'static,
+{
+    let tls_server =
+        tls_server.map(|tls_server| tls_server.with_protocols(version.alpn_protocols()));
+    let scheme = if tls_server.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    let local = listener.local_addr()?;
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let app = app.clone();
+        let tls_server = tls_server.clone();
+        async_std::task::spawn(async move {
+            let mut conn = Connection {
+                scheme,
+                version,
+                local,
+                peer,
+                peer_tls: None,
+            };
+            let res = match tls_server {
+                Some(tls_server) => {
+                    let (acceptor, peer_cert) = tls_server.acceptor().await;
+                    match acceptor.accept(stream).await {
+                        Ok(stream) => {
+                            conn.peer_tls = peer_cert.meta();
+                            conn.serve(stream, app).await
+                        }
+                        Err(e) => Err(Error::from(e)),
+                    }
+                }
+                None => conn.serve(stream, app).await,
+            };
+            if let Err(e) = res {
+                debug!("HTTP connection from {peer} failed: {e}");
+            }
+        });
+    }
+}
+
+/// An accepted server connection
+struct Connection {
+    scheme: &'static str,
+    version: HttpVersion,
+    local: SocketAddr,
+    peer: SocketAddr,
+    /// the verified client certificate
+    peer_tls: Option<Value<'static>>,
+}
+
+impl Connection {
+    async fn serve<IO, State>(self, io: IO, app: tide::Server<State>) -> Result<()>
+    where
+        IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+        State: Clone + Send + Sync + 'static,
+    {
+        let version = self.version;
+        let conn = Arc::new(self);
+        let service = service_fn(move |request| {
+            let app = app.clone();
+            let conn = conn.clone();
+            async move {
+                let response = match conn.to_request(request) {
+                    Ok(request) => app
+                        .respond::<_, http_types::Response>(request)
+                        .await
+                        .map_or_else(
+                            |e| {
+                                status_response(
+                                    hyper::StatusCode::from_u16(e.status() as u16)
+                                        .unwrap_or(hyper::StatusCode::INTERNAL_SERVER_ERROR),
+                                )
+                            },
+                            to_hyper_response,
+                        ),
+                    Err(e) => {
+                        debug!("Invalid HTTP request from {}: {e}", conn.peer);
+                        status_response(hyper::StatusCode::BAD_REQUEST)
+                    }
+                };
+                Ok::<_, Infallible>(response)
+            }
+        });
+        let mut http = Http::new().with_executor(Executor);
+        match version {
+            // hyper tells HTTP/1.1 and HTTP/2 apart by the connection preface
+            HttpVersion::Auto => (),
+            HttpVersion::Http1 => {
+                http.http1_only(true);
+            }
+            HttpVersion::Http2 => {
+                http.http2_only(true);
+            }
+        }
+        http.serve_connection(io.compat(), service).await?;
+        Ok(())
+    }
+
+    /// Turns a request received by hyper into the request handed to tide
+    fn to_request(&self, request: hyper::Request<Body>) -> Result<http_types::Request> {
+        let (parts, body) = request.into_parts();
+        // HTTP/2 requests carry the authority in the uri, HTTP/1.1 requests in the host header
+        let authority = parts
+            .uri
+            .authority()
+            .map(ToString::to_string)
+            .or_else(|| {
+                parts
+                    .headers
+                    .get(hyper::header::HOST)
+                    .and_then(|host| host.to_str().ok())
+                    .map(ToString::to_string)
+            })
+            .unwrap_or_else(|| self.local.to_string());
+        let path = parts.uri.path_and_query().map_or("/", |p| p.as_str());
+        let url = Url::parse(&format!("{}://{authority}{path}", self.scheme))?;
+        let mut request = http_types::Request::new(Method::from_str(parts.method.as_str())?, url);
+        request.set_version(from_hyper_version(parts.version));
+        request.set_local_addr(Some(self.local));
+        request.set_peer_addr(Some(self.peer));
+        if let Some(peer_tls) = &self.peer_tls {
+            request.set_ext(PeerTls(peer_tls.clone()));
+        }
+        append_headers(&mut request, &parts.headers);
+        request.set_body(from_hyper_body(body, content_length(&parts.headers)));
+        Ok(request)
+    }
+}
+
+fn to_hyper_request(mut request: http_types::Request) -> Result<hyper::Request<Body>> {
+    let mut builder = hyper::Request::builder()
+        .method(request.method().to_string().as_str())
+        .uri(request.url().as_str());
+    let body = request.take_body();
+    for (name, values) in request.iter() {
+        // the length is taken from the body, so it can't get out of sync
+        if *name == CONTENT_LENGTH && body.len().is_some() {
+            continue;
+        }
+        for value in values.iter() {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+    }
+    if let Some(len) = body.len() {
+        builder = builder.header(hyper::header::CONTENT_LENGTH, len);
+    }
+    Ok(builder.body(to_hyper_body(body))?)
+}
+
+fn from_hyper_response(response: hyper::Response<Body>) -> Result<http_types::Response> {
+    let (parts, body) = response.into_parts();
+    let mut response = http_types::Response::new(StatusCode::try_from(parts.status.as_u16())?);
+    response.set_version(from_hyper_version(parts.version));
+    append_headers(&mut response, &parts.headers);
+    // set after the headers, so a body without a content-type doesn't add one
+    response.set_body(from_hyper_body(body, content_length(&parts.headers)));
+    Ok(response)
+}
+
+fn to_hyper_response(mut response: http_types::Response) -> hyper::Response<Body> {
+    let mut builder = hyper::Response::builder().status(response.status() as u16);
+    let body = response.take_body();
+    for (name, values) in response.iter() {
+        if *name == CONTENT_LENGTH && body.len().is_some() {
+            continue;
+        }
+        for value in values.iter() {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+    }
+    if let Some(len) = body.len() {
+        builder = builder.header(hyper::header::CONTENT_LENGTH, len);
+    }
+    builder.body(to_hyper_body(body)).unwrap_or_else(|e| {
+        error!("Invalid HTTP response: {e}");
+        status_response(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+    })
+}
+
+fn status_response(status: hyper::StatusCode) -> hyper::Response<Body> {
+    let mut response = hyper::Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// Streams the body in chunks, so chunked bodies are sent as they are produced
+fn to_hyper_body(body: http_types::Body) -> Body {
+    Body::wrap_stream(futures::stream::try_unfold(body, |mut body| async move {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let len = body.read(&mut chunk).await?;
+        if len == 0 {
+            Ok::<_, std::io::Error>(None)
+        } else {
+            chunk.truncate(len);
+            Ok(Some((chunk, body)))
+        }
+    }))
+}
+
+fn from_hyper_body(body: Body, len: Option<usize>) -> http_types::Body {
+    let reader = body
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        .into_async_read();
+    http_types::Body::from_reader(reader, len)
+}
+
+fn content_length(headers: &hyper::HeaderMap) -> Option<usize> {
+    headers
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok())
+        .and_then(|len| len.parse().ok())
+}
+
+fn append_headers<T: AsMut<Headers>>(target: &mut T, headers: &hyper::HeaderMap) {
+    let target = target.as_mut();
+    for (name, value) in headers {
+        // http-types only supports visible ascii header values
+        if let Ok(value) = HeaderValue::from_bytes(value.as_bytes().to_vec()) {
+            target.append(name.as_str(), value);
+        }
+    }
+}
+
+fn from_hyper_version(version: hyper::Version) -> Option<Version> {
+    match version {
+        hyper::Version::HTTP_09 => Some(Version::Http0_9),
+        hyper::Version::HTTP_10 => Some(Version::Http1_0),
+        hyper::Version::HTTP_11 => Some(Version::Http1_1),
+        hyper::Version::HTTP_2 => Some(Version::Http2_0),
+        hyper::Version::HTTP_3 => Some(Version::Http3_0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::impls::http::utils::FixedBodyReader;
+
+    #[test]
+    fn alpn() {
+        assert_eq!(HttpVersion::Auto, HttpVersion::default());
+        assert_eq!(
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            HttpVersion::Auto.alpn_protocols()
+        );
+        assert_eq!(vec![b"h2".to_vec()], HttpVersion::Http2.alpn_protocols());
+    }
+
+    #[async_std::test]
+    async fn request_roundtrip() -> Result<()> {
+        let mut request =
+            http_types::Request::new(Method::Post, Url::parse("http://localhost:8080/snot?a=1")?);
+        request.append_header("x-snot", "badger");
+        request.append_header("content-length", "1000");
+        let reader = FixedBodyReader::new(vec![b"snot".to_vec(), b"badger".to_vec()]);
+        request.set_body(http_types::Body::from_reader(reader, Some(10)));
+        let request = to_hyper_request(request)?;
+        assert_eq!(hyper::Method::POST, request.method());
+        assert_eq!("/snot", request.uri().path());
+        assert_eq!(
+            Some("badger"),
+            request
+                .headers()
+                .get("x-snot")
+                .and_then(|v| v.to_str().ok())
+        );
+        assert_eq!(Some(10), content_length(request.headers()));
+
+        let conn = Connection {
+            scheme: "http",
+            version: HttpVersion::Auto,
+            local: "127.0.0.1:8080".parse()?,
+            peer: "127.0.0.1:4242".parse()?,
+            peer_tls: None,
+        };
+        let mut request = conn.to_request(request)?;
+        assert_eq!("http://localhost:8080/snot?a=1", request.url().as_str());
+        assert_eq!(Some(Version::Http1_1), request.version());
+        assert_eq!(Some("badger"), request.header("x-snot").map(|v| v.as_str()));
+        assert_eq!("snotbadger", request.body_string().await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Server-Sent Events
+//!
+//! Parsing and encoding of `text/event-stream` as specified in
+//! <https://html.spec.whatwg.org/multipage/server-sent-events.html>.
+//! The `event`, `id` and `retry` fields of an event are mapped to and from
+//! the `$sse` metadata. Outgoing events without a request to respond to are
+//! only sent to all open event streams if `$sse.broadcast` is `true`.
+
+pub(crate) mod client;
+
+use crate::connectors::prelude::*;
+use std::fmt::Write;
+
+/// MIME type of server-sent event streams
+pub(crate) const MIME_TYPE: &str = "text/event-stream";
+
+/// A dispatched server-sent event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SseEvent {
+    /// event type, `message` if the server didn't set one
+    pub(crate) event: String,
+    pub(crate) data: String,
+    /// last event id at the time this event was dispatched
+    pub(crate) id: Option<String>,
+    /// reconnection time in milliseconds
+    pub(crate) retry: Option<u64>,
+}
+
+impl SseEvent {
+    /// the `$sse` metadata of this event
+    pub(crate) fn meta(&self) -> Value<'static> {
+        let mut meta = literal!({ "event": self.event.clone() });
+        if let Some(id) = &self.id {
+            meta.try_insert("id", id.clone());
+        }
+        if let Some(retry) = self.retry {
+            meta.try_insert("retry", retry);
+        }
+        literal!({ "sse": meta })
+    }
+}
+
+/// Parses an event stream chunk by chunk
+#[derive(Debug, Default)]
+pub(crate) struct Parser {
+    /// bytes of the line currently being received
+    line: Vec<u8>,
+    /// the last chunk ended with a `\r`, which may be followed by a `\n`
+    skip_lf: bool,
+    event: Option<String>,
+    data: String,
+    last_event_id: String,
+    retry: Option<u64>,
+}
+
+impl Parser {
+    /// Feeds a chunk of the stream, returns the events it completed. Lines are
+    /// terminated by `\r\n`, `\n` or `\r`, also if split across chunks.
+    pub(crate) fn feed(&mut self, mut chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        if self.skip_lf && !chunk.is_empty() {
+            self.skip_lf = false;
+            chunk = chunk.strip_prefix(b"\n").unwrap_or(chunk);
+        }
+        while let Some(end) = memchr::memchr2(b'\r', b'\n', chunk) {
+            let (line, rest) = chunk.split_at(end);
+            self.line.extend_from_slice(line);
+            let line = std::mem::take(&mut self.line);
+            events.extend(self.line(&String::from_utf8_lossy(&line)));
+            chunk = match rest {
+                [b'\r'] => {
+                    self.skip_lf = true;
+                    &[]
+                }
+                [b'\r', b'\n', rest @ ..] | [_, rest @ ..] => rest,
+                [] => &[],
+            };
+        }
+        self.line.extend_from_slice(chunk);
+        events
+    }
+
+    /// Discards a partially received line and event, e.g. after the stream got
+    /// interrupted. The last event id and reconnection time are kept.
+    pub(crate) fn reset(&mut self) {
+        self.line.clear();
+        self.skip_lf = false;
+        self.event = None;
+        self.data.clear();
+    }
+
+    /// Handles a single line without its line terminator, returns an event if
+    /// the line completed one
+    fn line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // lines starting with a colon are comments, e.g. used as keep-alive
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = line.split_once(':').map_or((line, ""), |(field, value)| {
+            (field, value.strip_prefix(' ').unwrap_or(value))
+        });
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = value.to_string(),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry = value.parse().ok();
+            }
+            // unknown fields are ignored
+            _ => (),
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = std::mem::take(&mut self.data);
+        // remove the newline added after the last data line
+        data.pop();
+        Some(SseEvent {
+            event: event.unwrap_or_else(|| "message".to_string()),
+            data,
+            id: self.last_event_id(),
+            retry: self.retry,
+        })
+    }
+
+    /// The id of the last event, to be sent as `Last-Event-ID` upon reconnecting
+    pub(crate) fn last_event_id(&self) -> Option<String> {
+        if self.last_event_id.is_empty() {
+            None
+        } else {
+            Some(self.last_event_id.clone())
+        }
+    }
+
+    /// The reconnection time in milliseconds requested by the server
+    pub(crate) fn retry(&self) -> Option<u64> {
+        self.retry
+    }
+}
+
+/// Encodes `data` as a single event, setting its `event`, `id` and `retry`
+/// fields from the `$sse` metadata
+pub(crate) fn encode(data: &[u8], sse_meta: Option<&Value>) -> Vec<u8> {
+    let mut encoded = String::new();
+    if let Some(event) = sse_meta.get_str("event").filter(|e| is_single_line(e)) {
+        let _ = writeln!(encoded, "event: {event}");
+    }
+    let id = sse_meta.get("id").and_then(|id| {
+        id.as_str()
+            .map(ToString::to_string)
+            .or_else(|| id.as_u64().map(|id| id.to_string()))
+    });
+    // an id with a NULL would be ignored by the client
+    if let Some(id) = id.filter(|id| is_single_line(id) && !id.contains('\0')) {
+        let _ = writeln!(encoded, "id: {id}");
+    }
+    if let Some(retry) = sse_meta.get_u64("retry") {
+        let _ = writeln!(encoded, "retry: {retry}");
+    }
+    let data = String::from_utf8_lossy(data);
+    // a trailing line terminator is part of the serialized data, not of the event
+    let data = data
+        .strip_suffix("\r\n")
+        .or_else(|| data.strip_suffix('\n'))
+        .unwrap_or(&data);
+    for line in data.split("\r\n").flat_map(|l| l.split(['\r', '\n'])) {
+        let _ = writeln!(encoded, "data: {line}");
+    }
+    encoded.push('\n');
+    encoded.into_bytes()
+}
+
+fn is_single_line(s: &str) -> bool {
+    !s.contains(['\r', '\n'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(stream: &str) -> (Parser, Vec<SseEvent>) {
+        let mut parser = Parser::default();
+        let events = parser.feed(stream.as_bytes());
+        (parser, events)
+    }
+
+    #[test]
+    fn parse_events() {
+        let (parser, events) = parse(
+            ": keep-alive\n\ndata: snot\ndata:badger\n\nevent: update\nid: 1\nretry: 3000\ndata: {}\n\nid: 2\n\ndata: dangling\n",
+        );
+        assert_eq!(
+            vec![
+                SseEvent {
+                    event: "message".to_string(),
+                    data: "snot\nbadger".to_string(),
+                    id: None,
+                    retry: None
+                },
+                SseEvent {
+                    event: "update".to_string(),
+                    data: "{}".to_string(),
+                    id: Some("1".to_string()),
+                    retry: Some(3000)
+                }
+            ],
+            events
+        );
+        // ids without data are remembered, unfinished events are not dispatched
+        assert_eq!(Some("2".to_string()), parser.last_event_id());
+        assert_eq!(Some(3000), parser.retry());
+    }
+
+    #[test]
+    fn parse_line_terminators() {
+        let (_, events) = parse("data: cr\r\rdata: crlf\r\n\r\ndata: lf\n\n");
+        let data: Vec<&str> = events.iter().map(|e| e.data.as_str()).collect();
+        assert_eq!(vec!["cr", "crlf", "lf"], data);
+
+        // a `\r\n` split across chunks is a single line terminator
+        let mut parser = Parser::default();
+        assert!(parser.feed(b"data: sn").is_empty());
+        assert!(parser.feed(b"ot\r").is_empty());
+        assert!(parser.feed(b"\ndata: badger\r").is_empty());
+        let events = parser.feed(b"\r");
+        assert_eq!(1, events.len());
+        assert_eq!("snot\nbadger", events[0].data);
+    }
+
+    #[test]
+    fn reset_keeps_id_and_retry() {
+        let (mut parser, _) =
+            parse("id: 1\nretry: 100\ndata: snot\n\nevent: update\ndata: partial\ndata: li");
+        parser.reset();
+        let events = parser.feed(b"ne\ndata: badger\n\n");
+        assert_eq!(1, events.len());
+        assert_eq!("message", events[0].event);
+        assert_eq!("badger", events[0].data);
+        assert_eq!(Some("1".to_string()), events[0].id);
+        assert_eq!(Some(100), events[0].retry);
+    }
+
+    #[test]
+    fn parse_invalid_fields() {
+        let (parser, events) = parse("retry: soon\nid: a\0b\nsnot: badger\ndata\n\n");
+        assert_eq!(None, parser.retry());
+        assert_eq!(None, parser.last_event_id());
+        assert_eq!(1, events.len());
+        assert_eq!("", events[0].data);
+    }
+
+    #[test]
+    fn encode_events() {
+        let meta = literal!({"event": "update", "id": 42, "retry": 1000});
+        assert_eq!(
+            "event: update\nid: 42\nretry: 1000\ndata: {\"snot\":\ndata: \"badger\"}\n\n",
+            String::from_utf8_lossy(&encode(b"{\"snot\":\n\"badger\"}\n", Some(&meta)))
+        );
+        let meta = literal!({"event": "multi\nline"});
+        assert_eq!(
+            "data: snot\n\n",
+            String::from_utf8_lossy(&encode(b"snot", Some(&meta)))
+        );
+        assert_eq!("data: \n\n", String::from_utf8_lossy(&encode(b"", None)));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let meta = literal!({"event": "update", "id": "snot"});
+        let encoded = encode(b"badger\r\nbadger", Some(&meta));
+        let (_, events) = parse(&String::from_utf8_lossy(&encoded));
+        assert_eq!(1, events.len());
+        assert_eq!(
+            literal!({"sse": {"event": "update", "id": "snot"}}),
+            events[0].meta()
+        );
+        assert_eq!("badger\nbadger", events[0].data);
+    }
+}
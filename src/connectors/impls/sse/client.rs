@@ -0,0 +1,276 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Source consuming a server-sent events endpoint
+//!
+//! Each event becomes a discrete tremor event, its data is decoded with the
+//! configured codec, `event`, `id` and `retry` are available as `$sse` metadata.
+//! If the stream ends or fails, the connection is considered lost and
+//! re-established according to the connectors `reconnect` config, after waiting
+//! for the `retry` time requested by the server. The id of the last event is sent
+//! as `Last-Event-ID` header so the server can resume where the stream ended, an
+//! event that was only partially received before is discarded.
+
+use super::{Parser, MIME_TYPE};
+use crate::connectors::impls::http::{
+    auth::Auth,
+    transport::{self, HttpVersion},
+    utils::Header,
+};
+use crate::connectors::prelude::*;
+use crate::connectors::utils::tls::{tls_client_config, TLSClientConfig};
+use crate::errors::err_conector_def;
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::task::JoinHandle;
+use either::Either;
+use futures::AsyncReadExt;
+use halfbrown::HashMap;
+use http_types::{headers, Method, Request, StatusCode};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const CONNECTOR_TYPE: &str = "sse_client";
+/// size of the chunks the event stream is read in
+const CHUNK_SIZE: usize = 8192;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// URL of the event stream
+    url: Url,
+    /// Authorization method
+    #[serde(default = "Default::default")]
+    auth: Auth,
+    /// HTTP headers to send along
+    #[serde(default = "Default::default")]
+    headers: HashMap<String, Header>,
+    /// timeout for receiving the response headers in nanoseconds
+    #[serde(default = "Default::default")]
+    timeout: Option<u64>,
+    /// optional tls client config
+    #[serde(with = "either::serde_untagged_optional", default = "Default::default")]
+    tls: Option<Either<TLSClientConfig, bool>>,
+    /// HTTP versions to speak
+    #[serde(default = "Default::default")]
+    http_version: HttpVersion,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        CONNECTOR_TYPE.into()
+    }
+
+    async fn build_cfg(
+        &self,
+        id: &str,
+        _: &ConnectorConfig,
+        config: &Value,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(config)?;
+        let tls_client_config = match config.tls.as_ref() {
+            Some(Either::Right(true)) => {
+                Some(tls_client_config(&TLSClientConfig::default()).await?)
+            }
+            Some(Either::Left(tls_config)) => Some(tls_client_config(tls_config).await?),
+            Some(Either::Right(false)) | None => None,
+        };
+        if config.url.scheme() == "https" && tls_client_config.is_none() {
+            return Err(err_conector_def(
+                id,
+                &format!("missing tls config for {id} with 'https' url. Set 'tls' to 'true' or provide a full tls config."),
+            ));
+        }
+        Ok(Box::new(SseClient {
+            config,
+            tls_client_config,
+        }))
+    }
+}
+
+struct SseClient {
+    config: Config,
+    tls_client_config: Option<rustls::ClientConfig>,
+}
+
+#[async_trait::async_trait()]
+impl Connector for SseClient {
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Optional("string")
+    }
+
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        let url = self.config.url.url();
+        let origin_uri = EventOriginUri {
+            scheme: url.scheme().to_string(),
+            host: url.host_str().unwrap_or_default().to_string(),
+            port: url.port_or_known_default(),
+            path: url
+                .path_segments()
+                .map(|segments| segments.map(ToString::to_string).collect())
+                .unwrap_or_default(),
+        };
+        let client = transport::Client::new(
+            self.config.http_version,
+            self.tls_client_config.clone(),
+            1,
+            self.config.timeout.map(Duration::from_nanos),
+        );
+        let source = SseClientSource {
+            config: self.config.clone(),
+            client,
+            origin_uri,
+            parser: Arc::new(Mutex::new(Parser::default())),
+            reader: None,
+            tx,
+            rx,
+        };
+        builder.spawn(source, source_context).map(Some)
+    }
+}
+
+struct SseClientSource {
+    config: Config,
+    client: transport::Client,
+    origin_uri: EventOriginUri,
+    /// shared with the reader task, keeps the last event id and retry time across reconnects
+    parser: Arc<Mutex<Parser>>,
+    reader: Option<JoinHandle<()>>,
+    tx: Sender<SourceReply>,
+    rx: Receiver<SourceReply>,
+}
+
+impl SseClientSource {
+    async fn request(&self) -> Result<Request> {
+        let mut request = Request::new(Method::Get, self.config.url.url().clone());
+        for (name, values) in &self.config.headers {
+            match &values.0 {
+                Either::Left(values) => {
+                    for value in values {
+                        request.append_header(name.as_str(), value.as_str());
+                    }
+                }
+                Either::Right(value) => {
+                    request.append_header(name.as_str(), value.as_str());
+                }
+            }
+        }
+        request.insert_header(headers::ACCEPT, MIME_TYPE);
+        request.insert_header(headers::CACHE_CONTROL, "no-cache");
+        let last_event_id = self.parser.lock()?.last_event_id();
+        if let Some(last_event_id) = last_event_id {
+            request.insert_header("Last-Event-ID", last_event_id);
+        }
+        if let Some(auth_header) = self.config.auth.header_value().await? {
+            request.insert_header(headers::AUTHORIZATION, auth_header);
+        }
+        Ok(request)
+    }
+}
+
+#[async_trait::async_trait()]
+impl Source for SseClientSource {
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        if let Some(reader) = self.reader.take() {
+            reader.cancel().await;
+        }
+        self.parser.lock()?.reset();
+        let mut response = self.client.send(self.request().await?).await?;
+        if response.status() != StatusCode::Ok {
+            return Err(format!(
+                "Unexpected response status {} from {}",
+                response.status(),
+                self.config.url
+            )
+            .into());
+        }
+        if response
+            .content_type()
+            .map_or(true, |mime| mime.essence() != MIME_TYPE)
+        {
+            return Err(format!("{} is not a {MIME_TYPE} endpoint", self.config.url).into());
+        }
+        debug!("{ctx} Connected to {}", self.config.url);
+
+        let mut body = response.take_body();
+        let parser = self.parser.clone();
+        let origin_uri = self.origin_uri.clone();
+        let tx = self.tx.clone();
+        let reader_ctx = ctx.clone();
+        self.reader = Some(spawn_task(ctx.clone(), async move {
+            let res: Result<()> = async {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                loop {
+                    let len = body.read(&mut chunk).await?;
+                    if len == 0 {
+                        break;
+                    }
+                    let events = parser.lock()?.feed(&chunk[..len]);
+                    for event in events {
+                        let meta = event.meta();
+                        tx.send(SourceReply::Data {
+                            origin_uri: origin_uri.clone(),
+                            data: event.data.into_bytes(),
+                            meta: Some(meta),
+                            stream: None, // every event is a discrete unit
+                            port: None,
+                            codec_overwrite: None,
+                        })
+                        .await?;
+                    }
+                }
+                Ok(())
+            }
+            .await;
+            info!("{reader_ctx} Event stream ended.");
+            // honour the reconnection time requested by the server
+            let retry = parser.lock()?.retry();
+            if let Some(retry) = retry {
+                async_std::task::sleep(Duration::from_millis(retry)).await;
+            }
+            res?;
+            reader_ctx.notifier().connection_lost().await
+        }));
+        Ok(true)
+    }
+
+    async fn pull_data(&mut self, _pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+        Ok(self.rx.recv().await?)
+    }
+
+    async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
+        if let Some(reader) = self.reader.take() {
+            reader.cancel().await;
+        }
+        Ok(())
+    }
+
+    fn is_transactional(&self) -> bool {
+        false
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
@@ -192,4 +192,9 @@ where
         self.underlying_stream.shutdown(std::net::Shutdown::Write)?;
         Ok(StreamDone::StreamClosed)
     }
+    async fn on_close(&mut self, _stream: u64) -> Result<StreamDone> {
+        // also stops the reader, so the connection is gone entirely
+        self.underlying_stream.shutdown(std::net::Shutdown::Both)?;
+        Ok(StreamDone::StreamClosed)
+    }
 }
@@ -17,9 +17,11 @@ use crate::{
         prelude::*,
         sink::channel_sink::ChannelSinkMsg,
         utils::{
-            tls::{load_server_config, TLSServerConfig},
+            lifecycle::{Lifecycle, Tracked},
+            tls::{TLSServerConfig, TlsServer},
             ConnectionMeta,
         },
+        LIFECYCLE_OUT_PORTS_REF, OUT_PORTS_REF,
     },
     errors::err_conector_def,
 };
@@ -29,11 +31,9 @@ use async_std::{
     prelude::*,
     task::JoinHandle,
 };
-use async_tls::TlsAcceptor;
+use beef::Cow;
 use futures::io::AsyncReadExt;
-use rustls::ServerConfig;
 use simd_json::ValueAccess;
-use std::sync::Arc;
 
 const URL_SCHEME: &str = "tremor-tcp-server";
 
@@ -45,6 +45,9 @@ pub struct Config {
     // TCP: receive buffer size
     #[serde(default = "default_buf_size")]
     buf_size: usize,
+    /// emit `connected` and `disconnected` events on the `connections` port
+    #[serde(default = "default_false")]
+    lifecycle_events: bool,
 }
 
 impl ConfigImpl for Config {}
@@ -52,7 +55,7 @@ impl ConfigImpl for Config {}
 #[allow(clippy::module_name_repetitions)]
 pub struct TcpServer {
     config: Config,
-    tls_server_config: Option<ServerConfig>,
+    tls_server: Option<TlsServer>,
     sink_tx: Sender<ChannelSinkMsg<ConnectionMeta>>,
     sink_rx: Receiver<ChannelSinkMsg<ConnectionMeta>>,
 }
@@ -75,15 +78,14 @@ impl ConnectorBuilder for Builder {
         if config.url.port().is_none() {
             return Err(err_conector_def(id, "Missing port for TCP server"));
         }
-        let tls_server_config = if let Some(tls_config) = config.tls.as_ref() {
-            Some(load_server_config(tls_config)?)
-        } else {
-            None
+        let tls_server = match config.tls.as_ref() {
+            Some(tls_config) => Some(TlsServer::new(tls_config).await?),
+            None => None,
         };
         let (sink_tx, sink_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         Ok(Box::new(TcpServer {
             config,
-            tls_server_config,
+            tls_server,
             sink_tx,
             sink_rx,
         }))
@@ -104,17 +106,22 @@ fn resolve_connection_meta(meta: &Value) -> Option<ConnectionMeta> {
 
 #[async_trait::async_trait()]
 impl Connector for TcpServer {
+    fn output_ports(&self) -> &[Cow<'static, str>] {
+        if self.config.lifecycle_events {
+            LIFECYCLE_OUT_PORTS_REF
+        } else {
+            OUT_PORTS_REF
+        }
+    }
+
     async fn create_source(
         &mut self,
         ctx: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
         let sink_runtime = ChannelSinkRuntime::new(self.sink_tx.clone());
-        let source = TcpServerSource::new(
-            self.config.clone(),
-            self.tls_server_config.clone(),
-            sink_runtime,
-        );
+        let source =
+            TcpServerSource::new(self.config.clone(), self.tls_server.clone(), sink_runtime);
         builder.spawn(source, ctx).map(Some)
     }
 
@@ -140,7 +147,7 @@ impl Connector for TcpServer {
 
 struct TcpServerSource {
     config: Config,
-    tls_server_config: Option<ServerConfig>,
+    tls_server: Option<TlsServer>,
     accept_task: Option<JoinHandle<()>>,
     connection_rx: Receiver<SourceReply>,
     runtime: ChannelSourceRuntime,
@@ -150,14 +157,14 @@ struct TcpServerSource {
 impl TcpServerSource {
     fn new(
         config: Config,
-        tls_server_config: Option<ServerConfig>,
+        tls_server: Option<TlsServer>,
         sink_runtime: ChannelSinkRuntime<ConnectionMeta>,
     ) -> Self {
         let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         let runtime = ChannelSourceRuntime::new(tx);
         Self {
             config,
-            tls_server_config,
+            tls_server,
             accept_task: None,
             connection_rx: rx,
             runtime,
@@ -172,6 +179,7 @@ impl Source for TcpServerSource {
         let path = vec![self.config.url.port_or_dflt().to_string()];
         let accept_ctx = ctx.clone();
         let buf_size = self.config.buf_size;
+        let lifecycle_events = self.config.lifecycle_events;
 
         // cancel last accept task if necessary, this will drop the previous listener
         if let Some(previous_handle) = self.accept_task.take() {
@@ -184,7 +192,7 @@ impl Source for TcpServerSource {
         let listener = TcpListener::bind((host, port)).await?;
 
         let ctx = ctx.clone();
-        let tls_server_config = self.tls_server_config.clone();
+        let tls_server = self.tls_server.clone();
 
         let runtime = self.runtime.clone();
         let sink_runtime = self.sink_runtime.clone();
@@ -207,19 +215,38 @@ impl Source for TcpServerSource {
                             path: path.clone(), // captures server port
                         };
 
-                        let tls_acceptor: Option<TlsAcceptor> = tls_server_config
-                            .clone()
-                            .map(|sc| TlsAcceptor::from(Arc::new(sc)));
-                        if let Some(acceptor) = tls_acceptor {
-                            let tls_stream = acceptor.accept(stream.clone()).await?;
+                        if let Some(tls_server) = tls_server.as_ref() {
+                            let (acceptor, peer_cert) = tls_server.acceptor().await;
+                            let tls_stream = match acceptor.accept(stream.clone()).await {
+                                Ok(tls_stream) => tls_stream,
+                                Err(e) => {
+                                    // e.g. a client without a valid certificate, keep accepting others
+                                    warn!(
+                                        "{accept_ctx} TLS handshake with {peer_addr} failed: {e}"
+                                    );
+                                    continue;
+                                }
+                            };
                             let (tls_read_stream, tls_write_sink) = tls_stream.split();
+                            let mut peer = literal!({
+                                "host": peer_addr.ip().to_string(),
+                                "port": peer_addr.port()
+                            });
+                            if let Some(peer_tls) = peer_cert.meta() {
+                                peer.try_insert("tls", peer_tls);
+                            }
                             let meta = ctx.meta(literal!({
                                 "tls": true,
-                                "peer": {
-                                    "host": peer_addr.ip().to_string(),
-                                    "port": peer_addr.port()
-                                }
+                                "peer": peer
                             }));
+                            let lifecycle = if lifecycle_events {
+                                Some(
+                                    Lifecycle::connected(&runtime, stream_id, &origin_uri, &meta)
+                                        .await?,
+                                )
+                            } else {
+                                None
+                            };
                             let tls_reader = TcpReader::tls_server(
                                 tls_read_stream,
                                 stream.clone(),
@@ -234,11 +261,18 @@ impl Source for TcpServerSource {
                                     stream_id,
                                     Some(connection_meta.clone()),
                                     &ctx,
-                                    TcpWriter::tls_server(tls_write_sink, stream),
+                                    Tracked::new(
+                                        TcpWriter::tls_server(tls_write_sink, stream),
+                                        lifecycle.clone(),
+                                    ),
                                 )
                                 .await;
 
-                            runtime.register_stream_reader(stream_id, &ctx, tls_reader);
+                            runtime.register_stream_reader(
+                                stream_id,
+                                &ctx,
+                                Tracked::new(tls_reader, lifecycle),
+                            );
                         } else {
                             let meta = ctx.meta(literal!({
                                 "tls": false,
@@ -247,6 +281,14 @@ impl Source for TcpServerSource {
                                     "port": peer_addr.port()
                                 }
                             }));
+                            let lifecycle = if lifecycle_events {
+                                Some(
+                                    Lifecycle::connected(&runtime, stream_id, &origin_uri, &meta)
+                                        .await?,
+                                )
+                            } else {
+                                None
+                            };
                             let tcp_reader = TcpReader::new(
                                 stream.clone(),
                                 vec![0; buf_size],
@@ -260,11 +302,15 @@ impl Source for TcpServerSource {
                                     stream_id,
                                     Some(connection_meta.clone()),
                                     &ctx,
-                                    TcpWriter::new(stream),
+                                    Tracked::new(TcpWriter::new(stream), lifecycle.clone()),
                                 )
                                 .await;
 
-                            runtime.register_stream_reader(stream_id, &ctx, tcp_reader);
+                            runtime.register_stream_reader(
+                                stream_id,
+                                &ctx,
+                                Tracked::new(tcp_reader, lifecycle),
+                            );
                         }
                     }
                     Ok(Err(e)) => return Err(e.into()),
@@ -12,9 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 pub(crate) mod client;
+#[cfg(feature = "dtls")]
+pub(crate) mod dtls;
 pub(crate) mod server;
 
-use crate::connectors::prelude::Defaults;
+use crate::connectors::prelude::*;
+use async_std::net::UdpSocket;
+use std::net::{IpAddr, Ipv4Addr};
 
 pub(crate) struct UdpDefaults;
 impl Defaults for UdpDefaults {
@@ -22,3 +26,89 @@ impl Defaults for UdpDefaults {
     const HOST: &'static str = "localhost";
     const PORT: u16 = 0;
 }
+
+/// error for `tls` configs of builds without the `dtls` feature
+#[cfg(not(feature = "dtls"))]
+const DTLS_DISABLED: &str =
+    "DTLS for udp connectors requires tremor to be built with the `dtls` feature";
+
+/// the host of `url` to bind or connect to, IPv6 addresses without their brackets
+pub(crate) fn host(url: &Url<UdpDefaults>) -> &str {
+    url.host_or_local()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+}
+
+/// A multicast group to join
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MulticastGroup {
+    /// address of the group
+    group: IpAddr,
+    /// address of the interface to join an IPv4 group on, defaults to any interface
+    #[serde(default = "Default::default")]
+    interface: Option<Ipv4Addr>,
+    /// index of the interface to join an IPv6 group on, defaults to any interface
+    #[serde(default = "Default::default")]
+    interface_index: u32,
+}
+
+impl MulticastGroup {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !self.group.is_multicast() {
+            return Err(format!("{} is not a multicast address", self.group).into());
+        }
+        match self.group {
+            IpAddr::V4(_) if self.interface_index != 0 => Err(format!(
+                "`interface_index` is only supported for IPv6 groups, use `interface` to join {}",
+                self.group
+            )
+            .into()),
+            IpAddr::V6(_) if self.interface.is_some() => Err(format!(
+                "`interface` is only supported for IPv4 groups, use `interface_index` to join {}",
+                self.group
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn join(&self, socket: &UdpSocket) -> Result<()> {
+        match self.group {
+            IpAddr::V4(group) => {
+                socket.join_multicast_v4(group, self.interface.unwrap_or(Ipv4Addr::UNSPECIFIED))?
+            }
+            IpAddr::V6(group) => socket.join_multicast_v6(&group, self.interface_index)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(config: Value) -> Result<MulticastGroup> {
+        let group: MulticastGroup = tremor_value::structurize(config)?;
+        group.validate()?;
+        Ok(group)
+    }
+
+    #[test]
+    fn validate_groups() -> Result<()> {
+        group(literal!({"group": "239.0.0.1", "interface": "127.0.0.1"}))?;
+        group(literal!({"group": "ff02::1", "interface_index": 1}))?;
+        assert!(group(literal!({"group": "127.0.0.1"})).is_err());
+        assert!(group(literal!({"group": "239.0.0.1", "interface_index": 1})).is_err());
+        assert!(group(literal!({"group": "ff02::1", "interface": "127.0.0.1"})).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ipv6_host() -> Result<()> {
+        assert_eq!("::1", host(&Url::parse("[::1]:4242")?));
+        assert_eq!("127.0.0.1", host(&Url::parse("127.0.0.1:4242")?));
+        assert_eq!("localhost", host(&Url::default()));
+        Ok(())
+    }
+}
@@ -15,8 +15,13 @@
 
 //! UDP Client
 
+#[cfg(feature = "dtls")]
+use super::dtls;
 use crate::connectors::prelude::*;
+use crate::connectors::utils::tls::TLSClientConfig;
 use async_std::net::UdpSocket;
+use either::Either;
+use socket2::SockRef;
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
@@ -25,12 +30,26 @@ pub(crate) struct Config {
     url: Url<super::UdpDefaults>,
     #[serde(default = "Default::default")]
     bind: Url<super::UdpDefaults>,
+    /// allow sending to broadcast addresses
+    #[serde(default = "default_false")]
+    broadcast: bool,
+    /// time-to-live (IPv4) or hop limit (IPv6) of outgoing multicast datagrams
+    #[serde(default = "Default::default")]
+    multicast_ttl: Option<u32>,
+    /// whether outgoing multicast datagrams are looped back to the local host
+    #[serde(default = "Default::default")]
+    multicast_loop: Option<bool>,
+    /// DTLS config, `true` verifies the server with the system root certificates
+    #[serde(with = "either::serde_untagged_optional", default = "Default::default")]
+    tls: Option<Either<TLSClientConfig, bool>>,
 }
 
 impl ConfigImpl for Config {}
 
 struct UdpClient {
     config: Config,
+    #[cfg(feature = "dtls")]
+    dtls: Option<dtls::Client>,
 }
 
 #[derive(Debug, Default)]
@@ -51,8 +70,31 @@ impl ConnectorBuilder for Builder {
         if config.url.port().is_none() {
             return Err("Missing port for UDP client".into());
         }
-
-        Ok(Box::new(UdpClient { config }))
+        let tls_config = match config.tls.as_ref() {
+            Some(Either::Left(tls_config)) => Some(tls_config.clone()),
+            Some(Either::Right(true)) => Some(TLSClientConfig::default()),
+            Some(Either::Right(false)) | None => None,
+        };
+        #[cfg(feature = "dtls")]
+        {
+            let dtls = if let Some(tls_config) = tls_config {
+                let domain = tls_config
+                    .domain
+                    .clone()
+                    .unwrap_or_else(|| config.url.host_or_local().to_string());
+                Some(dtls::Client::new(&tls_config, domain)?)
+            } else {
+                None
+            };
+            Ok(Box::new(UdpClient { config, dtls }))
+        }
+        #[cfg(not(feature = "dtls"))]
+        {
+            if tls_config.is_some() {
+                return Err(super::DTLS_DISABLED.into());
+            }
+            Ok(Box::new(UdpClient { config }))
+        }
     }
 }
 
@@ -78,6 +120,10 @@ impl Connector for UdpClient {
         let sink = UdpClientSink {
             config: self.config.clone(),
             socket: None,
+            #[cfg(feature = "dtls")]
+            dtls: self.dtls.clone(),
+            #[cfg(feature = "dtls")]
+            session: None,
         };
         builder.spawn(sink, ctx).map(Some)
     }
@@ -86,6 +132,10 @@ impl Connector for UdpClient {
 struct UdpClientSink {
     config: Config,
     socket: Option<UdpSocket>,
+    #[cfg(feature = "dtls")]
+    dtls: Option<dtls::Client>,
+    #[cfg(feature = "dtls")]
+    session: Option<dtls::Session>,
 }
 
 impl UdpClientSink {
@@ -95,22 +145,68 @@ impl UdpClientSink {
         }
         Ok(())
     }
+
+    async fn send(&self, data: Vec<Vec<u8>>) -> Result<()> {
+        #[cfg(feature = "dtls")]
+        if let Some(session) = self.session.as_ref() {
+            return session.send(data).await;
+        }
+        let socket = self.socket.as_ref().ok_or(ErrorKind::NoSocket)?;
+        Self::send_event(socket, data).await
+    }
+
+    fn is_connected(&self) -> bool {
+        #[cfg(feature = "dtls")]
+        if self.session.is_some() {
+            return true;
+        }
+        self.socket.is_some()
+    }
+
+    #[cfg(feature = "dtls")]
+    async fn stop_session(&mut self) {
+        if let Some(session) = self.session.take() {
+            session.abort().await;
+        }
+    }
 }
 
 #[async_trait::async_trait()]
 impl Sink for UdpClientSink {
-    async fn connect(&mut self, _ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
+    #[cfg_attr(not(feature = "dtls"), allow(unused_variables))]
+    async fn connect(&mut self, ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
+        #[cfg(feature = "dtls")]
+        self.stop_session().await;
         let socket = UdpSocket::bind((
-            self.config.bind.host_or_local(),
+            super::host(&self.config.bind),
             self.config.bind.port_or_dflt(),
         ))
         .await?;
+        if self.config.broadcast {
+            socket.set_broadcast(true)?;
+        }
+        let ipv6 = socket.local_addr()?.is_ipv6();
+        match self.config.multicast_ttl {
+            Some(hops) if ipv6 => SockRef::from(&socket).set_multicast_hops_v6(hops)?,
+            Some(ttl) => socket.set_multicast_ttl_v4(ttl)?,
+            None => (),
+        }
+        match self.config.multicast_loop {
+            Some(multicast_loop) if ipv6 => socket.set_multicast_loop_v6(multicast_loop)?,
+            Some(multicast_loop) => socket.set_multicast_loop_v4(multicast_loop)?,
+            None => (),
+        }
         socket
             .connect((
-                self.config.url.host_or_local(),
+                super::host(&self.config.url),
                 self.config.url.port_or_dflt(),
             ))
             .await?;
+        #[cfg(feature = "dtls")]
+        if let Some(dtls) = self.dtls.as_ref() {
+            self.session = Some(dtls.handshake(ctx, socket).await?);
+            return Ok(true);
+        }
         self.socket = Some(socket);
         Ok(true)
     }
@@ -122,16 +218,17 @@ impl Sink for UdpClientSink {
         serializer: &mut EventSerializer,
         _start: u64,
     ) -> Result<SinkReply> {
-        let socket = self
-            .socket
-            .as_ref()
-            .ok_or_else(|| Error::from(ErrorKind::NoSocket))?;
+        if !self.is_connected() {
+            return Err(ErrorKind::NoSocket.into());
+        }
         for value in event.value_iter() {
             let data = serializer.serialize(value, event.ingest_ns)?;
-            if let Err(e) = Self::send_event(socket, data).await {
+            if let Err(e) = self.send(data).await {
                 error!("{} UDP Error: {}. Initiating Reconnect...", &ctx, &e);
                 // TODO: upon which errors to actually trigger a reconnect?
                 self.socket = None;
+                #[cfg(feature = "dtls")]
+                self.stop_session().await;
                 ctx.notifier().connection_lost().await?;
                 return Err(e);
             }
@@ -139,6 +236,14 @@ impl Sink for UdpClientSink {
         Ok(SinkReply::NONE)
     }
 
+    async fn on_stop(&mut self, _ctx: &SinkContext) -> Result<()> {
+        #[cfg(feature = "dtls")]
+        if let Some(session) = self.session.take() {
+            session.close().await;
+        }
+        Ok(())
+    }
+
     fn auto_ack(&self) -> bool {
         true
     }
@@ -0,0 +1,595 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! DTLS 1.2 for the udp connectors
+//!
+//! Handshake and record layer are handled by OpenSSL on blocking tasks. A
+//! session exchanges the datagrams of its peer with the socket via channels,
+//! so a single socket can serve many peers. Reads fail once the deadline of
+//! their [`Datagrams`] passed, so stalled handshakes and idle sessions end.
+//!
+//! Only available with the `dtls` feature, as it links against OpenSSL.
+
+use crate::connectors::prelude::*;
+use crate::connectors::utils::tls::{Changes, ClientAuth, TLSClientConfig, TLSServerConfig};
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::net::UdpSocket;
+use async_std::sync::Mutex as AsyncMutex;
+use async_std::task::JoinHandle;
+use openssl::ssl::{
+    ErrorCode, Ssl, SslAcceptor, SslConnector, SslFiletype, SslMethod, SslStream, SslVerifyMode,
+};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Keeps records below common path MTUs, as OpenSSL can't query the MTU of a
+/// channel
+const MTU: u32 = 1200;
+
+/// OpenSSL can't retransmit handshake messages over a channel, so a lost
+/// datagram stalls the handshake until it is given up on
+pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn acceptor(config: &TLSServerConfig) -> Result<SslAcceptor> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::dtls())?;
+    builder.set_certificate_chain_file(&config.cert)?;
+    builder.set_private_key_file(&config.key, SslFiletype::PEM)?;
+    builder.check_private_key()?;
+    if let Some(client_ca) = config.client_ca.as_ref() {
+        builder.set_ca_file(client_ca)?;
+        builder.set_verify(match config.client_auth {
+            ClientAuth::Required => SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT,
+            ClientAuth::Optional => SslVerifyMode::PEER,
+        });
+    }
+    Ok(builder.build())
+}
+
+/// Without a `cafile` the system root certificates are used
+fn connector(config: &TLSClientConfig) -> Result<SslConnector> {
+    let mut builder = SslConnector::builder(SslMethod::dtls())?;
+    if let Some(cafile) = config.cafile.as_ref() {
+        builder.set_ca_file(cafile)?;
+    }
+    if let (Some(cert), Some(key)) = (config.cert.as_ref(), config.key.as_ref()) {
+        builder.set_certificate_chain_file(cert)?;
+        builder.set_private_key_file(key, SslFiletype::PEM)?;
+        builder.check_private_key()?;
+    }
+    Ok(builder.build())
+}
+
+/// Performs the server side of the handshake, blocks until it is done or
+/// `handshake_timeout` passed
+fn accept(
+    acceptor: &SslAcceptor,
+    mut datagrams: Datagrams,
+    handshake_timeout: Duration,
+) -> Result<SslStream<Datagrams>> {
+    let mut ssl = Ssl::new(acceptor.context())?;
+    ssl.set_mtu(MTU)?;
+    datagrams.set_deadline(Some(Instant::now() + handshake_timeout));
+    let mut stream = ssl
+        .accept(datagrams)
+        .map_err(|e| format!("DTLS handshake failed: {e}"))?;
+    stream.get_mut().set_deadline(None);
+    Ok(stream)
+}
+
+/// Performs the client side of the handshake, blocks until it is done or
+/// `handshake_timeout` passed
+fn connect(
+    connector: &SslConnector,
+    domain: &str,
+    mut datagrams: Datagrams,
+    handshake_timeout: Duration,
+) -> Result<SslStream<Datagrams>> {
+    let mut ssl = connector.configure()?.into_ssl(domain)?;
+    ssl.set_mtu(MTU)?;
+    datagrams.set_deadline(Some(Instant::now() + handshake_timeout));
+    let mut stream = ssl
+        .connect(datagrams)
+        .map_err(|e| format!("DTLS handshake with {domain} failed: {e}"))?;
+    stream.get_mut().set_deadline(None);
+    Ok(stream)
+}
+
+/// The datagrams exchanged with a single peer, every read returns one
+/// received datagram, every write is sent as one datagram
+#[derive(Debug)]
+pub(crate) struct Datagrams {
+    incoming: Receiver<Vec<u8>>,
+    outgoing: Sender<Vec<u8>>,
+    /// reads fail with `TimedOut` once this passed
+    deadline: Option<Instant>,
+}
+
+impl Datagrams {
+    pub(crate) fn new(incoming: Receiver<Vec<u8>>, outgoing: Sender<Vec<u8>>) -> Self {
+        Self {
+            incoming,
+            outgoing,
+            deadline: None,
+        }
+    }
+
+    pub(crate) fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+}
+
+impl Read for Datagrams {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let received = if let Some(deadline) = self.deadline {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            async_std::task::block_on(async_std::future::timeout(timeout, self.incoming.recv()))
+                .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+        } else {
+            async_std::task::block_on(self.incoming.recv())
+        };
+        let datagram = received.map_err(|_| io::Error::from(io::ErrorKind::ConnectionAborted))?;
+        // datagrams larger than the buffer are truncated, just like on a socket
+        let len = datagram.len().min(buf.len());
+        buf[..len].copy_from_slice(&datagram[..len]);
+        Ok(len)
+    }
+}
+
+impl Write for Datagrams {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        async_std::task::block_on(self.outgoing.send(buf.to_vec()))
+            .map_err(|_| io::Error::from(io::ErrorKind::ConnectionAborted))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// DTLS for the `udp_client`
+#[derive(Clone)]
+pub(crate) struct Client {
+    connector: SslConnector,
+    domain: String,
+    handshake_timeout: Duration,
+}
+
+impl Client {
+    pub(crate) fn new(config: &TLSClientConfig, domain: String) -> Result<Self> {
+        Ok(Self {
+            connector: connector(config)?,
+            domain,
+            handshake_timeout: HANDSHAKE_TIMEOUT,
+        })
+    }
+
+    /// Establishes a DTLS session over the connected `socket`
+    pub(crate) async fn handshake(&self, ctx: &SinkContext, socket: UdpSocket) -> Result<Session> {
+        let qsize = crate::QSIZE.load(Ordering::Relaxed);
+        let socket = Arc::new(socket);
+        let (incoming_tx, incoming_rx) = bounded(qsize);
+        let (outgoing_tx, outgoing_rx) = bounded::<Vec<u8>>(qsize);
+        let reader = socket.clone();
+        let buf_size = default_buf_size();
+        let tasks = vec![
+            spawn_task(ctx.clone(), async move {
+                let mut buffer = vec![0; buf_size];
+                loop {
+                    let bytes_read = reader.recv(&mut buffer).await?;
+                    // the session is only read from during the handshake, later
+                    // datagrams are dropped once the queue is full
+                    // ALLOW: we know bytes_read is smaller than or equal buf_size
+                    let _ = incoming_tx.try_send(buffer[0..bytes_read].to_vec());
+                }
+            }),
+            spawn_task(ctx.clone(), async move {
+                while let Ok(datagram) = outgoing_rx.recv().await {
+                    socket.send(&datagram).await?;
+                }
+                Ok(())
+            }),
+        ];
+
+        let datagrams = Datagrams::new(incoming_rx, outgoing_tx);
+        let connector = self.connector.clone();
+        let domain = self.domain.clone();
+        let handshake_timeout = self.handshake_timeout;
+        let handshake = async_std::task::spawn_blocking(move || {
+            connect(&connector, &domain, datagrams, handshake_timeout)
+        });
+        match async_std::future::timeout(self.handshake_timeout, handshake).await {
+            Ok(Ok(stream)) => Ok(Session {
+                stream: Arc::new(Mutex::new(stream)),
+                tasks,
+            }),
+            Ok(Err(e)) => {
+                cancel(tasks).await;
+                Err(e)
+            }
+            Err(_) => {
+                // stopping the tasks closes the channels, which aborts the handshake
+                cancel(tasks).await;
+                Err(format!("{ctx} DTLS handshake timed out").into())
+            }
+        }
+    }
+}
+
+/// An established DTLS session of the `udp_client`
+pub(crate) struct Session {
+    stream: Arc<Mutex<SslStream<Datagrams>>>,
+    /// tasks moving datagrams between the socket and the session
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Session {
+    pub(crate) async fn send(&self, data: Vec<Vec<u8>>) -> Result<()> {
+        let stream = self.stream.clone();
+        async_std::task::spawn_blocking(move || -> Result<()> {
+            let mut stream = stream.lock()?;
+            for chunk in data {
+                stream.ssl_write(chunk.as_slice())?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Tells the server we're done, before stopping the session
+    pub(crate) async fn close(self) {
+        let stream = self.stream;
+        // errors don't matter at this point
+        async_std::task::spawn_blocking(move || {
+            if let Ok(mut stream) = stream.lock() {
+                let _ = stream.shutdown();
+            }
+        })
+        .await;
+        cancel(self.tasks).await;
+    }
+
+    /// Stops the session without telling the server
+    pub(crate) async fn abort(self) {
+        cancel(self.tasks).await;
+    }
+}
+
+async fn cancel(tasks: Vec<JoinHandle<()>>) {
+    for task in tasks {
+        task.cancel().await;
+    }
+}
+
+/// DTLS for the `udp_server`, every peer gets its own session and stream
+#[derive(Clone)]
+pub(crate) struct Server {
+    config: TLSServerConfig,
+    acceptor: Arc<AsyncMutex<SslAcceptor>>,
+    changes: Arc<AsyncMutex<Changes>>,
+    idle_timeout: Duration,
+    handshake_timeout: Duration,
+}
+
+impl Server {
+    pub(crate) async fn new(config: &TLSServerConfig, idle_timeout: u64) -> Result<Self> {
+        let changes = Changes::new(config).await;
+        let tls_config = config.clone();
+        let acceptor = async_std::task::spawn_blocking(move || acceptor(&tls_config)).await?;
+        Ok(Self {
+            config: config.clone(),
+            acceptor: Arc::new(AsyncMutex::new(acceptor)),
+            changes: Arc::new(AsyncMutex::new(changes)),
+            idle_timeout: Duration::from_nanos(idle_timeout),
+            handshake_timeout: HANDSHAKE_TIMEOUT,
+        })
+    }
+
+    /// Picks up changes of the certificates for new sessions if `reload` is set
+    async fn acceptor(&self) -> SslAcceptor {
+        let mut current = self.acceptor.lock().await;
+        if self.config.reload && self.changes.lock().await.check(&self.config).await {
+            let config = self.config.clone();
+            match async_std::task::spawn_blocking(move || acceptor(&config)).await {
+                Ok(reloaded) => {
+                    info!("Reloaded DTLS certificate {}", self.config.cert.display());
+                    *current = reloaded;
+                }
+                Err(e) => {
+                    warn!("Error reloading DTLS certificates, keeping the previous ones: {e}")
+                }
+            }
+        }
+        current.clone()
+    }
+}
+
+/// The DTLS sessions with the peers of a `udp_server` socket
+pub(crate) struct Sessions {
+    server: Server,
+    /// receives the datagrams read from all sessions
+    tx: Sender<SourceReply>,
+    rx: Receiver<SourceReply>,
+    demux: Option<JoinHandle<()>>,
+}
+
+impl Sessions {
+    pub(crate) fn new(server: Server) -> Self {
+        let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        Self {
+            server,
+            tx,
+            rx,
+            demux: None,
+        }
+    }
+
+    /// Serves the peers of `socket`, the previous socket needs to be stopped first
+    pub(crate) fn serve(
+        &mut self,
+        socket: UdpSocket,
+        ctx: &SourceContext,
+        origin_uri: EventOriginUri,
+        buf_size: usize,
+    ) {
+        self.demux = Some(spawn_task(
+            ctx.clone(),
+            demux(
+                Arc::new(socket),
+                self.server.clone(),
+                ctx.clone(),
+                origin_uri,
+                self.tx.clone(),
+                buf_size,
+            ),
+        ));
+    }
+
+    pub(crate) async fn recv(&self) -> Result<SourceReply> {
+        Ok(self.rx.recv().await?)
+    }
+
+    pub(crate) async fn stop(&mut self) {
+        if let Some(demux) = self.demux.take() {
+            demux.cancel().await;
+        }
+    }
+}
+
+/// Dispatches the received datagrams to the DTLS session of their peer,
+/// starting a new session for unknown peers
+///
+/// Sessions end once their handshake or idle timeout passes, ended sessions
+/// are evicted whenever a new one is started
+async fn demux(
+    socket: Arc<UdpSocket>,
+    server: Server,
+    ctx: SourceContext,
+    origin_uri: EventOriginUri,
+    tx: Sender<SourceReply>,
+    buf_size: usize,
+) -> Result<()> {
+    let qsize = crate::QSIZE.load(Ordering::Relaxed);
+    let mut sessions: HashMap<SocketAddr, Sender<Vec<u8>>> = HashMap::new();
+    let mut buffer = vec![0; buf_size];
+    let mut stream_id = DEFAULT_STREAM_ID;
+    loop {
+        let (bytes_read, peer) = socket.recv_from(&mut buffer).await?;
+        // ALLOW: we know bytes_read is smaller than or equal buf_size
+        let datagram = buffer[0..bytes_read].to_vec();
+        if let Some(session) = sessions.get(&peer).filter(|s| !s.is_closed()) {
+            // like the socket itself we drop datagrams if the session can't keep up
+            if session.try_send(datagram).is_err() {
+                debug!("{ctx} Dropping datagram from {peer}");
+            }
+            continue;
+        }
+        sessions.retain(|_, session| !session.is_closed());
+        stream_id += 1;
+
+        let (incoming_tx, incoming_rx) = bounded(qsize);
+        let (outgoing_tx, outgoing_rx) = bounded::<Vec<u8>>(qsize);
+        incoming_tx.try_send(datagram)?;
+        sessions.insert(peer, incoming_tx);
+
+        let writer_socket = socket.clone();
+        async_std::task::spawn(async move {
+            while let Ok(datagram) = outgoing_rx.recv().await {
+                if let Err(e) = writer_socket.send_to(&datagram, peer).await {
+                    debug!("Error sending to {peer}: {e}");
+                    break;
+                }
+            }
+        });
+        let datagrams = Datagrams::new(incoming_rx, outgoing_tx);
+        let acceptor = server.acceptor().await;
+        let timeouts = (server.handshake_timeout, server.idle_timeout);
+        let session_ctx = ctx.clone();
+        let origin_uri = origin_uri.clone();
+        let tx = tx.clone();
+        async_std::task::spawn_blocking(move || {
+            session(
+                &acceptor,
+                datagrams,
+                peer,
+                stream_id,
+                &session_ctx,
+                origin_uri,
+                &tx,
+                buf_size,
+                timeouts,
+            );
+        });
+    }
+}
+
+/// Runs a single DTLS session, blocks until the peer closes it or it was
+/// idle for the idle timeout
+#[allow(clippy::too_many_arguments)]
+fn session(
+    acceptor: &SslAcceptor,
+    datagrams: Datagrams,
+    peer: SocketAddr,
+    stream: u64,
+    ctx: &SourceContext,
+    origin_uri: EventOriginUri,
+    tx: &Sender<SourceReply>,
+    buf_size: usize,
+    (handshake_timeout, idle_timeout): (Duration, Duration),
+) {
+    let mut session = match accept(acceptor, datagrams, handshake_timeout) {
+        Ok(session) => session,
+        Err(e) => {
+            warn!("{ctx} Error establishing DTLS session with {peer}: {e}");
+            return;
+        }
+    };
+    debug!("{ctx} DTLS session with {peer} established");
+    let meta = literal!({
+        "udp": {
+            "host": peer.ip().to_string(),
+            "port": peer.port()
+        },
+        "tls": true
+    });
+    let mut buffer = vec![0; buf_size];
+    loop {
+        session
+            .get_mut()
+            .set_deadline(Some(Instant::now() + idle_timeout));
+        match session.ssl_read(&mut buffer) {
+            Ok(0) => break,
+            Ok(bytes_read) => {
+                let reply = SourceReply::Data {
+                    origin_uri: origin_uri.clone(),
+                    stream: Some(stream),
+                    meta: Some(meta.clone()),
+                    // ALLOW: we know bytes_read is smaller than or equal buf_size
+                    data: buffer[0..bytes_read].to_vec(),
+                    port: None,
+                    codec_overwrite: None,
+                };
+                if async_std::task::block_on(tx.send(reply)).is_err() {
+                    return;
+                }
+            }
+            Err(e) if e.code() == ErrorCode::ZERO_RETURN => break,
+            Err(e) if e.io_error().map(io::Error::kind) == Some(io::ErrorKind::TimedOut) => {
+                debug!("{ctx} DTLS session with {peer} idle for {idle_timeout:?}");
+                // let the peer know, the session is gone either way
+                session.get_mut().set_deadline(None);
+                let _ = session.shutdown();
+                break;
+            }
+            Err(e) => {
+                debug!("{ctx} DTLS session with {peer} failed: {e}");
+                break;
+            }
+        }
+    }
+    debug!("{ctx} DTLS session with {peer} closed");
+    let reply = SourceReply::EndStream {
+        origin_uri,
+        stream,
+        meta: Some(meta),
+    };
+    // the source is already gone if this fails
+    let _ = async_std::task::block_on(tx.send(reply));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::tests::setup_for_tls;
+    use async_std::channel::bounded;
+    use std::path::PathBuf;
+
+    #[async_std::test]
+    async fn handshake() -> Result<()> {
+        setup_for_tls();
+        let server_config = TLSServerConfig {
+            cert: PathBuf::from("./tests/localhost.cert"),
+            key: PathBuf::from("./tests/localhost.key"),
+            client_ca: None,
+            client_auth: ClientAuth::default(),
+            reload: false,
+        };
+        let client_config = TLSClientConfig {
+            cafile: Some(PathBuf::from("./tests/localhost.cert")),
+            ..TLSClientConfig::default()
+        };
+        let acceptor = acceptor(&server_config)?;
+        let connector = connector(&client_config)?;
+
+        let (to_server, server_incoming) = bounded(64);
+        let (to_client, client_incoming) = bounded(64);
+        let server = async_std::task::spawn_blocking(move || -> Result<Vec<u8>> {
+            let datagrams = Datagrams::new(server_incoming, to_client);
+            let mut stream = accept(&acceptor, datagrams, HANDSHAKE_TIMEOUT)?;
+            let mut buf = vec![0; 1024];
+            let len = stream.ssl_read(&mut buf)?;
+            buf.truncate(len);
+            Ok(buf)
+        });
+        async_std::task::spawn_blocking(move || -> Result<()> {
+            let mut stream = connect(
+                &connector,
+                "localhost",
+                Datagrams::new(client_incoming, to_server),
+                HANDSHAKE_TIMEOUT,
+            )?;
+            stream.ssl_write(b"snot")?;
+            Ok(())
+        })
+        .await?;
+        assert_eq!(b"snot".to_vec(), server.await?);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn handshake_deadline() -> Result<()> {
+        setup_for_tls();
+        let server_config = TLSServerConfig {
+            cert: PathBuf::from("./tests/localhost.cert"),
+            key: PathBuf::from("./tests/localhost.key"),
+            client_ca: None,
+            client_auth: ClientAuth::default(),
+            reload: false,
+        };
+        let acceptor = acceptor(&server_config)?;
+        let (_to_server, server_incoming) = bounded(64);
+        let (to_client, _client_incoming) = bounded(64);
+        let mut datagrams = Datagrams::new(server_incoming, to_client);
+        // a passed deadline fails reads right away
+        datagrams.set_deadline(Some(Instant::now()));
+        let mut buf = [0; 16];
+        assert_eq!(
+            Some(io::ErrorKind::TimedOut),
+            datagrams.read(&mut buf).err().map(|e| e.kind())
+        );
+        // a peer that never answers fails the handshake
+        let handshake_timeout = Duration::from_millis(100);
+        let start = Instant::now();
+        let res = async_std::task::spawn_blocking(move || {
+            accept(&acceptor, datagrams, handshake_timeout)
+        })
+        .await;
+        assert!(res.is_err());
+        assert!(start.elapsed() >= handshake_timeout);
+        Ok(())
+    }
+}
@@ -13,7 +13,11 @@
 // limitations under the License.
 
 ///! The UDP server will close the udp spcket on stop
+#[cfg(feature = "dtls")]
+use super::dtls;
+use super::MulticastGroup;
 use crate::connectors::prelude::*;
+use crate::connectors::utils::tls::TLSServerConfig;
 use async_std::net::UdpSocket;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -24,12 +28,27 @@ pub(crate) struct Config {
     // UDP: receive buffer size
     #[serde(default = "default_buf_size")]
     buf_size: usize,
+    /// multicast groups to join, `url` should be the wildcard address or the group address
+    #[serde(default = "Default::default")]
+    multicast: Vec<MulticastGroup>,
+    /// DTLS config, every peer gets its own session and stream
+    #[serde(default = "Default::default")]
+    tls: Option<TLSServerConfig>,
+    /// time in nanoseconds after which a DTLS session that received nothing is closed
+    #[serde(default = "default_idle_timeout")]
+    idle_timeout: u64,
 }
 
 impl ConfigImpl for Config {}
 
+fn default_idle_timeout() -> u64 {
+    60_000_000_000
+}
+
 struct UdpServer {
     config: Config,
+    #[cfg(feature = "dtls")]
+    dtls: Option<dtls::Server>,
 }
 
 #[derive(Debug, Default)]
@@ -47,7 +66,24 @@ impl ConnectorBuilder for Builder {
         raw: &Value,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(raw)?;
-        Ok(Box::new(UdpServer { config }))
+        for group in &config.multicast {
+            group.validate()?;
+        }
+        #[cfg(feature = "dtls")]
+        {
+            let dtls = match config.tls.as_ref() {
+                Some(tls) => Some(dtls::Server::new(tls, config.idle_timeout).await?),
+                None => None,
+            };
+            Ok(Box::new(UdpServer { config, dtls }))
+        }
+        #[cfg(not(feature = "dtls"))]
+        {
+            if config.tls.is_some() {
+                return Err(super::DTLS_DISABLED.into());
+            }
+            Ok(Box::new(UdpServer { config }))
+        }
     }
 }
 
@@ -62,7 +98,11 @@ impl Connector for UdpServer {
         source_context: SourceContext,
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
-        let source = UdpServerSource::new(self.config.clone());
+        let source = UdpServerSource {
+            #[cfg(feature = "dtls")]
+            dtls: self.dtls.clone().map(dtls::Sessions::new),
+            ..UdpServerSource::new(self.config.clone())
+        };
         builder.spawn(source, source_context).map(Some)
     }
 }
@@ -72,6 +112,8 @@ struct UdpServerSource {
     origin_uri: EventOriginUri,
     listener: Option<UdpSocket>,
     buffer: Vec<u8>,
+    #[cfg(feature = "dtls")]
+    dtls: Option<dtls::Sessions>,
 }
 
 impl UdpServerSource {
@@ -88,29 +130,50 @@ impl UdpServerSource {
             origin_uri,
             listener: None,
             buffer,
+            #[cfg(feature = "dtls")]
+            dtls: None,
         }
     }
 }
 
 #[async_trait::async_trait]
 impl Source for UdpServerSource {
-    async fn connect(&mut self, _ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+    #[cfg_attr(not(feature = "dtls"), allow(unused_variables))]
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        #[cfg(feature = "dtls")]
+        if let Some(sessions) = self.dtls.as_mut() {
+            // release the previous socket before binding again
+            sessions.stop().await;
+        }
         let listener = UdpSocket::bind((
-            self.config.url.host_or_local(),
+            super::host(&self.config.url),
             self.config.url.port_or_dflt(),
         ))
         .await?;
+        for group in &self.config.multicast {
+            group.join(&listener)?;
+        }
+        #[cfg(feature = "dtls")]
+        if let Some(sessions) = self.dtls.as_mut() {
+            let origin_uri = self.origin_uri.clone();
+            sessions.serve(listener, ctx, origin_uri, self.config.buf_size);
+            return Ok(true);
+        }
         self.listener = Some(listener);
         Ok(true)
     }
 
     async fn pull_data(&mut self, _pull_id: &mut u64, ctx: &SourceContext) -> Result<SourceReply> {
+        #[cfg(feature = "dtls")]
+        if let Some(sessions) = self.dtls.as_ref() {
+            return sessions.recv().await;
+        }
         let socket = self
             .listener
             .as_ref()
             .ok_or_else(|| Error::from(ErrorKind::NoSocket))?;
-        match socket.recv(&mut self.buffer).await {
-            Ok(bytes_read) => {
+        match socket.recv_from(&mut self.buffer).await {
+            Ok((bytes_read, sender)) => {
                 if bytes_read == 0 {
                     Ok(SourceReply::EndStream {
                         origin_uri: self.origin_uri.clone(),
@@ -121,7 +184,12 @@ impl Source for UdpServerSource {
                     Ok(SourceReply::Data {
                         origin_uri: self.origin_uri.clone(),
                         stream: Some(DEFAULT_STREAM_ID),
-                        meta: None,
+                        meta: Some(literal!({
+                            "udp": {
+                                "host": sender.ip().to_string(),
+                                "port": sender.port()
+                            }
+                        })),
                         // ALLOW: we know bytes_read is smaller than or equal buf_size
                         data: self.buffer[0..bytes_read].to_vec(),
                         port: None,
@@ -141,11 +209,26 @@ impl Source for UdpServerSource {
         }
     }
 
+    async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
+        #[cfg(feature = "dtls")]
+        if let Some(sessions) = self.dtls.as_mut() {
+            sessions.stop().await;
+        }
+        Ok(())
+    }
+
     fn is_transactional(&self) -> bool {
         false
     }
 
     fn asynchronous(&self) -> bool {
-        false
+        #[cfg(feature = "dtls")]
+        {
+            self.dtls.is_some()
+        }
+        #[cfg(not(feature = "dtls"))]
+        {
+            false
+        }
     }
 }
@@ -14,7 +14,7 @@
 
 use crate::connectors::prelude::*;
 use async_std::os::unix::net::UnixStream;
-use futures::{AsyncReadExt, AsyncWriteExt};
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tremor_pipeline::EventOriginUri;
 use tremor_value::Value;
 
@@ -24,24 +24,46 @@ pub(crate) mod server;
 /// unix domain socket client
 pub(crate) mod client;
 
-struct UnixSocketReader {
-    stream: UnixStream,
+struct UnixSocketReader<S>
+where
+    S: AsyncRead + Unpin + Sync + Send,
+{
+    wrapped_stream: S,
+    underlying_stream: UnixStream,
     buffer: Vec<u8>,
     alias: String,
     origin_uri: EventOriginUri,
     meta: Value<'static>,
 }
 
-impl UnixSocketReader {
+impl UnixSocketReader<UnixStream> {
     fn new(
         stream: UnixStream,
         buffer: Vec<u8>,
         alias: String,
         origin_uri: EventOriginUri,
         meta: Value<'static>,
+    ) -> Self {
+        Self::tls(stream.clone(), stream, buffer, alias, origin_uri, meta)
+    }
+}
+
+impl<S> UnixSocketReader<S>
+where
+    S: AsyncRead + Unpin + Sync + Send,
+{
+    /// reads from `stream`, e.g. the reading half of a TLS stream on top of `underlying_stream`
+    fn tls(
+        stream: S,
+        underlying_stream: UnixStream,
+        buffer: Vec<u8>,
+        alias: String,
+        origin_uri: EventOriginUri,
+        meta: Value<'static>,
     ) -> Self {
         Self {
-            stream,
+            wrapped_stream: stream,
+            underlying_stream,
             buffer,
             alias,
             origin_uri,
@@ -51,7 +73,10 @@ impl UnixSocketReader {
 }
 
 #[async_trait::async_trait()]
-impl StreamReader for UnixSocketReader {
+impl<S> StreamReader for UnixSocketReader<S>
+where
+    S: AsyncRead + Unpin + Sync + Send,
+{
     async fn quiesce(&mut self, stream: u64) -> Option<SourceReply> {
         Some(SourceReply::EndStream {
             origin_uri: self.origin_uri.clone(),
@@ -60,7 +85,7 @@ impl StreamReader for UnixSocketReader {
         })
     }
     async fn read(&mut self, stream: u64) -> Result<SourceReply> {
-        let bytes_read = self.stream.read(&mut self.buffer).await?;
+        let bytes_read = self.wrapped_stream.read(&mut self.buffer).await?;
         if bytes_read == 0 {
             // EOF
             trace!("[Connector::{}] Stream {stream} EOF", &self.alias);
@@ -91,7 +116,7 @@ impl StreamReader for UnixSocketReader {
 
     async fn on_done(&mut self, stream: u64) -> StreamDone {
         // THIS IS SHUTDOWN!
-        if let Err(e) = self.stream.shutdown(std::net::Shutdown::Read) {
+        if let Err(e) = self.underlying_stream.shutdown(std::net::Shutdown::Read) {
             warn!(
                 "[Connector::{}] Error shutting down reading half of stream {stream}: {e}",
                 &self.alias
@@ -101,18 +126,38 @@ impl StreamReader for UnixSocketReader {
     }
 }
 
-struct UnixSocketWriter {
-    stream: UnixStream,
+struct UnixSocketWriter<S>
+where
+    S: AsyncWrite + Unpin + Sync + Send,
+{
+    wrapped_stream: S,
+    underlying_stream: UnixStream,
 }
 
-impl UnixSocketWriter {
+impl UnixSocketWriter<UnixStream> {
     fn new(stream: UnixStream) -> Self {
-        Self { stream }
+        Self::tls(stream.clone(), stream)
+    }
+}
+
+impl<S> UnixSocketWriter<S>
+where
+    S: AsyncWrite + Unpin + Sync + Send,
+{
+    /// writes to `stream`, e.g. the writing half of a TLS stream on top of `underlying_stream`
+    fn tls(stream: S, underlying_stream: UnixStream) -> Self {
+        Self {
+            wrapped_stream: stream,
+            underlying_stream,
+        }
     }
 }
 
 #[async_trait::async_trait()]
-impl StreamWriter for UnixSocketWriter {
+impl<S> StreamWriter for UnixSocketWriter<S>
+where
+    S: AsyncWrite + Unpin + Sync + Send,
+{
     async fn write(&mut self, data: Vec<Vec<u8>>, _meta: Option<SinkMeta>) -> Result<()> {
         for chunk in data {
             let slice: &[u8] = &chunk;
@@ -120,14 +165,19 @@ impl StreamWriter for UnixSocketWriter {
                 "[UNIX SOCKET WRITER] WRITING: {}",
                 String::from_utf8_lossy(slice)
             );
-            self.stream.write_all(slice).await?;
+            self.wrapped_stream.write_all(slice).await?;
         }
         // TODO: necessary?
-        self.stream.flush().await?;
+        self.wrapped_stream.flush().await?;
         Ok(())
     }
     async fn on_done(&mut self, _stream: u64) -> Result<StreamDone> {
-        self.stream.shutdown(std::net::Shutdown::Write)?;
+        self.underlying_stream.shutdown(std::net::Shutdown::Write)?;
+        Ok(StreamDone::StreamClosed)
+    }
+    async fn on_close(&mut self, _stream: u64) -> Result<StreamDone> {
+        // also stops the reader, so the connection is gone entirely
+        self.underlying_stream.shutdown(std::net::Shutdown::Both)?;
         Ok(StreamDone::StreamClosed)
     }
 }
@@ -13,11 +13,13 @@
 // limitations under the License.
 
 use crate::connectors::prelude::*;
-use crate::errors::{Kind as ErrorKind, Result};
+use crate::connectors::utils::tls::{tls_client_connector, TLSClientConfig};
+use crate::errors::{err_conector_def, Kind as ErrorKind, Result};
 use async_std::channel::{bounded, Receiver, Sender};
 use async_std::os::unix::net::UnixStream;
 use async_std::path::PathBuf;
-use futures::AsyncWriteExt;
+use async_tls::TlsConnector;
+use futures::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use super::UnixSocketReader;
 
@@ -29,6 +31,8 @@ pub struct Config {
     path: String,
     #[serde(default = "default_buf_size")]
     buf_size: usize,
+    /// TLS configuration, `domain` is required to verify the server certificate
+    tls: Option<TLSClientConfig>,
 }
 
 impl ConfigImpl for Config {}
@@ -43,14 +47,24 @@ impl ConnectorBuilder for Builder {
     }
     async fn build_cfg(
         &self,
-        _: &str,
+        id: &str,
         _: &ConnectorConfig,
         conf: &Value,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(conf)?;
+        let tls = if let Some(tls_config) = config.tls.as_ref() {
+            // there is no host to derive the server name from
+            let domain = tls_config.domain.clone().ok_or_else(|| {
+                err_conector_def(id, "`tls.domain` is required for TLS over unix sockets")
+            })?;
+            Some((tls_client_connector(tls_config).await?, domain))
+        } else {
+            None
+        };
         let (source_tx, source_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         Ok(Box::new(Client {
             config,
+            tls,
             source_tx,
             source_rx,
         }))
@@ -59,6 +73,7 @@ impl ConnectorBuilder for Builder {
 
 pub struct Client {
     config: Config,
+    tls: Option<(TlsConnector, String)>,
     source_tx: Sender<SourceReply>,
     source_rx: Receiver<SourceReply>,
 }
@@ -84,30 +99,43 @@ impl Connector for Client {
         sink_context: SinkContext,
         builder: SinkManagerBuilder,
     ) -> Result<Option<SinkAddr>> {
-        let sink = UnixSocketSink::new(self.config.clone(), self.source_tx.clone());
+        let sink = UnixSocketSink::new(
+            self.config.clone(),
+            self.tls.clone(),
+            self.source_tx.clone(),
+        );
         builder.spawn(sink, sink_context).map(Some)
     }
 }
 
 struct UnixSocketSink {
     config: Config,
+    tls: Option<(TlsConnector, String)>,
     source_runtime: ChannelSourceRuntime,
     stream: Option<UnixStream>,
+    /// the stream to write to, wrapping `stream` if TLS is used
+    wrapped_stream: Option<Box<dyn AsyncWrite + Unpin + Send + Sync>>,
 }
 
 impl UnixSocketSink {
-    fn new(config: Config, source_tx: Sender<SourceReply>) -> Self {
+    fn new(
+        config: Config,
+        tls: Option<(TlsConnector, String)>,
+        source_tx: Sender<SourceReply>,
+    ) -> Self {
         let source_runtime = ChannelSourceRuntime::new(source_tx);
         Self {
             config,
+            tls,
             source_runtime,
             stream: None,
+            wrapped_stream: None,
         }
     }
 
     async fn write(&mut self, data: Vec<Vec<u8>>) -> Result<()> {
         let stream = self
-            .stream
+            .wrapped_stream
             .as_mut()
             .ok_or_else(|| Error::from(ErrorKind::NoSocket))?;
         for chunk in data {
@@ -120,6 +148,7 @@ impl UnixSocketSink {
     }
 
     async fn close(&mut self) -> Result<()> {
+        self.wrapped_stream = None;
         if let Some(stream) = self.stream.take() {
             stream.shutdown(std::net::Shutdown::Write)?;
         }
@@ -145,19 +174,40 @@ impl Sink for UnixSocketSink {
             port: None,
             path: vec![path.display().to_string()],
         };
-        let meta = ctx.meta(literal!({
-            "peer": path.display().to_string()
-        }));
         self.stream = Some(stream.clone());
-        let reader = UnixSocketReader::new(
-            stream,
-            vec![0; self.config.buf_size],
-            ctx.alias().to_string(),
-            origin_uri,
-            meta,
-        );
-        self.source_runtime
-            .register_stream_reader(DEFAULT_STREAM_ID, ctx, reader);
+        if let Some((tls_connector, domain)) = self.tls.as_ref() {
+            let tls_stream = tls_connector.connect(domain, stream.clone()).await?;
+            let (read, write) = tls_stream.split();
+            let meta = ctx.meta(literal!({
+                "peer": path.display().to_string(),
+                "tls": true
+            }));
+            self.wrapped_stream = Some(Box::new(write));
+            let reader = UnixSocketReader::tls(
+                read,
+                stream,
+                vec![0; self.config.buf_size],
+                ctx.alias().to_string(),
+                origin_uri,
+                meta,
+            );
+            self.source_runtime
+                .register_stream_reader(DEFAULT_STREAM_ID, ctx, reader);
+        } else {
+            let meta = ctx.meta(literal!({
+                "peer": path.display().to_string()
+            }));
+            self.wrapped_stream = Some(Box::new(stream.clone()));
+            let reader = UnixSocketReader::new(
+                stream,
+                vec![0; self.config.buf_size],
+                ctx.alias().to_string(),
+                origin_uri,
+                meta,
+            );
+            self.source_runtime
+                .register_stream_reader(DEFAULT_STREAM_ID, ctx, reader);
+        }
         Ok(true)
     }
 
@@ -175,6 +225,7 @@ impl Sink for UnixSocketSink {
             if let Err(e) = self.write(data).await {
                 error!("{ctx} Error sending data: {e}. Initiating Reconnect...");
                 self.stream = None;
+                self.wrapped_stream = None;
                 ctx.notifier().connection_lost().await?;
                 return Err(e);
             }
@@ -30,6 +30,11 @@
 //! We try to route the event to the connection with `stream_id` `123`.
 use crate::connectors::prelude::*;
 use crate::connectors::sink::channel_sink::ChannelSinkMsg;
+use crate::connectors::utils::{
+    lifecycle::{Lifecycle, Tracked},
+    tls::{TLSServerConfig, TlsServer},
+};
+use crate::connectors::{LIFECYCLE_OUT_PORTS_REF, OUT_PORTS_REF};
 use async_std::os::unix::net::UnixListener;
 use async_std::path::PathBuf;
 use async_std::task::JoinHandle;
@@ -37,6 +42,8 @@ use async_std::{
     channel::{bounded, Receiver, Sender},
     prelude::FutureExt,
 };
+use beef::Cow;
+use futures::AsyncReadExt;
 
 use super::{UnixSocketReader, UnixSocketWriter};
 
@@ -50,6 +57,11 @@ pub struct Config {
     /// receive buffer size
     #[serde(default = "default_buf_size")]
     buf_size: usize,
+    /// TLS configuration, if required
+    tls: Option<TLSServerConfig>,
+    /// emit `connected` and `disconnected` events on the `connections` port
+    #[serde(default = "default_false")]
+    lifecycle_events: bool,
 }
 
 impl ConfigImpl for Config {}
@@ -71,9 +83,14 @@ impl ConnectorBuilder for Builder {
         config: &Value,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(config)?;
+        let tls_server = match config.tls.as_ref() {
+            Some(tls_config) => Some(TlsServer::new(tls_config).await?),
+            None => None,
+        };
         let (sink_tx, sink_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         Ok(Box::new(UnixSocketServer {
             config,
+            tls_server,
             sink_tx,
             sink_rx,
         }))
@@ -99,12 +116,21 @@ fn resolve_connection_meta(meta: &Value) -> Option<ConnectionMeta> {
 
 struct UnixSocketServer {
     config: Config,
+    tls_server: Option<TlsServer>,
     sink_tx: Sender<ChannelSinkMsg<ConnectionMeta>>,
     sink_rx: Receiver<ChannelSinkMsg<ConnectionMeta>>,
 }
 
 #[async_trait::async_trait()]
 impl Connector for UnixSocketServer {
+    fn output_ports(&self) -> &[Cow<'static, str>] {
+        if self.config.lifecycle_events {
+            LIFECYCLE_OUT_PORTS_REF
+        } else {
+            OUT_PORTS_REF
+        }
+    }
+
     fn codec_requirements(&self) -> CodecReq {
         CodecReq::Required
     }
@@ -115,7 +141,8 @@ impl Connector for UnixSocketServer {
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
         let sink_runtime = ChannelSinkRuntime::new(self.sink_tx.clone());
-        let source = UnixSocketSource::new(self.config.clone(), sink_runtime);
+        let source =
+            UnixSocketSource::new(self.config.clone(), self.tls_server.clone(), sink_runtime);
         builder.spawn(source, source_context).map(Some)
     }
 
@@ -136,6 +163,7 @@ impl Connector for UnixSocketServer {
 
 struct UnixSocketSource {
     config: Config,
+    tls_server: Option<TlsServer>,
     listener_task: Option<JoinHandle<()>>,
     connection_rx: Receiver<SourceReply>,
     runtime: ChannelSourceRuntime,
@@ -143,11 +171,16 @@ struct UnixSocketSource {
 }
 
 impl UnixSocketSource {
-    fn new(config: Config, sink_runtime: ChannelSinkRuntime<ConnectionMeta>) -> Self {
+    fn new(
+        config: Config,
+        tls_server: Option<TlsServer>,
+        sink_runtime: ChannelSinkRuntime<ConnectionMeta>,
+    ) -> Self {
         let (tx, rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
         let runtime = ChannelSourceRuntime::new(tx);
         Self {
             config,
+            tls_server,
             listener_task: None,
             connection_rx: rx,
             runtime,
@@ -173,9 +206,11 @@ impl Source for UnixSocketSource {
             mode.set_mode_path(&path)?;
         }
         let buf_size = self.config.buf_size;
+        let lifecycle_events = self.config.lifecycle_events;
         let ctx = ctx.clone();
         let runtime = self.runtime.clone();
         let sink_runtime = self.sink_runtime.clone();
+        let tls_server = self.tls_server.clone();
         self.listener_task = Some(spawn_task(ctx.clone(), async move {
             let mut stream_id_gen = StreamIdGen::default();
             let origin_uri = EventOriginUri {
@@ -199,7 +234,65 @@ impl Source for UnixSocketSource {
 
                             let $unix_socket_server = { "peer": 123 };
                         */
+                        if let Some(tls_server) = tls_server.as_ref() {
+                            let (acceptor, peer_cert) = tls_server.acceptor().await;
+                            let tls_stream = match acceptor.accept(stream.clone()).await {
+                                Ok(tls_stream) => tls_stream,
+                                Err(e) => {
+                                    // e.g. a client without a valid certificate, keep accepting others
+                                    warn!("{ctx} TLS handshake failed: {e}");
+                                    continue;
+                                }
+                            };
+                            let (tls_read_stream, tls_write_sink) = tls_stream.split();
+                            let mut meta = literal!({ "peer": stream_id, "tls": true });
+                            if let Some(peer_tls) = peer_cert.meta() {
+                                meta.try_insert("peer_tls", peer_tls);
+                            }
+                            let meta = ctx.meta(meta);
+                            let lifecycle = if lifecycle_events {
+                                Some(
+                                    Lifecycle::connected(&runtime, stream_id, &origin_uri, &meta)
+                                        .await?,
+                                )
+                            } else {
+                                None
+                            };
+                            let reader = UnixSocketReader::tls(
+                                tls_read_stream,
+                                stream.clone(),
+                                vec![0; buf_size],
+                                ctx.alias().to_string(),
+                                origin_uri.clone(),
+                                meta,
+                            );
+                            sink_runtime
+                                .register_stream_writer(
+                                    stream_id,
+                                    Some(connection_meta),
+                                    &ctx,
+                                    Tracked::new(
+                                        UnixSocketWriter::tls(tls_write_sink, stream),
+                                        lifecycle.clone(),
+                                    ),
+                                )
+                                .await;
+                            runtime.register_stream_reader(
+                                stream_id,
+                                &ctx,
+                                Tracked::new(reader, lifecycle),
+                            );
+                            continue;
+                        }
                         let meta = ctx.meta(literal!({ "peer": stream_id }));
+                        let lifecycle = if lifecycle_events {
+                            Some(
+                                Lifecycle::connected(&runtime, stream_id, &origin_uri, &meta)
+                                    .await?,
+                            )
+                        } else {
+                            None
+                        };
                         let reader = UnixSocketReader::new(
                             stream.clone(),
                             vec![0; buf_size],
@@ -212,10 +305,14 @@ impl Source for UnixSocketSource {
                                 stream_id,
                                 Some(connection_meta),
                                 &ctx,
-                                UnixSocketWriter::new(stream),
+                                Tracked::new(UnixSocketWriter::new(stream), lifecycle.clone()),
                             )
                             .await;
-                        runtime.register_stream_reader(stream_id, &ctx, reader);
+                        runtime.register_stream_reader(
+                            stream_id,
+                            &ctx,
+                            Tracked::new(reader, lifecycle),
+                        );
                     }
                     Ok(Err(e)) => return Err(e.into()),
                     Err(_) => continue,
@@ -13,17 +13,19 @@
 // limitations under the License.
 
 use super::{WsReader, WsWriter};
-use crate::connectors::utils::tls::{load_server_config, TLSServerConfig};
+use crate::connectors::utils::{
+    lifecycle::{Lifecycle, Tracked},
+    tls::{PeerCert, TLSServerConfig, TlsServer},
+};
 use crate::connectors::{prelude::*, utils::ConnectionMeta};
+use crate::connectors::{LIFECYCLE_OUT_PORTS_REF, OUT_PORTS_REF};
 use async_std::task::JoinHandle;
 use async_std::{net::TcpListener, prelude::FutureExt};
-use async_tls::TlsAcceptor;
 use async_tungstenite::accept_async;
+use beef::Cow;
 use futures::StreamExt;
-use rustls::ServerConfig;
 use simd_json::ValueAccess;
 use std::net::SocketAddr;
-use std::sync::Arc;
 
 const URL_SCHEME: &str = "tremor-ws-server";
 
@@ -33,6 +35,9 @@ pub(crate) struct Config {
     // kept as a str, so it is re-resolved upon each connect
     url: Url<super::WsDefaults>,
     tls: Option<TLSServerConfig>,
+    /// emit `connected` and `disconnected` events on the `connections` port
+    #[serde(default = "default_false")]
+    lifecycle_events: bool,
 }
 
 impl ConfigImpl for Config {}
@@ -43,7 +48,7 @@ pub(crate) struct WsServer {
     accept_task: Option<JoinHandle<()>>,
     sink_runtime: Option<ChannelSinkRuntime<ConnectionMeta>>,
     source_runtime: Option<ChannelSourceRuntime>,
-    tls_server_config: Option<ServerConfig>,
+    tls_server: Option<TlsServer>,
 }
 
 #[derive(Debug, Default)]
@@ -62,10 +67,9 @@ impl ConnectorBuilder for Builder {
     ) -> crate::errors::Result<Box<dyn Connector>> {
         let config = Config::new(raw_config)?;
 
-        let tls_server_config = if let Some(tls_config) = config.tls.as_ref() {
-            Some(load_server_config(tls_config)?)
-        } else {
-            None
+        let tls_server = match config.tls.as_ref() {
+            Some(tls_config) => Some(TlsServer::new(tls_config).await?),
+            None => None,
         };
 
         Ok(Box::new(WsServer {
@@ -73,7 +77,7 @@ impl ConnectorBuilder for Builder {
             accept_task: None,  // not yet started
             sink_runtime: None, // replaced in create_sink()
             source_runtime: None,
-            tls_server_config,
+            tls_server,
         }))
     }
 }
@@ -91,22 +95,34 @@ fn resolve_connection_meta(meta: &Value) -> Option<ConnectionMeta> {
 }
 
 impl WsServer {
-    fn meta(peer: SocketAddr, has_tls: bool) -> Value<'static> {
+    fn meta(peer: SocketAddr, has_tls: bool, peer_cert: Option<&PeerCert>) -> Value<'static> {
         let peer_ip = peer.ip().to_string();
         let peer_port = peer.port();
 
+        let mut peer_meta = literal!({
+            "host": peer_ip,
+            "port": peer_port
+        });
+        if let Some(peer_tls) = peer_cert.and_then(PeerCert::meta) {
+            peer_meta.try_insert("tls", peer_tls);
+        }
         literal!({
             "tls": has_tls,
-            "peer": {
-                "host": peer_ip,
-                "port": peer_port
-            }
+            "peer": peer_meta
         })
     }
 }
 
 #[async_trait::async_trait()]
 impl Connector for WsServer {
+    fn output_ports(&self) -> &[Cow<'static, str>] {
+        if self.config.lifecycle_events {
+            LIFECYCLE_OUT_PORTS_REF
+        } else {
+            OUT_PORTS_REF
+        }
+    }
+
     async fn on_stop(&mut self, _ctx: &ConnectorContext) -> Result<()> {
         if let Some(accept_task) = self.accept_task.take() {
             // stop acceptin' new connections
@@ -176,7 +192,8 @@ impl Connector for WsServer {
         let listener = TcpListener::bind((host, port)).await?;
 
         let ctx = ctx.clone();
-        let tls_server_config = self.tls_server_config.clone();
+        let tls_server = self.tls_server.clone();
+        let lifecycle_events = self.config.lifecycle_events;
 
         // accept task
         self.accept_task = Some(spawn_task(ctx.clone(), async move {
@@ -196,25 +213,43 @@ impl Connector for WsServer {
                             path: path.clone(), // captures server port
                         };
 
-                        let tls_acceptor: Option<TlsAcceptor> = tls_server_config
-                            .clone()
-                            .map(|sc| TlsAcceptor::from(Arc::new(sc)));
-                        if let Some(acceptor) = tls_acceptor {
-                            let meta = ctx.meta(WsServer::meta(peer_addr, true));
+                        if let Some(tls_server) = tls_server.as_ref() {
+                            let (acceptor, peer_cert) = tls_server.acceptor().await;
                             // TODO: this should live in its own task, as it requires rome roundtrips :()
-                            let tls_stream = acceptor.accept(tcp_stream).await?;
+                            let tls_stream = match acceptor.accept(tcp_stream).await {
+                                Ok(tls_stream) => tls_stream,
+                                Err(e) => {
+                                    // e.g. a client without a valid certificate, keep accepting others
+                                    warn!("{ctx} TLS handshake with {peer_addr} failed: {e}");
+                                    continue;
+                                }
+                            };
+                            let meta = ctx.meta(WsServer::meta(peer_addr, true, Some(&peer_cert)));
                             let ws_stream = accept_async(tls_stream).await?;
                             debug!("{ctx} new connection from {peer_addr}");
 
                             let (ws_write, ws_read) = ws_stream.split();
 
+                            let lifecycle = if lifecycle_events {
+                                Some(
+                                    Lifecycle::connected(
+                                        &source_runtime,
+                                        stream_id,
+                                        &origin_uri,
+                                        &meta,
+                                    )
+                                    .await?,
+                                )
+                            } else {
+                                None
+                            };
                             let ws_writer = WsWriter::new_tls_server(ws_write);
                             sink_runtime
                                 .register_stream_writer(
                                     stream_id,
                                     Some(connection_meta.clone()),
                                     &ctx,
-                                    ws_writer,
+                                    Tracked::new(ws_writer, lifecycle.clone()),
                                 )
                                 .await;
 
@@ -225,7 +260,11 @@ impl Connector for WsServer {
                                 meta,
                                 ctx.clone(),
                             );
-                            source_runtime.register_stream_reader(stream_id, &ctx, ws_reader);
+                            source_runtime.register_stream_reader(
+                                stream_id,
+                                &ctx,
+                                Tracked::new(ws_reader, lifecycle),
+                            );
                         } else {
                             let ws_stream = match accept_async(tcp_stream).await {
                                 Ok(s) => s,
@@ -238,8 +277,21 @@ impl Connector for WsServer {
 
                             let (ws_write, ws_read) = ws_stream.split();
 
-                            let meta = ctx.meta(WsServer::meta(peer_addr, false));
-
+                            let meta = ctx.meta(WsServer::meta(peer_addr, false, None));
+
+                            let lifecycle = if lifecycle_events {
+                                Some(
+                                    Lifecycle::connected(
+                                        &source_runtime,
+                                        stream_id,
+                                        &origin_uri,
+                                        &meta,
+                                    )
+                                    .await?,
+                                )
+                            } else {
+                                None
+                            };
                             let ws_writer = WsWriter::new(ws_write);
 
                             sink_runtime
@@ -247,7 +299,7 @@ impl Connector for WsServer {
                                     stream_id,
                                     Some(connection_meta.clone()),
                                     &ctx,
-                                    ws_writer,
+                                    Tracked::new(ws_writer, lifecycle.clone()),
                                 )
                                 .await;
 
@@ -258,7 +310,11 @@ impl Connector for WsServer {
                                 meta,
                                 ctx.clone(),
                             );
-                            source_runtime.register_stream_reader(stream_id, &ctx, ws_reader);
+                            source_runtime.register_stream_reader(
+                                stream_id,
+                                &ctx,
+                                Tracked::new(ws_reader, lifecycle),
+                            );
                         }
                     }
                     Ok(Err(e)) => return Err(e.into()),
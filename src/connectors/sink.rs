@@ -250,6 +250,11 @@ pub(crate) trait StreamWriter: Send + Sync {
     async fn on_done(&mut self, _stream: u64) -> Result<StreamDone> {
         Ok(StreamDone::StreamClosed)
     }
+    /// handle the stream being closed from the sink side, e.g. by a `close` control event,
+    /// the connection should be closed entirely
+    async fn on_close(&mut self, stream: u64) -> Result<StreamDone> {
+        self.on_done(stream).await
+    }
 }
 
 #[async_trait::async_trait]
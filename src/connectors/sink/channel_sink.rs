@@ -64,7 +64,7 @@ where
         /// stream metadata used for resolving a stream
         meta: Option<M>,
         /// sender to the actual stream handling data
-        sender: Sender<SinkData>,
+        sender: Sender<StreamMsg>,
     },
     /// remove the stream
     RemoveStream(u64),
@@ -73,6 +73,15 @@ where
 /// Metadata for a sink message
 pub(crate) type SinkMeta = Value<'static>;
 
+/// messages for the writer of a `ChannelSink` stream
+#[derive(Clone, Debug)]
+pub(crate) enum StreamMsg {
+    /// data to write out
+    Data(SinkData),
+    /// close the stream after writing out the data received before
+    Close,
+}
+
 /// some data for a `ChannelSink` stream
 #[derive(Clone, Debug)]
 pub(crate) struct SinkData {
@@ -95,7 +104,7 @@ where
 {
     _b: PhantomData<B>,
     streams_meta: BiMap<M, u64>,
-    streams: HashMap<u64, Sender<SinkData>>,
+    streams: HashMap<u64, Sender<StreamMsg>>,
     resolver: F,
     tx: Sender<ChannelSinkMsg<M>>,
     rx: Receiver<ChannelSinkMsg<M>>,
@@ -214,8 +223,12 @@ where
         &self,
         meta: &'lt Value<'value>,
         ctx: &SinkContext,
-    ) -> Option<(&u64, &Sender<SinkData>)> {
+    ) -> Option<(&u64, &Sender<StreamMsg>)> {
         let sink_meta = get_sink_meta(meta, ctx);
+        // an explicit `stream_id` wins over connector specific connection metadata
+        if let Some(stream_id) = sink_meta.get_u64("stream_id") {
+            return self.streams.get_key_value(&stream_id);
+        }
         sink_meta
             .and_then(|sink_meta| (self.resolver)(sink_meta))
             .and_then(|stream_meta| self.streams_meta.get_by_left(&stream_meta))
@@ -270,7 +283,7 @@ where
         W: StreamWriter + 'static,
         C: Context + Send + Sync + 'static,
     {
-        let (stream_tx, stream_rx) = bounded::<SinkData>(QSIZE.load(Ordering::Relaxed));
+        let (stream_tx, stream_rx) = bounded::<StreamMsg>(QSIZE.load(Ordering::Relaxed));
         let stream_sink_tx = self.tx.clone();
         let ctx = ctx.clone();
         let tx = self.tx.clone();
@@ -284,6 +297,7 @@ where
             "Error sending NewStream msg to ChannelSink",
         );
         task::spawn(async move {
+            let mut closed = false;
             // receive loop from channel sink
             while let (true, sinkdata) = (
                 ctx.quiescence_beacon().continue_writing().await,
@@ -295,12 +309,12 @@ where
                         // timeout, just continue
                         continue;
                     }
-                    Ok(Ok(SinkData {
+                    Ok(Ok(StreamMsg::Data(SinkData {
                         data,
                         meta,
                         contraflow,
                         start,
-                    })) => {
+                    }))) => {
                         let failed = writer.write(data, meta).await.is_err();
 
                         // send async contraflow insights if requested (only if event.transactional)
@@ -318,13 +332,23 @@ where
                             break;
                         }
                     }
+                    Ok(Ok(StreamMsg::Close)) => {
+                        debug!("{ctx} Stream {stream} closed by ChannelSink");
+                        closed = true;
+                        break;
+                    }
                     Ok(Err(e)) => {
                         warn!("{ctx} Error receiving data from ChannelSink: {e}");
                         break;
                     }
                 }
             }
-            let error = match writer.on_done(stream).await {
+            let done = if closed {
+                writer.on_close(stream).await
+            } else {
+                writer.on_done(stream).await
+            };
+            let error = match done {
                 Err(e) => Some(e),
                 Ok(StreamDone::ConnectorClosed) => ctx.notifier().connection_lost().await.err(),
                 Ok(_) => None,
@@ -344,6 +368,9 @@ where
 ///
 /// The general path is `$<CONNECTOR_TYPE>`
 /// Example: `$tcp_server`
+///
+/// Setting `close` to `true` in there closes the connection the event is routed to
+/// instead of writing the event out.
 fn get_sink_meta<'lt, 'value>(
     meta: &'lt Value<'value>,
     ctx: &SinkContext,
@@ -390,6 +417,7 @@ where
         };
 
         let mut remove_streams = vec![];
+        let mut close_streams = vec![];
         let mut reply = SinkReply::default();
         for (value, meta) in event.value_meta_iter() {
             let mut errored = false;
@@ -408,6 +436,20 @@ where
                 |stream| Either::Left(std::iter::once(stream)),
             );
 
+            if get_sink_meta(meta, ctx).get_bool("close") == Some(true) {
+                for (stream_id, sender) in streams {
+                    found = true;
+                    // the stream is gone already if this fails
+                    let _ = sender.send(StreamMsg::Close).await;
+                    close_streams.push(*stream_id);
+                }
+                if !found {
+                    debug!("{ctx} No stream to close found for event: {}", &event.id);
+                    reply = SinkReply::FAIL;
+                }
+                continue;
+            }
+
             for (stream_id, sender) in streams {
                 trace!("{ctx} Send to stream {stream_id}.");
                 let data = serializer.serialize_for_stream(value, ingest_ns, *stream_id)?;
@@ -423,7 +465,7 @@ where
                     start,
                 };
                 found = true;
-                if sender.send(sink_data).await.is_err() {
+                if sender.send(StreamMsg::Data(sink_data)).await.is_err() {
                     error!("{ctx} Error sending to closed stream {stream_id}.",);
                     remove_streams.push(*stream_id);
                     errored = true;
@@ -440,6 +482,11 @@ where
             serializer.drop_stream(stream_id);
             // TODO: stream based CB
         }
+        for stream_id in close_streams {
+            debug!("{ctx} Closing stream {stream_id}");
+            self.remove_stream(stream_id);
+            serializer.drop_stream(stream_id);
+        }
         Ok(reply) // empty vec in case of success
     }
 
@@ -42,14 +42,14 @@ use crate::connectors::{
 };
 use crate::errors::{Error, Result};
 use crate::pipeline;
-use crate::preprocessor::{finish, make_preprocessors, preprocess, Preprocessors};
+use crate::preprocessor::{self, finish, make_preprocessors, preprocess, Preprocessors};
 use crate::{
     codec::{self, Codec},
     pipeline::InputTarget,
 };
 use async_std::channel::{Receiver, Sender};
 use beef::Cow;
-use tremor_common::ports::{ERR, OUT};
+use tremor_common::ports::{CONNECTIONS, ERR, OUT};
 use tremor_pipeline::{
     CbAction, Event, EventId, EventIdGenerator, EventOriginUri, DEFAULT_STREAM_ID,
 };
@@ -560,6 +560,8 @@ where
     addr: SourceAddr,
     pipelines_out: Vec<(DeployEndpoint, pipeline::Addr)>,
     pipelines_err: Vec<(DeployEndpoint, pipeline::Addr)>,
+    /// pipelines receiving connection lifecycle events
+    pipelines_connections: Vec<(DeployEndpoint, pipeline::Addr)>,
     streams: Streams,
     metrics_reporter: SourceReporter,
     // `Paused` is used for both explicitly pausing and CB close/open
@@ -610,6 +612,7 @@ where
             metrics_reporter: source_metrics_reporter,
             pipelines_out: Vec::with_capacity(1),
             pipelines_err: Vec::with_capacity(1),
+            pipelines_connections: Vec::new(),
             state: SourceState::Initialized,
             connectivity: Connectivity::Disconnected, // we always start as disconnected until `.connect()` connects us
             is_transactional,
@@ -837,6 +840,8 @@ where
             &mut self.pipelines_out
         } else if port.eq_ignore_ascii_case(ERR.as_ref()) {
             &mut self.pipelines_err
+        } else if port.eq_ignore_ascii_case(CONNECTIONS.as_ref()) {
+            &mut self.pipelines_connections
         } else {
             error!("{} Tried to connect to invalid port: {}", &self.ctx, &port);
             return Control::Continue;
@@ -859,7 +864,12 @@ where
 
     /// send a signal to all connected pipelines
     async fn send_signal(&mut self, signal: Event) -> Result<()> {
-        for (_url, addr) in self.pipelines_out.iter().chain(self.pipelines_err.iter()) {
+        for (_url, addr) in self
+            .pipelines_out
+            .iter()
+            .chain(self.pipelines_err.iter())
+            .chain(self.pipelines_connections.iter())
+        {
             addr.send(Box::new(pipeline::Msg::Signal(signal.clone())))
                 .await?;
         }
@@ -878,6 +888,8 @@ where
             } else if port.eq_ignore_ascii_case(ERR.as_ref()) {
                 self.metrics_reporter.increment_err();
                 &mut self.pipelines_err
+            } else if port.eq_ignore_ascii_case(CONNECTIONS.as_ref()) {
+                &mut self.pipelines_connections
             } else {
                 error!("{ctx} Trying to send event to invalid port: {port}");
                 continue;
@@ -1284,62 +1296,26 @@ fn build_events(
     meta: &Value<'static>,
     is_transactional: bool,
 ) -> Vec<(Cow<'static, str>, Event)> {
-    match preprocess(
+    let processed = preprocess(
         stream_state.preprocessors.as_mut_slice(),
         ingest_ns,
         data,
         alias,
-    ) {
-        Ok(processed) => {
-            let mut res = Vec::with_capacity(processed.len());
-            for chunk in processed {
-                let line_value = EventPayload::try_new::<Option<Error>, _>(chunk, |mut_data| {
-                    match stream_state.codec.decode(mut_data, *ingest_ns) {
-                        Ok(None) => Err(None),
-                        Err(e) => Err(Some(e)),
-                        Ok(Some(decoded)) => {
-                            Ok(ValueAndMeta::from_parts(decoded, meta.clone()))
-                            // TODO: avoid clone on last iterator element
-                        }
-                    }
-                });
-                let (port, payload) = match line_value {
-                    Ok(decoded) => (port.unwrap_or(&OUT).clone(), decoded),
-                    Err(None) => continue,
-                    Err(Some(e)) => (
-                        ERR,
-                        make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone()),
-                    ),
-                };
-                let event = build_event(
-                    stream_state,
-                    pull_id,
-                    *ingest_ns,
-                    payload,
-                    origin_uri.clone(), // TODO: use split_last to avoid this clone for the last item
-                    is_transactional,
-                );
-                res.push((port, event));
-            }
-            res
-        }
-        Err(e) => {
-            // preprocessor error
-            let err_payload = make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone());
-            let event = build_event(
-                stream_state,
-                pull_id,
-                *ingest_ns,
-                err_payload,
-                origin_uri.clone(),
-                is_transactional,
-            );
-            vec![(ERR, event)]
-        }
-    }
+    );
+    decode_events(
+        alias,
+        stream_state,
+        ingest_ns,
+        pull_id,
+        origin_uri,
+        port,
+        processed,
+        meta,
+        is_transactional,
+    )
 }
 
-/// build any number of `Event`s from a given Source Transport Unit (`data`)
+/// build any number of `Event`s from the data left in the preprocessors of a stream
 /// preprocessor or codec errors are turned into events to the ERR port of the source/connector
 #[allow(clippy::too_many_arguments)]
 fn build_last_events(
@@ -1352,27 +1328,67 @@ fn build_last_events(
     meta: &Value<'static>,
     is_transactional: bool,
 ) -> Vec<(Cow<'static, str>, Event)> {
-    match finish(stream_state.preprocessors.as_mut_slice(), alias) {
+    let processed = finish(stream_state.preprocessors.as_mut_slice(), alias);
+    decode_events(
+        alias,
+        stream_state,
+        ingest_ns,
+        pull_id,
+        origin_uri,
+        port,
+        processed,
+        meta,
+        is_transactional,
+    )
+}
+
+/// decode the preprocessed chunks into `Event`s
+#[allow(clippy::too_many_arguments)]
+fn decode_events(
+    alias: &str,
+    stream_state: &mut StreamState,
+    ingest_ns: &mut u64,
+    pull_id: u64,
+    origin_uri: &EventOriginUri,
+    port: Option<&Cow<'static, str>>,
+    processed: std::result::Result<Vec<Vec<u8>>, preprocessor::Failure>,
+    meta: &Value<'static>,
+    is_transactional: bool,
+) -> Vec<(Cow<'static, str>, Event)> {
+    match processed {
         Ok(processed) => {
             let mut res = Vec::with_capacity(processed.len());
             for chunk in processed {
-                let line_value = EventPayload::try_new::<Option<Error>, _>(chunk, |mut_data| {
-                    match stream_state.codec.decode(mut_data, *ingest_ns) {
-                        Ok(None) => Err(None),
-                        Err(e) => Err(Some(e)),
-                        Ok(Some(decoded)) => {
-                            Ok(ValueAndMeta::from_parts(decoded, meta.clone()))
-                            // TODO: avoid clone on last iterator element
+                let line_value =
+                    EventPayload::try_new_or_raw::<Option<Error>, _>(chunk, |mut_data| {
+                        match stream_state.codec.decode(mut_data, *ingest_ns) {
+                            Ok(None) => Err(None),
+                            Err(e) => Err(Some(e)),
+                            Ok(Some(decoded)) => {
+                                Ok(ValueAndMeta::from_parts(decoded, meta.clone()))
+                                // TODO: avoid clone on last iterator element
+                            }
                         }
-                    }
-                });
+                    });
                 let (port, payload) = match line_value {
                     Ok(decoded) => (port.unwrap_or(&OUT).clone(), decoded),
-                    Err(None) => continue,
-                    Err(Some(e)) => (
-                        ERR,
-                        make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone()),
-                    ),
+                    Err((None, _)) => continue,
+                    Err((Some(e), mut raw)) => {
+                        stream_state.codec.restore(&mut raw);
+                        let failed = Failed::Codec(stream_state.codec.name());
+                        (
+                            ERR,
+                            make_error(
+                                alias,
+                                &e,
+                                &failed,
+                                Some(raw),
+                                stream_state.stream_id,
+                                pull_id,
+                                meta.clone(),
+                            ),
+                        )
+                    }
                 };
                 let event = build_event(
                     stream_state,
@@ -1386,9 +1402,20 @@ fn build_last_events(
             }
             res
         }
-        Err(e) => {
-            // preprocessor error
-            let err_payload = make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone());
+        Err(preprocessor::Failure {
+            preprocessor,
+            error,
+            data,
+        }) => {
+            let err_payload = make_error(
+                alias,
+                &error,
+                &Failed::Preprocessor(&preprocessor),
+                data,
+                stream_state.stream_id,
+                pull_id,
+                meta.clone(),
+            );
             let event = build_event(
                 stream_state,
                 pull_id,
@@ -1402,21 +1429,38 @@ fn build_last_events(
     }
 }
 
+/// the part of a source that failed to turn data into events
+#[derive(Debug, Clone, Copy)]
+enum Failed<'a> {
+    Codec(&'a str),
+    Preprocessor(&'a str),
+}
+
 /// create an error payload
+///
+/// The data that failed to be preprocessed or decoded is added as `data`,
+/// so it can be replayed or quarantined downstream.
 fn make_error(
     connector_alias: &str,
     error: &Error,
+    failed: &Failed,
+    raw: Option<Vec<u8>>,
     stream_id: u64,
     pull_id: u64,
     mut meta: Value<'static>,
 ) -> EventPayload {
     let e_string = error.to_string();
-    let data = literal!({
+    let mut data = literal!({
         "error": e_string.clone(),
         "source": connector_alias.to_string(),
         "stream_id": stream_id,
-        "pull_id": pull_id
+        "pull_id": pull_id,
+        "data": raw.map_or_else(Value::null, |raw| Value::Bytes(raw.into()))
     });
+    match failed {
+        Failed::Codec(name) => data.try_insert("codec", (*name).to_string()),
+        Failed::Preprocessor(name) => data.try_insert("preprocessor", (*name).to_string()),
+    };
     meta.try_insert("error", e_string);
     EventPayload::from(ValueAndMeta::from_parts(data, meta))
 }
@@ -66,6 +66,11 @@ impl ChannelSourceRuntime {
     pub(crate) fn new(source_tx: Sender<SourceReply>) -> Self {
         Self { sender: source_tx }
     }
+
+    /// send a reply to the source outside of any stream reader
+    pub(crate) async fn send(&self, reply: SourceReply) -> Result<()> {
+        Ok(self.sender.send(reply).await?)
+    }
 }
 
 impl ChannelSourceRuntime {
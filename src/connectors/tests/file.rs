@@ -81,3 +81,53 @@ async fn file_connector() -> Result<()> {
 
     Ok(())
 }
+
+#[async_std::test]
+async fn file_connector_decode_error() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let input_path = Path::new(file!())
+        .parent()
+        .unwrap()
+        .join("../../..")
+        .join("tests")
+        .join("data")
+        .join("invalid.json");
+    let defn = literal!({
+        "codec": "json",
+        "preprocessors": ["separate"],
+        "config": {
+            "path": input_path.display().to_string(),
+            "mode": "read"
+        }
+    });
+
+    let harness = ConnectorHarness::new(function_name!(), &file::Builder::default(), &defn).await?;
+    let err = harness.err().expect("No err pipeline");
+    harness.start().await?;
+
+    harness.wait_for_connected().await?;
+
+    // the raw data is handed to the err port as it was read, even though the codec mutates it in place
+    let event = err.get_event().await?;
+    let value = event.data.suffix().value();
+    assert_eq!(Some("json"), value.get_str("codec"));
+    assert_eq!(
+        Some(br#"{"snot": "bad\nger", "badger": }"#.as_ref()),
+        value.get_bytes("data")
+    );
+
+    let (out_events, err_events) = harness.stop().await?;
+    assert!(
+        out_events.is_empty(),
+        "got some events on OUT port: {:?}",
+        out_events
+    );
+    assert!(
+        err_events.is_empty(),
+        "got some events on ERR port: {:?}",
+        err_events
+    );
+
+    Ok(())
+}
@@ -0,0 +1,314 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::connectors::impls::grpc::{client, server};
+use crate::connectors::tests::{free_port, ConnectorHarness};
+use crate::errors::Result;
+use tremor_common::ports::IN;
+use tremor_pipeline::Event;
+use tremor_value::{literal, prelude::*, Value};
+
+const DESCRIPTOR: &str = "./tests/data/grpc/echo.desc";
+
+/// A connected `grpc_server` and `grpc_client` pair serving `method`
+struct Roundtrip {
+    server: ConnectorHarness,
+    client: ConnectorHarness,
+}
+
+/// Builds a connector definition for `method`, extended by `extra`
+fn defn(port: u16, method: &str, extra: &Value<'static>) -> Value<'static> {
+    let mut config = literal!({
+        "url": format!("http://127.0.0.1:{port}"),
+        "descriptor": DESCRIPTOR,
+        "service": "tremor.test.Echo",
+        "method": method
+    });
+    for (k, v) in extra.as_object().into_iter().flatten() {
+        config.try_insert(k.clone(), v.clone());
+    }
+    literal!({ "config": config })
+}
+
+impl Roundtrip {
+    async fn new(
+        id: &str,
+        method: &str,
+        server_config: &Value<'static>,
+        client_config: &Value<'static>,
+    ) -> Result<Self> {
+        let port = free_port::find_free_tcp_port().await?;
+        let server = ConnectorHarness::new(
+            &format!("{id}_server"),
+            &server::Builder::default(),
+            &defn(port, method, server_config),
+        )
+        .await?;
+        server.start().await?;
+        server.wait_for_connected().await?;
+        let client = ConnectorHarness::new(
+            &format!("{id}_client"),
+            &client::Builder::default(),
+            &defn(port, method, client_config),
+        )
+        .await?;
+        client.start().await?;
+        client.wait_for_connected().await?;
+        Ok(Self { server, client })
+    }
+
+    /// Sends a request from the client
+    async fn request(&self, value: Value<'static>, meta: Value<'static>) -> Result<()> {
+        self.client.send_to_sink(event(value, meta), IN).await
+    }
+
+    /// Receives a request at the server, returns it together with its call id
+    async fn receive(&self) -> Result<(Value<'static>, u64)> {
+        let event = self
+            .server
+            .out()
+            .expect("No pipeline connected to 'out' port of grpc_server")
+            .get_event()
+            .await?;
+        let (value, meta) = event.data.parts();
+        let call_id = meta
+            .get("grpc")
+            .get_u64("call_id")
+            .ok_or("Missing `$grpc.call_id`")?;
+        Ok((value.clone_static(), call_id))
+    }
+
+    /// Responds to a call from the server
+    async fn respond(&self, call_id: u64, value: Value<'static>, end: bool) -> Result<()> {
+        let meta = literal!({ "grpc": { "call_id": call_id, "end": end } });
+        self.server.send_to_sink(event(value, meta), IN).await
+    }
+
+    /// Receives a response at the client
+    async fn response(&self) -> Result<Value<'static>> {
+        let event = self
+            .client
+            .out()
+            .expect("No pipeline connected to 'out' port of grpc_client")
+            .get_event()
+            .await?;
+        Ok(event.data.suffix().value().clone_static())
+    }
+
+    /// Receives a failed call at the client
+    async fn error(&self) -> Result<Value<'static>> {
+        let event = self
+            .client
+            .err()
+            .expect("No pipeline connected to 'err' port of grpc_client")
+            .get_event()
+            .await?;
+        Ok(event.data.suffix().value().clone_static())
+    }
+
+    async fn stop(self) -> Result<()> {
+        let (_out, err) = self.client.stop().await?;
+        assert!(err.is_empty());
+        let (_out, err) = self.server.stop().await?;
+        assert!(err.is_empty());
+        Ok(())
+    }
+}
+
+fn event(value: Value<'static>, meta: Value<'static>) -> Event {
+    Event {
+        data: (value, meta).into(),
+        ..Event::default()
+    }
+}
+
+#[async_std::test]
+async fn unary() -> Result<()> {
+    let _ = env_logger::try_init();
+    let rt = Roundtrip::new(function_name!(), "Unary", &literal!({}), &literal!({})).await?;
+
+    rt.request(
+        literal!({"message": "snot", "count": 1}),
+        literal!({"correlation": "badger"}),
+    )
+    .await?;
+    let (request, call_id) = rt.receive().await?;
+    assert_eq!(Some("snot"), request.get_str("message"));
+    assert_eq!(Some(1), request.get_u64("count"));
+    rt.respond(call_id, literal!({"message": "snot badger"}), false)
+        .await?;
+    let event = rt
+        .client
+        .out()
+        .expect("No pipeline connected to 'out' port of grpc_client")
+        .get_event()
+        .await?;
+    let (value, meta) = event.data.parts();
+    assert_eq!(Some("snot badger"), value.get_str("message"));
+    assert_eq!(Some("badger"), meta.get_str("correlation"));
+
+    rt.stop().await
+}
+
+#[async_std::test]
+async fn server_stream() -> Result<()> {
+    let _ = env_logger::try_init();
+    let rt = Roundtrip::new(
+        function_name!(),
+        "ServerStream",
+        &literal!({}),
+        &literal!({}),
+    )
+    .await?;
+
+    rt.request(literal!({"message": "snot", "count": 3}), literal!({}))
+        .await?;
+    let (_request, call_id) = rt.receive().await?;
+    // the call stays open until a response with `$grpc.end`
+    rt.respond(call_id, literal!({"message": "snot 1"}), false)
+        .await?;
+    rt.respond(call_id, literal!({"message": "snot 2"}), false)
+        .await?;
+    rt.respond(call_id, literal!({"message": "snot 3"}), true)
+        .await?;
+    for i in 1..=3 {
+        let message = format!("snot {i}");
+        assert_eq!(
+            Some(message.as_str()),
+            rt.response().await?.get_str("message")
+        );
+    }
+    // responses to a completed call are dropped
+    rt.respond(call_id, literal!({"message": "snot 4"}), false)
+        .await?;
+
+    rt.request(literal!({"message": "badger", "count": 1}), literal!({}))
+        .await?;
+    let (_request, call_id) = rt.receive().await?;
+    rt.respond(call_id, literal!({"message": "badger 1"}), true)
+        .await?;
+    assert_eq!(Some("badger 1"), rt.response().await?.get_str("message"));
+
+    rt.stop().await
+}
+
+#[async_std::test]
+async fn client_stream() -> Result<()> {
+    let _ = env_logger::try_init();
+    let rt = Roundtrip::new(
+        function_name!(),
+        "ClientStream",
+        &literal!({}),
+        &literal!({}),
+    )
+    .await?;
+
+    // the request stream stays open until a request with `$grpc.end`
+    rt.request(literal!({"message": "snot"}), literal!({}))
+        .await?;
+    rt.request(
+        literal!({"message": "badger"}),
+        literal!({"grpc": {"end": true}}),
+    )
+    .await?;
+    let (request, call_id) = rt.receive().await?;
+    assert_eq!(Some("snot"), request.get_str("message"));
+    let (request, other_call_id) = rt.receive().await?;
+    assert_eq!(Some("badger"), request.get_str("message"));
+    assert_eq!(call_id, other_call_id);
+    rt.respond(call_id, literal!({"message": "snot badger"}), false)
+        .await?;
+    assert_eq!(Some("snot badger"), rt.response().await?.get_str("message"));
+
+    rt.stop().await
+}
+
+#[async_std::test]
+async fn bidi_stream() -> Result<()> {
+    let _ = env_logger::try_init();
+    let rt = Roundtrip::new(function_name!(), "BidiStream", &literal!({}), &literal!({})).await?;
+
+    rt.request(literal!({"message": "snot"}), literal!({}))
+        .await?;
+    let (request, call_id) = rt.receive().await?;
+    assert_eq!(Some("snot"), request.get_str("message"));
+    rt.respond(call_id, literal!({"message": "snot"}), false)
+        .await?;
+    assert_eq!(Some("snot"), rt.response().await?.get_str("message"));
+
+    rt.request(
+        literal!({"message": "badger"}),
+        literal!({"grpc": {"end": true}}),
+    )
+    .await?;
+    let (request, _) = rt.receive().await?;
+    assert_eq!(Some("badger"), request.get_str("message"));
+    rt.respond(call_id, literal!({"message": "badger"}), true)
+        .await?;
+    assert_eq!(Some("badger"), rt.response().await?.get_str("message"));
+
+    rt.stop().await
+}
+
+#[async_std::test]
+async fn max_message_size() -> Result<()> {
+    let _ = env_logger::try_init();
+    let rt = Roundtrip::new(
+        function_name!(),
+        "Unary",
+        &literal!({}),
+        &literal!({"max_message_size": 8}),
+    )
+    .await?;
+
+    rt.request(literal!({"message": "snot"}), literal!({}))
+        .await?;
+    let (_request, call_id) = rt.receive().await?;
+    rt.respond(
+        call_id,
+        literal!({"message": "snot badger snot badger"}),
+        false,
+    )
+    .await?;
+    let error = rt.error().await?;
+    // RESOURCE_EXHAUSTED
+    assert_eq!(Some(8), error.get_u32("status"));
+
+    rt.stop().await
+}
+
+#[async_std::test]
+async fn deadline_exceeded() -> Result<()> {
+    let _ = env_logger::try_init();
+    let rt = Roundtrip::new(
+        function_name!(),
+        "Unary",
+        &literal!({"timeout": 100_000_000}),
+        &literal!({}),
+    )
+    .await?;
+
+    // the call is never responded to
+    rt.request(literal!({"message": "snot"}), literal!({}))
+        .await?;
+    let (_request, call_id) = rt.receive().await?;
+    let error = rt.error().await?;
+    // DEADLINE_EXCEEDED
+    assert_eq!(Some(4), error.get_u32("status"));
+    // a late response is dropped
+    rt.respond(call_id, literal!({"message": "snot"}), false)
+        .await?;
+
+    rt.stop().await
+}
@@ -13,4 +13,6 @@
 // limitations under the License.
 
 mod client;
+mod poll;
 mod server;
+mod sse;
@@ -0,0 +1,263 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    connectors::{
+        impls::http,
+        tests::{free_port::find_free_tcp_port, ConnectorHarness},
+    },
+    errors::Result,
+};
+use async_std::{
+    net::TcpStream,
+    path::Path,
+    task::{sleep, spawn, JoinHandle},
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tremor_value::{literal, prelude::*, Value};
+
+/// polls only once within a test
+const HOUR: u64 = 3_600_000_000_000;
+
+#[derive(Clone, Default)]
+struct State {
+    /// makes the last cursor page return a new item
+    more: Arc<AtomicBool>,
+}
+
+fn query(req: &tide::Request<State>, name: &str) -> Option<String> {
+    req.url()
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn json(body: &Value) -> tide::Response {
+    let mut res = tide::Response::new(tide::StatusCode::Ok);
+    res.set_body(body.encode());
+    res.set_content_type(http_types::mime::JSON);
+    res
+}
+
+/// three pages linked via the `Link` header
+async fn link(req: tide::Request<State>) -> tide::Result<tide::Response> {
+    let page: u64 = query(&req, "page")
+        .and_then(|page| page.parse().ok())
+        .unwrap_or(1);
+    let mut res = json(&literal!([page]));
+    if page < 3 {
+        res.insert_header("Link", format!("</link?page={}>; rel=\"next\"", page + 1));
+    }
+    Ok(res)
+}
+
+/// pages linked via the `next` cursor, the last cursor yields new items once there are `more`
+async fn cursor(req: tide::Request<State>) -> tide::Result<tide::Response> {
+    let page = match query(&req, "cursor").as_deref() {
+        None => literal!({"data": [1, 2], "next": "a"}),
+        Some("a") => literal!({"data": [3], "next": "b"}),
+        Some("b") if req.state().more.load(Ordering::Acquire) => literal!({"data": [4]}),
+        _ => literal!({"data": []}),
+    };
+    Ok(json(&page))
+}
+
+/// five items paged with `offset` and `limit`
+async fn offset(req: tide::Request<State>) -> tide::Result<tide::Response> {
+    let offset: u64 = query(&req, "offset")
+        .and_then(|offset| offset.parse().ok())
+        .unwrap_or_default();
+    let limit: u64 = query(&req, "limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(10);
+    let items: Vec<u64> = (offset..(offset + limit).min(5)).collect();
+    Ok(json(&literal!({ "items": items })))
+}
+
+/// Starts the mock server, returns once it accepts connections
+async fn serve(state: State) -> Result<(u16, JoinHandle<Result<()>>)> {
+    let port = find_free_tcp_port().await?;
+    let mut app = tide::with_state(state);
+    app.at("/link").get(link);
+    app.at("/cursor").get(cursor);
+    app.at("/offset").get(offset);
+    let server = spawn(async move {
+        app.listen(format!("127.0.0.1:{port}")).await?;
+        Ok(())
+    });
+    let start = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+        if start.elapsed() > Duration::from_secs(10) {
+            return Err("Mock server not listening".into());
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+    Ok((port, server))
+}
+
+async fn poller(id: &str, url: String, poll: Value<'static>) -> Result<ConnectorHarness> {
+    let defn = literal!({
+        "codec": "json",
+        "config": {
+            "url": url,
+            "poll": poll
+        }
+    });
+    let harness = ConnectorHarness::new(id, &http::client::Builder::default(), &defn).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    Ok(harness)
+}
+
+/// Receives the next `n` items
+async fn items(harness: &ConnectorHarness, n: usize) -> Result<Vec<u64>> {
+    let out = harness.out().expect("No pipeline connected to 'out' port");
+    let mut items = Vec::with_capacity(n);
+    for _ in 0..n {
+        let event = out.get_event().await?;
+        items.push(
+            event
+                .data
+                .suffix()
+                .value()
+                .as_u64()
+                .ok_or("Item is not a number")?,
+        );
+    }
+    Ok(items)
+}
+
+/// Waits until the poll position was persisted as `expected`
+async fn wait_for_state(path: &Path, expected: &str) -> Result<()> {
+    let start = Instant::now();
+    loop {
+        let state = async_std::fs::read_to_string(path).await.ok();
+        if state.as_deref() == Some(expected) {
+            return Ok(());
+        }
+        if start.elapsed() > Duration::from_secs(10) {
+            return Err(format!("Expected state {expected}, got {state:?}").into());
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+}
+
+async fn stop(harness: ConnectorHarness) -> Result<()> {
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn link_pagination() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (port, server) = serve(State::default()).await?;
+    let poll = literal!({
+        "interval": HOUR,
+        "pagination": {"type": "link"},
+        "emit": "item"
+    });
+    let harness = poller(
+        function_name!(),
+        format!("http://127.0.0.1:{port}/link"),
+        poll,
+    )
+    .await?;
+    assert_eq!(vec![1, 2, 3], items(&harness, 3).await?);
+
+    stop(harness).await?;
+    server.cancel().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn cursor_pagination_resumes() -> Result<()> {
+    let _ = env_logger::try_init();
+    let state = State::default();
+    let (port, server) = serve(state.clone()).await?;
+    let dir = tempfile::tempdir()?;
+    let state_file = dir.path().join("poll.json");
+    let poll = literal!({
+        "interval": HOUR,
+        "pagination": {"type": "cursor", "field": "next", "param": "cursor"},
+        "emit": "item",
+        "items": "data",
+        "state_file": state_file.display().to_string()
+    });
+    let url = format!("http://127.0.0.1:{port}/cursor");
+
+    let harness = poller(
+        &format!("{}_1", function_name!()),
+        url.clone(),
+        poll.clone(),
+    )
+    .await?;
+    assert_eq!(vec![1, 2, 3], items(&harness, 3).await?);
+    // the last page came without a cursor, the poll stops at the last cursor
+    wait_for_state(Path::new(&state_file), r#"{"cursor":"b"}"#).await?;
+    stop(harness).await?;
+
+    // a restarted connector resumes from the last cursor, instead of starting over
+    state.more.store(true, Ordering::Release);
+    let harness = poller(&format!("{}_2", function_name!()), url, poll).await?;
+    assert_eq!(vec![4], items(&harness, 1).await?);
+    wait_for_state(Path::new(&state_file), r#"{"cursor":"b"}"#).await?;
+    stop(harness).await?;
+
+    server.cancel().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn offset_pagination_max_pages() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (port, server) = serve(State::default()).await?;
+    let dir = tempfile::tempdir()?;
+    let state_file = dir.path().join("poll.json");
+    let poll = literal!({
+        "interval": HOUR,
+        "pagination": {"type": "offset", "limit": 2},
+        "emit": "item",
+        "items": "items",
+        "max_pages": 2,
+        "state_file": state_file.display().to_string()
+    });
+    let url = format!("http://127.0.0.1:{port}/offset");
+
+    // the poll stops after two pages
+    let harness = poller(
+        &format!("{}_1", function_name!()),
+        url.clone(),
+        poll.clone(),
+    )
+    .await?;
+    assert_eq!(vec![0, 1, 2, 3], items(&harness, 4).await?);
+    wait_for_state(Path::new(&state_file), r#"{"offset":4}"#).await?;
+    stop(harness).await?;
+
+    // and the next one continues with the following page
+    let harness = poller(&format!("{}_2", function_name!()), url, poll).await?;
+    assert_eq!(vec![4], items(&harness, 1).await?);
+    wait_for_state(Path::new(&state_file), r#"{"offset":5}"#).await?;
+    stop(harness).await?;
+
+    server.cancel().await;
+    Ok(())
+}
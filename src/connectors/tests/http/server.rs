@@ -19,7 +19,10 @@ use std::{
 // limitations under the License.
 use crate::{
     connectors::{
-        impls::http::server,
+        impls::http::{
+            server,
+            transport::{self, HttpVersion},
+        },
         sink::SinkMsg,
         tests::{free_port, setup_for_tls, ConnectorHarness},
         utils::tls::{tls_client_config, TLSClientConfig},
@@ -31,11 +34,12 @@ use http_client::{h1::H1Client, Body, Config as HttpClientConfig, HttpClient};
 use http_types::{
     headers::{self, HeaderValue},
     mime::BYTE_STREAM,
-    Method, StatusCode, Url,
+    Method, StatusCode, Url, Version,
 };
+use ring::hmac;
 use std::str::FromStr;
 use tremor_common::ports::IN;
-use tremor_pipeline::{Event, EventId};
+use tremor_pipeline::{Event, EventId, METRICS_CHANNEL};
 use tremor_script::ValueAndMeta;
 use tremor_value::{literal, value::StaticValue, Value};
 use value_trait::ValueAccess;
@@ -322,6 +326,286 @@ async fn http_server_test() -> Result<()> {
     Ok(())
 }
 
+/// Sends a request that the `http_server` answers by itself
+async fn send_rejected(req: surf::Request) -> Result<surf::Response> {
+    Ok(surf::client()
+        .send(req)
+        .timeout(Duration::from_secs(5))
+        .await??)
+}
+
+#[async_std::test]
+async fn http_server_rejections() -> Result<()> {
+    let _ = env_logger::try_init();
+    let alias = function_name!();
+    // subscribe before anything is counted
+    let mut metrics = METRICS_CHANNEL.rx();
+    let port = free_port::find_free_tcp_port().await?;
+    let url = format!("http://localhost:{port}");
+    let defn = literal!({
+        "codec": "string",
+        "metrics_interval_s": 1,
+        "config": {
+            "url": url.clone(),
+            "allowed_methods": ["POST"],
+            "allowed_paths": ["/hooks/*"],
+            "max_body_size": 16,
+            "signature": {
+                "header": "X-Hub-Signature-256",
+                "prefix": "sha256=",
+                "secret": "snot"
+            }
+        }
+    });
+    let connector = ConnectorHarness::new(alias, &server::Builder::default(), &defn).await?;
+    connector.start().await?;
+    connector.wait_for_connected().await?;
+    let hook = Url::parse(&format!("{url}/hooks/github"))?;
+
+    // retry until the http server is actually up
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let req = surf::Request::builder(Method::Get, hook.clone()).build();
+    let mut res = send_rejected(req.clone()).await;
+    while let Err(e) = res {
+        if start.elapsed() > timeout {
+            return Err(format!("HTTP Server not listening after {timeout:?}: {e}").into());
+        }
+        async_std::task::sleep(Duration::from_millis(100)).await;
+        res = send_rejected(req.clone()).await;
+    }
+    let res = res?;
+    assert_eq!(StatusCode::MethodNotAllowed, res.status());
+    assert_eq!(
+        &HeaderValue::from_str("POST")?,
+        res.header("allow").unwrap().last()
+    );
+
+    // `*` does not match across path separators
+    for path in ["/hooks/github/nested", "/admin"] {
+        let req = surf::Request::builder(Method::Post, Url::parse(&format!("{url}{path}"))?)
+            .body_string("badger".to_string())
+            .build();
+        assert_eq!(StatusCode::NotFound, send_rejected(req).await?.status());
+    }
+
+    let req = surf::Request::builder(Method::Post, hook.clone())
+        .body_bytes(&[0_u8; 17])
+        .build();
+    assert_eq!(
+        StatusCode::PayloadTooLarge,
+        send_rejected(req).await?.status()
+    );
+
+    let req = surf::Request::builder(Method::Post, hook.clone())
+        .header("X-Hub-Signature-256", "sha256=00")
+        .body_string("badger".to_string())
+        .build();
+    assert_eq!(StatusCode::Unauthorized, send_rejected(req).await?.status());
+
+    // none of the rejected requests reached the pipeline
+    let out = connector.out().expect("No pipeline connected to out");
+    assert!(out.get_events()?.is_empty());
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, b"snot");
+    let signature = hex::encode(hmac::sign(&key, b"badger"));
+    let req = surf::Request::builder(Method::Post, hook)
+        .header("X-Hub-Signature-256", format!("sha256={signature}"))
+        .body_string("badger".to_string())
+        .build();
+    let mut res = handle_req(
+        req,
+        |req_data| {
+            assert_eq!(Some("badger"), req_data.value().as_str());
+            (literal!("accepted"), literal!({})).into()
+        },
+        &connector,
+        false,
+    )
+    .await?;
+    assert_eq!(StatusCode::Ok, res.status());
+    assert_eq!("accepted", res.body_string().await?);
+
+    // the first event flushes the metrics
+    let fields = async {
+        loop {
+            let msg = metrics
+                .recv()
+                .await
+                .map_err(|e| format!("Metrics channel closed: {e}"))?;
+            let value = msg.payload.suffix().value();
+            if value.get_str("measurement") == Some("http_server_requests")
+                && value.get("tags").get_str("connector") == Some(alias)
+            {
+                let fields = value.get("fields").map(Value::clone_static);
+                return Ok::<_, crate::errors::Error>(fields);
+            }
+        }
+    }
+    .timeout(Duration::from_secs(10))
+    .await??;
+    let fields = fields.ok_or("Missing metrics fields")?;
+    assert_eq!(Some(1), fields.get_u64("accepted"));
+    assert_eq!(Some(1), fields.get_u64("rejected_method"));
+    assert_eq!(Some(2), fields.get_u64("rejected_path"));
+    assert_eq!(Some(1), fields.get_u64("rejected_size"));
+    assert_eq!(Some(1), fields.get_u64("rejected_signature"));
+
+    let (_out, err) = connector.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn http_server_h2c_test() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_port::find_free_tcp_port().await?;
+    let url = format!("http://localhost:{port}/");
+    let defn = literal!({
+        "codec": "json",
+        "config": {
+            "url": url.clone()
+        }
+    });
+    let connector =
+        ConnectorHarness::new(function_name!(), &server::Builder::default(), &defn).await?;
+    connector.start().await?;
+    connector.wait_for_connected().await?;
+
+    // respond with the url of the request
+    let out = connector
+        .out()
+        .expect("No pipeline connected to out")
+        .clone();
+    let c_addr = connector.addr.clone();
+    let handle = async_std::task::spawn::<_, Result<()>>(async move {
+        while let Ok(inbound) = out.get_event().await {
+            let url = inbound
+                .data
+                .suffix()
+                .meta()
+                .get("http_server")
+                .get("request")
+                .get("url")
+                .map(Value::clone_static);
+            let event = Event {
+                id: inbound.id.clone(),
+                data: ValueAndMeta::from_parts(url.unwrap_or_default(), literal!({})).into(),
+                ..Event::default()
+            };
+            c_addr.send_sink(SinkMsg::Event { event, port: IN }).await?;
+        }
+        Ok(())
+    });
+
+    // h2c with prior knowledge, retry until the http server is actually up
+    let client = transport::Client::new(HttpVersion::Http2, None, 1, Some(Duration::from_secs(5)));
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let mut response = loop {
+        let req = http_types::Request::new(Method::Get, Url::parse(&url)?);
+        match client.send(req).await {
+            Ok(response) => break response,
+            Err(e) if start.elapsed() > timeout => {
+                return Err(format!("HTTP Server not listening after {timeout:?}: {e}").into());
+            }
+            Err(_) => async_std::task::sleep(Duration::from_millis(100)).await,
+        }
+    };
+    assert_eq!(StatusCode::Ok, response.status());
+    assert_eq!(Some(Version::Http2_0), response.version());
+    assert_eq!(format!("\"{url}\""), response.body_string().await?);
+    if let Some(res) = handle.cancel().await {
+        res?;
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn https_server_h2_test() -> Result<()> {
+    let _ = env_logger::try_init();
+    setup_for_tls();
+    let cert_file = "./tests/localhost.cert";
+    let key_file = "./tests/localhost.key";
+
+    let port = free_port::find_free_tcp_port().await?;
+    let url = format!("https://localhost:{port}/");
+    let defn = literal!({
+        "codec": "json",
+        "config": {
+            "url": url.clone(),
+            "tls": {
+                "cert": cert_file,
+                "key": key_file
+            }
+        }
+    });
+    let connector =
+        ConnectorHarness::new(function_name!(), &server::Builder::default(), &defn).await?;
+    connector.start().await?;
+    connector.wait_for_connected().await?;
+
+    // respond with the url of the request
+    let out = connector
+        .out()
+        .expect("No pipeline connected to out")
+        .clone();
+    let c_addr = connector.addr.clone();
+    let handle = async_std::task::spawn::<_, Result<()>>(async move {
+        while let Ok(inbound) = out.get_event().await {
+            let url = inbound
+                .data
+                .suffix()
+                .meta()
+                .get("http_server")
+                .get("request")
+                .get("url")
+                .map(Value::clone_static);
+            let event = Event {
+                id: inbound.id.clone(),
+                data: ValueAndMeta::from_parts(url.unwrap_or_default(), literal!({})).into(),
+                ..Event::default()
+            };
+            c_addr.send_sink(SinkMsg::Event { event, port: IN }).await?;
+        }
+        Ok(())
+    });
+
+    // h2 is negotiated via ALPN, retry until the https server is actually up
+    let tls_config = tls_client_config(&TLSClientConfig {
+        cafile: Some(PathBuf::from(cert_file)),
+        domain: Some("localhost".to_string()),
+        cert: None,
+        key: None,
+    })
+    .await?;
+    let client = transport::Client::new(
+        HttpVersion::Auto,
+        Some(tls_config),
+        1,
+        Some(Duration::from_secs(5)),
+    );
+    let start = Instant::now();
+    let timeout = Duration::from_secs(30);
+    let mut response = loop {
+        let req = http_types::Request::new(Method::Get, Url::parse(&url)?);
+        match client.send(req).await {
+            Ok(response) => break response,
+            Err(e) if start.elapsed() > timeout => {
+                return Err(format!("HTTPS Server not listening after {timeout:?}: {e}").into());
+            }
+            Err(_) => async_std::task::sleep(Duration::from_millis(100)).await,
+        }
+    };
+    assert_eq!(StatusCode::Ok, response.status());
+    assert_eq!(Some(Version::Http2_0), response.version());
+    assert_eq!(format!("\"{url}\""), response.body_string().await?);
+    if let Some(res) = handle.cancel().await {
+        res?;
+    }
+    Ok(())
+}
+
 #[async_std::test]
 async fn https_server_test() -> Result<()> {
     let _ = env_logger::try_init();
@@ -0,0 +1,281 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    connectors::{
+        impls::{
+            http::{
+                server,
+                transport::{self, HttpVersion},
+            },
+            sse::{self, client, Parser, SseEvent},
+        },
+        tests::{free_port, ConnectorHarness},
+    },
+    errors::Result,
+};
+use async_std::{
+    net::{TcpListener, TcpStream},
+    prelude::FutureExt,
+    task::{spawn, JoinHandle},
+};
+use futures::{AsyncReadExt, AsyncWriteExt};
+use http_types::{headers, Method, StatusCode, Url};
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tremor_common::ports::IN;
+use tremor_pipeline::{Event, EventId};
+use tremor_value::{literal, Value};
+use value_trait::ValueAccess;
+
+/// Reads the request head sent by the client
+async fn read_head(stream: &mut TcpStream) -> Result<String> {
+    let mut head = Vec::new();
+    let mut byte = [0_u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).await?;
+        head.extend_from_slice(&byte);
+    }
+    Ok(String::from_utf8_lossy(&head).to_lowercase())
+}
+
+#[async_std::test]
+async fn sse_client_reconnect() -> Result<()> {
+    let _ = env_logger::try_init();
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/events", listener.local_addr()?);
+    let server = spawn::<_, Result<(String, TcpStream)>>(async move {
+        // `\r` terminated lines, the stream ends in the middle of an event
+        let (mut stream, _) = listener.accept().await?;
+        read_head(&mut stream).await?;
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n\
+                id: 1\rdata: snot\r\revent: update\rdata: part",
+            )
+            .await?;
+        drop(stream);
+        // the client reconnects and resumes after the last event
+        let (mut stream, _) = listener.accept().await?;
+        let head = read_head(&mut stream).await?;
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n\
+                ial\r\n\r\ndata: badger\r\n\r\n",
+            )
+            .await?;
+        Ok((head, stream))
+    });
+    let defn = literal!({
+        "reconnect": {
+            "retry": {
+                "interval_ms": 100,
+                "max_retries": 10
+            }
+        },
+        "codec": "string",
+        "config": {
+            "url": url
+        }
+    });
+    let connector =
+        ConnectorHarness::new(function_name!(), &client::Builder::default(), &defn).await?;
+    connector.start().await?;
+    connector.wait_for_connected().await?;
+    let out = connector
+        .out()
+        .expect("No pipeline connected to out")
+        .clone();
+
+    let event = out.get_event().await?;
+    let (value, meta) = (event.data.suffix().value(), event.data.suffix().meta());
+    assert_eq!(Some("snot"), value.as_str());
+    assert_eq!(
+        Some(&literal!({"event": "message", "id": "1"})),
+        meta.get("sse")
+    );
+
+    // the partially received event is discarded, the last event id is kept
+    let event = out.get_event().await?;
+    let (value, meta) = (event.data.suffix().value(), event.data.suffix().meta());
+    assert_eq!(Some("badger"), value.as_str());
+    assert_eq!(
+        Some(&literal!({"event": "message", "id": "1"})),
+        meta.get("sse")
+    );
+
+    let (head, _stream) = server.await?;
+    assert!(head.contains("\r\nlast-event-id: 1\r\n"), "{head}");
+    assert!(head.contains("\r\naccept: text/event-stream\r\n"), "{head}");
+
+    let (_out, err) = connector.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+/// Reads the events of an open event stream
+struct EventReader {
+    body: http_types::Body,
+    parser: Parser,
+    events: VecDeque<SseEvent>,
+}
+
+impl EventReader {
+    fn new(mut response: http_types::Response) -> Self {
+        Self {
+            body: response.take_body(),
+            parser: Parser::default(),
+            events: VecDeque::new(),
+        }
+    }
+
+    async fn next(&mut self) -> Result<SseEvent> {
+        let mut chunk = vec![0; 1024];
+        while self.events.is_empty() {
+            let len = self
+                .body
+                .read(&mut chunk)
+                .timeout(Duration::from_secs(10))
+                .await??;
+            if len == 0 {
+                return Err("Event stream ended".into());
+            }
+            self.events
+                .extend(self.parser.feed(chunk.get(..len).unwrap_or_default()));
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| "No event received".into())
+    }
+}
+
+/// Sends a request asking for an event stream, retrying until the http server is up
+fn open_stream(
+    client: Arc<transport::Client>,
+    url: String,
+) -> JoinHandle<Result<http_types::Response>> {
+    spawn(async move {
+        let start = Instant::now();
+        let timeout = Duration::from_secs(30);
+        loop {
+            let mut req = http_types::Request::new(Method::Get, Url::parse(&url)?);
+            req.insert_header(headers::ACCEPT, sse::MIME_TYPE);
+            match client.send(req).await {
+                Ok(response) => break Ok(response),
+                Err(e) if start.elapsed() > timeout => {
+                    return Err(format!("HTTP Server not listening after {timeout:?}: {e}").into());
+                }
+                Err(_) => async_std::task::sleep(Duration::from_millis(100)).await,
+            }
+        }
+    })
+}
+
+fn sse_event(meta: Value<'static>, data: &str) -> Event {
+    Event {
+        // no pull id to resolve a request from, only the metadata is used
+        id: EventId::from_id(u64::MAX, u64::MAX, 0),
+        data: (Value::from(data.to_string()), meta).into(),
+        ..Event::default()
+    }
+}
+
+#[async_std::test]
+async fn http_server_sse() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_port::find_free_tcp_port().await?;
+    let url = format!("http://localhost:{port}/");
+    let defn = literal!({
+        "codec": "json",
+        "config": {
+            "url": url.clone()
+        }
+    });
+    let connector =
+        ConnectorHarness::new(function_name!(), &server::Builder::default(), &defn).await?;
+    connector.start().await?;
+    connector.wait_for_connected().await?;
+    let out = connector
+        .out()
+        .expect("No pipeline connected to out")
+        .clone();
+    let client = Arc::new(transport::Client::new(HttpVersion::Http1, None, 2, None));
+
+    // open two event streams, each answered with an initial event
+    let mut readers = Vec::new();
+    let mut request_ids = Vec::new();
+    for name in ["a", "b"] {
+        let response = open_stream(client.clone(), url.clone());
+        let request = out.get_event().await?;
+        let request_id = request
+            .data
+            .suffix()
+            .meta()
+            .get("http_server")
+            .get("request_id")
+            .map(Value::clone_static)
+            .ok_or("No request_id")?;
+        let meta = literal!({
+            "http_server": {"request_id": request_id.clone()},
+            "sse": {"event": "open"}
+        });
+        connector.send_to_sink(sse_event(meta, name), IN).await?;
+        let response = response.await?;
+        assert_eq!(StatusCode::Ok, response.status());
+        assert_eq!(
+            Some(sse::MIME_TYPE),
+            response.content_type().as_ref().map(|m| m.essence())
+        );
+        let mut reader = EventReader::new(response);
+        let event = reader.next().await?;
+        assert_eq!("open", event.event);
+        assert_eq!(format!("\"{name}\""), event.data);
+        readers.push(reader);
+        request_ids.push(request_id);
+    }
+
+    // an event for a request only goes to its stream
+    let meta = literal!({
+        "http_server": {"request_id": request_ids[0].clone()},
+        "sse": {"id": "1"}
+    });
+    connector
+        .send_to_sink(sse_event(meta, "only-a"), IN)
+        .await?;
+    // without a request id events are only broadcast if asked to
+    let meta = literal!({"sse": {"event": "news"}});
+    connector
+        .send_to_sink(sse_event(meta, "not-broadcast"), IN)
+        .await?;
+    let meta = literal!({"sse": {"event": "news", "broadcast": true}});
+    connector.send_to_sink(sse_event(meta, "all"), IN).await?;
+
+    let event = readers[0].next().await?;
+    assert_eq!("message", event.event);
+    assert_eq!(Some("1".to_string()), event.id);
+    assert_eq!("\"only-a\"", event.data);
+    for reader in &mut readers {
+        let event = reader.next().await?;
+        assert_eq!("news", event.event);
+        assert_eq!("\"all\"", event.data);
+    }
+
+    drop(readers);
+    let (_out, err) = connector.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
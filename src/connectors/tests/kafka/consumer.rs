@@ -250,7 +250,9 @@ async fn connector_kafka_consumer_transactional_retry() -> Result<()> {
             "error": "SIMD JSON error: InternalError at character 0 ('}')",
             "source": "connector_kafka_consumer_transactional_retry",
             "stream_id": 8589934592_u64,
-            "pull_id": 1u64
+            "pull_id": 1u64,
+            "codec": "sorted-json",
+            "data": Value::Bytes(Cow::owned("}\n".as_bytes().to_vec()))
         }),
         e5.data.suffix().value()
     );
@@ -475,7 +477,9 @@ async fn connector_kafka_consumer_transactional_no_retry() -> Result<()> {
             "error": "SIMD JSON error: InternalError at character 0 ('}')",
             "source": "connector_kafka_consumer_transactional_no_retry",
             "stream_id": 8589934592_u64,
-            "pull_id": 1u64
+            "pull_id": 1u64,
+            "codec": "sorted-json",
+            "data": Value::Bytes(Cow::owned("}\n".as_bytes().to_vec()))
         }),
         e5.data.suffix().value()
     );
@@ -701,7 +705,9 @@ async fn connector_kafka_consumer_non_transactional() -> Result<()> {
             "error": "SIMD JSON error: InternalError at character 0 ('}')",
             "source": "connector_kafka_consumer_non_transactional",
             "stream_id": 8589934592_u64,
-            "pull_id": 1u64
+            "pull_id": 1u64,
+            "codec": "sorted-json",
+            "data": Value::Bytes(Cow::owned("}\n".as_bytes().to_vec()))
         }),
         e5.data.suffix().value()
     );
@@ -27,6 +27,8 @@ mod file_non_existent;
 #[cfg(feature = "file-integration")]
 mod file_xz;
 mod gpubsub;
+#[cfg(feature = "grpc-integration")]
+mod grpc;
 #[cfg(feature = "http-integration")]
 mod http;
 #[cfg(feature = "kafka-integration")]
@@ -38,6 +40,8 @@ mod pause_resume;
 mod s3;
 #[cfg(feature = "tcp-integration")]
 mod tcp;
+#[cfg(feature = "udp-integration")]
+mod udp;
 #[cfg(feature = "socket-integration")]
 mod unix_socket;
 #[cfg(feature = "wal-integration")]
@@ -273,13 +277,19 @@ impl ConnectorHarness {
         self.get_pipe(OUT)
     }
 
-    #[cfg(any(feature = "kafka-integration", feature = "es-integration",))]
+    #[cfg(any(
+        feature = "kafka-integration",
+        feature = "es-integration",
+        feature = "file-integration",
+        feature = "grpc-integration"
+    ))]
 
     /// get the err pipeline - if any
     pub(crate) fn err(&self) -> Option<&TestPipeline> {
         self.get_pipe(ERR)
     }
     #[cfg(any(
+        feature = "grpc-integration",
         feature = "http-integration",
         feature = "es-integration",
         feature = "socket-integration",
@@ -432,6 +442,7 @@ impl TestPipeline {
 }
 
 #[cfg(any(
+    feature = "grpc-integration",
     feature = "http-integration",
     feature = "ws-integration",
     feature = "s3-integration"
@@ -16,7 +16,7 @@ mod client;
 mod server;
 
 use crate::{
-    connectors::utils::tls::{load_server_config, TLSServerConfig},
+    connectors::utils::tls::{load_server_config, ClientAuth, TLSServerConfig},
     errors::{Error, Result},
 };
 use async_std::{
@@ -98,6 +98,9 @@ impl EchoServer {
             Some(load_server_config(&TLSServerConfig {
                 cert: "./tests/localhost.cert".into(),
                 key: "./tests/localhost.key".into(),
+                client_ca: None,
+                client_auth: ClientAuth::Required,
+                reload: false,
             })?)
         } else {
             None
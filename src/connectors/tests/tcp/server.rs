@@ -18,7 +18,7 @@ use crate::connectors::impls::tcp;
 use crate::connectors::tests::{free_port, ConnectorHarness};
 use crate::errors::Result;
 use async_std::{io::WriteExt, net::TcpStream, prelude::*};
-use tremor_common::ports::IN;
+use tremor_common::ports::{CONNECTIONS, ERR, IN, OUT};
 use tremor_pipeline::{Event, EventId};
 use tremor_value::{literal, prelude::*, Value};
 use value_trait::Builder;
@@ -112,3 +112,79 @@ async fn server_event_routing() -> Result<()> {
     assert!(err.is_empty());
     Ok(())
 }
+
+#[async_std::test]
+async fn server_lifecycle_events() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+    let server_addr = format!("127.0.0.1:{}", free_port);
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "lifecycle_events": true
+      }
+    });
+    let harness = ConnectorHarness::new_with_ports(
+        function_name!(),
+        &tcp::server::Builder::default(),
+        &defn,
+        vec![IN],
+        vec![OUT, ERR, CONNECTIONS],
+    )
+    .await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    let connections = harness
+        .get_pipe(CONNECTIONS)
+        .expect("No pipeline connected to 'connections' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let mut socket = TcpStream::connect(&server_addr).await?;
+    let event = connections.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!(Some("connected"), data.get_str("event"));
+    let stream_id = data.get_u64("stream_id").expect("No stream_id");
+    assert_eq!(Some(false), meta.get("tcp_server").get_bool("tls"));
+
+    socket.write_all("snot\n".as_bytes()).await?;
+    let event = out_pipeline.get_event().await?;
+    assert_eq!(Some("snot"), event.data.suffix().value().as_str());
+
+    // data queued before the close is still written out
+    let meta = literal!({ "tcp_server": { "stream_id": stream_id } });
+    let event = Event {
+        data: (Value::String("badger".into()), meta).into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    let meta = literal!({ "tcp_server": { "stream_id": stream_id, "close": true } });
+    let event = Event {
+        data: (Value::String("".into()), meta).into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+
+    let mut received = Vec::new();
+    socket
+        .read_to_end(&mut received)
+        .timeout(Duration::from_secs(5))
+        .await??;
+    assert_eq!("badger", &String::from_utf8_lossy(&received));
+
+    let event = connections.get_event().await?;
+    let data = event.data.suffix().value();
+    assert_eq!(Some("disconnected"), data.get_str("event"));
+    assert_eq!(Some(stream_id), data.get_u64("stream_id"));
+    assert_eq!(Some("sink"), data.get_str("reason"));
+    assert_eq!(Some(5), data.get_u64("bytes_received"));
+    assert_eq!(Some(6), data.get_u64("bytes_sent"));
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
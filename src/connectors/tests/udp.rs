@@ -0,0 +1,176 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ConnectorHarness;
+use crate::{connectors::impls::udp, errors::Result};
+use async_std::net::UdpSocket;
+use tremor_common::ports::IN;
+use tremor_pipeline::{Event, EventId};
+use tremor_value::{literal, prelude::*, Value};
+use value_trait::Builder;
+
+async fn free_udp_port() -> Result<u16> {
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    Ok(socket.local_addr()?.port())
+}
+
+async fn server(url: String, multicast: Value<'static>) -> Result<ConnectorHarness> {
+    let defn = literal!({
+      "codec": "string",
+      "config": {
+          "url": url,
+          "multicast": multicast
+      }
+    });
+    let harness =
+        ConnectorHarness::new("udp_server", &udp::server::Builder::default(), &defn).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    Ok(harness)
+}
+
+async fn client(config: Value<'static>) -> Result<ConnectorHarness> {
+    let defn = literal!({
+      "codec": "string",
+      "config": config
+    });
+    let harness =
+        ConnectorHarness::new("udp_client", &udp::client::Builder::default(), &defn).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    Ok(harness)
+}
+
+async fn send(client: &ConnectorHarness, data: &'static str) -> Result<()> {
+    let event = Event {
+        id: EventId::default(),
+        data: (Value::from(data), Value::object()).into(),
+        ..Event::default()
+    };
+    client.send_to_sink(event, IN).await
+}
+
+#[async_std::test]
+async fn udp_sender_metadata() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_udp_port().await?;
+    let server = server(format!("127.0.0.1:{port}"), Value::array()).await?;
+    let out = server
+        .out()
+        .expect("No pipeline connected to 'out' port of udp_server connector");
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    socket.send_to(b"snot", ("127.0.0.1", port)).await?;
+    let event = out.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!("snot", data.to_string());
+    assert_eq!(Some("127.0.0.1"), meta.get("udp").get_str("host"));
+    assert_eq!(
+        Some(u64::from(socket.local_addr()?.port())),
+        meta.get("udp").get_u64("port")
+    );
+
+    let (_out, err) = server.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn udp_broadcast() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_udp_port().await?;
+    let server = server(format!("0.0.0.0:{port}"), Value::array()).await?;
+    let out = server
+        .out()
+        .expect("No pipeline connected to 'out' port of udp_server connector");
+
+    let client = client(literal!({
+        "url": format!("127.255.255.255:{port}"),
+        "bind": "127.0.0.1:0",
+        "broadcast": true
+    }))
+    .await?;
+    send(&client, "badger").await?;
+    let event = out.get_event().await?;
+    assert_eq!("badger", event.data.parts().0.to_string());
+
+    let (_out, err) = server.stop().await?;
+    assert!(err.is_empty());
+    let (_out, err) = client.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn udp_multicast() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_udp_port().await?;
+    let server = server(
+        format!("0.0.0.0:{port}"),
+        literal!([{"group": "239.0.0.1", "interface": "127.0.0.1"}]),
+    )
+    .await?;
+    let out = server
+        .out()
+        .expect("No pipeline connected to 'out' port of udp_server connector");
+
+    let client = client(literal!({
+        "url": format!("239.0.0.1:{port}"),
+        "bind": "127.0.0.1:0",
+        "multicast_ttl": 1,
+        "multicast_loop": true
+    }))
+    .await?;
+    send(&client, "fleek").await?;
+    let event = out.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!("fleek", data.to_string());
+    assert_eq!(Some("127.0.0.1"), meta.get("udp").get_str("host"));
+
+    let (_out, err) = server.stop().await?;
+    assert!(err.is_empty());
+    let (_out, err) = client.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn udp_ipv6_multicast_hops() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_udp_port().await?;
+    let server = server(format!("[::1]:{port}"), Value::array()).await?;
+    let out = server
+        .out()
+        .expect("No pipeline connected to 'out' port of udp_server connector");
+
+    // the hop limit is set on IPv6 sockets as well
+    let client = client(literal!({
+        "url": format!("[::1]:{port}"),
+        "bind": "[::1]:0",
+        "multicast_ttl": 2,
+        "multicast_loop": false
+    }))
+    .await?;
+    send(&client, "carfuffle").await?;
+    let event = out.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!("carfuffle", data.to_string());
+    assert_eq!(Some("::1"), meta.get("udp").get_str("host"));
+
+    let (_out, err) = server.stop().await?;
+    assert!(err.is_empty());
+    let (_out, err) = client.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
@@ -25,7 +25,7 @@ pub(crate) mod quiescence;
 pub(crate) mod reconnect;
 
 /// Transport Level Security facilities
-pub(crate) mod tls;
+pub mod tls;
 
 /// MIME encoding utilities
 pub(crate) mod mime;
@@ -33,6 +33,9 @@ pub(crate) mod mime;
 /// Protocol Buffer utilities
 pub(crate) mod pb;
 
+/// Connection lifecycle events
+pub(crate) mod lifecycle;
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub(crate) struct ConnectionMeta {
     pub(crate) host: String,
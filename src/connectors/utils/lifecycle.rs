@@ -0,0 +1,309 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Connection lifecycle events for stream based server connectors
+//!
+//! For every accepted connection a `connected` event is sent on the `connections` port,
+//! once the connection is gone a `disconnected` event follows, carrying the reason and the
+//! number of bytes transferred. Both events carry the connection metadata, so replies can
+//! be routed to the connection via its `stream_id` or the usual connector specific metadata.
+//!
+//! ```json
+//! {"event": "connected", "stream_id": 1}
+//! {"event": "disconnected", "stream_id": 1, "reason": "peer", "bytes_received": 42, "bytes_sent": 23, "duration_ns": 1000}
+//! ```
+
+use crate::connectors::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::{Arc, Mutex};
+use tremor_common::ports::CONNECTIONS;
+use tremor_common::time::nanotime;
+
+/// Why a connection is gone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Reason {
+    /// the peer closed the connection
+    Peer,
+    /// the connection was closed via an event sent to the sink
+    Sink,
+    /// the connector stopped
+    Stopped,
+    /// reading or writing failed
+    Error(String),
+}
+
+impl Reason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Reason::Peer => "peer",
+            Reason::Sink => "sink",
+            Reason::Stopped => "stopped",
+            Reason::Error(_) => "error",
+        }
+    }
+}
+
+/// Lifecycle of a single connection, shared by its reader and writer
+pub(crate) struct Lifecycle {
+    source_runtime: ChannelSourceRuntime,
+    stream: u64,
+    origin_uri: EventOriginUri,
+    meta: Value<'static>,
+    connected_at: u64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    reason: Mutex<Option<Reason>>,
+    disconnected: AtomicBool,
+}
+
+impl Lifecycle {
+    /// Sends the `connected` event for the connection identified by `stream`
+    ///
+    /// # Errors
+    ///   * if the source is gone
+    pub(crate) async fn connected(
+        source_runtime: &ChannelSourceRuntime,
+        stream: u64,
+        origin_uri: &EventOriginUri,
+        meta: &Value<'static>,
+    ) -> Result<Arc<Self>> {
+        let lifecycle = Arc::new(Self {
+            source_runtime: source_runtime.clone(),
+            stream,
+            origin_uri: origin_uri.clone(),
+            meta: meta.clone(),
+            connected_at: nanotime(),
+            bytes_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            reason: Mutex::new(None),
+            disconnected: AtomicBool::new(false),
+        });
+        // on the connection stream, so the event id routes replies to it
+        lifecycle
+            .emit(
+                literal!({"event": "connected", "stream_id": stream}),
+                stream,
+            )
+            .await?;
+        Ok(lifecycle)
+    }
+
+    /// Records why the connection is gone, the first reason wins
+    fn set_reason(&self, reason: Reason) {
+        if let Ok(mut current) = self.reason.lock() {
+            current.get_or_insert(reason);
+        }
+    }
+
+    /// Sends the `disconnected` event, only once
+    async fn disconnected(&self) -> Result<()> {
+        if self.disconnected.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let reason = self.reason.lock()?.clone().unwrap_or(Reason::Peer);
+        let mut event = literal!({
+            "event": "disconnected",
+            "stream_id": self.stream,
+            "reason": reason.as_str(),
+            "bytes_received": self.bytes_received.load(Ordering::Acquire),
+            "bytes_sent": self.bytes_sent.load(Ordering::Acquire),
+            "duration_ns": nanotime().saturating_sub(self.connected_at)
+        });
+        if let Reason::Error(e) = reason {
+            event.try_insert("error", e);
+        }
+        // the connection stream has already ended at this point
+        self.emit(event, DEFAULT_STREAM_ID).await
+    }
+
+    async fn emit(&self, event: Value<'static>, stream: u64) -> Result<()> {
+        let payload = EventPayload::from(ValueAndMeta::from_parts(event, self.meta.clone()));
+        self.source_runtime
+            .send(SourceReply::Structured {
+                origin_uri: self.origin_uri.clone(),
+                payload,
+                stream,
+                port: Some(CONNECTIONS),
+            })
+            .await
+    }
+}
+
+/// Wraps the reader and writer of a connection to track its lifecycle,
+/// does nothing without a `Lifecycle`
+pub(crate) struct Tracked<T> {
+    inner: T,
+    lifecycle: Option<Arc<Lifecycle>>,
+}
+
+impl<T> Tracked<T> {
+    pub(crate) fn new(inner: T, lifecycle: Option<Arc<Lifecycle>>) -> Self {
+        Self { inner, lifecycle }
+    }
+}
+
+#[async_trait::async_trait]
+impl<R> StreamReader for Tracked<R>
+where
+    R: StreamReader,
+{
+    async fn read(&mut self, stream: u64) -> Result<SourceReply> {
+        let res = self.inner.read(stream).await;
+        if let Some(lifecycle) = self.lifecycle.as_ref() {
+            match &res {
+                Ok(SourceReply::Data { data, .. }) => {
+                    lifecycle
+                        .bytes_received
+                        .fetch_add(data.len() as u64, Ordering::AcqRel);
+                }
+                Ok(SourceReply::EndStream { .. }) => lifecycle.set_reason(Reason::Peer),
+                Ok(_) => (),
+                Err(e) => lifecycle.set_reason(Reason::Error(e.to_string())),
+            }
+        }
+        res
+    }
+
+    async fn quiesce(&mut self, stream: u64) -> Option<SourceReply> {
+        if let Some(lifecycle) = self.lifecycle.as_ref() {
+            lifecycle.set_reason(Reason::Stopped);
+        }
+        self.inner.quiesce(stream).await
+    }
+
+    async fn on_done(&mut self, stream: u64) -> StreamDone {
+        let done = self.inner.on_done(stream).await;
+        if let Some(lifecycle) = self.lifecycle.as_ref() {
+            if let Err(e) = lifecycle.disconnected().await {
+                warn!("Error sending disconnected event for stream {stream}: {e}");
+            }
+        }
+        done
+    }
+}
+
+#[async_trait::async_trait]
+impl<W> StreamWriter for Tracked<W>
+where
+    W: StreamWriter,
+{
+    async fn write(&mut self, data: Vec<Vec<u8>>, meta: Option<SinkMeta>) -> Result<()> {
+        let bytes = data.iter().map(Vec::len).sum::<usize>() as u64;
+        let res = self.inner.write(data, meta).await;
+        if let Some(lifecycle) = self.lifecycle.as_ref() {
+            match &res {
+                Ok(()) => {
+                    lifecycle.bytes_sent.fetch_add(bytes, Ordering::AcqRel);
+                }
+                Err(e) => lifecycle.set_reason(Reason::Error(e.to_string())),
+            }
+        }
+        res
+    }
+
+    async fn on_done(&mut self, stream: u64) -> Result<StreamDone> {
+        self.inner.on_done(stream).await
+    }
+
+    async fn on_close(&mut self, stream: u64) -> Result<StreamDone> {
+        if let Some(lifecycle) = self.lifecycle.as_ref() {
+            lifecycle.set_reason(Reason::Sink);
+        }
+        self.inner.on_close(stream).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::channel::bounded;
+
+    fn origin_uri() -> EventOriginUri {
+        EventOriginUri {
+            scheme: "tremor-test".to_string(),
+            host: "localhost".to_string(),
+            port: None,
+            path: vec![],
+        }
+    }
+
+    struct Chunks(Vec<Vec<u8>>);
+
+    #[async_trait::async_trait]
+    impl StreamReader for Chunks {
+        async fn read(&mut self, stream: u64) -> Result<SourceReply> {
+            Ok(match self.0.pop() {
+                Some(data) => SourceReply::Data {
+                    origin_uri: origin_uri(),
+                    data,
+                    meta: None,
+                    stream: Some(stream),
+                    port: None,
+                    codec_overwrite: None,
+                },
+                None => SourceReply::EndStream {
+                    origin_uri: origin_uri(),
+                    stream,
+                    meta: None,
+                },
+            })
+        }
+        async fn quiesce(&mut self, _stream: u64) -> Option<SourceReply> {
+            None
+        }
+    }
+
+    #[async_std::test]
+    async fn connected_and_disconnected() -> Result<()> {
+        let (tx, rx) = bounded(8);
+        let runtime = ChannelSourceRuntime::new(tx);
+        let meta = literal!({"peer": 1});
+        let lifecycle = Lifecycle::connected(&runtime, 1, &origin_uri(), &meta).await?;
+        let mut reader = Tracked::new(
+            Chunks(vec![b"snot".to_vec(), b"badger".to_vec()]),
+            Some(lifecycle),
+        );
+        while let SourceReply::Data { .. } = reader.read(1).await? {}
+        reader.on_done(1).await;
+
+        match rx.recv().await? {
+            SourceReply::Structured {
+                payload,
+                stream,
+                port,
+                ..
+            } => {
+                assert_eq!(1, stream);
+                assert_eq!(Some(CONNECTIONS), port);
+                assert_eq!(Some("connected"), payload.suffix().value().get_str("event"));
+                assert_eq!(&meta, payload.suffix().meta());
+            }
+            _ => panic!("expected a connected event"),
+        }
+        match rx.recv().await? {
+            SourceReply::Structured {
+                payload, stream, ..
+            } => {
+                assert_eq!(DEFAULT_STREAM_ID, stream);
+                let event = payload.suffix().value();
+                assert_eq!(Some("disconnected"), event.get_str("event"));
+                assert_eq!(Some("peer"), event.get_str("reason"));
+                assert_eq!(Some(10), event.get_u64("bytes_received"));
+                assert_eq!(Some(0), event.get_u64("bytes_sent"));
+            }
+            _ => panic!("expected a disconnected event"),
+        }
+        Ok(())
+    }
+}
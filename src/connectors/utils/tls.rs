@@ -15,13 +15,22 @@
 //! TLS utilities
 
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use crate::errors::{Error, Kind as ErrorKind, Result};
-use async_tls::TlsConnector;
+use async_std::sync::Mutex;
+use async_tls::{TlsAcceptor, TlsConnector};
 use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
-use rustls::{Certificate, ClientConfig, NoClientAuth, PrivateKey, RootCertStore, ServerConfig};
+use rustls::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, Certificate,
+    ClientCertVerified, ClientCertVerifier, ClientConfig, DistinguishedNames, NoClientAuth,
+    NoServerSessionStorage, PrivateKey, RootCertStore, ServerConfig, TLSError,
+};
 use rustls_native_certs::load_native_certs;
 use std::io::{BufReader, Cursor};
+use tremor_common::time::nanotime;
+use tremor_value::{literal, Value};
 
 lazy_static! {
     static ref SYSTEM_ROOT_CERTS: RootCertStore = {
@@ -37,12 +46,51 @@ lazy_static! {
     };
 }
 
+/// TLS configuration for server connectors
 #[derive(Debug, Clone, Deserialize)]
 pub struct TLSServerConfig {
+    /// Path to the pem-encoded certificate (-chain) of the server
     pub(crate) cert: PathBuf,
+    /// Path to the private key of the server
     pub(crate) key: PathBuf,
+    /// Path to the pem-encoded CA certificates to verify client certificates with.
+    /// If set, clients authenticate with a certificate (mTLS).
+    #[serde(default)]
+    pub(crate) client_ca: Option<PathBuf>,
+    /// Whether clients are `required` to present a certificate or if it is `optional`.
+    /// Only used together with `client_ca`.
+    #[serde(default)]
+    pub(crate) client_auth: ClientAuth,
+    /// Pick up changes of `cert`, `key` and `client_ca` on disk for new connections
+    #[serde(default)]
+    pub(crate) reload: bool,
 }
 
+impl TLSServerConfig {
+    fn files(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.cert.as_path())
+            .chain(std::iter::once(self.key.as_path()))
+            .chain(self.client_ca.as_deref())
+    }
+}
+
+/// Client authentication of TLS server connectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    /// Clients without a valid certificate are rejected
+    Required,
+    /// Clients may connect without a certificate, but if they present one it needs to be valid
+    Optional,
+}
+
+impl Default for ClientAuth {
+    fn default() -> Self {
+        Self::Required
+    }
+}
+
+/// TLS configuration for client connectors
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct TLSClientConfig {
@@ -57,7 +105,10 @@ pub struct TLSClientConfig {
 }
 
 /// Load the passed certificates file
-pub(crate) fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
+///
+/// # Errors
+///   * if the file cannot be read or contains no valid certificates
+pub fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
     let certfile = tremor_common::file::open(path)?;
     let mut reader = BufReader::new(certfile);
     certs(&mut reader)
@@ -80,7 +131,10 @@ pub(crate) fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
 }
 
 /// Load the passed private key file
-pub(crate) fn load_keys(path: &Path) -> Result<PrivateKey> {
+///
+/// # Errors
+///   * if the file cannot be read or contains no valid RSA or PKCS8 private key
+pub fn load_keys(path: &Path) -> Result<PrivateKey> {
     // prefer to load pkcs8 keys
     // this will only error if we have invalid pkcs8 key base64 or we couldnt read the file.
     let mut keys: Vec<PrivateKey> = {
@@ -117,12 +171,48 @@ pub(crate) fn load_keys(path: &Path) -> Result<PrivateKey> {
     }
 }
 
+/// Load the passed pem-encoded CA certificates file into a root certificate store,
+/// e.g. for verifying client certificates
+///
+/// # Errors
+///   * if the file cannot be read or contains no valid certificates
+pub fn load_root_cert_store(path: &Path) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(&cert).map_err(|e| {
+            Error::from(ErrorKind::TLSError(format!(
+                "Invalid CA certificate in {}: {e}",
+                path.display()
+            )))
+        })?;
+    }
+    Ok(roots)
+}
+
+fn client_cert_verifier(
+    client_auth: ClientAuth,
+    client_roots: Option<RootCertStore>,
+) -> Arc<dyn ClientCertVerifier> {
+    match (client_roots, client_auth) {
+        (Some(roots), ClientAuth::Required) => AllowAnyAuthenticatedClient::new(roots),
+        (Some(roots), ClientAuth::Optional) => AllowAnyAnonymousOrAuthenticatedClient::new(roots),
+        (None, _) => NoClientAuth::new(),
+    }
+}
+
 pub(crate) fn load_server_config(config: &TLSServerConfig) -> Result<ServerConfig> {
     let certs = load_certs(&config.cert)?;
 
     let keys = load_keys(&config.key)?;
 
-    let mut server_config = ServerConfig::new(NoClientAuth::new());
+    let client_roots = config
+        .client_ca
+        .as_deref()
+        .map(load_root_cert_store)
+        .transpose()?;
+
+    let mut server_config =
+        ServerConfig::new(client_cert_verifier(config.client_auth, client_roots));
     server_config
         // set this server to use one cert together with the loaded private key
         .set_single_cert(certs, keys)?;
@@ -130,6 +220,202 @@ pub(crate) fn load_server_config(config: &TLSServerConfig) -> Result<ServerConfi
     Ok(server_config)
 }
 
+/// how often, at most, the files of a [`TlsServer`] are checked for changes, in nanoseconds
+const RELOAD_CHECK_INTERVAL: u64 = 1_000_000_000;
+
+/// Detects changes of the files of a [`TLSServerConfig`], by modification time and size
+pub(crate) struct Changes {
+    modified: Vec<Option<(SystemTime, u64)>>,
+    checked_at: u64,
+}
+
+impl Changes {
+    /// Records the current state of the files, take this before reading them so
+    /// changes while loading are picked up with the next check
+    pub(crate) async fn new(config: &TLSServerConfig) -> Self {
+        Self {
+            modified: modification_times(config).await,
+            checked_at: nanotime(),
+        }
+    }
+
+    /// Returns `true` if the files changed since the previous check, they are
+    /// checked at most once per second
+    pub(crate) async fn check(&mut self, config: &TLSServerConfig) -> bool {
+        let now = nanotime();
+        if now.saturating_sub(self.checked_at) < RELOAD_CHECK_INTERVAL {
+            return false;
+        }
+        self.checked_at = now;
+        let modified = modification_times(config).await;
+        if modified == self.modified {
+            return false;
+        }
+        // a failed reload is only retried once the files changed again
+        self.modified = modified;
+        true
+    }
+}
+
+/// TLS setup of a server connector, handing out an acceptor for each new connection
+///
+/// With `reload` enabled, the configured files are checked for changes when a
+/// connection is accepted (at most once per second) and reloaded if they did change.
+/// If reloading fails the previously loaded certificates stay in use.
+#[derive(Clone)]
+pub(crate) struct TlsServer {
+    config: TLSServerConfig,
+    protocols: Vec<Vec<u8>>,
+    loaded: Arc<Mutex<Loaded>>,
+    changes: Arc<Mutex<Changes>>,
+}
+
+struct Loaded {
+    /// carries the server certificate and key
+    server_config: ServerConfig,
+    client_roots: Option<RootCertStore>,
+}
+
+impl Loaded {
+    fn load(config: &TLSServerConfig) -> Result<Self> {
+        let mut server_config = ServerConfig::new(NoClientAuth::new());
+        server_config.set_single_cert(load_certs(&config.cert)?, load_keys(&config.key)?)?;
+        let client_roots = config
+            .client_ca
+            .as_deref()
+            .map(load_root_cert_store)
+            .transpose()?;
+        Ok(Self {
+            server_config,
+            client_roots,
+        })
+    }
+}
+
+async fn modification_times(config: &TLSServerConfig) -> Vec<Option<(SystemTime, u64)>> {
+    let mut times = Vec::new();
+    for path in config.files() {
+        // the size catches changes within the resolution of the modification time
+        let metadata = async_std::fs::metadata(path).await.ok();
+        times.push(metadata.and_then(|metadata| Some((metadata.modified().ok()?, metadata.len()))));
+    }
+    times
+}
+
+impl TlsServer {
+    /// Loads the configured certificates, key and client CAs
+    ///
+    /// # Errors
+    ///   * if any of the files can't be read or is invalid
+    pub(crate) async fn new(config: &TLSServerConfig) -> Result<Self> {
+        let changes = Changes::new(config).await;
+        Ok(Self {
+            config: config.clone(),
+            protocols: Vec::new(),
+            loaded: Arc::new(Mutex::new(Loaded::load(config)?)),
+            changes: Arc::new(Mutex::new(changes)),
+        })
+    }
+
+    /// Offer the given ALPN protocols, in order of preference
+    #[must_use]
+    pub(crate) fn with_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
+    /// An acceptor for a new connection, along with the client certificate it verifies
+    /// during the handshake
+    pub(crate) async fn acceptor(&self) -> (TlsAcceptor, PeerCert) {
+        let mut loaded = self.loaded.lock().await;
+        if self.config.reload {
+            self.reload_if_changed(&mut loaded).await;
+        }
+        let peer = PeerCert::default();
+        let mut server_config = if let Some(client_roots) = loaded.client_roots.clone() {
+            let verifier = Arc::new(RecordingVerifier {
+                inner: client_cert_verifier(self.config.client_auth, Some(client_roots)),
+                peer: peer.clone(),
+            });
+            let mut server_config = ServerConfig::new(verifier);
+            server_config.cert_resolver = loaded.server_config.cert_resolver.clone();
+            // resumed sessions skip client certificate verification, we'd miss the peer certificate
+            server_config.session_storage = Arc::new(NoServerSessionStorage {});
+            server_config
+        } else {
+            loaded.server_config.clone()
+        };
+        server_config.set_protocols(&self.protocols);
+        (TlsAcceptor::from(Arc::new(server_config)), peer)
+    }
+
+    async fn reload_if_changed(&self, loaded: &mut Loaded) {
+        if !self.changes.lock().await.check(&self.config).await {
+            return;
+        }
+        match Loaded::load(&self.config) {
+            Ok(reloaded) => {
+                info!("Reloaded TLS certificate {}", self.config.cert.display());
+                *loaded = reloaded;
+            }
+            Err(e) => warn!("Error reloading TLS certificates, keeping the previous ones: {e}"),
+        }
+    }
+}
+
+/// The certificate a client authenticated with during the TLS handshake of a connection
+#[derive(Clone, Default)]
+pub(crate) struct PeerCert(Arc<std::sync::Mutex<Option<Certificate>>>);
+
+impl PeerCert {
+    /// Connection metadata describing the verified client certificate,
+    /// `None` if the client did not present one
+    pub(crate) fn meta(&self) -> Option<Value<'static>> {
+        let peer = self.0.lock().ok()?;
+        let (_, cert) = x509_parser::parse_x509_certificate(&peer.as_ref()?.0).ok()?;
+        Some(literal!({
+            "subject": cert.subject().to_string(),
+            "issuer": cert.issuer().to_string(),
+            "serial": cert.raw_serial_as_string()
+        }))
+    }
+}
+
+/// Verifies client certificates and records the verified one for the connection metadata
+struct RecordingVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+    peer: PeerCert,
+}
+
+impl ClientCertVerifier for RecordingVerifier {
+    fn offer_client_auth(&self) -> bool {
+        self.inner.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self, sni: Option<&webpki::DNSName>) -> Option<bool> {
+        self.inner.client_auth_mandatory(sni)
+    }
+
+    fn client_auth_root_subjects(
+        &self,
+        sni: Option<&webpki::DNSName>,
+    ) -> Option<DistinguishedNames> {
+        self.inner.client_auth_root_subjects(sni)
+    }
+
+    fn verify_client_cert(
+        &self,
+        presented_certs: &[Certificate],
+        sni: Option<&webpki::DNSName>,
+    ) -> std::result::Result<ClientCertVerified, TLSError> {
+        let verified = self.inner.verify_client_cert(presented_certs, sni)?;
+        if let (Some(cert), Ok(mut peer)) = (presented_certs.first(), self.peer.0.lock()) {
+            *peer = Some(cert.clone());
+        }
+        Ok(verified)
+    }
+}
+
 /// if we have a cafile configured, we only load it, and no other ca certificates
 /// if there is no cafile configured, we load the default webpki-roots from Mozilla
 pub(crate) async fn tls_client_connector(config: &TLSClientConfig) -> Result<TlsConnector> {
@@ -137,6 +423,13 @@ pub(crate) async fn tls_client_connector(config: &TLSClientConfig) -> Result<Tls
     Ok(TlsConnector::from(tls_config))
 }
 
+/// a client config trusting the system root certificates, without a client certificate
+pub(crate) fn system_client_config() -> ClientConfig {
+    let mut tls_config = ClientConfig::new();
+    tls_config.root_store = SYSTEM_ROOT_CERTS.clone();
+    tls_config
+}
+
 pub(crate) async fn tls_client_config(tremor_config: &TLSClientConfig) -> Result<ClientConfig> {
     let mut tls_config = ClientConfig::new();
     // load server cert verification stuff
@@ -205,6 +498,92 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn load_root_cert_store_valid() -> Result<()> {
+        setup_for_tls();
+
+        let roots = load_root_cert_store(Path::new("./tests/localhost.cert"))?;
+        assert_eq!(1, roots.len());
+        Ok(())
+    }
+
+    #[test]
+    fn server_config_defaults() -> Result<()> {
+        let tls_config: TLSServerConfig = tremor_value::structurize(literal!({
+            "cert": "./tests/localhost.cert",
+            "key": "./tests/localhost.key"
+        }))?;
+        assert_eq!(None, tls_config.client_ca);
+        assert_eq!(ClientAuth::Required, tls_config.client_auth);
+        assert!(!tls_config.reload);
+        Ok(())
+    }
+
+    #[test]
+    fn server_config_client_auth() -> Result<()> {
+        setup_for_tls();
+
+        let tls_config = TLSServerConfig {
+            cert: Path::new("./tests/localhost.cert").to_path_buf(),
+            key: Path::new("./tests/localhost.key").to_path_buf(),
+            client_ca: Some(Path::new("./tests/localhost.cert").to_path_buf()),
+            client_auth: ClientAuth::Optional,
+            reload: false,
+        };
+        assert!(load_server_config(&tls_config).is_ok());
+        let tls_config = TLSServerConfig {
+            client_ca: Some(Path::new("./tests/does_not_exist.cert").to_path_buf()),
+            ..tls_config
+        };
+        assert!(load_server_config(&tls_config).is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn server_reload() -> Result<()> {
+        setup_for_tls();
+
+        let dir = tempfile::tempdir()?;
+        let cert = dir.path().join("server.cert");
+        let key = dir.path().join("server.key");
+        let client_ca = dir.path().join("ca.cert");
+        std::fs::copy("./tests/localhost.cert", &cert)?;
+        std::fs::copy("./tests/localhost.key", &key)?;
+        std::fs::copy("./tests/localhost.cert", &client_ca)?;
+        let server = TlsServer::new(&TLSServerConfig {
+            cert,
+            key,
+            client_ca: Some(client_ca.clone()),
+            client_auth: ClientAuth::Required,
+            reload: true,
+        })
+        .await?;
+
+        // a broken file keeps the previous certificates in place
+        std::fs::write(&client_ca, b"Brueghelflinsch\n")?;
+        let mut loaded = server.loaded.lock().await;
+        server.changes.lock().await.checked_at = 0;
+        let before = server.changes.lock().await.modified.clone();
+        server.reload_if_changed(&mut loaded).await;
+        assert_eq!(
+            Some(1),
+            loaded.client_roots.as_ref().map(RootCertStore::len)
+        );
+        assert_ne!(before, server.changes.lock().await.modified);
+
+        // a valid one is picked up
+        std::fs::write(&client_ca, std::fs::read("./tests/localhost.cert")?)?;
+        let mut cert = std::fs::OpenOptions::new().append(true).open(&client_ca)?;
+        cert.write_all(&std::fs::read("./tests/localhost.cert")?)?;
+        server.changes.lock().await.checked_at = 0;
+        server.reload_if_changed(&mut loaded).await;
+        assert_eq!(
+            Some(2),
+            loaded.client_roots.as_ref().map(RootCertStore::len)
+        );
+        Ok(())
+    }
+
     #[async_std::test]
     async fn client_config() -> Result<()> {
         setup_for_tls();
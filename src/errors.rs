@@ -152,17 +152,24 @@ error_chain! {
         GoogleAuthError(gouth::Error);
         GrokError(grok::Error);
         Hex(hex::FromHexError);
+        HttpError(http::Error);
         HttpHeaderError(http::header::InvalidHeaderValue);
+        HyperError(hyper::Error);
         InfluxEncoderError(influx::EncoderError);
         Io(std::io::Error);
         JsonAccessError(value_trait::AccessError);
         JsonError(simd_json::Error);
+        JwtError(jsonwebtoken::errors::Error);
         KafkaError(rdkafka::error::KafkaError);
         ModeParseError(file_mode::ModeParseError);
         MsgPackDecoderError(rmp_serde::decode::Error);
         MsgPackEncoderError(rmp_serde::encode::Error);
+        OpenSslError(openssl::error::ErrorStack) #[cfg(feature = "dtls")];
+        OpenSslSslError(openssl::ssl::Error) #[cfg(feature = "dtls")];
         ParseIntError(std::num::ParseIntError);
         ParseFloatError(std::num::ParseFloatError);
+        ProstDecodeError(prost::DecodeError);
+        ProstDescriptorError(prost_reflect::DescriptorError);
         //Postgres(postgres::Error);
         RegexError(regex::Error);
         ReqwestError(reqwest::Error);
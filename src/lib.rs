@@ -117,6 +117,9 @@ pub async fn load_troy_file(world: &World, file_name: &str) -> Result<usize> {
     let deployable = match deployable {
         Ok(deployable) => {
             deployable.format_warnings_with(&mut h)?;
+            if tremor_script::profile::is_enabled() {
+                tremor_script::profile::register_file(deployable.aid, file_name)?;
+            }
             deployable
         }
         Err(e) => {
@@ -26,7 +26,8 @@ use beef::Cow;
 use std::{fmt, sync::atomic::Ordering, time::Duration};
 use tremor_common::{ids::OperatorIdGen, time::nanotime};
 use tremor_pipeline::{
-    errors::ErrorKind as PipelineErrorKind, CbAction, Event, ExecutableGraph, SignalKind,
+    errors::ErrorKind as PipelineErrorKind, CbAction, Event, ExecutableGraph, GraphTopology,
+    SignalKind,
 };
 use tremor_script::{ast::DeployEndpoint, highlighter::Dumb, prelude::BaseExpr};
 
@@ -87,6 +88,27 @@ impl Addr {
     pub(crate) async fn resume(&self) -> Result<()> {
         self.send_mgmt(MgmtMsg::Resume).await
     }
+
+    /// number of data-plane messages queued for this pipeline, not yet handled
+    pub(crate) fn queued(&self) -> usize {
+        self.addr.len()
+    }
+
+    /// request the current state and the graph topology of this pipeline
+    pub(crate) async fn report_topology(&self) -> Result<TopologyReport> {
+        let (tx, rx) = bounded(1);
+        self.send_mgmt(MgmtMsg::ReportTopology(tx)).await?;
+        Ok(rx.recv().await?)
+    }
+}
+
+/// Current state and graph topology of a pipeline instance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyReport {
+    /// state of the pipeline
+    pub state: State,
+    /// operators of the pipeline and the connections between them
+    pub graph: GraphTopology,
 }
 
 impl fmt::Debug for Addr {
@@ -112,7 +134,7 @@ pub(crate) enum InputTarget {
 }
 
 impl InputTarget {
-    async fn send_insight(&self, insight: Event) -> Result<()> {
+    pub(crate) async fn send_insight(&self, insight: Event) -> Result<()> {
         match self {
             InputTarget::Pipeline(addr) => addr.send_insight(insight).await,
             InputTarget::Source(addr) => addr.send(SourceMsg::Cb(insight.cb, insight.id)).await,
@@ -144,11 +166,15 @@ impl TryFrom<connectors::Addr> for OutputTarget {
 
 pub(crate) fn spawn(
     alias: &str,
+    flow_id: &str,
     config: &tremor_pipeline::query::Query,
     operator_id_gen: &mut OperatorIdGen,
 ) -> Result<Addr> {
     let qsize = crate::QSIZE.load(Ordering::Relaxed);
     let mut pipeline = config.to_pipe(operator_id_gen)?;
+    pipeline.set_flow(flow_id);
+    // the topology as it was defined, optimizing removes skippable nodes
+    let topology = pipeline.topology();
     pipeline.optimize();
 
     let (tx, rx) = bounded::<Box<Msg>>(qsize);
@@ -179,6 +205,7 @@ pub(crate) fn spawn(
         .spawn(pipeline_task(
             alias.to_string(),
             pipeline,
+            topology,
             addr.clone(),
             rx,
             cf_rx,
@@ -217,13 +244,15 @@ pub(crate) enum MgmtMsg {
     Resume,
     /// stop the pipeline
     Stop,
+    /// report the current state and graph topology
+    ReportTopology(Sender<TopologyReport>),
     #[cfg(test)]
     Inspect(Sender<report::StatusReport>),
 }
 
 #[cfg(test)]
 #[allow(dead_code)]
-mod report {
+pub(crate) mod report {
     use super::{DeployEndpoint, InputTarget, OutputTarget, State};
 
     #[derive(Debug, Clone)]
@@ -443,6 +472,7 @@ fn maybe_send(r: Result<()>) {
 pub(crate) async fn pipeline_task(
     alias: String,
     mut pipeline: ExecutableGraph,
+    topology: GraphTopology,
     addr: Addr,
     rx: Receiver<Box<Msg>>,
     cf_rx: Receiver<CfMsg>,
@@ -584,6 +614,15 @@ pub(crate) async fn pipeline_task(
                 info!("[Pipeline::{}] Stopping...", alias);
                 break;
             }
+            AnyMsg::Mgmt(MgmtMsg::ReportTopology(tx)) => {
+                let report = TopologyReport {
+                    state,
+                    graph: topology.clone(),
+                };
+                if tx.send(report).await.is_err() {
+                    error!("[Pipeline::{alias}] Error sending topology report.");
+                }
+            }
             #[cfg(test)]
             AnyMsg::Mgmt(MgmtMsg::Inspect(tx)) => {
                 use report::*;
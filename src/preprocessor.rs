@@ -90,6 +90,23 @@ pub fn make_preprocessors(preprocessors: &[PreprocessorConfig]) -> Result<Prepro
     preprocessors.iter().map(lookup_with_config).collect()
 }
 
+/// A failed preprocessor chain
+#[derive(Debug)]
+pub struct Failure {
+    /// name of the failing preprocessor
+    pub preprocessor: String,
+    /// the error reported by the preprocessor
+    pub error: Error,
+    /// the data handed to the preprocessor chain, if any
+    pub data: Option<Vec<u8>>,
+}
+
+impl From<Failure> for Error {
+    fn from(f: Failure) -> Self {
+        f.error
+    }
+}
+
 /// Canonical way to preprocess data before it is fed to a codec for decoding.
 ///
 /// Preprocessors might split up the given data in multiple chunks. Each of those
@@ -97,29 +114,42 @@ pub fn make_preprocessors(preprocessors: &[PreprocessorConfig]) -> Result<Prepro
 ///
 /// # Errors
 ///
-///   * If a preprocessor failed
+///   * If a preprocessor failed, the original data is handed back as part of the `Failure`
 pub fn preprocess(
     preprocessors: &mut [Box<dyn Preprocessor>],
     ingest_ns: &mut u64,
     data: Vec<u8>,
     alias: &str,
-) -> Result<Vec<Vec<u8>>> {
-    let mut data = vec![data];
-    let mut data1 = Vec::new();
-    for pp in preprocessors {
-        data1.clear();
-        for (i, d) in data.iter().enumerate() {
+) -> std::result::Result<Vec<Vec<u8>>, Failure> {
+    // we keep the original data around until the whole chain succeeded
+    let (head, tail) = match preprocessors.split_first_mut() {
+        Some(split) => split,
+        None => return Ok(vec![data]),
+    };
+    let fail = |pp: &dyn Preprocessor, error: Error, data: Vec<u8>| {
+        error!("[{alias}] Preprocessor '{}' error: {error}", pp.name());
+        Failure {
+            preprocessor: pp.name().to_string(),
+            error,
+            data: Some(data),
+        }
+    };
+    let mut processed = match head.process(ingest_ns, &data) {
+        Ok(r) => r,
+        Err(e) => return Err(fail(&**head, e, data)),
+    };
+    let mut processed1 = Vec::new();
+    for pp in tail {
+        processed1.clear();
+        for d in &processed {
             match pp.process(ingest_ns, d) {
-                Ok(mut r) => data1.append(&mut r),
-                Err(e) => {
-                    error!("[{}] Preprocessor [{}] error: {}", alias, i, e);
-                    return Err(e);
-                }
+                Ok(mut r) => processed1.append(&mut r),
+                Err(e) => return Err(fail(&**pp, e, data)),
             }
         }
-        std::mem::swap(&mut data, &mut data1);
+        std::mem::swap(&mut processed, &mut processed1);
     }
-    Ok(data)
+    Ok(processed)
 }
 
 /// Canonical way to finish preprocessors up
@@ -130,17 +160,22 @@ pub fn preprocess(
 pub fn finish(
     preprocessors: &mut [Box<dyn Preprocessor>],
     instance_id: &str,
-) -> Result<Vec<Vec<u8>>> {
+) -> std::result::Result<Vec<Vec<u8>>, Failure> {
+    let fail = |pp: &dyn Preprocessor, error: Error| {
+        error!(
+            "[{instance_id}] Preprocessor '{}' finish error: {error}",
+            pp.name()
+        );
+        Failure {
+            preprocessor: pp.name().to_string(),
+            error,
+            data: None,
+        }
+    };
     if let Some((head, tail)) = preprocessors.split_first_mut() {
         let mut data = match head.finish(None) {
             Ok(d) => d,
-            Err(e) => {
-                error!(
-                    "[{instance_id}] Preprocessor '{}' finish error: {e}",
-                    head.name()
-                );
-                return Err(e);
-            }
+            Err(e) => return Err(fail(&**head, e)),
         };
         let mut data1 = Vec::new();
         for pp in tail {
@@ -148,13 +183,7 @@ pub fn finish(
             for d in &data {
                 match pp.finish(Some(d)) {
                     Ok(mut r) => data1.append(&mut r),
-                    Err(e) => {
-                        error!(
-                            "[{instance_id}] Preprocessor '{}' finish error: {e}",
-                            pp.name()
-                        );
-                        return Err(e);
-                    }
+                    Err(e) => return Err(fail(&**pp, e)),
                 }
             }
             std::mem::swap(&mut data, &mut data1);
@@ -470,6 +499,20 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn preprocess_failure() -> Result<()> {
+        let mut it = 0;
+        let mut pps: Vec<Box<dyn Preprocessor>> =
+            vec![Box::new(Separate::default()), Box::new(Base64::default())];
+        let data = b"c25vdA==\n!!!\n".to_vec();
+        let failure = preprocess(pps.as_mut_slice(), &mut it, data.clone(), "test")
+            .err()
+            .ok_or("expected a preprocessor failure")?;
+        assert_eq!("base64", failure.preprocessor);
+        assert_eq!(Some(data), failure.data);
+        Ok(())
+    }
+
     const LOOKUP_TABLE: [&str; 8] = [
         "separate",
         "base64",
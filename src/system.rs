@@ -15,8 +15,10 @@
 /// contains Flow definition, control plane task and lifecycle management
 pub mod flow;
 mod flow_supervisor;
+pub mod shutdown;
 
 use self::flow::Flow;
+use self::shutdown::{DrainConfig, ShutdownReport};
 use crate::errors::{Error, Kind as ErrorKind, Result};
 use crate::{connectors, QSIZE};
 use async_std::channel::bounded;
@@ -32,12 +34,15 @@ pub struct WorldConfig {
     pub qsize: usize,
     /// if debug connectors should be loaded
     pub debug_connectors: bool,
+    /// how flows are drained during a graceful shutdown
+    pub drain: DrainConfig,
 }
 impl Default for WorldConfig {
     fn default() -> Self {
         Self {
             qsize: QSIZE.load(Ordering::Relaxed),
             debug_connectors: false,
+            drain: DrainConfig::default(),
         }
     }
 }
@@ -50,7 +55,7 @@ pub const DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 #[derive(Debug, PartialEq)]
 /// shutdown mode - controls how we shutdown Tremor
 pub enum ShutdownMode {
-    /// shut down by stopping all binding instances and wait for quiescence
+    /// shut down by draining all flows, upstream flows first, each within its drain timeout, then stopping them
     Graceful,
     /// Just stop everything and not wait
     Forceful,
@@ -147,7 +152,8 @@ impl World {
     /// # Errors
     ///  * if the world manager can't be started
     pub async fn start(config: WorldConfig) -> Result<(Self, JoinHandle<Result<()>>)> {
-        let (system_h, system) = flow_supervisor::FlowSupervisor::new(config.qsize).start();
+        let (system_h, system) =
+            flow_supervisor::FlowSupervisor::new(config.qsize, config.drain).start();
 
         let world = Self { system };
 
@@ -160,24 +166,46 @@ impl World {
     /// # Errors
     ///  * if the system failed to drain
     pub async fn drain(&self, timeout: Duration) -> Result<()> {
-        let (tx, rx) = bounded(1);
-        self.system.send(flow_supervisor::Msg::Drain(tx)).await?;
-        if let Ok(res) = rx.recv().timeout(timeout).await {
-            res??;
+        if let Ok(res) = self.drain_with_report().timeout(timeout).await {
+            res?;
         } else {
             warn!("Timeout draining all Flows after {}s", timeout.as_secs());
         }
         Ok(())
     }
 
+    /// Drain the runtime as configured in the `drain` config of the `WorldConfig`
+    ///
+    /// Flows are drained in dependency order, each within its configured drain timeout.
+    /// The returned report lists the connectors that failed to drain in time and the number of
+    /// messages abandoned in queues.
+    ///
+    /// # Errors
+    ///  * if the system failed to drain
+    pub async fn drain_with_report(&self) -> Result<ShutdownReport> {
+        let (tx, rx) = bounded(1);
+        self.system.send(flow_supervisor::Msg::Drain(tx)).await?;
+        rx.recv().await?
+    }
+
     /// Stop the runtime
     ///
     /// # Errors
     ///  * if the system failed to stop
     pub async fn stop(&self, mode: ShutdownMode) -> Result<()> {
         if mode == ShutdownMode::Graceful {
-            if let Err(e) = self.drain(DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT).await {
-                error!("Error draining all Flows: {}", e);
+            match self.drain_with_report().await {
+                Ok(report) => {
+                    for flow in report.flows.iter().filter(|f| !f.is_drained()) {
+                        warn!(
+                            "Flow {} did not drain: undrained connectors: [{}], abandoned messages: {}",
+                            flow.flow,
+                            flow.undrained_connectors.join(", "),
+                            flow.abandoned_messages
+                        );
+                    }
+                }
+                Err(e) => error!("Error draining all Flows: {}", e),
             }
         }
         let res = self.system.send(flow_supervisor::Msg::Stop).await;
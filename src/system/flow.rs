@@ -19,6 +19,7 @@ use crate::{
     log_error,
     pipeline::{self, InputTarget},
     primerge::PriorityMerge,
+    system::shutdown::{FlowDrainReport, Resources},
 };
 use async_std::prelude::*;
 use async_std::{
@@ -34,6 +35,10 @@ use tremor_script::{
     errors::not_defined_err,
 };
 
+mod dead_letter;
+/// Live topology of a flow
+pub mod topology;
+
 /// unique identifier of a flow instance within a tremor instance
 #[derive(Debug, PartialEq, PartialOrd, Eq, Hash, Clone, Serialize)]
 pub(crate) struct Id(pub(crate) String);
@@ -118,6 +123,10 @@ pub(crate) enum Msg {
     GetConnector(ConnectorAlias, Sender<Result<connectors::Addr>>),
     /// Get the addresses for all connectors of this flow
     GetConnectors(Sender<Result<Vec<connectors::Addr>>>),
+    /// Get all instances of this flow, to collect its topology
+    GetInstances(Sender<Result<topology::Instances>>),
+    /// Get the connectors that did not yet drain and the number of messages still queued within this flow
+    DrainStatus(Sender<Result<FlowDrainReport>>),
 }
 type Addr = Sender<Msg>;
 
@@ -126,6 +135,7 @@ type Addr = Sender<Msg>;
 pub struct Flow {
     alias: String,
     addr: Addr,
+    resources: Resources,
 }
 
 /// Status Report for a Flow instance
@@ -149,6 +159,16 @@ impl Flow {
     pub(crate) async fn drain(&self, tx: Sender<Result<()>>) -> Result<()> {
         self.addr.send(Msg::Drain(tx)).await.map_err(Error::from)
     }
+    /// external resources shared with other flows
+    pub(crate) fn resources(&self) -> &Resources {
+        &self.resources
+    }
+    /// request the connectors that did not yet drain and the number of messages still queued within this flow
+    pub(crate) async fn drain_status(&self) -> Result<FlowDrainReport> {
+        let (tx, rx) = bounded(1);
+        self.addr.send(Msg::DrainStatus(tx)).await?;
+        rx.recv().await?
+    }
 
     /// request a `StatusReport` from this `Flow`
     ///
@@ -181,6 +201,16 @@ impl Flow {
         rx.recv().await?
     }
 
+    /// Get the live topology of this flow with all its connectors, pipelines and their connections
+    ///
+    /// # Errors
+    /// if the flow or any of its instances is not running anymore and can't be reached
+    pub async fn report_topology(&self) -> Result<topology::Topology> {
+        let (tx, rx) = bounded(1);
+        self.addr.send(Msg::GetInstances(tx)).await?;
+        rx.recv().await??.collect().await
+    }
+
     /// Pause this flow and all connectors in it.
     ///
     /// # Errors
@@ -207,6 +237,7 @@ impl Flow {
     ) -> Result<Self> {
         let mut pipelines = HashMap::new();
         let mut connectors = HashMap::new();
+        let mut resources = Resources::default();
 
         for create in &flow.defn.creates {
             let alias: &str = &create.instance_alias;
@@ -221,6 +252,12 @@ impl Flow {
                             .ok_or_else(|| {
                                 ErrorKind::UnknownConnectorType(config.connector_type.to_string())
                             })?;
+                    resources.add(
+                        alias,
+                        &config.connector_type,
+                        &config.config,
+                        &flow.defn.connections,
+                    );
                     connectors.insert(
                         ConnectorAlias::from(alias),
                         connectors::spawn(alias, connector_id_gen, builder.as_ref(), config)
@@ -238,7 +275,8 @@ impl Flow {
                     let pipeline = tremor_pipeline::query::Query(
                         tremor_script::query::Query::from_query(query),
                     );
-                    let addr = pipeline::spawn(alias, &pipeline, operator_id_gen)?;
+                    let addr =
+                        pipeline::spawn(alias, &flow.instance_alias, &pipeline, operator_id_gen)?;
                     pipelines.insert(PipelineId::from(alias), addr);
                 }
             }
@@ -249,11 +287,42 @@ impl Flow {
             link(&connectors, &pipelines, connect).await?;
         }
 
+        // route all unconnected errors to the dead-letter connector, if configured
+        let dead_letter = if let Some(dead_letter) = &flow.defn.dead_letter {
+            Some(
+                dead_letter::link(
+                    &flow.instance_alias,
+                    dead_letter,
+                    &connectors,
+                    &pipelines,
+                    &flow.defn.connections,
+                )
+                .await?,
+            )
+        } else {
+            None
+        };
+
+        let mut connections: Vec<_> = flow
+            .defn
+            .connections
+            .iter()
+            .map(topology::Connection::from)
+            .collect();
+        if let Some(dead_letter) = &flow.defn.dead_letter {
+            connections.push(topology::Connection::dead_letter(
+                &flow.instance_alias,
+                dead_letter,
+            ));
+        }
+
         let addr = spawn_task(
             flow.instance_alias.clone(),
             pipelines,
             connectors,
             &flow.defn.connections,
+            dead_letter,
+            connections,
         )
         .await?;
 
@@ -262,6 +331,7 @@ impl Flow {
         let this = Flow {
             alias: flow.instance_alias.to_string(),
             addr,
+            resources,
         };
 
         Ok(this)
@@ -399,6 +469,8 @@ async fn spawn_task(
     pipelines: HashMap<PipelineId, pipeline::Addr>,
     connectors: HashMap<ConnectorAlias, connectors::Addr>,
     links: &[ConnectStmt],
+    dead_letter: Option<dead_letter::DeadLetter>,
+    connections: Vec<topology::Connection>,
 ) -> Result<Addr> {
     #[derive(Debug)]
     /// wrapper for all possible messages handled by the flow task
@@ -429,7 +501,7 @@ async fn spawn_task(
     // let registries = self.reg.clone();
 
     // extracting connectors and pipes from the links
-    let sink_connectors: HashSet<ConnectorAlias> = links
+    let mut sink_connectors: HashSet<ConnectorAlias> = links
         .iter()
         .filter_map(|c| {
             if let ConnectStmt::PipelineToConnector { to, .. } = c {
@@ -450,7 +522,18 @@ async fn spawn_task(
         })
         .collect();
 
-    let pipelines: Vec<_> = pipelines.values().cloned().collect();
+    let named_pipelines: Vec<_> = pipelines
+        .iter()
+        .map(|(id, addr)| (id.0.clone(), addr.clone()))
+        .collect();
+    let mut pipelines: Vec<_> = pipelines.values().cloned().collect();
+
+    // the dead-letter connector might not be connected otherwise, but it needs to be managed as sink
+    // and its forwarding task is managed like a pipeline
+    if let Some(dead_letter) = dead_letter {
+        sink_connectors.insert(dead_letter.connector);
+        pipelines.push(dead_letter.addr);
+    }
 
     let start_points: Vec<_> = source_connectors
         .difference(&sink_connectors)
@@ -472,6 +555,10 @@ async fn spawn_task(
     let mut expected_drains: usize = 0;
     let mut expected_stops: usize = 0;
 
+    // connectors we are still waiting for to drain and those that failed to drain
+    let mut pending_drains: HashSet<String> = HashSet::new();
+    let mut failed_drains: Vec<String> = Vec::new();
+
     // for storing senders that have been sent to us
     let mut drain_senders = Vec::with_capacity(1);
     let mut stop_senders = Vec::with_capacity(1);
@@ -562,11 +649,14 @@ async fn spawn_task(
 
                         // source only connectors
                         for addr in start_points.iter().chain(&mixed_pickles).chain(&end_points) {
-                            if !log_error!(
+                            if log_error!(
                                 addr.drain(drain_tx.clone()).await,
                                 "{prefix} Error starting Draining Connector {addr:?}: {e}"
                             ) {
+                                failed_drains.push(addr.alias.clone());
+                            } else {
                                 expected_drains += 1;
+                                pending_drains.insert(addr.alias.clone());
                             }
                         }
                     }
@@ -630,15 +720,55 @@ async fn spawn_task(
                         "{prefix} Error sending GetConnectors response: {e}"
                     );
                 }
+                MsgWrapper::Msg(Msg::GetInstances(reply_tx)) => {
+                    let instances = topology::Instances {
+                        alias: alias.clone(),
+                        status: state,
+                        connectors: connectors.values().cloned().collect(),
+                        pipelines: named_pipelines.clone(),
+                        connections: connections.clone(),
+                    };
+                    log_error!(
+                        reply_tx.send(Ok(instances)).await,
+                        "{prefix} Error sending GetInstances response: {e}"
+                    );
+                }
+                MsgWrapper::Msg(Msg::DrainStatus(reply_tx)) => {
+                    let mut undrained_connectors: Vec<_> = pending_drains
+                        .iter()
+                        .chain(&failed_drains)
+                        .cloned()
+                        .collect();
+                    undrained_connectors.sort();
+                    let abandoned_messages = connectors
+                        .values()
+                        .filter(|c| pending_drains.contains(&c.alias))
+                        .map(connectors::Addr::queued)
+                        .chain(pipelines.iter().map(pipeline::Addr::queued))
+                        .sum();
+                    let report = FlowDrainReport {
+                        flow: alias.clone(),
+                        timed_out: false,
+                        undrained_connectors,
+                        abandoned_messages,
+                    };
+                    log_error!(
+                        reply_tx.send(Ok(report)).await,
+                        "{prefix} Error sending DrainStatus response: {e}"
+                    );
+                }
 
                 MsgWrapper::DrainResult(conn_res) => {
                     info!("[Flow::{}] Connector {} drained.", &alias, &conn_res.alias);
 
-                    log_error!(
+                    pending_drains.remove(&conn_res.alias);
+                    if log_error!(
                         conn_res.res,
                         "{prefix} Error during Draining in Connector {}: {e}",
                         &conn_res.alias
-                    );
+                    ) {
+                        failed_drains.push(conn_res.alias);
+                    }
 
                     let old = expected_drains;
                     expected_drains = expected_drains.saturating_sub(1);
@@ -712,6 +842,7 @@ mod tests {
     use tremor_common::ids::{ConnectorIdGen, OperatorIdGen};
     use tremor_script::{ast::DeployStmt, deploy::Deploy, FN_REGISTRY};
     use tremor_value::literal;
+    use value_trait::ValueAccess;
 
     mod connector {
 
@@ -912,6 +1043,281 @@ mod tests {
         flow.drain(tx.clone()).await?;
         rx.recv().await??;
 
+        let drain_status = flow.drain_status().await?;
+        assert_eq!("test", drain_status.flow);
+        assert!(drain_status.is_drained());
+        assert_eq!(0, drain_status.abandoned_messages);
+
+        flow.stop(tx).await?;
+        rx.recv().await??;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn flow_dead_letter() -> Result<()> {
+        let mut operator_id_gen = OperatorIdGen::default();
+        let mut connector_id_gen = ConnectorIdGen::default();
+        let aggr_reg = tremor_script::aggr_registry();
+        let src = r#"
+        define flow test
+        flow
+            define connector foo from fake
+            with
+                codec = "json",
+                config = {}
+            end;
+
+            define pipeline main
+            pipeline
+                define script fail
+                script
+                    event.snot + 1
+                end;
+                create script fail;
+                select event from in into fail;
+                select event from fail/err into err;
+            end;
+
+            create connector foo;
+            create connector dead_letters from foo;
+            create pipeline main;
+
+            connect /connector/foo to /pipeline/main;
+            connect /flow/err to /connector/dead_letters;
+        end;
+        deploy flow test;
+        "#;
+        let deployable = Deploy::parse(&src, &*FN_REGISTRY.read()?, &aggr_reg)?;
+        let deploy = deployable
+            .deploy
+            .stmts
+            .into_iter()
+            .find_map(|stmt| match stmt {
+                DeployStmt::DeployFlowStmt(deploy_flow) => Some((*deploy_flow).clone()),
+                _other => None,
+            })
+            .expect("No deploy in the given troy file");
+        let mut known_connectors = Known::new();
+        let (connector_tx, connector_rx) = unbounded();
+        let builder = connector::FakeBuilder { tx: connector_tx };
+        known_connectors.insert(builder.connector_type(), Box::new(builder));
+        let flow = Flow::start(
+            deploy,
+            &mut operator_id_gen,
+            &mut connector_id_gen,
+            &known_connectors,
+        )
+        .await?;
+
+        // the script error is routed to the dead-letter connector, enriched with its origin
+        let event = connector_rx.recv().await?;
+        let value = event.data.suffix().value();
+        assert_eq!(Some("test"), value.get_str("flow"));
+        assert_eq!(Some("main"), value.get_str("node"));
+        assert_eq!(Some("pipeline"), value.get_str("node_kind"));
+        assert_eq!(Some("err"), value.get_str("port"));
+        assert_eq!(
+            Some(&literal!({"snot": "badger"})),
+            value.get("event").and_then(|e| e.get("event"))
+        );
+        assert!(!event.transactional);
+
+        // the dead-letter connector input is linked to the flow `err` port
+        let dead_letters = flow.get_connector("dead_letters".to_string()).await?;
+        let inputs = dead_letters
+            .pipelines
+            .get("in")
+            .expect("dead-letter connector input not linked");
+        assert_eq!(1, inputs.len());
+        assert_eq!("flow", inputs[0].alias());
+        assert_eq!("err", inputs[0].port());
+
+        // the forwarding task takes part in the flow lifecycle
+        let mut report = flow.report_status().await?;
+        while report.status == instance::State::Initializing {
+            task::sleep(Duration::from_millis(100)).await;
+            report = flow.report_status().await?;
+        }
+        flow.pause().await?;
+        assert_eq!(instance::State::Paused, flow.report_status().await?.status);
+        flow.resume().await?;
+        assert_eq!(instance::State::Running, flow.report_status().await?.status);
+
+        let (tx, rx) = bounded(1);
+        flow.drain(tx.clone()).await?;
+        rx.recv().await??;
+        assert!(flow.drain_status().await?.is_drained());
+
+        flow.stop(tx).await?;
+        rx.recv().await??;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn flow_dead_letter_connector() -> Result<()> {
+        let mut operator_id_gen = OperatorIdGen::default();
+        let mut connector_id_gen = ConnectorIdGen::default();
+        let aggr_reg = tremor_script::aggr_registry();
+        let src = r#"
+        define flow test
+        flow
+            define connector foo from fake
+            with
+                codec = "json",
+                config = {}
+            end;
+            define connector bad from fake
+            with
+                codec = "json",
+                preprocessors = ["base64"],
+                config = {}
+            end;
+
+            create connector bad;
+            create connector dead_letters from foo;
+
+            connect /flow/err to /connector/dead_letters;
+        end;
+        deploy flow test;
+        "#;
+        let deployable = Deploy::parse(&src, &*FN_REGISTRY.read()?, &aggr_reg)?;
+        let deploy = deployable
+            .deploy
+            .stmts
+            .into_iter()
+            .find_map(|stmt| match stmt {
+                DeployStmt::DeployFlowStmt(deploy_flow) => Some((*deploy_flow).clone()),
+                _other => None,
+            })
+            .expect("No deploy in the given troy file");
+        let mut known_connectors = Known::new();
+        let (connector_tx, connector_rx) = unbounded();
+        let builder = connector::FakeBuilder { tx: connector_tx };
+        known_connectors.insert(builder.connector_type(), Box::new(builder));
+        let flow = Flow::start(
+            deploy,
+            &mut operator_id_gen,
+            &mut connector_id_gen,
+            &known_connectors,
+        )
+        .await?;
+
+        // the preprocessor error of the connector is routed to the dead-letter connector
+        let event = connector_rx.recv().await?;
+        let value = event.data.suffix().value();
+        assert_eq!(Some("test"), value.get_str("flow"));
+        assert_eq!(Some("bad"), value.get_str("node"));
+        assert_eq!(Some("connector"), value.get_str("node_kind"));
+        assert_eq!(Some("err"), value.get_str("port"));
+        let error = value.get("event").expect("no error event");
+        assert_eq!(Some("bad"), error.get_str("source"));
+        assert_eq!(Some("base64"), error.get_str("preprocessor"));
+        assert!(!event.transactional);
+
+        let (tx, rx) = bounded(1);
+        flow.drain(tx.clone()).await?;
+        rx.recv().await??;
+        assert!(flow.drain_status().await?.is_drained());
+
+        flow.stop(tx).await?;
+        rx.recv().await??;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn flow_topology() -> Result<()> {
+        let mut operator_id_gen = OperatorIdGen::default();
+        let mut connector_id_gen = ConnectorIdGen::default();
+        let aggr_reg = tremor_script::aggr_registry();
+        let src = r#"
+        define flow test
+        flow
+            define connector foo from fake
+            with
+                codec = "json",
+                config = {}
+            end;
+
+            define pipeline main
+            pipeline
+                select event from in into out;
+            end;
+
+            create connector foo;
+            create pipeline main;
+
+            connect /connector/foo to /pipeline/main;
+            connect /pipeline/main to /connector/foo;
+        end;
+        deploy flow test;
+        "#;
+        let deployable = Deploy::parse(&src, &*FN_REGISTRY.read()?, &aggr_reg)?;
+        let deploy = deployable
+            .deploy
+            .stmts
+            .into_iter()
+            .find_map(|stmt| match stmt {
+                DeployStmt::DeployFlowStmt(deploy_flow) => Some((*deploy_flow).clone()),
+                _other => None,
+            })
+            .expect("No deploy in the given troy file");
+        let mut known_connectors = Known::new();
+        let (connector_tx, _connector_rx) = unbounded();
+        let builder = connector::FakeBuilder { tx: connector_tx };
+        known_connectors.insert(builder.connector_type(), Box::new(builder));
+        let flow = Flow::start(
+            deploy,
+            &mut operator_id_gen,
+            &mut connector_id_gen,
+            &known_connectors,
+        )
+        .await?;
+
+        let topology = flow.report_topology().await?;
+        assert_eq!("test", topology.alias);
+        assert_eq!(1, topology.connectors.len());
+        assert_eq!("foo", topology.connectors[0].alias);
+        assert_eq!("fake", topology.connectors[0].connector_type);
+        assert_eq!(1, topology.pipelines.len());
+        let pipeline = &topology.pipelines[0];
+        assert_eq!("main", pipeline.alias);
+        assert!(pipeline.graph.nodes.iter().any(|n| n.id == "in"));
+        assert!(pipeline.graph.nodes.iter().any(|n| n.id == "out"));
+        assert!(!pipeline.graph.edges.is_empty());
+        assert_eq!(
+            vec![
+                topology::Connection {
+                    from: topology::Endpoint {
+                        kind: topology::NodeKind::Connector,
+                        alias: "foo".to_string(),
+                        port: "out".to_string()
+                    },
+                    to: topology::Endpoint {
+                        kind: topology::NodeKind::Pipeline,
+                        alias: "main".to_string(),
+                        port: "in".to_string()
+                    }
+                },
+                topology::Connection {
+                    from: topology::Endpoint {
+                        kind: topology::NodeKind::Pipeline,
+                        alias: "main".to_string(),
+                        port: "out".to_string()
+                    },
+                    to: topology::Endpoint {
+                        kind: topology::NodeKind::Connector,
+                        alias: "foo".to_string(),
+                        port: "in".to_string()
+                    }
+                }
+            ],
+            topology.connections
+        );
+
+        let (tx, rx) = bounded(1);
         flow.stop(tx).await?;
         rx.recv().await??;
 
@@ -0,0 +1,350 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Flow-wide dead-letter routing
+//!
+//! Error events emitted via the `err` port of connectors or pipelines, that are not
+//! connected to anything within the flow, would be lost. If the flow `err` port is connected:
+//!
+//! ```troy
+//! connect /flow/err to /connector/dead_letters;
+//! ```
+//!
+//! all those `err` ports are linked to a small forwarding task, which wraps each error event
+//! with the context of its origin and sends it to the dead-letter connector.
+//!
+//! The forwarding task is linked to the dead-letter connector like a pipeline, so `start` and `drain`
+//! signals of the origins reach the connector and its circuit breaker and drain contraflow reaches the origins.
+
+use super::{ConnectorAlias, PipelineId};
+use crate::{
+    connectors,
+    errors::Result,
+    instance::State,
+    pipeline::{self, AnyMsg, CfMsg, InputTarget, MgmtMsg, Msg, OutputTarget, TopologyReport},
+    primerge::PriorityMerge,
+};
+use async_std::prelude::*;
+use async_std::{
+    channel::{bounded, unbounded, Receiver},
+    task,
+};
+use hashbrown::{HashMap, HashSet};
+use std::{sync::atomic::Ordering, time::Duration};
+use tremor_common::ports::ERR;
+use tremor_pipeline::{CbAction, Event, GraphTopology, SignalKind};
+use tremor_script::{
+    ast::{ConnectStmt, DeployEndpoint},
+    errors::not_defined_err,
+    prelude::BaseExpr,
+};
+use tremor_value::{literal, Value};
+use value_trait::Mutable;
+
+/// The dead-letter facility of a flow
+#[derive(Debug)]
+pub(crate) struct DeadLetter {
+    /// the connector receiving all dead letters
+    pub(crate) connector: ConnectorAlias,
+    /// the forwarding task, it is managed like a pipeline
+    pub(crate) addr: pipeline::Addr,
+}
+
+/// Link all unconnected `err` ports of the flows connectors and pipelines to the dead-letter connector
+pub(crate) async fn link(
+    flow_alias: &str,
+    dead_letter: &DeployEndpoint,
+    connectors: &HashMap<ConnectorAlias, connectors::Addr>,
+    pipelines: &HashMap<PipelineId, pipeline::Addr>,
+    connections: &[ConnectStmt],
+) -> Result<DeadLetter> {
+    let connector = connectors
+        .get(dead_letter.alias())
+        .ok_or_else(|| not_defined_err(dead_letter, "connector"))?;
+    let target = OutputTarget::try_from(connector.clone())?;
+
+    // origins that already route their errors somewhere
+    let mut routed_connectors = HashSet::new();
+    let mut routed_pipelines = HashSet::new();
+    for connect in connections {
+        match connect {
+            ConnectStmt::ConnectorToPipeline { from, .. } if is_err(from) => {
+                routed_connectors.insert(from.alias());
+            }
+            ConnectStmt::PipelineToConnector { from, .. }
+            | ConnectStmt::PipelineToPipeline { from, .. }
+                if is_err(from) =>
+            {
+                routed_pipelines.insert(from.alias());
+            }
+            _ => (),
+        }
+    }
+
+    let qsize = crate::QSIZE.load(Ordering::Relaxed);
+    let (tx, rx) = bounded::<Box<Msg>>(qsize);
+    let (cf_tx, cf_rx) = unbounded::<CfMsg>();
+    let (mgmt_tx, mgmt_rx) = bounded::<MgmtMsg>(qsize);
+    let addr = pipeline::Addr::new(tx, cf_tx, mgmt_tx, format!("{flow_alias}::dead_letter"));
+
+    // link the dead-letter connector input, so it knows about the forwarding task
+    // and sends its contraflow there
+    let (result_tx, result_rx) = bounded(1);
+    connector
+        .send(connectors::Msg::LinkInput {
+            port: dead_letter.port().to_string().into(),
+            pipelines: vec![(
+                DeployEndpoint::new(&"flow", &ERR, dead_letter.meta()),
+                addr.clone(),
+            )],
+            result_tx,
+        })
+        .await?;
+    result_rx.recv().timeout(Duration::from_secs(2)).await???;
+
+    // every origin is linked via its own input, so we can attach its context to each error event
+    let mut origins = HashMap::new();
+    for (alias, connector) in connectors {
+        let source = match connector.source() {
+            Some(source)
+                if alias.0 != dead_letter.alias()
+                    && !routed_connectors.contains(alias.0.as_str()) =>
+            {
+                source
+            }
+            _ => continue,
+        };
+        let input = format!("connector/{}", alias.0);
+        let endpoint = DeployEndpoint::new(&dead_letter.alias(), &input, dead_letter.meta());
+        origins.insert(input, origin(flow_alias, "connector", &alias.0));
+
+        let (result_tx, result_rx) = bounded(1);
+        connector
+            .send(connectors::Msg::LinkOutput {
+                port: ERR,
+                pipelines: vec![(endpoint, addr.clone())],
+                result_tx,
+            })
+            .await?;
+        result_rx.recv().timeout(Duration::from_secs(2)).await???;
+        // register the source right away, so contraflow reaches it even before
+        // the source got around to announce itself; error events are forwarded
+        // non-transactionally, so only circuit breaker insights are relevant
+        addr.send_mgmt(MgmtMsg::ConnectInput {
+            endpoint: DeployEndpoint::new(&alias.0, &ERR, dead_letter.meta()),
+            target: InputTarget::Source(source.clone()),
+            is_transactional: false,
+        })
+        .await?;
+    }
+    for (alias, pipeline) in pipelines {
+        if routed_pipelines.contains(alias.0.as_str()) {
+            continue;
+        }
+        let input = format!("pipeline/{}", alias.0);
+        let endpoint = DeployEndpoint::new(&dead_letter.alias(), &input, dead_letter.meta());
+        origins.insert(input, origin(flow_alias, "pipeline", &alias.0));
+
+        pipeline
+            .send_mgmt(MgmtMsg::ConnectOutput {
+                port: ERR,
+                endpoint,
+                target: addr.clone().into(),
+            })
+            .await?;
+        addr.send_mgmt(MgmtMsg::ConnectInput {
+            endpoint: DeployEndpoint::new(&alias.0, &ERR, dead_letter.meta()),
+            target: InputTarget::Pipeline(Box::new(pipeline.clone())),
+            is_transactional: true,
+        })
+        .await?;
+    }
+    info!(
+        "[Flow::{flow_alias}] Routing unconnected errors of {} nodes to {dead_letter}",
+        origins.len()
+    );
+
+    task::Builder::new()
+        .name(format!("dead-letter-{flow_alias}"))
+        .spawn(run(
+            format!("[Flow::{flow_alias}::dead_letter]"),
+            origins,
+            rx,
+            cf_rx,
+            mgmt_rx,
+            (dead_letter.clone(), target),
+        ))?;
+
+    Ok(DeadLetter {
+        connector: ConnectorAlias::from(dead_letter),
+        addr,
+    })
+}
+
+fn is_err(endpoint: &DeployEndpoint) -> bool {
+    endpoint.port().eq_ignore_ascii_case(ERR.as_ref())
+}
+
+/// context attached to every error event coming from the given node
+fn origin(flow: &str, kind: &str, alias: &str) -> Value<'static> {
+    literal!({
+        "flow": flow.to_string(),
+        "node": alias.to_string(),
+        "node_kind": kind.to_string(),
+        "port": ERR.to_string()
+    })
+}
+
+/// forwards all received error events to the dead-letter connector
+///
+/// Start and drain signals of the origins are forwarded as well, contraflow is sent back to all origins.
+#[allow(clippy::too_many_lines)]
+async fn run(
+    prefix: String,
+    origins: HashMap<String, Value<'static>>,
+    rx: Receiver<Box<Msg>>,
+    cf_rx: Receiver<CfMsg>,
+    mgmt_rx: Receiver<MgmtMsg>,
+    output: (DeployEndpoint, OutputTarget),
+) -> Result<()> {
+    let mut inputs: HashMap<DeployEndpoint, (bool, InputTarget)> = HashMap::new();
+    let mut outputs = vec![output];
+    let mut state = State::Initializing;
+
+    let ff = rx.map(|e| AnyMsg::Flow(*e));
+    let cf = cf_rx.map(AnyMsg::Contraflow);
+    let mf = mgmt_rx.map(AnyMsg::Mgmt);
+    let mut s = PriorityMerge::new(mf, PriorityMerge::new(cf, ff));
+    while let Some(msg) = s.next().await {
+        match msg {
+            AnyMsg::Flow(Msg::Event { input, mut event }) => {
+                let context = origins
+                    .get(&*input)
+                    .cloned()
+                    .unwrap_or_else(|| literal!({ "port": input.to_string() }));
+                event.data.rent_mut(|data| {
+                    let (value, _meta) = data.parts_mut();
+                    let mut error_event: Value = context;
+                    std::mem::swap(&mut error_event, value);
+                    value.try_insert("event", error_event);
+                });
+                // the dead-letter connector is not part of the event's path,
+                // so acks or fails would not reach its origin
+                event.transactional = false;
+                for (endpoint, target) in &mut outputs {
+                    let port = endpoint.port().to_string().into();
+                    if let Err(e) = target.send_event(port, event.clone()).await {
+                        error!("{prefix} Error sending dead letter to {endpoint}: {e}");
+                    }
+                }
+            }
+            // the connector needs to see start and drain signals of all origins in order to drain properly,
+            // ticks are generated by each origin, so we don't forward them
+            AnyMsg::Flow(Msg::Signal(signal)) => {
+                if matches!(
+                    signal.kind,
+                    Some(SignalKind::Start(_) | SignalKind::Drain(_))
+                ) {
+                    for (endpoint, target) in &mut outputs {
+                        if let Err(e) = target.send_signal(signal.clone()).await {
+                            error!("{prefix} Error sending signal to {endpoint}: {e}");
+                        }
+                    }
+                }
+            }
+            AnyMsg::Contraflow(CfMsg::Insight(insight)) => {
+                send_insight(&prefix, &inputs, &insight).await;
+            }
+            AnyMsg::Mgmt(MgmtMsg::ConnectInput {
+                endpoint,
+                target,
+                is_transactional,
+            }) => {
+                info!("{prefix} Connecting {endpoint}");
+                inputs.insert(endpoint, (is_transactional, target));
+            }
+            AnyMsg::Mgmt(MgmtMsg::ConnectOutput {
+                endpoint, target, ..
+            }) => {
+                info!("{prefix} Connecting to {endpoint}");
+                outputs.push((endpoint, target));
+            }
+            AnyMsg::Mgmt(MgmtMsg::Start) if state == State::Initializing => {
+                state = State::Running;
+            }
+            AnyMsg::Mgmt(MgmtMsg::Pause) if state == State::Running => {
+                state = State::Paused;
+            }
+            AnyMsg::Mgmt(MgmtMsg::Resume) if state == State::Paused => {
+                state = State::Running;
+            }
+            AnyMsg::Mgmt(msg @ (MgmtMsg::Start | MgmtMsg::Pause | MgmtMsg::Resume)) => {
+                info!("{prefix} Ignoring {msg:?} in state {state}");
+            }
+            AnyMsg::Mgmt(MgmtMsg::ReportTopology(tx)) => {
+                let report = TopologyReport {
+                    state,
+                    graph: GraphTopology::default(),
+                };
+                if tx.send(report).await.is_err() {
+                    error!("{prefix} Error sending topology report.");
+                }
+            }
+            AnyMsg::Mgmt(MgmtMsg::Stop) => {
+                info!("{prefix} Stopping...");
+                break;
+            }
+            #[cfg(test)]
+            AnyMsg::Mgmt(MgmtMsg::Inspect(tx)) => {
+                use pipeline::report::{InputReport, OutputReport, StatusReport};
+                let inputs = inputs
+                    .iter()
+                    .map(|(endpoint, (_, target))| InputReport::new(endpoint, target))
+                    .collect();
+                let mut report = StatusReport {
+                    state,
+                    inputs,
+                    outputs: halfbrown::HashMap::new(),
+                };
+                report.outputs.insert(
+                    ERR.to_string(),
+                    outputs.iter().map(OutputReport::from).collect(),
+                );
+                if tx.send(report).await.is_err() {
+                    error!("{prefix} Error sending status report.");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// send an insight back to all inputs, like a pipeline does
+async fn send_insight(
+    prefix: &str,
+    inputs: &HashMap<DeployEndpoint, (bool, InputTarget)>,
+    insight: &Event,
+) {
+    if insight.cb == CbAction::None {
+        return;
+    }
+    let always_deliver = insight.cb.always_deliver();
+    for (endpoint, (is_transactional, input)) in inputs {
+        if always_deliver || *is_transactional {
+            if let Err(e) = input.send_insight(insight.clone()).await {
+                error!("{prefix} Failed to send insight to {endpoint}: {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,516 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Live topology of a flow instance
+//!
+//! The topology is collected from the running connectors and pipelines of a flow,
+//! so it reflects their current state. It can be rendered as graphviz dot or mermaid.
+
+use crate::{
+    connectors::{self, Connectivity},
+    errors::Result,
+    instance::State,
+    pipeline,
+};
+use std::fmt::Write;
+use tremor_pipeline::GraphTopology;
+use tremor_script::ast::{ConnectStmt, DeployEndpoint};
+
+/// Live topology of a flow instance
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Topology {
+    /// alias of the flow instance
+    pub alias: String,
+    /// the current state of the flow
+    pub status: State,
+    /// all connectors of the flow
+    pub connectors: Vec<ConnectorNode>,
+    /// all pipelines of the flow
+    pub pipelines: Vec<PipelineNode>,
+    /// all connections between connectors and pipelines
+    pub connections: Vec<Connection>,
+}
+
+/// A connector within a flow topology
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConnectorNode {
+    /// alias of the connector instance
+    pub alias: String,
+    /// the type of the connector, e.g. `tcp_server`
+    pub connector_type: String,
+    /// the current state of the connector
+    pub status: State,
+    /// the current connectivity of the connector
+    pub connectivity: Connectivity,
+}
+
+/// A pipeline within a flow topology
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PipelineNode {
+    /// alias of the pipeline instance
+    pub alias: String,
+    /// the current state of the pipeline
+    pub status: State,
+    /// operators of the pipeline and the connections between them
+    pub graph: GraphTopology,
+}
+
+/// The kind of a node within a flow topology
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    /// the flow itself, used for dead-letter routing via `/flow/err`
+    Flow,
+    /// a connector
+    Connector,
+    /// a pipeline
+    Pipeline,
+}
+
+impl NodeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Flow => "flow",
+            Self::Connector => "connector",
+            Self::Pipeline => "pipeline",
+        }
+    }
+}
+
+/// One side of a connection within a flow topology
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// the kind of the node
+    pub kind: NodeKind,
+    /// alias of the node
+    pub alias: String,
+    /// the connected port
+    pub port: String,
+}
+
+impl Endpoint {
+    fn new(kind: NodeKind, endpoint: &DeployEndpoint) -> Self {
+        Self {
+            kind,
+            alias: endpoint.alias().to_string(),
+            port: endpoint.port().to_string(),
+        }
+    }
+}
+
+/// A connection between two nodes of a flow
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    /// where events are coming from
+    pub from: Endpoint,
+    /// where events are going to
+    pub to: Endpoint,
+}
+
+impl From<&ConnectStmt> for Connection {
+    fn from(connect: &ConnectStmt) -> Self {
+        let (from, to) = match connect {
+            ConnectStmt::ConnectorToPipeline { from, to, .. } => (
+                Endpoint::new(NodeKind::Connector, from),
+                Endpoint::new(NodeKind::Pipeline, to),
+            ),
+            ConnectStmt::PipelineToConnector { from, to, .. } => (
+                Endpoint::new(NodeKind::Pipeline, from),
+                Endpoint::new(NodeKind::Connector, to),
+            ),
+            ConnectStmt::PipelineToPipeline { from, to, .. } => (
+                Endpoint::new(NodeKind::Pipeline, from),
+                Endpoint::new(NodeKind::Pipeline, to),
+            ),
+        };
+        Self { from, to }
+    }
+}
+
+impl Connection {
+    /// the connection of the flow `err` port to the dead-letter connector
+    pub(crate) fn dead_letter(flow_alias: &str, to: &DeployEndpoint) -> Self {
+        Self {
+            from: Endpoint {
+                kind: NodeKind::Flow,
+                alias: flow_alias.to_string(),
+                port: tremor_common::ports::ERR.to_string(),
+            },
+            to: Endpoint::new(NodeKind::Connector, to),
+        }
+    }
+}
+
+/// The instances of a flow, from which its live topology is collected
+#[derive(Debug)]
+pub(crate) struct Instances {
+    pub(crate) alias: String,
+    pub(crate) status: State,
+    pub(crate) connectors: Vec<connectors::Addr>,
+    pub(crate) pipelines: Vec<(String, pipeline::Addr)>,
+    pub(crate) connections: Vec<Connection>,
+}
+
+impl Instances {
+    /// ask every instance for its current state
+    pub(crate) async fn collect(self) -> Result<Topology> {
+        let mut connectors = Vec::with_capacity(self.connectors.len());
+        for addr in &self.connectors {
+            let report = addr.report_status().await?;
+            connectors.push(ConnectorNode {
+                alias: report.alias,
+                connector_type: report.connector_type.to_string(),
+                status: report.status,
+                connectivity: report.connectivity,
+            });
+        }
+        connectors.sort_by(|a, b| a.alias.cmp(&b.alias));
+
+        let mut pipelines = Vec::with_capacity(self.pipelines.len());
+        for (alias, addr) in self.pipelines {
+            let report = addr.report_topology().await?;
+            pipelines.push(PipelineNode {
+                alias,
+                status: report.state,
+                graph: report.graph,
+            });
+        }
+        pipelines.sort_by(|a, b| a.alias.cmp(&b.alias));
+
+        Ok(Topology {
+            alias: self.alias,
+            status: self.status,
+            connectors,
+            pipelines,
+            connections: self.connections,
+        })
+    }
+}
+
+/// quote a string for use as dot id or label, `\n` is kept as line break
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\\\""))
+}
+
+/// turn a path into a valid and unique mermaid id
+///
+/// Parts are joined with `__`, all other characters than ascii alphanumerics are escaped as `_<hex>_`,
+/// so different paths never end up with the same id.
+fn mermaid_id(parts: &[&str]) -> String {
+    parts
+        .iter()
+        .map(|p| {
+            p.chars().fold(String::with_capacity(p.len()), |mut id, c| {
+                if c.is_ascii_alphanumeric() {
+                    id.push(c);
+                } else {
+                    // writing to a String never fails
+                    let _ = write!(id, "_{:x}_", u32::from(c));
+                }
+                id
+            })
+        })
+        .collect::<Vec<_>>()
+        .join("__")
+}
+
+/// quote a string for use as mermaid label
+fn mermaid_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "#quot;"))
+}
+
+/// the id of the node an endpoint refers to, pipeline ports refer to the nodes within the pipeline
+fn endpoint_id(endpoint: &Endpoint) -> Vec<String> {
+    match endpoint.kind {
+        NodeKind::Pipeline => vec![
+            endpoint.kind.as_str().to_string(),
+            endpoint.alias.clone(),
+            endpoint.port.clone(),
+        ],
+        NodeKind::Connector | NodeKind::Flow => {
+            vec![endpoint.kind.as_str().to_string(), endpoint.alias.clone()]
+        }
+    }
+}
+
+impl Topology {
+    /// Render this topology as graphviz dot
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let id = |parts: Vec<String>| dot_quote(&parts.join("/"));
+        let mut dot = String::new();
+        // writing to a String never fails
+        let _ = writeln!(dot, "digraph {} {{", dot_quote(&self.alias));
+        let _ = writeln!(dot, "  rankdir=LR;");
+        let _ = writeln!(
+            dot,
+            "  label={};",
+            dot_quote(&format!("flow {} ({})", self.alias, self.status))
+        );
+        for connector in &self.connectors {
+            let _ = writeln!(
+                dot,
+                "  {} [shape=box, label={}];",
+                id(vec!["connector".to_string(), connector.alias.clone()]),
+                dot_quote(&format!(
+                    "{}\\n{} ({}, {})",
+                    connector.alias,
+                    connector.connector_type,
+                    connector.status,
+                    connectivity_str(connector.connectivity)
+                ))
+            );
+        }
+        for pipeline in &self.pipelines {
+            let node_id = |node: &str| {
+                id(vec![
+                    "pipeline".to_string(),
+                    pipeline.alias.clone(),
+                    node.to_string(),
+                ])
+            };
+            let _ = writeln!(
+                dot,
+                "  subgraph {} {{",
+                dot_quote(&format!("cluster_pipeline_{}", pipeline.alias))
+            );
+            let _ = writeln!(
+                dot,
+                "    label={};",
+                dot_quote(&format!(
+                    "pipeline {} ({})",
+                    pipeline.alias, pipeline.status
+                ))
+            );
+            for node in &pipeline.graph.nodes {
+                let _ = writeln!(
+                    dot,
+                    "    {} [label={}];",
+                    node_id(&node.id),
+                    dot_quote(&format!("{}\\n{}", node.id, node.op_type))
+                );
+            }
+            for edge in &pipeline.graph.edges {
+                let _ = writeln!(
+                    dot,
+                    "    {} -> {} [label={}];",
+                    node_id(&edge.from),
+                    node_id(&edge.to),
+                    dot_quote(&format!("{} -> {}", edge.from_port, edge.to_port))
+                );
+            }
+            let _ = writeln!(dot, "  }}");
+        }
+        for connection in &self.connections {
+            let _ = writeln!(
+                dot,
+                "  {} -> {} [label={}];",
+                id(endpoint_id(&connection.from)),
+                id(endpoint_id(&connection.to)),
+                dot_quote(&format!(
+                    "{} -> {}",
+                    connection.from.port, connection.to.port
+                ))
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render this topology as mermaid flowchart
+    #[must_use]
+    pub fn to_mermaid(&self) -> String {
+        let id =
+            |parts: Vec<String>| mermaid_id(&parts.iter().map(String::as_str).collect::<Vec<_>>());
+        let mut mermaid = String::new();
+        // writing to a String never fails
+        let _ = writeln!(mermaid, "flowchart LR");
+        for connector in &self.connectors {
+            let _ = writeln!(
+                mermaid,
+                "  {}[{}]",
+                id(vec!["connector".to_string(), connector.alias.clone()]),
+                mermaid_quote(&format!(
+                    "{}: {} ({}, {})",
+                    connector.alias,
+                    connector.connector_type,
+                    connector.status,
+                    connectivity_str(connector.connectivity)
+                ))
+            );
+        }
+        for pipeline in &self.pipelines {
+            let node_id = |node: &str| {
+                id(vec![
+                    "pipeline".to_string(),
+                    pipeline.alias.clone(),
+                    node.to_string(),
+                ])
+            };
+            let _ = writeln!(
+                mermaid,
+                "  subgraph {}[{}]",
+                id(vec!["pipeline".to_string(), pipeline.alias.clone()]),
+                mermaid_quote(&format!(
+                    "pipeline {} ({})",
+                    pipeline.alias, pipeline.status
+                ))
+            );
+            for node in &pipeline.graph.nodes {
+                let _ = writeln!(
+                    mermaid,
+                    "    {}[{}]",
+                    node_id(&node.id),
+                    mermaid_quote(&format!("{}: {}", node.id, node.op_type))
+                );
+            }
+            for edge in &pipeline.graph.edges {
+                let _ = writeln!(
+                    mermaid,
+                    "    {} -->|{}| {}",
+                    node_id(&edge.from),
+                    mermaid_quote(&format!("{} -> {}", edge.from_port, edge.to_port)),
+                    node_id(&edge.to),
+                );
+            }
+            let _ = writeln!(mermaid, "  end");
+        }
+        for connection in &self.connections {
+            let _ = writeln!(
+                mermaid,
+                "  {} -->|{}| {}",
+                id(endpoint_id(&connection.from)),
+                mermaid_quote(&format!(
+                    "{} -> {}",
+                    connection.from.port, connection.to.port
+                )),
+                id(endpoint_id(&connection.to)),
+            );
+        }
+        mermaid
+    }
+}
+
+fn connectivity_str(connectivity: Connectivity) -> &'static str {
+    match connectivity {
+        Connectivity::Connected => "connected",
+        Connectivity::Disconnected => "disconnected",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_pipeline::{TopologyEdge, TopologyNode};
+
+    fn topology() -> Topology {
+        let endpoint = |kind, alias: &str, port: &str| Endpoint {
+            kind,
+            alias: alias.to_string(),
+            port: port.to_string(),
+        };
+        let node = |id: &str, kind: &str, op_type: &str| TopologyNode {
+            id: id.to_string(),
+            kind: kind.to_string(),
+            op_type: op_type.to_string(),
+        };
+        let edge = |from: &str, to: &str| TopologyEdge {
+            from: from.to_string(),
+            from_port: "out".to_string(),
+            to: to.to_string(),
+            to_port: "in".to_string(),
+        };
+        Topology {
+            alias: "test".to_string(),
+            status: State::Running,
+            connectors: vec![ConnectorNode {
+                alias: "my-null".to_string(),
+                connector_type: "null".to_string(),
+                status: State::Running,
+                connectivity: Connectivity::Connected,
+            }],
+            pipelines: vec![PipelineNode {
+                alias: "main".to_string(),
+                status: State::Running,
+                graph: GraphTopology {
+                    nodes: vec![
+                        node("in", "input", "passthrough"),
+                        node("select", "select", "trickle::select"),
+                        node("out", "output", "passthrough"),
+                    ],
+                    edges: vec![edge("in", "select"), edge("select", "out")],
+                },
+            }],
+            connections: vec![
+                Connection {
+                    from: endpoint(NodeKind::Connector, "my-null", "out"),
+                    to: endpoint(NodeKind::Pipeline, "main", "in"),
+                },
+                Connection {
+                    from: endpoint(NodeKind::Pipeline, "main", "out"),
+                    to: endpoint(NodeKind::Connector, "my-null", "in"),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn dot() {
+        let dot = topology().to_dot();
+        assert!(dot.starts_with("digraph \"test\" {\n"));
+        assert!(dot.contains(
+            "  \"connector/my-null\" [shape=box, label=\"my-null\\nnull (running, connected)\"];\n"
+        ));
+        assert!(dot.contains("  subgraph \"cluster_pipeline_main\" {\n"));
+        assert!(dot.contains(
+            "    \"pipeline/main/in\" -> \"pipeline/main/select\" [label=\"out -> in\"];\n"
+        ));
+        assert!(dot
+            .contains("  \"connector/my-null\" -> \"pipeline/main/in\" [label=\"out -> in\"];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn mermaid() {
+        let mermaid = topology().to_mermaid();
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(
+            mermaid.contains("  connector__my_2d_null[\"my-null: null (running, connected)\"]\n")
+        );
+        assert!(mermaid.contains("  subgraph pipeline__main[\"pipeline main (running)\"]\n"));
+        assert!(
+            mermaid.contains("    pipeline__main__select -->|\"out -> in\"| pipeline__main__out\n")
+        );
+        assert!(
+            mermaid.contains("  pipeline__main__out -->|\"out -> in\"| connector__my_2d_null\n")
+        );
+    }
+
+    #[test]
+    fn mermaid_ids_are_unique() {
+        let ids = [
+            mermaid_id(&["connector", "my-null"]),
+            mermaid_id(&["connector", "my_null"]),
+            mermaid_id(&["connector", "my__null"]),
+            mermaid_id(&["connector", "my", "null"]),
+            mermaid_id(&["pipeline", "a__b", "c"]),
+            mermaid_id(&["pipeline", "a", "b__c"]),
+            mermaid_id(&["pipeline", "a", "b", "c"]),
+        ];
+        for (i, id) in ids.iter().enumerate() {
+            assert!(id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+            assert!(!ids[i + 1..].contains(id), "duplicate id {id}");
+        }
+    }
+}
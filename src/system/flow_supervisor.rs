@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use super::flow::{Flow, Id};
+use super::shutdown::{self, DrainConfig, FlowDrainReport, ShutdownReport};
 use crate::errors::{Kind as ErrorKind, Result};
 use crate::system::DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT;
 use crate::{
@@ -23,6 +24,7 @@ use async_std::channel::{bounded, Sender};
 use async_std::prelude::*;
 use async_std::task::{self, JoinHandle};
 use hashbrown::{hash_map::Entry, HashMap};
+use std::time::Duration;
 use tremor_common::ids::{ConnectorIdGen, OperatorIdGen};
 use tremor_script::ast::DeployFlow;
 
@@ -46,7 +48,7 @@ pub(crate) enum Msg {
     GetFlows(Sender<Result<Vec<Flow>>>),
     GetFlow(Id, Sender<Result<Flow>>),
     /// Initiate the Quiescence process
-    Drain(Sender<Result<()>>),
+    Drain(Sender<Result<ShutdownReport>>),
     /// stop this manager
     Stop,
 }
@@ -58,16 +60,18 @@ pub(crate) struct FlowSupervisor {
     connector_id_gen: ConnectorIdGen,
     known_connectors: connectors::Known,
     qsize: usize,
+    drain: DrainConfig,
 }
 
 impl FlowSupervisor {
-    pub fn new(qsize: usize) -> Self {
+    pub fn new(qsize: usize, drain: DrainConfig) -> Self {
         Self {
             flows: HashMap::new(),
             known_connectors: connectors::Known::new(),
             operator_id_gen: OperatorIdGen::new(),
             connector_id_gen: ConnectorIdGen::new(),
             qsize,
+            drain,
         }
     }
 
@@ -149,41 +153,39 @@ impl FlowSupervisor {
         }
         Ok(())
     }
-    async fn handle_drain(&self, sender: Sender<Result<()>>) {
-        if self.flows.is_empty() {
-            log_error!(
-                sender.send(Ok(())).await,
-                "Failed to send drain result: {e}"
-            );
-        } else {
-            let num_flows = self.flows.len();
-            info!("Draining all {num_flows} Flows ...");
-            let mut alive_flows = 0_usize;
-            let (tx, rx) = bounded(num_flows);
-            for (_, flow) in &self.flows {
-                if !log_error!(
-                    flow.drain(tx.clone()).await,
-                    "Failed to drain Deployment \"{alias}\": {e}",
-                    alias = flow.alias()
-                ) {
-                    alive_flows += 1;
-                }
+    async fn handle_drain(&self, sender: Sender<Result<ShutdownReport>>) {
+        let num_flows = self.flows.len();
+        info!("Draining all {num_flows} Flows ...");
+        // drain upstream flows first, so downstream flows can still pick up their last events
+        let resources: Vec<_> = self
+            .flows
+            .values()
+            .map(|flow| (flow.alias(), flow.resources()))
+            .collect();
+        let waves: Vec<Vec<Flow>> = shutdown::drain_waves(&resources)
+            .into_iter()
+            .map(|wave| {
+                wave.into_iter()
+                    .filter_map(|alias| self.flows.get(&Id::from(alias)))
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        let config = self.drain.clone();
+        task::spawn::<_, Result<()>>(async move {
+            let mut report = ShutdownReport::default();
+            for wave in waves {
+                let drains = wave
+                    .iter()
+                    .map(|flow| drain_flow(flow, config.timeout_for(flow.alias())));
+                report.flows.extend(futures::future::join_all(drains).await);
             }
-            task::spawn::<_, Result<()>>(async move {
-                let rx_futures = std::iter::repeat_with(|| rx.recv()).take(alive_flows);
-                for result in futures::future::join_all(rx_futures).await {
-                    match result {
-                        Ok(Err(e)) => {
-                            error!("Error during Draining: {}", e);
-                        }
-                        Err(_) | Ok(Ok(())) => {}
-                    }
-                }
+            if report.is_drained() {
                 info!("Flows drained.");
-                sender.send(Ok(())).await?;
-                Ok(())
-            });
-        }
+            }
+            sender.send(Ok(report)).await?;
+            Ok(())
+        });
     }
 
     pub fn start(mut self) -> (JoinHandle<Result<()>>, Channel) {
@@ -214,3 +216,48 @@ impl FlowSupervisor {
         (system_h, tx)
     }
 }
+
+/// drain a single flow within `timeout` and report what could not be drained
+async fn drain_flow(flow: &Flow, timeout: Duration) -> FlowDrainReport {
+    let alias = flow.alias();
+    let (tx, rx) = bounded(1);
+    let timed_out = if log_error!(
+        flow.drain(tx).await,
+        "Failed to drain Flow \"{alias}\": {e}"
+    ) {
+        false
+    } else {
+        match rx.recv().timeout(timeout).await {
+            Ok(Ok(Err(e))) => {
+                error!("Error during Draining Flow \"{alias}\": {e}");
+                false
+            }
+            Ok(_) => false,
+            Err(_) => {
+                warn!(
+                    "Timeout draining Flow \"{alias}\" after {}ms",
+                    timeout.as_millis()
+                );
+                true
+            }
+        }
+    };
+    let status = flow
+        .drain_status()
+        .timeout(timeout)
+        .await
+        .unwrap_or_else(|e| Err(e.into()));
+    match status {
+        Ok(report) => FlowDrainReport {
+            timed_out,
+            ..report
+        },
+        Err(e) => {
+            error!("Error getting drain status of Flow \"{alias}\": {e}");
+            FlowDrainReport {
+                timed_out,
+                ..FlowDrainReport::new(alias)
+            }
+        }
+    }
+}
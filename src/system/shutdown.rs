@@ -0,0 +1,254 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Graceful shutdown
+//!
+//! Flows are drained in waves. Flows writing to an external resource, like a `wal` or `kv` directory,
+//! that another flow reads from are drained before the reading flow, so their last events can still be picked up.
+//! Every flow is given its own drain timeout. Flows that did not drain in time are reported
+//! with their undrained connectors and the number of messages still queued within the flow.
+
+use crate::connectors::ConnectorType;
+use hashbrown::{HashMap, HashSet};
+use std::time::Duration;
+use tremor_pipeline::ConfigMap;
+use tremor_script::ast::ConnectStmt;
+use value_trait::ValueAccess;
+
+use super::DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT;
+
+/// config keys of connectors pointing to external resources that can be shared between flows
+const RESOURCE_KEYS: [&str; 2] = ["dir", "path"];
+
+/// Configuration of how flows are drained during a graceful shutdown
+#[derive(Debug, Clone)]
+pub struct DrainConfig {
+    /// drain timeout for all flows without a specific timeout
+    pub timeout: Duration,
+    /// drain timeouts for specific flows, by flow alias
+    pub flow_timeouts: HashMap<String, Duration>,
+}
+
+impl Default for DrainConfig {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT,
+            flow_timeouts: HashMap::new(),
+        }
+    }
+}
+
+impl DrainConfig {
+    /// the drain timeout for the flow with the given alias
+    #[must_use]
+    pub fn timeout_for(&self, flow: &str) -> Duration {
+        self.flow_timeouts
+            .get(flow)
+            .copied()
+            .unwrap_or(self.timeout)
+    }
+}
+
+/// Outcome of draining a single flow
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FlowDrainReport {
+    /// alias of the flow
+    pub flow: String,
+    /// true if the flow did not drain within its timeout
+    pub timed_out: bool,
+    /// connectors that did not drain in time or failed to drain
+    pub undrained_connectors: Vec<String>,
+    /// number of messages still queued within the flow, these are abandoned on shutdown
+    pub abandoned_messages: usize,
+}
+
+impl FlowDrainReport {
+    /// an empty report for the given flow
+    pub(crate) fn new(flow: &str) -> Self {
+        Self {
+            flow: flow.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// true if everything within the flow has been drained
+    #[must_use]
+    pub fn is_drained(&self) -> bool {
+        !self.timed_out && self.undrained_connectors.is_empty()
+    }
+}
+
+/// Outcome of draining all flows
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// reports for all flows, in the order they have been drained
+    pub flows: Vec<FlowDrainReport>,
+}
+
+impl ShutdownReport {
+    /// true if all flows have been drained
+    #[must_use]
+    pub fn is_drained(&self) -> bool {
+        self.flows.iter().all(FlowDrainReport::is_drained)
+    }
+
+    /// total number of messages abandoned in all flows
+    #[must_use]
+    pub fn abandoned_messages(&self) -> usize {
+        self.flows.iter().map(|f| f.abandoned_messages).sum()
+    }
+}
+
+/// How a flow uses external resources shared with other flows
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Resources {
+    /// resources written to by sinks of the flow
+    writes: HashSet<String>,
+    /// resources read from by sources of the flow
+    reads: HashSet<String>,
+}
+
+impl Resources {
+    /// record the resource used by a connector, depending on how it is connected
+    pub(crate) fn add(
+        &mut self,
+        alias: &str,
+        connector_type: &ConnectorType,
+        config: &ConfigMap,
+        connections: &[ConnectStmt],
+    ) {
+        let location = config.as_ref().and_then(|config| {
+            RESOURCE_KEYS
+                .iter()
+                .find_map(|key| config.get_str(key))
+                .map(|location| format!("{connector_type}:{location}"))
+        });
+        if let Some(location) = location {
+            for connect in connections {
+                match connect {
+                    ConnectStmt::ConnectorToPipeline { from, .. } if from.alias() == alias => {
+                        self.reads.insert(location.clone());
+                    }
+                    ConnectStmt::PipelineToConnector { to, .. } if to.alias() == alias => {
+                        self.writes.insert(location.clone());
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// true if `self` writes to a resource `other` reads from
+    fn feeds(&self, other: &Self) -> bool {
+        !self.writes.is_disjoint(&other.reads)
+    }
+}
+
+/// Order flows into waves to drain, upstream flows come first.
+///
+/// Flows within a wave do not depend on each other and can be drained concurrently.
+/// Flows depending on each other in a cycle are drained together in the last wave.
+pub(crate) fn drain_waves<'flow>(flows: &[(&'flow str, &Resources)]) -> Vec<Vec<&'flow str>> {
+    let mut remaining: Vec<_> = flows.to_vec();
+    let mut waves = Vec::new();
+    while !remaining.is_empty() {
+        // flows not fed by any other remaining flow
+        let (wave, rest): (Vec<_>, Vec<_>) =
+            remaining.iter().copied().partition(|(alias, resources)| {
+                !remaining
+                    .iter()
+                    .any(|(other, upstream)| other != alias && upstream.feeds(resources))
+            });
+        if wave.is_empty() {
+            warn!("Cyclic dependencies between flows, draining them together.");
+            waves.push(remaining.iter().map(|(alias, _)| *alias).collect());
+            break;
+        }
+        waves.push(wave.iter().map(|(alias, _)| *alias).collect());
+        remaining = rest;
+    }
+    waves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resources(writes: &[&str], reads: &[&str]) -> Resources {
+        Resources {
+            writes: writes.iter().map(ToString::to_string).collect(),
+            reads: reads.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn drain_waves_ordering() {
+        let independent = resources(&[], &[]);
+        let producer = resources(&["wal:/tmp/wal"], &[]);
+        let relay = resources(&["kv:/tmp/kv"], &["wal:/tmp/wal"]);
+        let consumer = resources(&[], &["kv:/tmp/kv"]);
+        let waves = drain_waves(&[
+            ("consumer", &consumer),
+            ("independent", &independent),
+            ("relay", &relay),
+            ("producer", &producer),
+        ]);
+        assert_eq!(
+            vec![
+                vec!["independent", "producer"],
+                vec!["relay"],
+                vec!["consumer"]
+            ],
+            waves
+        );
+    }
+
+    #[test]
+    fn drain_waves_cycle() {
+        let a = resources(&["wal:a"], &["wal:b"]);
+        let b = resources(&["wal:b"], &["wal:a"]);
+        let c = resources(&[], &[]);
+        let waves = drain_waves(&[("a", &a), ("b", &b), ("c", &c)]);
+        assert_eq!(vec![vec!["c"], vec!["a", "b"]], waves);
+    }
+
+    #[test]
+    fn drain_config() {
+        let mut config = DrainConfig::default();
+        config
+            .flow_timeouts
+            .insert("slow".to_string(), Duration::from_secs(30));
+        assert_eq!(Duration::from_secs(30), config.timeout_for("slow"));
+        assert_eq!(
+            DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT,
+            config.timeout_for("fast")
+        );
+    }
+
+    #[test]
+    fn shutdown_report() {
+        let mut report = ShutdownReport {
+            flows: vec![FlowDrainReport::new("a")],
+        };
+        assert!(report.is_drained());
+        report.flows.push(FlowDrainReport {
+            flow: "b".to_string(),
+            timed_out: true,
+            undrained_connectors: vec!["out".to_string()],
+            abandoned_messages: 3,
+        });
+        assert!(!report.is_drained());
+        assert_eq!(3, report.abandoned_messages());
+    }
+}
@@ -19,7 +19,7 @@ use tremor_script::FN_REGISTRY;
 use serial_test::serial;
 use tremor_runtime::errors::*;
 use tremor_script::highlighter::{Dumb, Highlighter};
-use tremor_script::{module::Manager, Script};
+use tremor_script::{module::Manager, set_type_check, Script, TypeCheck};
 
 macro_rules! test_cases {
     ($($file:ident),* ,) => {
@@ -29,6 +29,7 @@ macro_rules! test_cases {
             fn $file() -> Result<()> {
 
                 tremor_runtime::functions::load()?;
+                set_type_check(TypeCheck::Warn);
                 let script_dir = concat!("tests/script_warnings/", stringify!($file), "/").to_string();
                 let script_file = concat!("tests/script_warnings/", stringify!($file), "/script.tremor");
                 let err_file = concat!("tests/script_warnings/", stringify!($file), "/warning.txt");
@@ -65,6 +66,11 @@ test_cases!(
     match_imut_no_default,
     match_imut_multiple_default,
     // INSERT
+    unreachable_case,
+    always_true_guard,
+    unused_let,
+    shadowed_local,
+    type_mismatch,
     recordpattern_absence_and_extractor,
     recordpattern_presence_and_extractor,
 );
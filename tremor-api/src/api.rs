@@ -24,10 +24,13 @@ use http_types::{
 };
 use serde::{Deserialize, Serialize};
 use tide::Response;
+use tide_rustls::TlsListener;
 use tremor_runtime::system::World;
 
 pub mod flow;
 pub mod prelude;
+pub mod profile;
+pub mod security;
 pub mod status;
 pub mod version;
 
@@ -48,6 +51,9 @@ pub enum ResourceType {
     Yaml,
     Trickle,
     Troy,
+    Dot,
+    Mermaid,
+    Text,
 }
 
 impl std::fmt::Display for ResourceType {
@@ -63,6 +69,9 @@ impl ResourceType {
             Self::Json => "application/json",
             Self::Trickle => "application/vnd.trickle",
             Self::Troy => "application/vnd.troy",
+            Self::Dot => "text/vnd.graphviz",
+            Self::Mermaid => "text/vnd.mermaid",
+            Self::Text => "text/plain",
         }
     }
 }
@@ -101,6 +110,9 @@ pub fn accept(req: &Request) -> ResourceType {
         Some("application/yaml") => ResourceType::Yaml,
         Some("application/vnd.trickle") => ResourceType::Trickle,
         Some("application/vnd.troy") => ResourceType::Troy,
+        Some("text/vnd.graphviz") => ResourceType::Dot,
+        Some("text/vnd.mermaid") => ResourceType::Mermaid,
+        Some("text/plain") => ResourceType::Text,
         _ => ResourceType::Json,
     }
 }
@@ -127,7 +139,11 @@ pub fn serialize_error(t: ResourceType, d: Error) -> Result<Response> {
         ResourceType::Json | ResourceType::Yaml => serialize(t, &d, d.code),
         // formatting errors as trickle does not make sense so for this
         // fall back to the error's conversion into tide response
-        ResourceType::Trickle | ResourceType::Troy => Ok(d.into()),
+        ResourceType::Trickle
+        | ResourceType::Troy
+        | ResourceType::Dot
+        | ResourceType::Mermaid
+        | ResourceType::Text => Ok(d.into()),
     }
 }
 
@@ -170,11 +186,17 @@ async fn handle_api_request<
 }
 
 /// server the tremor API in a separately spawned task
+///
+/// Depending on the given `security` config the API is served via https and
+/// requests are authenticated and authorized before they reach any route.
 #[must_use]
-pub fn serve(host: String, world: &World) -> JoinHandle<Result<()>> {
+pub fn serve(host: String, world: &World, security: security::Config) -> JoinHandle<Result<()>> {
     let mut v1_app = tide::Server::with_state(State {
         world: world.clone(),
     });
+    if security.requires_auth() {
+        v1_app.with(security::Authenticator::new(security.clone()));
+    }
     v1_app
         .at("/version")
         .get(|r| handle_api_request(r, version::get));
@@ -188,6 +210,9 @@ pub fn serve(host: String, world: &World) -> JoinHandle<Result<()>> {
         .at("/flows/:id")
         .get(|r| handle_api_request(r, flow::get_flow))
         .patch(|r| handle_api_request(r, flow::patch_flow_status));
+    v1_app
+        .at("/flows/:id/topology")
+        .get(|r| handle_api_request(r, flow::get_flow_topology));
     v1_app
         .at("/flows/:id/connectors")
         .get(|r| handle_api_request(r, flow::get_flow_connectors));
@@ -195,13 +220,27 @@ pub fn serve(host: String, world: &World) -> JoinHandle<Result<()>> {
         .at("/flows/:id/connectors/:connector")
         .get(|r| handle_api_request(r, flow::get_flow_connector_status))
         .patch(|r| handle_api_request(r, flow::patch_flow_connector_status));
+    v1_app
+        .at("/flows/:id/profile")
+        .get(|r| handle_api_request(r, profile::get_flow_profile));
+    v1_app
+        .at("/profile")
+        .get(|r| handle_api_request(r, profile::get_profile))
+        .patch(|r| handle_api_request(r, profile::patch_profile))
+        .delete(|r| handle_api_request(r, profile::delete_profile));
 
     let mut app = tide::Server::new();
     app.at("/v1").nest(v1_app);
 
     // spawn API listener
     async_std::task::spawn(async move {
-        let res = app.listen(host).await;
+        let res = if let Some(tls) = security.tls.as_ref() {
+            let server_config = tls.server_config()?;
+            app.listen(TlsListener::build().addrs(&host).config(server_config))
+                .await
+        } else {
+            app.listen(host).await
+        };
         warn!("API stopped.");
         if let Err(e) = res {
             error!("API Error: {}", e);
@@ -240,6 +279,7 @@ mod tests {
         let config = WorldConfig {
             qsize: 16,
             debug_connectors: true,
+            ..WorldConfig::default()
         };
         let (world, world_handle) = World::start(config).await?;
 
@@ -250,7 +290,7 @@ mod tests {
             port
         };
         let host = format!("127.0.0.1:{free_port}");
-        let api_handle = serve(host.clone(), &world);
+        let api_handle = serve(host.clone(), &world, security::Config::default());
         info!("Listening on: {}", host);
 
         let src = r#"
@@ -407,6 +447,7 @@ mod tests {
             literal!([
                 {
                     "alias": "my_null",
+                    "connector_type": "null",
                     "status": "running",
                     "connectivity": "connected",
                     "pipelines": {
@@ -444,6 +485,7 @@ mod tests {
         assert_eq!(
             literal!({
                 "alias": "my_null",
+                "connector_type": "null",
                 "status": "running",
                 "connectivity": "connected",
                 "pipelines": {
@@ -478,6 +520,7 @@ mod tests {
         assert_eq!(
             literal!({
                 "alias": "my_null",
+                "connector_type": "null",
                 "status": "paused",
                 "connectivity": "connected",
                 "pipelines": {
@@ -521,6 +564,7 @@ mod tests {
         assert_eq!(
             literal!({
                 "alias": "my_null",
+                "connector_type": "null",
                 "status": "running",
                 "connectivity": "connected",
                 "pipelines": {
@@ -541,6 +585,95 @@ mod tests {
             body
         );
 
+        // get the flow topology
+        let body = client
+            .get("/v1/flows/api_test/topology")
+            .await?
+            .body_json::<StaticValue>()
+            .await?
+            .into_value();
+        assert_eq!(Some("api_test"), body.get_str("alias"));
+        assert_eq!(
+            Some(&literal!([{
+                "alias": "my_null",
+                "connector_type": "null",
+                "status": "running",
+                "connectivity": "connected"
+            }])),
+            body.get("connectors")
+        );
+        assert_eq!(
+            Some("main"),
+            body.get("pipelines")
+                .and_then(|p| p.get_idx(0))
+                .and_then(|p| p.get_str("alias"))
+        );
+        assert_eq!(2, body.get_array("connections").map_or(0, Vec::len));
+
+        let mut res = client
+            .get("/v1/flows/api_test/topology")
+            .header(headers::ACCEPT, ResourceType::Dot.as_str())
+            .await?;
+        assert_eq!(
+            Some("text/vnd.graphviz"),
+            res.content_type().as_ref().map(|m| m.essence())
+        );
+        let dot = res.body_string().await?;
+        assert!(dot.starts_with("digraph \"api_test\" {"));
+
+        let mut res = client
+            .get("/v1/flows/api_test/topology")
+            .header(headers::ACCEPT, ResourceType::Mermaid.as_str())
+            .await?;
+        let mermaid = res.body_string().await?;
+        assert!(mermaid.starts_with("flowchart LR"));
+
+        // check the profiler endpoints
+        let body = client
+            .patch("/v1/profile")
+            .body_json(&literal!({"enabled": true}))?
+            .await?
+            .body_json::<StaticValue>()
+            .await?
+            .into_value();
+        assert_eq!(Some(true), body.get_bool("enabled"));
+        let mut res = client
+            .get("/v1/profile")
+            .header(headers::ACCEPT, ResourceType::Text.as_str())
+            .await?;
+        assert_eq!(StatusCode::Ok, res.status());
+        assert_eq!(
+            Some("text/plain"),
+            res.content_type().as_ref().map(|m| m.essence())
+        );
+        res.body_string().await?;
+        let body = client
+            .get("/v1/flows/api_test/profile")
+            .await?
+            .body_json::<StaticValue>()
+            .await?
+            .into_value();
+        assert_eq!(Some(true), body.get_bool("enabled"));
+        assert!(body.get_object("stacks").is_some());
+        let mut res = client
+            .get("/v1/flows/api_test/profile")
+            .header(headers::ACCEPT, ResourceType::Text.as_str())
+            .await?;
+        assert_eq!(StatusCode::Ok, res.status());
+        res.body_string().await?;
+        let res = client.get("/v1/flows/i_do_not_exist/profile").await?;
+        assert_eq!(StatusCode::NotFound, res.status());
+        let body = client
+            .patch("/v1/profile")
+            .body_json(&literal!({"enabled": false}))?
+            .await?
+            .body_json::<StaticValue>()
+            .await?
+            .into_value();
+        assert_eq!(Some(false), body.get_bool("enabled"));
+        let res = client.delete("/v1/profile").await?;
+        assert_eq!(StatusCode::Ok, res.status());
+
         // cleanup
         world.stop(ShutdownMode::Graceful).await?;
         world_handle.cancel().await;
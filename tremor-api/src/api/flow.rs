@@ -15,6 +15,7 @@
 //! Flow API
 
 use crate::api::prelude::*;
+use http_types::headers;
 use tremor_runtime::instance::State;
 
 pub(crate) async fn list_flows(req: Request) -> Result<Response> {
@@ -36,6 +37,25 @@ pub(crate) async fn get_flow(req: Request) -> Result<Response> {
     reply(&req, report, StatusCode::Ok)
 }
 
+pub(crate) async fn get_flow_topology(req: Request) -> Result<Response> {
+    let world = &req.state().world;
+    let flow_id = req.param("id")?.to_string();
+    let flow = world.get_flow(flow_id).await?;
+    let topology = flow.report_topology().await?;
+    match accept(&req) {
+        t @ ResourceType::Dot => Ok(text(t, topology.to_dot())),
+        t @ ResourceType::Mermaid => Ok(text(t, topology.to_mermaid())),
+        _ => reply(&req, topology, StatusCode::Ok),
+    }
+}
+
+fn text(t: ResourceType, body: String) -> Response {
+    Response::builder(StatusCode::Ok)
+        .header(headers::CONTENT_TYPE, t.as_str())
+        .body(body)
+        .build()
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct PatchStatus {
     pub(crate) status: State,
@@ -0,0 +1,95 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Profiler API
+//!
+//! Profiling covers every script and pipeline operator of all running flows,
+//! the samples of a single flow are available under `/flows/:id/profile`.
+//! Requesting `text/plain` returns the samples as folded stacks that can be
+//! fed to flamegraph tooling directly.
+
+use crate::api::prelude::*;
+use http_types::headers;
+use std::collections::BTreeMap;
+use tremor_script::profile::{self, Sample};
+
+#[derive(Serialize, Debug)]
+pub(crate) struct Profile {
+    enabled: bool,
+    stacks: BTreeMap<String, Sample>,
+}
+
+impl Profile {
+    fn collect() -> Result<Self> {
+        Ok(Self {
+            enabled: profile::is_enabled(),
+            stacks: profile::stacks()?,
+        })
+    }
+
+    fn collect_flow(flow: &str) -> Result<Self> {
+        Ok(Self {
+            enabled: profile::is_enabled(),
+            stacks: profile::flow_stacks(flow)?,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct PatchProfile {
+    enabled: bool,
+}
+
+// ALLOW: We allow this since it's required for generalizing accept fuinctions
+#[allow(clippy::unused_async)]
+pub(crate) async fn get_profile(req: Request) -> Result<Response> {
+    match accept(&req) {
+        t @ ResourceType::Text => Ok(Response::builder(StatusCode::Ok)
+            .header(headers::CONTENT_TYPE, t.as_str())
+            .body(profile::folded()?)
+            .build()),
+        _ => reply(&req, Profile::collect()?, StatusCode::Ok),
+    }
+}
+
+pub(crate) async fn get_flow_profile(req: Request) -> Result<Response> {
+    let world = &req.state().world;
+    let flow_id = req.param("id")?.to_string();
+    // only report profiles of deployed flows
+    world.get_flow(flow_id.clone()).await?;
+    match accept(&req) {
+        t @ ResourceType::Text => Ok(Response::builder(StatusCode::Ok)
+            .header(headers::CONTENT_TYPE, t.as_str())
+            .body(profile::flow_folded(&flow_id)?)
+            .build()),
+        _ => reply(&req, Profile::collect_flow(&flow_id)?, StatusCode::Ok),
+    }
+}
+
+pub(crate) async fn patch_profile(mut req: Request) -> Result<Response> {
+    let patch: PatchProfile = req.body_json().await?;
+    if patch.enabled {
+        profile::enable();
+    } else {
+        profile::disable();
+    }
+    reply(&req, Profile::collect()?, StatusCode::Ok)
+}
+
+// ALLOW: We allow this since it's required for generalizing accept fuinctions
+#[allow(clippy::unused_async)]
+pub(crate) async fn delete_profile(req: Request) -> Result<Response> {
+    profile::reset()?;
+    reply(&req, Profile::collect()?, StatusCode::Ok)
+}
@@ -0,0 +1,323 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transport security, authentication and authorization for the API
+
+use crate::api::{accept, serialize_error, State};
+use crate::errors::Error;
+use http_types::{headers, Method, StatusCode};
+use rustls::{AllowAnyAuthenticatedClient, ServerConfig};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tide::{Middleware, Next, Request};
+use tremor_runtime::connectors::utils::tls::{load_certs, load_keys, load_root_cert_store};
+use tremor_runtime::errors::Error as TremorError;
+
+/// Security configuration of the API
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// TLS configuration, if set the API is served via https
+    #[serde(default)]
+    pub tls: Option<Tls>,
+    /// Bearer tokens accepted by the API and the role they grant
+    #[serde(default)]
+    pub tokens: Vec<Token>,
+}
+
+impl Config {
+    /// Load the configuration from the given yaml file
+    pub fn from_file(path: &str) -> crate::Result<Self> {
+        let file = tremor_common::file::open(path)?;
+        Ok(serde_yaml::from_reader(file)?)
+    }
+
+    /// Returns true if requests need to be authenticated
+    #[must_use]
+    pub fn requires_auth(&self) -> bool {
+        !self.tokens.is_empty() || self.client_cert_role().is_some()
+    }
+
+    /// The role granted to clients that authenticated via a client certificate
+    fn client_cert_role(&self) -> Option<Role> {
+        self.tls
+            .as_ref()
+            .and_then(|tls| tls.client_ca.as_ref().map(|_| tls.client_cert_role))
+    }
+}
+
+/// TLS configuration of the API
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tls {
+    /// Path to the pem-encoded server certificate (-chain)
+    pub cert: PathBuf,
+    /// Path to the pem-encoded server private key
+    pub key: PathBuf,
+    /// Path to the pem-encoded CA certificates used to verify client certificates.
+    /// If set, clients are required to present a valid certificate (mTLS).
+    #[serde(default)]
+    pub client_ca: Option<PathBuf>,
+    /// The role granted to clients presenting a valid certificate but no bearer token
+    #[serde(default = "Role::read_only")]
+    pub client_cert_role: Role,
+}
+
+impl Tls {
+    /// Build the rustls server config
+    pub(crate) fn server_config(&self) -> crate::Result<ServerConfig> {
+        let tls_error = |e: TremorError| {
+            Error::new(
+                StatusCode::InternalServerError,
+                format!("Invalid API TLS configuration: {e}"),
+            )
+        };
+        let mut server_config = if let Some(client_ca) = self.client_ca.as_ref() {
+            let roots = load_root_cert_store(client_ca).map_err(tls_error)?;
+            ServerConfig::new(AllowAnyAuthenticatedClient::new(roots))
+        } else {
+            ServerConfig::new(rustls::NoClientAuth::new())
+        };
+        let certs = load_certs(&self.cert).map_err(tls_error)?;
+        let key = load_keys(&self.key).map_err(tls_error)?;
+        server_config
+            .set_single_cert(certs, key)
+            .map_err(|e| tls_error(e.into()))?;
+        Ok(server_config)
+    }
+}
+
+/// A bearer token accepted by the API
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Token {
+    /// The secret token value, as sent in the `Authorization: Bearer <token>` header
+    pub token: String,
+    /// The role granted when presenting this token
+    pub role: Role,
+}
+
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // never leak the secret into logs
+        f.debug_struct("Token")
+            .field("token", &"<redacted>")
+            .field("role", &self.role)
+            .finish()
+    }
+}
+
+/// Role of an API client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// may only issue reading requests (`GET`, `HEAD`, `OPTIONS`)
+    ReadOnly,
+    /// may issue all requests, including `PATCH` and `POST`
+    Admin,
+}
+
+impl Role {
+    fn read_only() -> Self {
+        Self::ReadOnly
+    }
+
+    /// The role required for issuing a request with the given method
+    fn required_for(method: Method) -> Self {
+        match method {
+            Method::Get | Method::Head | Method::Options => Self::ReadOnly,
+            _ => Self::Admin,
+        }
+    }
+}
+
+/// compare two byte slices in constant time (with respect to their content)
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0_u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Outcome of authenticating a request
+#[derive(Debug, PartialEq, Eq)]
+enum Auth {
+    Granted(Role),
+    Missing,
+    Invalid,
+}
+
+/// Middleware authenticating and authorizing API requests
+#[derive(Debug, Clone)]
+pub(crate) struct Authenticator {
+    config: Arc<Config>,
+}
+
+impl Authenticator {
+    pub(crate) fn new(config: Config) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+
+    fn authenticate(&self, authorization: Option<&str>) -> Auth {
+        if !self.config.requires_auth() {
+            return Auth::Granted(Role::Admin);
+        }
+        match authorization {
+            Some(value) => {
+                let token = value
+                    .strip_prefix("Bearer ")
+                    .or_else(|| value.strip_prefix("bearer "))
+                    .map(str::trim);
+                token
+                    .and_then(|token| {
+                        self.config
+                            .tokens
+                            .iter()
+                            .find(|t| constant_time_eq(t.token.as_bytes(), token.as_bytes()))
+                    })
+                    .map_or(Auth::Invalid, |t| Auth::Granted(t.role))
+            }
+            // only authenticated clients can establish a connection if client certificates are required
+            None => self
+                .config
+                .client_cert_role()
+                .map_or(Auth::Missing, Auth::Granted),
+        }
+    }
+}
+
+#[tide::utils::async_trait]
+impl Middleware<State> for Authenticator {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let auth = self.authenticate(
+            req.header(headers::AUTHORIZATION)
+                .map(headers::HeaderValues::last)
+                .map(headers::HeaderValue::as_str),
+        );
+        let error = match auth {
+            Auth::Granted(role) if role >= Role::required_for(req.method()) => {
+                return Ok(next.run(req).await);
+            }
+            Auth::Granted(role) => Error::new(
+                StatusCode::Forbidden,
+                format!(
+                    "Role {role:?} is not allowed to issue {} requests",
+                    req.method()
+                ),
+            ),
+            Auth::Missing => Error::new(StatusCode::Unauthorized, "Missing credentials".into()),
+            Auth::Invalid => Error::new(StatusCode::Unauthorized, "Invalid credentials".into()),
+        };
+        warn!(
+            "[API {} {}] Rejected: {error}",
+            req.method(),
+            req.url().path()
+        );
+        let code = error.code;
+        let mut res = serialize_error(accept(&req), error).unwrap_or_else(Into::into);
+        if code == StatusCode::Unauthorized {
+            res.insert_header(headers::WWW_AUTHENTICATE, "Bearer");
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(client_ca: bool) -> Config {
+        Config {
+            tls: Some(Tls {
+                cert: PathBuf::from("cert.pem"),
+                key: PathBuf::from("key.pem"),
+                client_ca: client_ca.then(|| PathBuf::from("ca.pem")),
+                client_cert_role: Role::ReadOnly,
+            }),
+            tokens: vec![
+                Token {
+                    token: "snot".to_string(),
+                    role: Role::Admin,
+                },
+                Token {
+                    token: "badger".to_string(),
+                    role: Role::ReadOnly,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn no_auth() {
+        let auth = Authenticator::new(Config::default());
+        assert_eq!(Auth::Granted(Role::Admin), auth.authenticate(None));
+        assert_eq!(
+            Auth::Granted(Role::Admin),
+            auth.authenticate(Some("Bearer snot"))
+        );
+    }
+
+    #[test]
+    fn bearer_tokens() {
+        let auth = Authenticator::new(config(false));
+        assert_eq!(Auth::Missing, auth.authenticate(None));
+        assert_eq!(
+            Auth::Granted(Role::Admin),
+            auth.authenticate(Some("Bearer snot"))
+        );
+        assert_eq!(
+            Auth::Granted(Role::ReadOnly),
+            auth.authenticate(Some("Bearer badger"))
+        );
+        assert_eq!(Auth::Invalid, auth.authenticate(Some("Bearer snotbadger")));
+        assert_eq!(Auth::Invalid, auth.authenticate(Some("Basic snot")));
+    }
+
+    #[test]
+    fn client_certificates() {
+        let auth = Authenticator::new(config(true));
+        assert_eq!(Auth::Granted(Role::ReadOnly), auth.authenticate(None));
+        assert_eq!(
+            Auth::Granted(Role::Admin),
+            auth.authenticate(Some("Bearer snot"))
+        );
+        assert_eq!(Auth::Invalid, auth.authenticate(Some("Bearer nope")));
+    }
+
+    #[test]
+    fn roles() {
+        assert!(Role::ReadOnly >= Role::required_for(Method::Get));
+        assert!(Role::ReadOnly < Role::required_for(Method::Patch));
+        assert!(Role::Admin >= Role::required_for(Method::Post));
+    }
+
+    #[test]
+    fn config_from_yaml() -> crate::Result<()> {
+        let config: Config = serde_yaml::from_str(
+            r#"
+tls:
+  cert: cert.pem
+  key: key.pem
+  client_ca: ca.pem
+tokens:
+  - token: snot
+    role: admin
+"#,
+        )?;
+        assert!(config.requires_auth());
+        assert_eq!(Some(Role::ReadOnly), config.client_cert_role());
+        assert_eq!(Role::Admin, config.tokens[0].role);
+        Ok(())
+    }
+}
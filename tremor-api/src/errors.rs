@@ -121,6 +121,15 @@ impl From<tremor_pipeline::errors::Error> for Error {
     }
 }
 
+impl From<tremor_script::errors::Error> for Error {
+    fn from(e: tremor_script::errors::Error) -> Self {
+        Self::new(
+            StatusCode::InternalServerError,
+            format!("Script error: {}", e),
+        )
+    }
+}
+
 impl From<PoisonError<MutexGuard<'_, tremor_script::Registry>>> for Error {
     fn from(e: PoisonError<MutexGuard<tremor_script::Registry>>) -> Self {
         Self::new(
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use clap::{ArgEnum, Parser};
+use tremor_script::TypeCheck;
 
 /// Tremor cli - Command Line Interface
 #[derive(Parser, Debug)]
@@ -48,6 +49,16 @@ pub(crate) enum Command {
     Run(Run),
     /// Generates documention from tremor script files
     Doc(Doc),
+    /// Formats tremor-script, trickle and troy sources canonically, keeping comments
+    Fmt(Fmt),
+    /// Lints tremor-script, trickle and troy sources
+    Lint(Lint),
+    /// Runs the language server for tremor-script, trickle and troy on stdin and stdout
+    #[cfg(feature = "lsp")]
+    Lsp,
+    /// Interactive shell for tremor-script and trickle
+    #[cfg(feature = "repl")]
+    Repl(Repl),
     /// Creates a template tremor project
     New { name: String },
 }
@@ -92,6 +103,13 @@ pub(crate) struct Test {
     /// Timeout in seconds for each test
     #[clap(short, long)]
     pub(crate) timeout: Option<u64>,
+    /// Static type checking of scripts: `off`, `warn` or `strict`
+    #[clap(long, default_value = "off")]
+    pub(crate) type_check: TypeCheck,
+    /// Writes line and branch coverage of scripts and queries to the given
+    /// path in lcov format
+    #[clap(long)]
+    pub(crate) coverage: Option<String>,
 }
 
 /// Shell type
@@ -107,6 +125,10 @@ pub(crate) enum TestMode {
     Integration,
     /// Run tremor script unit tests
     Unit,
+    /// Run in-process pipeline tests against fixtures
+    Pipeline,
+    /// Run property based tests of tremor scripts
+    Property,
 }
 
 impl ToString for TestMode {
@@ -116,6 +138,8 @@ impl ToString for TestMode {
             TestMode::Integration => "integration".to_string(),
             TestMode::Command => "command".to_string(),
             TestMode::Unit => "unit".to_string(),
+            TestMode::Pipeline => "pipeline".to_string(),
+            TestMode::Property => "property".to_string(),
             TestMode::All => "all".to_string(),
         }
     }
@@ -137,6 +161,53 @@ pub(crate) struct Doc {
     pub(crate) outdir: String,
 }
 
+#[derive(Parser, Debug)]
+pub(crate) struct Fmt {
+    /// Files or directories to format
+    #[clap(default_value = ".")]
+    pub(crate) paths: Vec<String>,
+    /// Only check if the sources are formatted, exits with an error if they are not
+    #[clap(long)]
+    pub(crate) check: bool,
+}
+
+#[derive(Parser, Debug)]
+pub(crate) struct Lint {
+    /// Files or directories to lint
+    #[clap(default_value = ".")]
+    pub(crate) paths: Vec<String>,
+    /// Output format
+    #[clap(long, arg_enum, default_value_t)]
+    pub(crate) format: LintFormat,
+    /// Lint configuration, defaults to `tremor-lint.yaml` in the working directory if present
+    #[clap(short, long)]
+    pub(crate) config: Option<String>,
+}
+
+/// Output format of `tremor lint`
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LintFormat {
+    /// Human readable, one line per finding
+    Text,
+    /// A JSON array of findings
+    Json,
+    /// SARIF 2.1.0 for code scanning tools
+    Sarif,
+}
+
+impl Default for LintFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+#[derive(Parser, Debug)]
+pub(crate) struct Repl {
+    /// Start in query mode, evaluating input as trickle statements
+    #[clap(long)]
+    pub(crate) query: bool,
+}
+
 #[derive(Parser, Debug)]
 pub(crate) struct Run {
     /// filename to run the data through
@@ -169,6 +240,14 @@ pub(crate) struct Run {
 
     #[clap(short, long)]
     pub(crate) port: Option<String>,
+    /// Waits for a debug adapter protocol client on the given port and lets it
+    /// step through the script [ tremor scripts only ]
+    #[clap(long)]
+    pub(crate) debug: Option<u16>,
+    /// Profiles the script, query or deployment and writes the collected
+    /// samples as folded stacks to the given file
+    #[clap(long)]
+    pub(crate) profile: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -249,9 +328,21 @@ pub(crate) struct ServerRun {
     /// The `host:port` to listen for the API
     #[clap(short, long, default_value = "0.0.0.0:9898")]
     pub(crate) api_host: String,
+    /// Path to a yaml file configuring TLS and authentication for the API
+    #[clap(long)]
+    pub(crate) api_security: Option<String>,
     /// function tail-recursion stack depth limit
     #[clap(short, long, default_value = "1024")]
     pub(crate) recursion_limit: u32,
+    /// Timeout in seconds for draining each flow on graceful shutdown
+    #[clap(long, default_value = "5")]
+    pub(crate) drain_timeout: u64,
+    /// Timeout for draining a specific flow on graceful shutdown, as `<flow>=<seconds>`
+    #[clap(long)]
+    pub(crate) flow_drain_timeout: Vec<String>,
+    /// Static type checking of scripts: `off`, `warn` or `strict`
+    #[clap(long, default_value = "off")]
+    pub(crate) type_check: TypeCheck,
 }
 
 // TODO: since the API will change this isn't translated yet
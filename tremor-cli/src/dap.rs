@@ -0,0 +1,779 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A debug adapter protocol server for `tremor run --debug <port>`
+//!
+//! The server waits for a single client (an editor) to connect, lets it set
+//! line breakpoints and only starts processing events once the client sent
+//! `configurationDone`. Whenever the script stops on a breakpoint or after a
+//! step the client can inspect `event`, `state`, `$` and the top level locals
+//! and evaluate expressions against them.
+//!
+//! A line is stopped on once per evaluation, so breakpoints within loops and
+//! comprehensions are hit on every iteration. `next` steps over calls of
+//! functions defined in the script, `stepIn` enters them and `stepOut` runs
+//! until the current function returned.
+
+use crate::env::{self, TremorCliEnv};
+use crate::errors::{Error, Result};
+use halfbrown::HashMap;
+use simd_json::OwnedValue;
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use tremor_common::time::nanotime;
+use tremor_script::{
+    arena,
+    debug::{self, Debugger, Frame},
+    pos::Span,
+    prelude::*,
+    script::{AggrType, Return, Script},
+    Value,
+};
+use tremor_value::literal;
+
+/// tremor-script runs single threaded, so there is only ever one thread
+const THREAD_ID: u64 = 1;
+/// Variable references of the scopes, nested values are numbered after these
+const SCOPES: [&str; 4] = ["Event", "State", "Metadata", "Locals"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Run until a breakpoint is hit
+    Run,
+    /// Stop on the next line, within called functions too, with the reason
+    /// reported to the client
+    Step(&'static str),
+    /// Stop on the next line that is not within a function called from the
+    /// given depth
+    Next(usize),
+    /// Stop on the next line after the function at the given depth returned
+    Out(usize),
+}
+
+/// The line evaluated at a call depth along with the expressions of it that
+/// were evaluated so far, evaluating one of them again is the next iteration
+/// of a loop
+#[derive(Default)]
+struct Visit {
+    line: usize,
+    spans: BTreeSet<Span>,
+}
+
+/// The script is stopped on a line, with a snapshot of its variables
+struct Stopped {
+    line: usize,
+    column: usize,
+    /// call depth of the stopped expression
+    depth: usize,
+    /// values that can be expanded, `variablesReference` n is the value at
+    /// index n - 1 and the first ones are the scopes
+    values: Vec<Value<'static>>,
+}
+
+struct State {
+    mode: Mode,
+    breakpoints: BTreeSet<usize>,
+    stopped: Option<Stopped>,
+    /// the current line of every call depth, so we stop once per line
+    visits: Vec<Visit>,
+    configured: bool,
+    connected: bool,
+}
+
+impl State {
+    /// Records the evaluation of an expression, returns true if it is the
+    /// first one of a line or of a new iteration over the line
+    fn visit(&mut self, frame: &Frame) -> bool {
+        let line = frame.span.start().line();
+        // drop the lines of functions that returned
+        self.visits.truncate(frame.depth + 1);
+        if self.visits.len() <= frame.depth {
+            self.visits.resize_with(frame.depth + 1, Visit::default);
+        }
+        let visit = &mut self.visits[frame.depth];
+        if visit.line == line && visit.spans.insert(frame.span) {
+            return false;
+        }
+        visit.line = line;
+        visit.spans.clear();
+        visit.spans.insert(frame.span);
+        true
+    }
+}
+
+struct Writer {
+    stream: TcpStream,
+    seq: u64,
+}
+
+pub(crate) struct Server {
+    path: String,
+    aid: arena::Index,
+    locals: Vec<(String, usize)>,
+    env: TremorCliEnv,
+    state: Mutex<State>,
+    changed: Condvar,
+    writer: Mutex<Writer>,
+}
+
+impl Server {
+    /// Waits for a client on the given port and starts serving it
+    pub(crate) fn listen(port: u16, path: &str, script: &Script) -> Result<Arc<Self>> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        eprintln!(
+            "Waiting for a debug adapter client on 127.0.0.1:{}",
+            listener.local_addr()?.port()
+        );
+        Self::accept(&listener, path, script)
+    }
+
+    /// Serves the first client connecting to the listener
+    fn accept(listener: &TcpListener, path: &str, script: &Script) -> Result<Arc<Self>> {
+        let (stream, peer) = listener.accept()?;
+        eprintln!("Debugger connected from {}", peer);
+        let server = Arc::new(Self {
+            path: path.to_string(),
+            aid: script.aid,
+            locals: script.script.local_names().to_vec(),
+            env: env::setup()?,
+            state: Mutex::new(State {
+                mode: Mode::Run,
+                breakpoints: BTreeSet::new(),
+                stopped: None,
+                visits: Vec::new(),
+                configured: false,
+                connected: true,
+            }),
+            changed: Condvar::new(),
+            writer: Mutex::new(Writer {
+                stream: stream.try_clone()?,
+                seq: 0,
+            }),
+        });
+        let reader = server.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = reader.serve(BufReader::new(stream)) {
+                eprintln!("Debugger connection failed: {}", e);
+            }
+            if let Ok(mut state) = reader.state() {
+                state.connected = false;
+                state.stopped = None;
+            }
+            reader.changed.notify_all();
+        });
+        debug::attach(server.clone())?;
+        server.wait_configured()?;
+        Ok(server)
+    }
+
+    fn state(&self) -> Result<MutexGuard<State>> {
+        self.state
+            .lock()
+            .map_err(|_| Error::from("Debugger state lock poisoned"))
+    }
+
+    /// Blocks until the client finished setting up breakpoints
+    fn wait_configured(&self) -> Result<()> {
+        let mut state = self.state()?;
+        while state.connected && !state.configured {
+            state = self
+                .changed
+                .wait(state)
+                .map_err(|_| Error::from("Debugger state lock poisoned"))?;
+        }
+        Ok(())
+    }
+
+    /// To be called before every event so breakpoints trigger again
+    pub(crate) fn new_event(&self) -> Result<()> {
+        self.state()?.visits.clear();
+        Ok(())
+    }
+
+    /// Tells the client that the script is done and detaches the debugger
+    pub(crate) fn finish(&self) -> Result<()> {
+        debug::detach()?;
+        if self.state()?.connected {
+            self.event("terminated", Value::object())?;
+            self.event("exited", literal!({ "exitCode": 0 }))?;
+        }
+        Ok(())
+    }
+
+    fn send(&self, mut message: Value<'static>) -> Result<()> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| Error::from("Debugger writer lock poisoned"))?;
+        writer.seq += 1;
+        if let Some(o) = message.as_object_mut() {
+            o.insert("seq".into(), Value::from(writer.seq));
+        }
+        let body = message.encode();
+        write!(
+            writer.stream,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        writer.stream.flush()?;
+        Ok(())
+    }
+
+    fn event(&self, event: &str, body: Value<'static>) -> Result<()> {
+        self.send(literal!({
+            "type": "event",
+            "event": event.to_string(),
+            "body": body
+        }))
+    }
+
+    fn respond(&self, request: &OwnedValue, result: Result<Value<'static>>) -> Result<()> {
+        let request_seq = request.get_u64("seq").unwrap_or_default();
+        let command = request.get_str("command").unwrap_or_default().to_string();
+        let response = match result {
+            Ok(body) => literal!({
+                "type": "response",
+                "request_seq": request_seq,
+                "success": true,
+                "command": command,
+                "body": body
+            }),
+            Err(e) => literal!({
+                "type": "response",
+                "request_seq": request_seq,
+                "success": false,
+                "command": command,
+                "message": e.to_string()
+            }),
+        };
+        self.send(response)
+    }
+
+    /// Handles requests until the client disconnects
+    fn serve(&self, mut reader: BufReader<TcpStream>) -> Result<()> {
+        while let Some(mut body) = read_message(&mut reader)? {
+            let request = simd_json::to_owned_value(&mut body)?;
+            let command = request.get_str("command").unwrap_or_default();
+            let args = request.get("arguments");
+            let result = self.handle(command, args);
+            self.respond(&request, result)?;
+            match command {
+                "initialize" => self.event("initialized", Value::object())?,
+                "disconnect" => return Ok(()),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&self, command: &str, args: Option<&OwnedValue>) -> Result<Value<'static>> {
+        match command {
+            "initialize" => Ok(literal!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsEvaluateForHovers": true
+            })),
+            "launch" | "attach" => {
+                if args.and_then(|a| a.get_bool("stopOnEntry")) == Some(true) {
+                    self.state()?.mode = Mode::Step("entry");
+                }
+                Ok(Value::object())
+            }
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setExceptionBreakpoints" => Ok(Value::object()),
+            "configurationDone" => {
+                self.state()?.configured = true;
+                self.changed.notify_all();
+                Ok(Value::object())
+            }
+            "threads" => Ok(literal!({
+                "threads": [{"id": THREAD_ID, "name": "script"}]
+            })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => {
+                let scopes: Vec<_> = SCOPES
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| {
+                        literal!({
+                            "name": (*name).to_string(),
+                            "variablesReference": i + 1,
+                            "expensive": false
+                        })
+                    })
+                    .collect();
+                Ok(literal!({ "scopes": scopes }))
+            }
+            "variables" => self.variables(
+                args.and_then(|a| a.get_usize("variablesReference"))
+                    .unwrap_or_default(),
+            ),
+            "evaluate" => self.evaluate(args.and_then(|a| a.get_str("expression")).unwrap_or("")),
+            "continue" => {
+                self.resume(Mode::Run)?;
+                Ok(literal!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                let depth = self.stopped_depth()?;
+                self.resume(Mode::Next(depth))?;
+                Ok(Value::object())
+            }
+            "stepIn" => {
+                self.resume(Mode::Step("step"))?;
+                Ok(Value::object())
+            }
+            "stepOut" => {
+                let depth = self.stopped_depth()?;
+                self.resume(Mode::Out(depth))?;
+                Ok(Value::object())
+            }
+            "pause" => {
+                self.state()?.mode = Mode::Step("pause");
+                Ok(Value::object())
+            }
+            "disconnect" => {
+                self.resume(Mode::Run)?;
+                let mut state = self.state()?;
+                state.connected = false;
+                state.breakpoints.clear();
+                Ok(Value::object())
+            }
+            other => Err(format!("Unsupported request `{}`", other).into()),
+        }
+    }
+
+    fn stopped_depth(&self) -> Result<usize> {
+        Ok(self.state()?.stopped.as_ref().map_or(0, |s| s.depth))
+    }
+
+    fn resume(&self, mode: Mode) -> Result<()> {
+        let mut state = self.state()?;
+        state.mode = mode;
+        state.stopped = None;
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    fn set_breakpoints(&self, args: Option<&OwnedValue>) -> Result<Value<'static>> {
+        let source = args
+            .and_then(|a| a.get("source"))
+            .and_then(|s| s.get_str("path"))
+            .unwrap_or_default();
+        let ours = is_same_file(source, &self.path);
+        let lines: Vec<usize> = args
+            .and_then(|a| a.get_array("breakpoints"))
+            .map(|bs| bs.iter().filter_map(|b| b.get_usize("line")).collect())
+            .unwrap_or_default();
+        if ours {
+            self.state()?.breakpoints = lines.iter().copied().collect();
+        }
+        let breakpoints: Vec<_> = lines
+            .into_iter()
+            .map(|line| literal!({ "verified": ours, "line": line }))
+            .collect();
+        Ok(literal!({ "breakpoints": breakpoints }))
+    }
+
+    fn stack_trace(&self) -> Result<Value<'static>> {
+        let state = self.state()?;
+        let frames: Vec<_> = state
+            .stopped
+            .iter()
+            .map(|s| {
+                literal!({
+                    "id": 1,
+                    "name": "script",
+                    "line": s.line,
+                    "column": s.column,
+                    "source": {
+                        "name": crate::util::basename(&self.path),
+                        "path": self.path.clone()
+                    }
+                })
+            })
+            .collect();
+        Ok(literal!({ "totalFrames": frames.len(), "stackFrames": frames }))
+    }
+
+    fn variables(&self, reference: usize) -> Result<Value<'static>> {
+        let mut state = self.state()?;
+        let stopped = state.stopped.as_mut().ok_or("The script is not stopped")?;
+        let value = reference
+            .checked_sub(1)
+            .and_then(|i| stopped.values.get(i))
+            .cloned()
+            .ok_or_else(|| Error::from(format!("Unknown variable reference {}", reference)))?;
+        let children: Vec<(String, Value<'static>)> = if let Some(o) = value.as_object() {
+            let mut children: Vec<_> = o.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+            children.sort_by(|a, b| a.0.cmp(&b.0));
+            children
+        } else if let Some(a) = value.as_array() {
+            a.iter()
+                .enumerate()
+                .map(|(i, v)| (i.to_string(), v.clone()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let variables: Vec<_> = children
+            .into_iter()
+            .map(|(name, value)| {
+                let reference = if value.is_object() || value.is_array() {
+                    stopped.values.push(value.clone());
+                    stopped.values.len()
+                } else {
+                    0
+                };
+                literal!({
+                    "name": name,
+                    "value": value.encode(),
+                    "variablesReference": reference
+                })
+            })
+            .collect();
+        Ok(literal!({ "variables": variables }))
+    }
+
+    /// Evaluates an expression against the snapshot of the stopped script
+    fn evaluate(&self, expression: &str) -> Result<Value<'static>> {
+        let (mut event, mut state, mut meta, locals) = {
+            let state = self.state()?;
+            let stopped = state.stopped.as_ref().ok_or("The script is not stopped")?;
+            match stopped.values.as_slice() {
+                [event, state, meta, locals, ..] => {
+                    (event.clone(), state.clone(), meta.clone(), locals.clone())
+                }
+                _ => return Err("The script is not stopped".into()),
+            }
+        };
+        let mut locals: HashMap<String, Value<'static>> = locals
+            .as_object()
+            .map(|o| o.iter().map(|(k, v)| (k.to_string(), v.clone())).collect())
+            .unwrap_or_default();
+        let script = Script::parse(expression, &self.env.fun)?;
+        let result = script.run_with_locals(
+            &EventContext::new(nanotime(), None),
+            AggrType::Emit,
+            &mut event,
+            &mut state,
+            &mut meta,
+            &mut locals,
+        )?;
+        let result = match result {
+            Return::Emit { value, .. } => value.encode(),
+            Return::EmitEvent { .. } => event.encode(),
+            Return::Drop => "drop".to_string(),
+        };
+        Ok(literal!({ "result": result, "variablesReference": 0 }))
+    }
+
+    fn snapshot(&self, frame: &Frame) -> Vec<Value<'static>> {
+        // we only know the names of the locals of the script itself
+        let locals: Value<'static> = if frame.depth == 0 {
+            self.locals
+                .iter()
+                .filter_map(|(name, idx)| Some((name.clone(), frame.local(*idx)?.clone_static())))
+                .collect()
+        } else {
+            Value::object()
+        };
+        vec![
+            frame.event.clone_static(),
+            frame.state.clone(),
+            frame.meta.clone_static(),
+            locals,
+        ]
+    }
+}
+
+impl Debugger for Server {
+    fn step(&self, frame: &Frame) {
+        // expressions of modules and of evaluated expressions are not stepped through
+        if frame.aid() != self.aid {
+            return;
+        }
+        let line = frame.span.start().line();
+        let mut state = if let Ok(state) = self.state() {
+            state
+        } else {
+            return;
+        };
+        if !state.connected || !state.visit(frame) {
+            return;
+        }
+        let reason = match state.mode {
+            Mode::Step(reason) => reason,
+            Mode::Next(depth) if frame.depth <= depth => "step",
+            Mode::Out(depth) if frame.depth < depth => "step",
+            _ if state.breakpoints.contains(&line) => "breakpoint",
+            _ => return,
+        };
+        state.mode = Mode::Run;
+        state.stopped = Some(Stopped {
+            line,
+            column: frame.span.start().column(),
+            depth: frame.depth,
+            values: self.snapshot(frame),
+        });
+        let stopped = literal!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true
+        });
+        if let Err(e) = self.event("stopped", stopped) {
+            eprintln!("Failed to notify debugger: {}", e);
+            return;
+        }
+        while state.stopped.is_some() {
+            state = if let Ok(state) = self.changed.wait(state) {
+                state
+            } else {
+                return;
+            };
+        }
+    }
+}
+
+fn is_same_file(a: &str, b: &str) -> bool {
+    match (
+        tremor_common::file::canonicalize(Path::new(a)),
+        tremor_common::file::canonicalize(Path::new(b)),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Reads a single `Content-Length` framed message
+fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            len = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|e| Error::from(format!("Invalid Content-Length: {}", e)))?,
+            );
+        }
+    }
+    let len = len.ok_or("Missing Content-Length header")?;
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    const SCRIPT: &str = r#"fn double(x) with
+  x * 2
+end;
+let doubled = for event of
+  case (i, e) => double(e)
+end;
+doubled
+"#;
+
+    /// A scripted debug adapter client
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+        seq: u64,
+        /// events received while waiting for a response
+        events: Vec<OwnedValue>,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Result<Self> {
+            let writer = TcpStream::connect(addr)?;
+            // a failing test disconnects instead of blocking forever
+            writer.set_read_timeout(Some(Duration::from_secs(10)))?;
+            Ok(Self {
+                reader: BufReader::new(writer.try_clone()?),
+                writer,
+                seq: 0,
+                events: Vec::new(),
+            })
+        }
+
+        fn read(&mut self) -> Result<OwnedValue> {
+            let mut body = read_message(&mut self.reader)?.ok_or("Connection closed")?;
+            Ok(simd_json::to_owned_value(&mut body)?)
+        }
+
+        /// Sends a request and returns the body of its response
+        fn request(&mut self, command: &str, arguments: Value<'static>) -> Result<OwnedValue> {
+            self.seq += 1;
+            let body = literal!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command.to_string(),
+                "arguments": arguments
+            })
+            .encode();
+            write!(
+                self.writer,
+                "Content-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )?;
+            loop {
+                let message = self.read()?;
+                if message.get_str("type") == Some("response")
+                    && message.get_u64("request_seq") == Some(self.seq)
+                {
+                    if message.get_bool("success") != Some(true) {
+                        return Err(format!("{} failed: {}", command, message.encode()).into());
+                    }
+                    return Ok(message.get("body").cloned().unwrap_or_default());
+                }
+                self.events.push(message);
+            }
+        }
+
+        /// Waits for an event and returns its body
+        fn event(&mut self, event: &str) -> Result<OwnedValue> {
+            let is_event = |m: &OwnedValue| m.get_str("event") == Some(event);
+            let received = self.events.iter().position(is_event);
+            let message = if let Some(i) = received {
+                self.events.remove(i)
+            } else {
+                loop {
+                    let message = self.read()?;
+                    if is_event(&message) {
+                        break message;
+                    }
+                    self.events.push(message);
+                }
+            };
+            Ok(message.get("body").cloned().unwrap_or_default())
+        }
+
+        /// Waits for the script to stop, returns the reason and the line
+        fn stopped(&mut self) -> Result<(String, u64)> {
+            let stopped = self.event("stopped")?;
+            let reason = stopped.get_str("reason").unwrap_or_default().to_string();
+            let trace = self.request("stackTrace", literal!({ "threadId": THREAD_ID }))?;
+            let line = trace
+                .get_array("stackFrames")
+                .and_then(|frames| frames.first())
+                .and_then(|frame| frame.get_u64("line"))
+                .ok_or("No stack frame")?;
+            Ok((reason, line))
+        }
+
+        fn resume(&mut self, command: &str) -> Result<()> {
+            self.request(command, literal!({ "threadId": THREAD_ID }))?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn session() -> Result<()> {
+        let dir = temp_dir::TempDir::new()?;
+        let file = dir.child("debug.tremor");
+        std::fs::write(&file, SCRIPT)?;
+        let path = file.to_string_lossy().to_string();
+        let env = env::setup()?;
+        let script = Script::parse(SCRIPT, &env.fun)?;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let addr = listener.local_addr()?;
+        let source = path.clone();
+        let client = std::thread::spawn(move || -> Result<Vec<(String, u64)>> {
+            let mut client = Client::connect(addr)?;
+            client.request("initialize", literal!({ "adapterID": "tremor" }))?;
+            client.event("initialized")?;
+            client.request(
+                "setBreakpoints",
+                literal!({
+                    "source": { "path": source },
+                    "breakpoints": [{ "line": 5 }]
+                }),
+            )?;
+            client.request("configurationDone", Value::object())?;
+            let mut stops = vec![client.stopped()?];
+            client.resume("continue")?;
+            stops.push(client.stopped()?);
+            client.resume("stepIn")?;
+            stops.push(client.stopped()?);
+            client.resume("stepOut")?;
+            stops.push(client.stopped()?);
+            client.resume("next")?;
+            client.event("terminated")?;
+            client.request("disconnect", Value::object())?;
+            Ok(stops)
+        });
+
+        let server = Server::accept(&listener, &path, &script)?;
+        server.new_event()?;
+        let mut event = literal!([1, 2]);
+        let mut state = Value::null();
+        let mut meta = Value::object();
+        let result = script.run(
+            &EventContext::new(0, None),
+            AggrType::Emit,
+            &mut event,
+            &mut state,
+            &mut meta,
+        );
+        server.finish()?;
+        let stops = client
+            .join()
+            .map_err(|_| Error::from("Debug adapter client panicked"))??;
+        result?;
+        assert_eq!(
+            vec![
+                // the breakpoint in the comprehension is hit for every element
+                ("breakpoint".to_string(), 5),
+                ("breakpoint".to_string(), 5),
+                // into `double`
+                ("step".to_string(), 2),
+                // out of `double` and the comprehension
+                ("step".to_string(), 7),
+            ],
+            stops
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn framing() -> Result<()> {
+        let data = b"Content-Length: 2\r\n\r\n{}Content-Length: 13\r\nX: y\r\n\r\n{\"seq\": 1234}";
+        let mut reader = BufReader::new(&data[..]);
+        assert_eq!(Some(b"{}".to_vec()), read_message(&mut reader)?);
+        assert_eq!(
+            Some(b"{\"seq\": 1234}".to_vec()),
+            read_message(&mut reader)?
+        );
+        assert_eq!(None, read_message(&mut reader)?);
+        assert!(read_message(&mut BufReader::new(&b"X: y\r\n\r\n{}"[..])).is_err());
+        Ok(())
+    }
+}
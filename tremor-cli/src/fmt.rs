@@ -0,0 +1,195 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli::Fmt;
+use crate::errors::{Error, Result};
+use crate::util::{get_source_kind, slurp_string, visit_path_str, SourceKind};
+use std::cell::RefCell;
+use std::path::Path;
+use tremor_script::formatter::{self, Language};
+use tremor_script::highlighter::{Highlighter, Term as TermHighlighter};
+
+impl Fmt {
+    pub(crate) fn run(&self) -> Result<()> {
+        let unformatted = RefCell::new(Vec::new());
+        let failed = RefCell::new(Vec::new());
+        let visitor = |_rel: Option<&Path>, path: &Path| -> Result<()> {
+            let path_str = path.to_string_lossy().to_string();
+            let language = match get_source_kind(&path_str) {
+                SourceKind::Tremor => Language::Script,
+                SourceKind::Trickle => Language::Query,
+                SourceKind::Troy => Language::Deploy,
+                SourceKind::Json | SourceKind::Unsupported(_) => return Ok(()),
+            };
+            let src = slurp_string(path)?;
+            match formatter::format(&src, language) {
+                Ok(formatted) if formatted == src => (),
+                Ok(formatted) => {
+                    if self.check {
+                        println!("{path_str}");
+                    } else {
+                        std::fs::write(path, formatted)?;
+                    }
+                    unformatted.borrow_mut().push(path_str);
+                }
+                Err(e) => {
+                    eprintln!("Error formatting {path_str}:");
+                    let mut h = TermHighlighter::stderr();
+                    if let Err(e) = h.format_error(&e) {
+                        eprintln!("Error: {e}");
+                    };
+                    failed.borrow_mut().push(path_str);
+                }
+            }
+            Ok(())
+        };
+        for path in &self.paths {
+            visit_path_str(path, &visitor)?;
+        }
+
+        let failed = failed.into_inner();
+        let unformatted = unformatted.into_inner();
+        if !failed.is_empty() {
+            Err(Error::from(format!(
+                "Failed to format {} file(s): {}",
+                failed.len(),
+                failed.join(", ")
+            )))
+        } else if self.check && !unformatted.is_empty() {
+            Err(Error::from(format!(
+                "{} file(s) are not formatted",
+                unformatted.len()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use temp_dir::TempDir;
+
+    const SCRIPT: &str = r#"# the answer
+let   answer=42; # inline
+  match event of
+case %{ a == 1 } => answer
+      # fallback
+default=>null
+end
+"#;
+
+    const QUERY: &str = r#"define script add
+script
+# add one
+event+1
+end;
+create script add;
+select event from in into add; # route
+  select event from add into out;
+"#;
+
+    const DEPLOY: &str = r#"define flow test
+flow
+# the pipeline
+define pipeline main
+pipeline
+select event from in into out;
+end;
+  create pipeline main;
+end;
+deploy flow test;
+"#;
+
+    const FORMATTED_SCRIPT: &str = r#"# the answer
+let answer = 42; # inline
+match event of
+  case %{a == 1} => answer
+  # fallback
+  default => null
+end
+"#;
+
+    fn write(dir: &TempDir, name: &str, src: &str) -> Result<String> {
+        let path = dir.child(name);
+        std::fs::write(&path, src)?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    fn comments(src: &str) -> Vec<&str> {
+        src.lines()
+            .filter_map(|l| l.find('#').map(|i| l[i..].trim_end()))
+            .collect()
+    }
+
+    #[test]
+    fn format_files() -> Result<()> {
+        let dir = TempDir::new()?;
+        let files = [
+            (write(&dir, "script.tremor", SCRIPT)?, SCRIPT),
+            (write(&dir, "query.trickle", QUERY)?, QUERY),
+            (write(&dir, "deploy.troy", DEPLOY)?, DEPLOY),
+        ];
+        let check = Fmt {
+            paths: vec![dir.path().to_string_lossy().to_string()],
+            check: true,
+        };
+        let fmt = Fmt {
+            paths: check.paths.clone(),
+            check: false,
+        };
+
+        // checking does not touch the files
+        assert!(check.run().is_err());
+        for (path, src) in &files {
+            assert_eq!(*src, slurp_string(path)?);
+        }
+
+        fmt.run()?;
+        for (path, src) in &files {
+            let formatted = slurp_string(path)?;
+            assert_ne!(*src, formatted, "{path} was not formatted");
+            // all comments are kept in order
+            assert_eq!(comments(src), comments(&formatted), "{path} lost comments");
+        }
+        assert_eq!(FORMATTED_SCRIPT, slurp_string(&files[0].0)?);
+
+        // formatting is idempotent
+        check.run()?;
+        let formatted = files
+            .iter()
+            .map(|(path, _)| slurp_string(path))
+            .collect::<Result<Vec<_>>>()?;
+        fmt.run()?;
+        for ((path, _), formatted) in files.iter().zip(formatted) {
+            assert_eq!(formatted, slurp_string(path)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_files_are_not_touched() -> Result<()> {
+        let dir = TempDir::new()?;
+        let src = "let a = ; # broken\n";
+        let path = write(&dir, "broken.tremor", src)?;
+        let fmt = Fmt {
+            paths: vec![path.clone()],
+            check: false,
+        };
+        assert!(fmt.run().is_err());
+        assert_eq!(src, slurp_string(&path)?);
+        Ok(())
+    }
+}
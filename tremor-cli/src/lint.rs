@@ -0,0 +1,389 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli::{Lint, LintFormat};
+use crate::env::{self, TremorCliEnv};
+use crate::errors::{Error, Result};
+use crate::util::{get_source_kind, slurp_string, visit_path_str, SourceKind};
+use std::cell::RefCell;
+use std::path::Path;
+use tremor_script::{
+    arena::Arena,
+    ast::helper::Warning,
+    deploy::Deploy,
+    errors::Error as ScriptError,
+    lint::{Config, Rule, Severity},
+    module::{Id, Module},
+    pos::Span,
+    query::Query,
+    Script,
+};
+
+/// Configuration file picked up from the working directory if no `--config` is given
+const DEFAULT_CONFIG: &str = "tremor-lint.yaml";
+/// Rule id used for sources that fail to compile
+const COMPILE_ERROR: &str = "compile_error";
+
+/// Severity of a finding, compile errors are always errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Level {
+    Warning,
+    Error,
+}
+
+/// A single lint or compile error found in a source file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+struct Finding {
+    file: String,
+    rule: String,
+    severity: Level,
+    message: String,
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+impl Finding {
+    fn new(file: &str, rule: &str, severity: Level, message: String, span: Option<Span>) -> Self {
+        let (start, end) = span.map_or(((1, 1), (1, 1)), |s| {
+            (
+                (s.start().line(), s.start().column()),
+                (s.end().line(), s.end().column()),
+            )
+        });
+        Self {
+            file: file.to_string(),
+            rule: rule.to_string(),
+            severity,
+            message,
+            start_line: start.0,
+            start_column: start.1,
+            end_line: end.0,
+            end_column: end.1,
+        }
+    }
+
+    fn from_warning(file: &str, severity: Severity, warning: &Warning) -> Self {
+        let level = if severity == Severity::Deny {
+            Level::Error
+        } else {
+            Level::Warning
+        };
+        Self::new(
+            file,
+            warning.rule.name(),
+            level,
+            warning.msg.clone(),
+            Some(warning.inner),
+        )
+    }
+
+    fn from_error(file: &str, error: &ScriptError) -> Self {
+        let (outer, inner) = error.context();
+        Self::new(
+            file,
+            COMPILE_ERROR,
+            Level::Error,
+            error.to_string(),
+            inner.or(outer),
+        )
+    }
+}
+
+impl Lint {
+    pub(crate) fn run(&self) -> Result<()> {
+        let config = self.config()?;
+        let env = env::setup()?;
+        let findings = RefCell::new(Vec::new());
+        let visitor = |_rel: Option<&Path>, path: &Path| -> Result<()> {
+            let path_str = path.to_string_lossy().to_string();
+            let kind = get_source_kind(&path_str);
+            if matches!(kind, SourceKind::Json | SourceKind::Unsupported(_)) {
+                return Ok(());
+            }
+            let src = slurp_string(path)?;
+            findings
+                .borrow_mut()
+                .append(&mut lint(&env, &config, &path_str, &src, &kind));
+            Ok(())
+        };
+        for path in &self.paths {
+            visit_path_str(path, &visitor)?;
+        }
+        let findings = findings.into_inner();
+
+        match self.format {
+            LintFormat::Text => {
+                for f in &findings {
+                    println!(
+                        "{}:{}:{}: {}[{}]: {}",
+                        f.file,
+                        f.start_line,
+                        f.start_column,
+                        if f.severity == Level::Error {
+                            "error"
+                        } else {
+                            "warning"
+                        },
+                        f.rule,
+                        f.message
+                    );
+                }
+            }
+            LintFormat::Json => println!("{}", simd_json::to_string_pretty(&findings)?),
+            LintFormat::Sarif => println!("{}", simd_json::to_string_pretty(&sarif(&findings))?),
+        }
+
+        let errors = findings
+            .iter()
+            .filter(|f| f.severity == Level::Error)
+            .count();
+        if errors > 0 {
+            Err(Error::from(format!(
+                "Linting failed with {} error(s)",
+                errors
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Loads the configured lint configuration or the one in the working directory
+    fn config(&self) -> Result<Config> {
+        let path = match &self.config {
+            Some(path) => path.clone(),
+            None if Path::new(DEFAULT_CONFIG).is_file() => DEFAULT_CONFIG.to_string(),
+            None => return Ok(Config::default()),
+        };
+        let data = slurp_string(&path)?;
+        serde_yaml::from_str(&data)
+            .map_err(|e| Error::from(format!("Invalid lint configuration `{}`: {}", path, e)))
+    }
+}
+
+/// Lints a single source
+fn lint(
+    env: &TremorCliEnv,
+    config: &Config,
+    file: &str,
+    src: &str,
+    kind: &SourceKind,
+) -> Vec<Finding> {
+    let result = match kind {
+        SourceKind::Tremor => {
+            Script::parse(src, &env.fun).map(|s| s.warnings().cloned().collect::<Vec<_>>())
+        }
+        SourceKind::Trickle => Query::parse(src, &env.fun, &env.aggr)
+            .map(|q| q.warnings.iter().cloned().collect::<Vec<_>>()),
+        SourceKind::Troy => Deploy::parse(src, &env.fun, &env.aggr)
+            .map(|d| d.warnings.iter().cloned().collect::<Vec<_>>()),
+        SourceKind::Json | SourceKind::Unsupported(_) => Ok(Vec::new()),
+    };
+    match result {
+        Ok(warnings) => config
+            .apply(&warnings)
+            .into_iter()
+            .map(|(severity, w)| Finding::from_warning(file, severity, w))
+            .collect(),
+        // tremor and troy files can be modules that are only ever `use`d
+        Err(_) if *kind != SourceKind::Trickle && is_module(src) => Vec::new(),
+        Err(e) => vec![Finding::from_error(file, &e)],
+    }
+}
+
+fn is_module(src: &str) -> bool {
+    Arena::insert(src)
+        .and_then(|(aid, src)| Module::load(Id::from(src.as_bytes()), &mut Vec::new(), aid, src))
+        .is_ok()
+}
+
+#[derive(Serialize)]
+struct SarifLog<'f> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun<'f>>,
+}
+
+#[derive(Serialize)]
+struct SarifRun<'f> {
+    tool: SarifTool,
+    results: Vec<SarifResult<'f>>,
+}
+
+#[derive(Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifDriver {
+    name: &'static str,
+    information_uri: &'static str,
+    version: &'static str,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRule {
+    id: &'static str,
+    short_description: SarifMessage<'static>,
+}
+
+#[derive(Serialize)]
+struct SarifMessage<'m> {
+    text: &'m str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'f> {
+    rule_id: &'f str,
+    level: &'static str,
+    message: SarifMessage<'f>,
+    locations: Vec<SarifLocation<'f>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation<'f> {
+    physical_location: SarifPhysicalLocation<'f>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation<'f> {
+    artifact_location: SarifArtifactLocation<'f>,
+    region: SarifRegion,
+}
+
+#[derive(Serialize)]
+struct SarifArtifactLocation<'f> {
+    uri: &'f str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+/// Builds a SARIF 2.1.0 log from the findings
+fn sarif(findings: &[Finding]) -> SarifLog {
+    let mut rules: Vec<SarifRule> = Rule::ALL
+        .iter()
+        .map(|r| SarifRule {
+            id: r.name(),
+            short_description: SarifMessage {
+                text: r.description(),
+            },
+        })
+        .collect();
+    rules.push(SarifRule {
+        id: COMPILE_ERROR,
+        short_description: SarifMessage {
+            text: "The source fails to compile",
+        },
+    });
+    let results = findings
+        .iter()
+        .map(|f| SarifResult {
+            rule_id: &f.rule,
+            level: match f.severity {
+                Level::Warning => "warning",
+                Level::Error => "error",
+            },
+            message: SarifMessage { text: &f.message },
+            locations: vec![SarifLocation {
+                physical_location: SarifPhysicalLocation {
+                    artifact_location: SarifArtifactLocation { uri: &f.file },
+                    region: SarifRegion {
+                        start_line: f.start_line,
+                        start_column: f.start_column,
+                        end_line: f.end_line,
+                        end_column: f.end_column,
+                    },
+                },
+            }],
+        })
+        .collect();
+    SarifLog {
+        schema: "https://json.schemastore.org/sarif-2.1.0.json",
+        version: "2.1.0",
+        runs: vec![SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver {
+                    name: "tremor",
+                    information_uri: "https://www.tremor.rs",
+                    version: env!("CARGO_PKG_VERSION"),
+                    rules,
+                },
+            },
+            results,
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn severity_and_compile_errors() -> Result<()> {
+        let env = env::setup()?;
+        let mut config = Config::default();
+        let src = "let a = 1; let b = 2; b";
+        let findings = lint(&env, &config, "test.tremor", src, &SourceKind::Tremor);
+        assert_eq!(1, findings.len());
+        assert_eq!("unused_let", findings[0].rule);
+        assert_eq!(Level::Warning, findings[0].severity);
+        assert_eq!(1, findings[0].start_line);
+
+        config.rules.insert(Rule::UnusedLet, Severity::Deny);
+        let findings = lint(&env, &config, "test.tremor", src, &SourceKind::Tremor);
+        assert_eq!(Level::Error, findings[0].severity);
+
+        config.rules.insert(Rule::UnusedLet, Severity::Allow);
+        let findings = lint(&env, &config, "test.tremor", src, &SourceKind::Tremor);
+        assert!(findings.is_empty());
+
+        let findings = lint(&env, &config, "bad.trickle", "select", &SourceKind::Trickle);
+        assert_eq!(1, findings.len());
+        assert_eq!(COMPILE_ERROR, findings[0].rule);
+        Ok(())
+    }
+
+    #[test]
+    fn sarif_log() -> Result<()> {
+        let findings = vec![Finding::new(
+            "test.tremor",
+            "unused_let",
+            Level::Warning,
+            "unused".to_string(),
+            None,
+        )];
+        let log = simd_json::to_string(&sarif(&findings))?;
+        assert!(log.contains(r#""version":"2.1.0""#));
+        assert!(log.contains(r#""ruleId":"unused_let""#));
+        assert!(log.contains(r#""uri":"test.tremor""#));
+        Ok(())
+    }
+}
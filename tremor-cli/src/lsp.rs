@@ -0,0 +1,840 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Language server for tremor-script, trickle and troy
+//!
+//! Speaks the language server protocol over stdin and stdout and offers diagnostics,
+//! hover, completion, go-to-definition and document symbols.
+//!
+//! Every change of a document is parsed to provide diagnostics, so the parsed sources
+//! need to be freed again once the document is replaced or closed. This is why the language
+//! server is only available when built with the `lsp` feature, which enables deletions in the
+//! tremor-script arena.
+
+mod analysis;
+
+use crate::env::{self, TremorCliEnv};
+use crate::errors::{Error, Result};
+use crate::util::{get_source_kind, SourceKind};
+use analysis::{Definition, LineIndex, PathAt};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as RequestTrait,
+    },
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse,
+    Documentation, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind, NumberOrString,
+    OneOf, PublishDiagnosticsParams, Range, ServerCapabilities, SymbolKind,
+    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use tremor_script::{
+    arena::{self, Arena},
+    ast::{docs::FnDoc, helper::Warning, NodeId},
+    deploy::Deploy,
+    errors::{Error as ScriptError, ErrorWithIndex},
+    module::{Id, Manager, Module},
+    query::Query,
+    Script,
+};
+
+const SOURCE: &str = "tremor";
+
+// used with `map_err`, which passes the error by value
+#[allow(clippy::needless_pass_by_value)]
+fn err<E: ToString>(e: E) -> Error {
+    Error::from(e.to_string())
+}
+
+/// Runs the language server until the client shuts it down
+pub(crate) fn run() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = serde_json::to_value(&capabilities()).map_err(err)?;
+    let params = connection.initialize(capabilities).map_err(err)?;
+    let params: InitializeParams = serde_json::from_value(params).map_err(err)?;
+
+    // modules within the workspace can be `use`d
+    let folders = params
+        .workspace_folders
+        .into_iter()
+        .flatten()
+        .map(|folder| folder.uri)
+        .chain(params.root_uri);
+    for folder in folders {
+        if let Ok(path) = folder.to_file_path() {
+            Manager::add_path(&path.to_string_lossy())?;
+        }
+    }
+
+    let mut server = Server {
+        connection,
+        env: env::setup()?,
+        documents: HashMap::new(),
+    };
+    server.main_loop()?;
+    // the connection needs to be dropped for the io threads to finish
+    drop(server);
+    io_threads.join()?;
+    Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![":".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Language {
+    Script,
+    Query,
+    Deploy,
+}
+
+impl Language {
+    fn of(uri: &Url) -> Option<Self> {
+        match get_source_kind(uri.path()) {
+            SourceKind::Tremor => Some(Self::Script),
+            SourceKind::Trickle => Some(Self::Query),
+            SourceKind::Troy => Some(Self::Deploy),
+            SourceKind::Json | SourceKind::Unsupported(_) => None,
+        }
+    }
+}
+
+/// What a path within a document refers to
+enum Target {
+    /// a definition within the document itself
+    Local(Definition),
+    /// a definition within a module file, along with the source of the file
+    Definition(PathBuf, String, Definition),
+    /// a module file
+    Module(PathBuf),
+    /// a function that is only known to the function registry
+    Function {
+        name: String,
+        arity: RangeInclusive<usize>,
+    },
+}
+
+/// Arena indexes of parsed sources, freed when dropped
+///
+/// Errors and parsed sources may reference the source they were parsed from, so the
+/// sources are kept alive until the document they belong to is replaced or closed.
+#[derive(Default)]
+struct Sources(Vec<arena::Index>);
+
+impl Sources {
+    fn insert(&mut self, src: &str) -> tremor_script::Result<(arena::Index, &'static str)> {
+        let (aid, src) = Arena::insert(src)?;
+        self.0.push(aid);
+        Ok((aid, src))
+    }
+}
+
+impl Drop for Sources {
+    fn drop(&mut self) {
+        for aid in self.0.drain(..) {
+            // ALLOW: the sources are owned by a document or a lookup, and everything parsed from
+            // them has been dropped before they are
+            free(unsafe { Arena::delte_index_this_is_really_unsafe_dont_use_it(aid) });
+        }
+    }
+}
+
+/// An open document along with the sources parsed for its diagnostics
+struct Document {
+    text: String,
+    // only kept to be freed along with the document
+    #[allow(dead_code)]
+    sources: Sources,
+}
+
+struct Server {
+    connection: Connection,
+    env: TremorCliEnv,
+    documents: HashMap<Url, Document>,
+}
+
+impl Server {
+    fn main_loop(&mut self) -> Result<()> {
+        while let Ok(msg) = self.connection.receiver.recv() {
+            match msg {
+                Message::Request(req) => {
+                    if self.connection.handle_shutdown(&req).map_err(err)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(req);
+                    self.send(response)?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn send<M: Into<Message>>(&self, msg: M) -> Result<()> {
+        self.connection.sender.send(msg.into()).map_err(err)
+    }
+
+    fn handle_request(&self, req: Request) -> Response {
+        match req.method.as_str() {
+            HoverRequest::METHOD => self.request::<HoverRequest, _>(req, Self::hover),
+            Completion::METHOD => self.request::<Completion, _>(req, Self::completion),
+            GotoDefinition::METHOD => self.request::<GotoDefinition, _>(req, Self::definition),
+            DocumentSymbolRequest::METHOD => {
+                self.request::<DocumentSymbolRequest, _>(req, Self::document_symbols)
+            }
+            _ => Response::new_err(
+                req.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request: {}", req.method),
+            ),
+        }
+    }
+
+    fn request<R, F>(&self, req: Request, handler: F) -> Response
+    where
+        R: RequestTrait,
+        F: Fn(&Self, R::Params) -> R::Result,
+    {
+        let id = req.id.clone();
+        match req.extract::<R::Params>(R::METHOD) {
+            Ok((id, params)) => Response::new_ok(id, handler(self, params)),
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = notification
+                    .extract(DidOpenTextDocument::METHOD)
+                    .map_err(err)?;
+                let doc = params.text_document;
+                self.update(doc.uri, doc.text, doc.version)
+            }
+            DidChangeTextDocument::METHOD => {
+                let mut params: DidChangeTextDocumentParams = notification
+                    .extract(DidChangeTextDocument::METHOD)
+                    .map_err(err)?;
+                // we only support full syncs, so the last change contains the whole document
+                if let Some(change) = params.content_changes.pop() {
+                    let doc = params.text_document;
+                    self.update(doc.uri, change.text, doc.version)?;
+                }
+                Ok(())
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = notification
+                    .extract(DidCloseTextDocument::METHOD)
+                    .map_err(err)?;
+                let uri = params.text_document.uri;
+                // frees the sources parsed for the document
+                self.documents.remove(&uri);
+                self.publish(uri, Vec::new(), None)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn update(&mut self, uri: Url, text: String, version: i32) -> Result<()> {
+        let mut sources = Sources::default();
+        let diagnostics = Language::of(&uri)
+            .map(|language| diagnostics(&self.env, &text, language, &mut sources))
+            .unwrap_or_default();
+        // replacing the document frees the sources parsed for its previous version
+        self.documents
+            .insert(uri.clone(), Document { text, sources });
+        self.publish(uri, diagnostics, Some(version))
+    }
+
+    fn publish(&self, uri: Url, diagnostics: Vec<Diagnostic>, version: Option<i32>) -> Result<()> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version,
+        };
+        self.send(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            params,
+        ))
+    }
+
+    /// looks up what the path refers to, using the `use` statements of the document
+    fn lookup(&self, tokens: &[tremor_script::lexer::Spanned], path: &PathAt) -> Option<Target> {
+        let imports = analysis::imports(tokens);
+        if path.module.is_empty() {
+            if let Some(def) = analysis::definitions(tokens)
+                .into_iter()
+                .find(|def| def.name == path.name)
+            {
+                return Some(Target::Local(def));
+            }
+        } else {
+            for id in analysis::module_candidates(&imports, &path.module) {
+                if let Some((file, src)) = module_source(&id) {
+                    let def = analysis::definitions(&analysis::tokens(&src))
+                        .into_iter()
+                        .find(|def| def.name == path.name);
+                    if let Some(def) = def {
+                        return Some(Target::Definition(file, src, def));
+                    }
+                }
+                if let Some((name, arity)) = self.registry_function(&id, &path.name) {
+                    return Some(Target::Function { name, arity });
+                }
+            }
+        }
+        // the path itself might be a module
+        let mut module = path.module.clone();
+        module.push(path.name.clone());
+        analysis::module_candidates(&imports, &module)
+            .iter()
+            .find_map(module_source)
+            .map(|(file, _)| Target::Module(file))
+    }
+
+    /// finds a function within the function registry, the registry does not know about `std`
+    fn registry_function(
+        &self,
+        module: &NodeId,
+        name: &str,
+    ) -> Option<(String, RangeInclusive<usize>)> {
+        let module = registry_module(module);
+        let function = self.env.fun.find(&module, name).ok()?;
+        Some((format!("{module}::{name}"), function.arity()))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let src = &self.documents.get(&text_document.uri)?.text;
+        let index = LineIndex::new(src);
+        let tokens = analysis::tokens(src);
+        let path = analysis::path_at(&tokens, index.offset(position))?;
+        let value = match self.lookup(&tokens, &path)? {
+            Target::Definition(_, src, def) if def.kind == SymbolKind::FUNCTION => {
+                module_fn_doc(&src, &def.name).map_or_else(|| def.markdown(), |doc| doc.to_string())
+            }
+            Target::Local(def) | Target::Definition(_, _, def) => def.markdown(),
+            Target::Module(file) => {
+                let src = std::fs::read_to_string(&file).ok()?;
+                let name = file.file_stem()?.to_string_lossy().to_string();
+                let doc = analysis::module_doc(&analysis::tokens(&src)).unwrap_or_default();
+                format!("\n# {name}\n\n{doc}\n")
+            }
+            Target::Function { name, arity } => {
+                format!(
+                    "\n### {name}\n\ntakes {} to {} arguments\n",
+                    arity.start(),
+                    arity.end()
+                )
+            }
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(index.range(path.start, path.end)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let src = &self.documents.get(&text_document.uri)?.text;
+        let index = LineIndex::new(src);
+        let tokens = analysis::tokens(src);
+        let path = analysis::path_at(&tokens, index.offset(position))?;
+        let location = match self.lookup(&tokens, &path)? {
+            Target::Local(def) => {
+                Location::new(text_document.uri, index.range(def.name_start, def.name_end))
+            }
+            Target::Definition(file, src, def) => Location::new(
+                Url::from_file_path(file).ok()?,
+                LineIndex::new(&src).range(def.name_start, def.name_end),
+            ),
+            Target::Module(file) => {
+                Location::new(Url::from_file_path(file).ok()?, Range::default())
+            }
+            Target::Function { .. } => return None,
+        };
+        Some(GotoDefinitionResponse::Scalar(location))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let src = &self.documents.get(&text_document.uri)?.text;
+        let index = LineIndex::new(src);
+        let tokens = analysis::tokens(src);
+        let path = analysis::path_at(&tokens, index.offset(position)).unwrap_or_default();
+        let imports = analysis::imports(&tokens);
+
+        let mut items = Vec::new();
+        let mut seen = HashSet::new();
+        let mut add = |item: CompletionItem| {
+            if seen.insert(item.label.clone()) {
+                items.push(item);
+            }
+        };
+        if path.module.is_empty() {
+            // local definitions, imported modules and the modules of the standard library
+            for def in analysis::definitions(&tokens) {
+                add(definition_item(&def));
+            }
+            let registry_modules = self.env.fun.modules().filter(|m| !m.contains("::"));
+            for module in imports
+                .iter()
+                .map(|import| import.alias.as_str())
+                .chain(registry_modules)
+            {
+                add(CompletionItem {
+                    label: module.to_string(),
+                    kind: Some(CompletionItemKind::MODULE),
+                    ..CompletionItem::default()
+                });
+            }
+        } else {
+            // members of the module
+            for id in analysis::module_candidates(&imports, &path.module) {
+                if let Some((_, src)) = module_source(&id) {
+                    for def in analysis::definitions(&analysis::tokens(&src)) {
+                        add(definition_item(&def));
+                    }
+                }
+                if let Some(functions) = self.env.fun.find_module(&registry_module(&id)) {
+                    for name in functions.keys() {
+                        add(CompletionItem {
+                            label: name.clone(),
+                            kind: Some(CompletionItemKind::FUNCTION),
+                            ..CompletionItem::default()
+                        });
+                    }
+                }
+            }
+        }
+        Some(CompletionResponse::Array(items))
+    }
+
+    // `DocumentSymbol::deprecated` is deprecated but needs to be set
+    #[allow(deprecated)]
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let uri = params.text_document.uri;
+        let src = &self.documents.get(&uri)?.text;
+        let index = LineIndex::new(src);
+        let symbols = analysis::definitions(&analysis::tokens(src))
+            .into_iter()
+            .map(|def| DocumentSymbol {
+                name: def.name.clone(),
+                detail: Some(def.detail()),
+                kind: def.kind,
+                tags: None,
+                deprecated: None,
+                range: index.range(def.start, def.name_end),
+                selection_range: index.range(def.name_start, def.name_end),
+                children: None,
+            })
+            .collect();
+        Some(DocumentSymbolResponse::Nested(symbols))
+    }
+}
+
+/// the file a module is loaded from, along with its source
+fn module_source(id: &NodeId) -> Option<(PathBuf, String)> {
+    let file = Manager::resolve(id).ok()??;
+    let src = std::fs::read_to_string(&file).ok()?;
+    Some((file, src))
+}
+
+/// name of a module within the function registry
+fn registry_module(id: &NodeId) -> String {
+    let module = id.module();
+    let module = if module.first().map(String::as_str) == Some("std") {
+        &module[1..]
+    } else {
+        module
+    };
+    module
+        .iter()
+        .map(String::as_str)
+        .chain(std::iter::once(id.id()))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+fn definition_item(def: &Definition) -> CompletionItem {
+    let kind = match def.kind {
+        SymbolKind::FUNCTION => CompletionItemKind::FUNCTION,
+        SymbolKind::CONSTANT => CompletionItemKind::CONSTANT,
+        SymbolKind::VARIABLE => CompletionItemKind::VARIABLE,
+        SymbolKind::EVENT => CompletionItemKind::EVENT,
+        SymbolKind::OPERATOR => CompletionItemKind::OPERATOR,
+        SymbolKind::STRUCT => CompletionItemKind::STRUCT,
+        SymbolKind::CLASS => CompletionItemKind::CLASS,
+        _ => CompletionItemKind::MODULE,
+    };
+    CompletionItem {
+        label: def.name.clone(),
+        kind: Some(kind),
+        detail: Some(def.detail()),
+        documentation: Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: def.markdown(),
+        })),
+        ..CompletionItem::default()
+    }
+}
+
+/// An error preventing a source from being parsed
+struct Failure {
+    error: ScriptError,
+    /// if the error is located within the source, and not within a `use`d module
+    local: bool,
+}
+
+impl Failure {
+    fn new(aid: arena::Index, error: ScriptError) -> Self {
+        let local = error.aid() == aid;
+        Self { error, local }
+    }
+
+    fn offset(&self) -> usize {
+        let (outer, inner) = self.error.context();
+        if self.local {
+            inner.or(outer).map_or(0, |span| span.start().absolute())
+        } else {
+            0
+        }
+    }
+}
+
+/// Parses a source, returning its warnings
+///
+/// The parsed sources are added to `sources`, the failure may reference them.
+fn check(
+    env: &TremorCliEnv,
+    src: &str,
+    language: Language,
+    sources: &mut Sources,
+) -> std::result::Result<Vec<Warning>, Failure> {
+    let result = match language {
+        Language::Script => Script::parse_with_aid(src, &env.fun).map(|script| {
+            sources.0.push(script.aid);
+            script.warnings().cloned().collect()
+        }),
+        Language::Query => Query::parse_with_aid(src, &env.fun, &env.aggr).map(|query| {
+            sources.0.push(query.aid);
+            query.warnings.into_iter().collect()
+        }),
+        Language::Deploy => Deploy::parse_with_aid(src, &env.fun, &env.aggr).map(|deploy| {
+            sources.0.push(deploy.aid);
+            deploy.warnings.into_iter().collect()
+        }),
+    };
+    match result {
+        Ok(warnings) => Ok(warnings),
+        Err(ErrorWithIndex(aid, error)) => {
+            sources.0.push(aid);
+            let failure = Failure::new(aid, error);
+            if language == Language::Query {
+                return Err(failure);
+            }
+            // tremor and troy files can be modules, we report the error of the parser that got further
+            match check_module(src, sources) {
+                Ok(()) => Ok(Vec::new()),
+                Err(module_failure) if module_failure.offset() > failure.offset() => {
+                    Err(module_failure)
+                }
+                Err(_) => Err(failure),
+            }
+        }
+    }
+}
+
+/// Parses a source as a module, adding it to `sources`
+fn check_module(src: &str, sources: &mut Sources) -> std::result::Result<(), Failure> {
+    let (aid, src) = sources
+        .insert(src)
+        .map_err(|e| Failure::new(arena::Index::INVALID, e))?;
+    Module::load(Id::from(src.as_bytes()), &mut Vec::new(), aid, src)
+        .map(drop)
+        .map_err(|e| Failure::new(aid, e))
+}
+
+/// The documentation of a function, as collected when compiling the module it is defined in
+fn module_fn_doc(src: &str, name: &str) -> Option<FnDoc> {
+    let mut sources = Sources::default();
+    let (aid, src) = sources.insert(src).ok()?;
+    // the module is dropped before the sources are, only the owned docs outlive them
+    let module = Module::load(Id::from(src.as_bytes()), &mut Vec::new(), aid, src).ok()?;
+    module.docs.fns.into_iter().find(|f| f.name == name)
+}
+
+fn free(result: tremor_script::Result<()>) {
+    if let Err(e) = result {
+        warn!("Failed to free parsed source: {e}");
+    }
+}
+
+/// Diagnostics of a source, they are owned and do not reference `sources`
+fn diagnostics(
+    env: &TremorCliEnv,
+    src: &str,
+    language: Language,
+    sources: &mut Sources,
+) -> Vec<Diagnostic> {
+    let index = LineIndex::new(src);
+    match check(env, src, language, sources) {
+        Ok(warnings) => warnings
+            .into_iter()
+            .map(|warning| Diagnostic {
+                range: index.range(
+                    warning.inner.start().absolute(),
+                    warning.inner.end().absolute(),
+                ),
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String(warning.rule.to_string())),
+                source: Some(SOURCE.to_string()),
+                message: warning.msg,
+                ..Diagnostic::default()
+            })
+            .collect(),
+        Err(failure) => {
+            let (outer, inner) = failure.error.context();
+            let range = match inner.or(outer) {
+                Some(span) if failure.local => {
+                    index.range(span.start().absolute(), span.end().absolute())
+                }
+                _ => Range::default(),
+            };
+            let mut message = failure.error.to_string();
+            if !failure.local {
+                message = format!("Error in a used module: {message}");
+            }
+            if let Some(hint) = failure.error.hint() {
+                message = format!("{message}\n\nNOTE: {hint}");
+            }
+            vec![Diagnostic {
+                range,
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some(SOURCE.to_string()),
+                message,
+                ..Diagnostic::default()
+            }]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_diagnostics() -> Result<()> {
+        let env = env::setup()?;
+        let errors = |src, language| {
+            diagnostics(&env, src, language, &mut Sources::default())
+                .into_iter()
+                .filter(|d| d.severity == Some(DiagnosticSeverity::ERROR))
+                .count()
+        };
+        assert_eq!(0, errors("let a = 1;\na\n", Language::Script));
+
+        let d = diagnostics(
+            &env,
+            "let a = 1;\nlet b = ;\n",
+            Language::Script,
+            &mut Sources::default(),
+        );
+        assert_eq!(1, d.len());
+        assert_eq!(Some(DiagnosticSeverity::ERROR), d[0].severity);
+        assert_eq!(1, d[0].range.start.line);
+
+        // module files are no scripts, but valid
+        let module = "## the length\nintrinsic fn len(input) as string::len;\n";
+        assert_eq!(0, errors(module, Language::Script));
+        Ok(())
+    }
+
+    #[test]
+    fn hover_fn_docs() -> Result<()> {
+        let dir = temp_dir::TempDir::new()?;
+        std::fs::write(
+            dir.child("hover_docs.tremor"),
+            "## Adds two numbers\n##   and returns the sum\nfn add(a, b) with\n  a + b\nend;\n",
+        )?;
+        Manager::add_path(&dir.path().to_string_lossy())?;
+        let (connection, _client) = Connection::memory();
+        let mut server = Server {
+            connection,
+            env: env::setup()?,
+            documents: HashMap::new(),
+        };
+        let uri = Url::parse("file:///hover.tremor").map_err(err)?;
+        server.update(
+            uri.clone(),
+            "use hover_docs;\nhover_docs::add(1, 2)\n".to_string(),
+            1,
+        )?;
+        let hover = server
+            .hover(HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: lsp_types::TextDocumentIdentifier { uri },
+                    position: lsp_types::Position::new(1, 13),
+                },
+                work_done_progress_params: lsp_types::WorkDoneProgressParams::default(),
+            })
+            .ok_or("no hover")?;
+        if let HoverContents::Markup(content) = hover.contents {
+            assert_eq!(
+                "\n### add(a, b)\n\nAdds two numbers\nand returns the sum\n",
+                content.value
+            );
+        } else {
+            return Err("hover is not markdown".into());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn document_sources() -> Result<()> {
+        let (connection, client) = Connection::memory();
+        let mut server = Server {
+            connection,
+            env: env::setup()?,
+            documents: HashMap::new(),
+        };
+        let uri = Url::parse("file:///sources.tremor").map_err(err)?;
+        let sources = |server: &Server| {
+            server
+                .documents
+                .get(&uri)
+                .map(|doc| doc.sources.0.clone())
+                .unwrap_or_default()
+        };
+        let alive = |aids: &[arena::Index]| -> Result<bool> {
+            for aid in aids {
+                if Arena::get(*aid)?.is_none() {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        };
+
+        // the sources of a document with errors stay around while it is open
+        server.handle_notification(Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem::new(
+                    uri.clone(),
+                    "tremor".to_string(),
+                    1,
+                    "let b = ;\n".to_string(),
+                ),
+            },
+        ))?;
+        let opened = sources(&server);
+        assert!(!opened.is_empty());
+        assert!(alive(&opened)?);
+        if let Ok(Message::Notification(n)) = client.receiver.try_recv() {
+            let params: PublishDiagnosticsParams = serde_json::from_value(n.params).map_err(err)?;
+            assert_eq!(1, params.diagnostics.len());
+        } else {
+            return Err("no diagnostics published".into());
+        }
+
+        // replacing the document frees the sources of the previous version
+        server.handle_notification(Notification::new(
+            DidChangeTextDocument::METHOD.to_string(),
+            DidChangeTextDocumentParams {
+                text_document: lsp_types::VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+                content_changes: vec![lsp_types::TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: "let b = 1;\nb\n".to_string(),
+                }],
+            },
+        ))?;
+        let changed = sources(&server);
+        assert!(!alive(&opened)?);
+        assert!(alive(&changed)?);
+
+        // as does closing it
+        server.handle_notification(Notification::new(
+            DidCloseTextDocument::METHOD.to_string(),
+            DidCloseTextDocumentParams {
+                text_document: lsp_types::TextDocumentIdentifier::new(uri.clone()),
+            },
+        ))?;
+        assert!(sources(&server).is_empty());
+        assert!(!alive(&changed)?);
+        Ok(())
+    }
+
+    #[test]
+    fn deploy_diagnostics() -> Result<()> {
+        let env = env::setup()?;
+        let d = diagnostics(
+            &env,
+            "define flow test\nflow\n  create connector out from unknown_connector;\nend;\ndeploy flow test;",
+            Language::Deploy,
+            &mut Sources::default(),
+        );
+        assert_eq!(1, d.len());
+        assert_eq!(Some(DiagnosticSeverity::ERROR), d[0].severity);
+        Ok(())
+    }
+
+    #[test]
+    fn registry_modules() {
+        assert_eq!(
+            "string",
+            registry_module(&NodeId::new(&"string", &["std".to_string()]))
+        );
+        assert_eq!(
+            "time::nanos",
+            registry_module(&NodeId::new(
+                &"nanos",
+                &["std".to_string(), "time".to_string()]
+            ))
+        );
+    }
+}
@@ -0,0 +1,522 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Token based source analysis
+//!
+//! Symbols, `use` statements and paths are taken from the token stream instead of the AST,
+//! so they are still available while a source is being edited and does not parse.
+
+use lsp_types::{Position, Range, SymbolKind};
+use tremor_script::arena;
+use tremor_script::ast::{docs::FnDoc, NodeId};
+use tremor_script::lexer::{Lexer, Spanned, Token};
+
+/// Maps byte offsets within a source to LSP positions and back
+pub(crate) struct LineIndex<'src> {
+    src: &'src str,
+    /// byte offsets of the start of every line
+    lines: Vec<usize>,
+}
+
+impl<'src> LineIndex<'src> {
+    pub(crate) fn new(src: &'src str) -> Self {
+        let lines = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { src, lines }
+    }
+
+    /// position of a byte offset, columns are counted in utf-16 code units
+    pub(crate) fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.src.len());
+        let line = match self.lines.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line.saturating_sub(1),
+        };
+        let start = self.lines.get(line).copied().unwrap_or_default();
+        let character = self
+            .src
+            .get(start..offset)
+            .map_or(0, |s| s.encode_utf16().count());
+        Position::new(to_u32(line), to_u32(character))
+    }
+
+    /// byte offset of a position, clamped to the end of its line
+    pub(crate) fn offset(&self, position: Position) -> usize {
+        let start = match self.lines.get(position.line as usize) {
+            Some(start) => *start,
+            None => return self.src.len(),
+        };
+        let line = self.src[start..].split('\n').next().unwrap_or_default();
+        let mut units = 0;
+        for (i, c) in line.char_indices() {
+            if units >= position.character as usize {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        start + line.len()
+    }
+
+    pub(crate) fn range(&self, start: usize, end: usize) -> Range {
+        Range::new(self.position(start), self.position(end))
+    }
+}
+
+fn to_u32(n: usize) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
+
+/// Significant tokens of a source, lexing stops at the first lexer error
+pub(crate) fn tokens(src: &str) -> Vec<Spanned> {
+    Lexer::new(src, arena::Index::INVALID)
+        .tokenize_until_err()
+        .filter(|t| {
+            !matches!(
+                t.value,
+                Token::Whitespace(_) | Token::NewLine | Token::SingleLineComment(_)
+            )
+        })
+        .collect()
+}
+
+fn start(token: &Spanned) -> usize {
+    token.span.start().absolute()
+}
+
+fn end(token: &Spanned) -> usize {
+    token.span.end().absolute()
+}
+
+fn ident<'tkn>(token: Option<&'tkn Spanned>) -> Option<&'tkn str> {
+    match token.map(|t| &t.value) {
+        Some(Token::Ident(id, _)) => Some(id.as_ref()),
+        _ => None,
+    }
+}
+
+/// A definition within a source
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Definition {
+    pub(crate) name: String,
+    /// what is defined, like `connector` or `fn`
+    pub(crate) what: &'static str,
+    pub(crate) kind: SymbolKind,
+    /// arguments of functions
+    pub(crate) args: Vec<String>,
+    /// the doc comment preceeding the definition
+    pub(crate) doc: Option<String>,
+    /// byte offset of the start of the definition
+    pub(crate) start: usize,
+    /// byte offsets of the defined name
+    pub(crate) name_start: usize,
+    pub(crate) name_end: usize,
+}
+
+impl Definition {
+    /// markdown documentation of the definition
+    pub(crate) fn markdown(&self) -> String {
+        if self.kind == SymbolKind::FUNCTION {
+            FnDoc {
+                name: self.name.clone(),
+                args: self.args.clone(),
+                doc: self.doc.clone(),
+                open: false,
+            }
+            .to_string()
+        } else {
+            format!(
+                "\n### {} {}\n\n{}\n",
+                self.what,
+                self.name,
+                self.doc.clone().unwrap_or_default()
+            )
+        }
+    }
+
+    /// signature of the definition
+    pub(crate) fn detail(&self) -> String {
+        if self.kind == SymbolKind::FUNCTION {
+            format!("fn {}({})", self.name, self.args.join(", "))
+        } else {
+            format!("{} {}", self.what, self.name)
+        }
+    }
+}
+
+fn defined(token: &Token) -> Option<(&'static str, SymbolKind)> {
+    Some(match token {
+        Token::Flow => ("flow", SymbolKind::NAMESPACE),
+        Token::Connector => ("connector", SymbolKind::CLASS),
+        Token::Pipeline => ("pipeline", SymbolKind::MODULE),
+        Token::Script => ("script", SymbolKind::FUNCTION),
+        Token::Operator => ("operator", SymbolKind::OPERATOR),
+        Token::Window => ("window", SymbolKind::STRUCT),
+        Token::Stream => ("stream", SymbolKind::EVENT),
+        _ => return None,
+    })
+}
+
+fn doc_before(tokens: &[Spanned], idx: usize) -> Option<String> {
+    let lines: Vec<_> = tokens[..idx]
+        .iter()
+        .rev()
+        .map_while(|t| match t.value {
+            Token::DocComment(line) => Some(line.strip_prefix(' ').unwrap_or(line)),
+            _ => None,
+        })
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.into_iter().rev().collect::<Vec<_>>().join("\n"))
+    }
+}
+
+fn fn_args(tokens: &[Spanned]) -> Vec<String> {
+    let mut args = Vec::new();
+    if matches!(tokens.first().map(|t| &t.value), Some(Token::LParen)) {
+        for t in &tokens[1..] {
+            match &t.value {
+                Token::Ident(id, _) => args.push(id.to_string()),
+                Token::Dot => {
+                    // varargs
+                    if args.last().map(String::as_str) != Some("...") {
+                        args.push("...".to_string());
+                    }
+                }
+                Token::Comma => (),
+                _ => break,
+            }
+        }
+    }
+    args
+}
+
+/// All definitions within a source: functions, constants, `define`d and `create`d entities and deployed flows
+pub(crate) fn definitions(tokens: &[Spanned]) -> Vec<Definition> {
+    let mut defs = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let (what, kind, def_start, name_idx) = match token.value {
+            Token::Fun => {
+                // `intrinsic fn` starts at `intrinsic`
+                let def_start = match i.checked_sub(1).map(|p| &tokens[p].value) {
+                    Some(Token::Intrinsic) => i - 1,
+                    _ => i,
+                };
+                ("fn", SymbolKind::FUNCTION, def_start, i + 1)
+            }
+            Token::Const => ("const", SymbolKind::CONSTANT, i, i + 1),
+            Token::Define | Token::Create | Token::Deploy => {
+                if let Some((what, kind)) = tokens.get(i + 1).and_then(|t| defined(&t.value)) {
+                    let kind = if matches!(token.value, Token::Define) {
+                        kind
+                    } else {
+                        SymbolKind::VARIABLE
+                    };
+                    (what, kind, i, i + 2)
+                } else {
+                    continue;
+                }
+            }
+            _ => continue,
+        };
+        if let Some(name) = ident(tokens.get(name_idx)) {
+            let name_token = &tokens[name_idx];
+            let args = if kind == SymbolKind::FUNCTION {
+                fn_args(&tokens[name_idx + 1..])
+            } else {
+                Vec::new()
+            };
+            defs.push(Definition {
+                name: name.to_string(),
+                what,
+                kind,
+                args,
+                doc: doc_before(tokens, def_start),
+                start: start(&tokens[def_start]),
+                name_start: start(name_token),
+                name_end: end(name_token),
+            });
+        }
+    }
+    defs
+}
+
+/// The module level documentation of a source
+pub(crate) fn module_doc(tokens: &[Spanned]) -> Option<String> {
+    let lines: Vec<_> = tokens
+        .iter()
+        .filter_map(|t| match t.value {
+            Token::ModComment(line) => Some(line.strip_prefix(' ').unwrap_or(line)),
+            _ => None,
+        })
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// A `use` statement
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Import {
+    /// the name the module is available as
+    pub(crate) alias: String,
+    /// full path of the module
+    pub(crate) path: Vec<String>,
+}
+
+/// All `use` statements within a source
+pub(crate) fn imports(tokens: &[Spanned]) -> Vec<Import> {
+    let mut imports = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.value != Token::Use {
+            continue;
+        }
+        let mut path = Vec::new();
+        let mut rest = tokens[i + 1..].iter();
+        while let Some(id) = ident(rest.next()) {
+            path.push(id.to_string());
+            match rest.next().map(|t| &t.value) {
+                Some(Token::ColonColon) => continue,
+                Some(Token::As) => {
+                    if let Some(alias) = ident(rest.next()) {
+                        imports.push(Import {
+                            alias: alias.to_string(),
+                            path: path.clone(),
+                        });
+                    }
+                    break;
+                }
+                _ => {
+                    if let Some(alias) = path.last() {
+                        imports.push(Import {
+                            alias: alias.clone(),
+                            path: path.clone(),
+                        });
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    imports
+}
+
+/// A (partial) path like `string::for` at a position in a source
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct PathAt {
+    /// module segments of the path
+    pub(crate) module: Vec<String>,
+    /// last segment of the path, empty if the path ends in `::`
+    pub(crate) name: String,
+    /// byte offsets of the path
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+/// The path at, or ending right before, a byte offset
+pub(crate) fn path_at(tokens: &[Spanned], offset: usize) -> Option<PathAt> {
+    let idx = tokens
+        .iter()
+        .position(|t| start(t) <= offset && offset <= end(t) && ident(Some(t)).is_some())
+        .or_else(|| {
+            tokens
+                .iter()
+                .rposition(|t| end(t) <= offset && t.value == Token::ColonColon)
+                .filter(|i| end(&tokens[*i]) == offset)
+        })?;
+    let mut path = PathAt {
+        start: start(&tokens[idx]),
+        end: end(&tokens[idx]),
+        ..PathAt::default()
+    };
+    let mut i = idx;
+    if let Some(name) = ident(tokens.get(idx)) {
+        path.name = name.to_string();
+    } else {
+        // we are right after a `::`
+        i += 1;
+    }
+    while i >= 2 && tokens[i - 1].value == Token::ColonColon {
+        if let Some(module) = ident(tokens.get(i - 2)) {
+            path.module.insert(0, module.to_string());
+            path.start = start(&tokens[i - 2]);
+            i -= 2;
+        } else {
+            break;
+        }
+    }
+    Some(path)
+}
+
+/// Candidates for the module a path refers to, the path is resolved using the `use` statements of the source.
+/// Functions of the standard library can be used without importing them, so the module is
+/// also looked for within `std`.
+pub(crate) fn module_candidates(imports: &[Import], module: &[String]) -> Vec<NodeId> {
+    let (first, rest) = match module.split_first() {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+    let mut candidates: Vec<Vec<String>> = Vec::new();
+    for import in imports.iter().filter(|i| &i.alias == first) {
+        candidates.push(import.path.iter().chain(rest).cloned().collect::<Vec<_>>());
+    }
+    candidates.push(module.to_vec());
+    candidates.push(
+        std::iter::once("std".to_string())
+            .chain(module.iter().cloned())
+            .collect(),
+    );
+    candidates
+        .into_iter()
+        .filter_map(|mut path| {
+            let id = path.pop()?;
+            Some(NodeId::new(&id, &path))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_index() {
+        let src = "let a = 1;\nlet ü = \"😀x\";\n";
+        let idx = LineIndex::new(src);
+        assert_eq!(Position::new(0, 0), idx.position(0));
+        assert_eq!(Position::new(1, 0), idx.position(11));
+        let x = src.find('x').unwrap_or_default();
+        // the emoji takes up two utf-16 code units
+        assert_eq!(Position::new(1, 11), idx.position(x));
+        assert_eq!(x, idx.offset(Position::new(1, 11)));
+        assert_eq!(11, idx.offset(Position::new(1, 0)));
+        assert_eq!(10, idx.offset(Position::new(0, 100)));
+        assert_eq!(src.len(), idx.offset(Position::new(10, 0)));
+    }
+
+    #[test]
+    fn definitions_and_imports() {
+        let src = r#"
+use std::string;
+use std::time::nanos as ns;
+
+## Adds two numbers
+## and returns the sum
+fn add(a, b) with
+  a + b
+end;
+
+const answer = 42;
+
+## does things
+intrinsic fn format(format, ...) as string::format;
+
+define flow main
+flow
+  define connector out from stdio;
+  create connector out;
+end;
+deploy flow main;
+"#;
+        let tokens = tokens(src);
+        let defs = definitions(&tokens);
+        let names: Vec<_> = defs.iter().map(|d| (d.what, d.name.as_str())).collect();
+        assert_eq!(
+            vec![
+                ("fn", "add"),
+                ("const", "answer"),
+                ("fn", "format"),
+                ("flow", "main"),
+                ("connector", "out"),
+                ("connector", "out"),
+                ("flow", "main"),
+            ],
+            names
+        );
+        let add = &defs[0];
+        assert_eq!(vec!["a", "b"], add.args);
+        assert_eq!(
+            Some("Adds two numbers\nand returns the sum".to_string()),
+            add.doc
+        );
+        assert_eq!("fn add(a, b)", add.detail());
+        assert_eq!(&src[add.name_start..add.name_end], "add");
+        assert_eq!(vec!["format", "..."], defs[2].args);
+        assert_eq!(Some("does things".to_string()), defs[2].doc);
+        assert_eq!(src.find("intrinsic").unwrap_or_default(), defs[2].start);
+        assert_eq!(SymbolKind::CLASS, defs[4].kind);
+        assert_eq!(SymbolKind::VARIABLE, defs[5].kind);
+        assert_eq!(None, module_doc(&tokens));
+        assert_eq!(
+            Some("a module\nwith docs".to_string()),
+            module_doc(&super::tokens("### a module\n### with docs\nconst a = 1;"))
+        );
+
+        assert_eq!(
+            vec![
+                Import {
+                    alias: "string".to_string(),
+                    path: vec!["std".to_string(), "string".to_string()]
+                },
+                Import {
+                    alias: "ns".to_string(),
+                    path: vec!["std".to_string(), "time".to_string(), "nanos".to_string()]
+                }
+            ],
+            imports(&tokens)
+        );
+    }
+
+    #[test]
+    fn paths() {
+        let src = "string::format(x); ns::";
+        let tokens = tokens(src);
+        let path = path_at(&tokens, 10).unwrap_or_default();
+        assert_eq!(vec!["string"], path.module);
+        assert_eq!("format", path.name);
+        assert_eq!((0, 14), (path.start, path.end));
+
+        let path = path_at(&tokens, 2).unwrap_or_default();
+        assert!(path.module.is_empty());
+        assert_eq!("string", path.name);
+
+        let path = path_at(&tokens, src.len()).unwrap_or_default();
+        assert_eq!(vec!["ns"], path.module);
+        assert_eq!("", path.name);
+
+        assert_eq!(None, path_at(&tokens, 17));
+    }
+
+    #[test]
+    fn candidates() {
+        let imports = vec![Import {
+            alias: "ns".to_string(),
+            path: vec!["std".to_string(), "time".to_string(), "nanos".to_string()],
+        }];
+        let c = module_candidates(&imports, &["ns".to_string()]);
+        assert_eq!(
+            vec![
+                NodeId::new(&"nanos", &["std".to_string(), "time".to_string()]),
+                NodeId::new(&"ns", &[]),
+                NodeId::new(&"ns", &["std".to_string()]),
+            ],
+            c
+        );
+        assert!(module_candidates(&imports, &[]).is_empty());
+    }
+}
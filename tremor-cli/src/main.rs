@@ -39,10 +39,17 @@ use tremor_common::file;
 
 mod alloc;
 mod completions;
+mod dap;
 mod debug;
 mod doc;
 mod env;
 mod errors;
+mod fmt;
+mod lint;
+#[cfg(feature = "lsp")]
+mod lsp;
+#[cfg(feature = "repl")]
+mod repl;
 // mod explain;
 pub(crate) mod cli;
 mod report;
@@ -125,6 +132,12 @@ async fn run(cli: Cli) -> Result<()> {
         Command::Dbg(d) => d.run(),
         Command::Run(r) => r.run().await,
         Command::Doc(d) => d.run(),
+        Command::Fmt(f) => f.run(),
+        Command::Lint(l) => l.run(),
+        #[cfg(feature = "lsp")]
+        Command::Lsp => lsp::run(),
+        #[cfg(feature = "repl")]
+        Command::Repl(r) => r.run(),
         Command::New { name } => create_template(std::env::current_dir()?, &name),
     }
 }
@@ -0,0 +1,566 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli::Repl;
+use crate::env::{self, TremorCliEnv};
+use crate::errors::{Error, Result};
+use crate::util::highlight;
+use halfbrown::HashMap;
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter as LineHighlighter,
+    hint::Hinter, validate::Validator, Editor, Helper,
+};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use tremor_common::{ids::OperatorIdGen, time::nanotime};
+use tremor_pipeline::{Event, EventId, ExecutableGraph};
+use tremor_script::{
+    arena::{self, Arena},
+    ast::helper::Warning,
+    ctx::EventContext,
+    errors::{Error as ScriptError, ErrorKind as ScriptErrorKind},
+    highlighter::{Dumb as DumbHighlighter, Highlighter, Term as TermHighlighter},
+    lexer::Lexer,
+    lint::Rule,
+    module::Manager,
+    prelude::*,
+    query::Query,
+    script::{AggrType, Return, Script},
+    EventPayload, Value, ValueAndMeta,
+};
+
+const HELP: &str = r#"Enter tremor-script expressions, or trickle statements in query mode.
+
+Commands:
+  :event [expr]   show or set the current event
+  :meta [expr]    show or set the current metadata
+  :state [expr]   show or set the state
+  :load <path>    load a module file, or add a directory to the module path
+  :script         evaluate input as tremor-script (default)
+  :query          evaluate input as trickle statements
+  :send           send the current event through the query
+  :reset          forget bindings, state, loaded modules and the query
+  :help           show this help
+  :quit           leave the repl
+"#;
+
+/// Warnings that are noise when entering one expression at a time
+const QUIET: [Rule; 2] = [Rule::UnusedLet, Rule::UnusedDefine];
+
+/// What input is evaluated as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Script,
+    Query,
+}
+
+/// Result of evaluating a line of input
+#[derive(Debug, PartialEq)]
+enum Output {
+    /// A value to print
+    Value(Value<'static>),
+    /// Values emitted on a port
+    Emitted(Vec<(String, Value<'static>)>),
+    /// The event was dropped
+    Drop,
+    /// A message for the user
+    Info(String),
+    /// Nothing to print
+    Nothing,
+    /// The user asked to leave
+    Quit,
+}
+
+/// The state kept across inputs
+struct Session {
+    env: TremorCliEnv,
+    mode: Mode,
+    event: Value<'static>,
+    meta: Value<'static>,
+    state: Value<'static>,
+    locals: HashMap<String, Value<'static>>,
+    uses: Vec<String>,
+    statements: Vec<String>,
+    pipeline: Option<ExecutableGraph>,
+    event_id: u64,
+}
+
+impl Session {
+    fn new(env: TremorCliEnv, mode: Mode) -> Self {
+        Self {
+            env,
+            mode,
+            event: Value::object(),
+            meta: Value::object(),
+            state: Value::null(),
+            locals: HashMap::new(),
+            uses: Vec::new(),
+            statements: Vec::new(),
+            pipeline: None,
+            event_id: 0,
+        }
+    }
+
+    fn prompt(&self) -> &'static str {
+        match self.mode {
+            Mode::Script => "tremor> ",
+            Mode::Query => "trickle> ",
+        }
+    }
+
+    /// Evaluates a command or a (complete) input
+    fn eval(&mut self, input: &str) -> Result<Output> {
+        let input = input.trim();
+        if let Some(cmd) = input.strip_prefix(':') {
+            let (cmd, arg) = cmd.split_once(char::is_whitespace).unwrap_or((cmd, ""));
+            self.command(cmd, arg.trim())
+        } else if input.is_empty() {
+            Ok(Output::Nothing)
+        } else if self.mode == Mode::Script {
+            self.run_script(input)
+        } else {
+            self.add_statement(input)
+        }
+    }
+
+    fn command(&mut self, cmd: &str, arg: &str) -> Result<Output> {
+        match (cmd, arg) {
+            ("q" | "quit" | "exit", _) => Ok(Output::Quit),
+            ("h" | "help", _) => Ok(Output::Info(HELP.to_string())),
+            ("event", "") => Ok(Output::Value(self.event.clone())),
+            ("meta", "") => Ok(Output::Value(self.meta.clone())),
+            ("state", "") => Ok(Output::Value(self.state.clone())),
+            ("event", expr) => {
+                self.event = self.value_of(expr)?;
+                Ok(Output::Value(self.event.clone()))
+            }
+            ("meta", expr) => {
+                self.meta = self.value_of(expr)?;
+                Ok(Output::Value(self.meta.clone()))
+            }
+            ("state", expr) => {
+                self.state = self.value_of(expr)?;
+                Ok(Output::Value(self.state.clone()))
+            }
+            ("load", "") => Err("`:load` needs a module file or directory".into()),
+            ("load", path) => self.load(path),
+            ("script", _) => {
+                self.mode = Mode::Script;
+                Ok(Output::Nothing)
+            }
+            ("query", _) => {
+                self.mode = Mode::Query;
+                Ok(Output::Nothing)
+            }
+            ("send", _) => self.send(),
+            ("reset", _) => {
+                self.state = Value::null();
+                self.locals.clear();
+                self.uses.clear();
+                self.statements.clear();
+                self.pipeline = None;
+                Ok(Output::Info("Session reset".to_string()))
+            }
+            (other, _) => Err(format!("Unknown command `:{}`, try `:help`", other).into()),
+        }
+    }
+
+    /// Source with the `use` statements of all loaded modules prepended
+    fn source(&self, input: &str) -> String {
+        let mut src = String::new();
+        for stmt in &self.uses {
+            src.push_str(stmt);
+            src.push('\n');
+        }
+        src.push_str(input);
+        src
+    }
+
+    fn parse_script(&self, input: &str) -> Result<Script> {
+        let script = Script::parse(&self.source(input), &self.env.fun)?;
+        print_warnings(script.aid, script.warnings())?;
+        Ok(script)
+    }
+
+    fn run_script(&mut self, input: &str) -> Result<Output> {
+        let script = self.parse_script(input)?;
+        let ret = script.run_with_locals(
+            &EventContext::new(nanotime(), None),
+            AggrType::Emit,
+            &mut self.event,
+            &mut self.state,
+            &mut self.meta,
+            &mut self.locals,
+        )?;
+        Ok(match ret {
+            Return::Emit { value, port: None } => Output::Value(value),
+            Return::Emit {
+                value,
+                port: Some(port),
+            } => Output::Emitted(vec![(port, value)]),
+            Return::EmitEvent { port } => Output::Emitted(vec![(
+                port.unwrap_or_else(|| "out".to_string()),
+                self.event.clone(),
+            )]),
+            Return::Drop => Output::Drop,
+        })
+    }
+
+    /// Evaluates an expression without touching the current event, metadata or state
+    fn value_of(&self, expr: &str) -> Result<Value<'static>> {
+        let script = self.parse_script(expr)?;
+        let mut event = self.event.clone();
+        let mut meta = self.meta.clone();
+        let mut state = self.state.clone();
+        let mut locals = self.locals.clone();
+        match script.run_with_locals(
+            &EventContext::new(nanotime(), None),
+            AggrType::Emit,
+            &mut event,
+            &mut state,
+            &mut meta,
+            &mut locals,
+        )? {
+            Return::Emit { value, .. } => Ok(value),
+            Return::EmitEvent { .. } => Ok(event),
+            Return::Drop => Err("The expression dropped the event".into()),
+        }
+    }
+
+    fn load(&mut self, path: &str) -> Result<Output> {
+        let p = Path::new(path);
+        if p.is_dir() {
+            Manager::add_path(&path)?;
+            return Ok(Output::Info(format!("Added `{}` to the module path", path)));
+        }
+        let module = if p.is_file() {
+            let dir = p
+                .parent()
+                .filter(|d| !d.as_os_str().is_empty())
+                .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+            Manager::add_path(&dir.to_string_lossy())?;
+            p.file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .ok_or_else(|| Error::from(format!("`{}` is not a module file", path)))?
+        } else {
+            // a module on the module path, e.g. `std::string`
+            path.to_string()
+        };
+        let stmt = format!("use {};", module);
+        // make sure the module can be loaded before remembering it
+        Script::parse(&format!("{}\nnull", stmt), &self.env.fun)?;
+        if !self.uses.contains(&stmt) {
+            self.uses.push(stmt);
+        }
+        Ok(Output::Info(format!("Loaded module `{}`", module)))
+    }
+
+    /// Adds a trickle statement to the query, rebuilding the pipeline
+    fn add_statement(&mut self, stmt: &str) -> Result<Output> {
+        let mut statements = self.statements.clone();
+        statements.push(stmt.to_string());
+        let query = Query::parse(
+            &self.source(&statements.join("\n")),
+            &self.env.fun,
+            &self.env.aggr,
+        )?;
+        print_warnings(query.aid, query.warnings.iter())?;
+        let pipeline = tremor_pipeline::query::Query(query).to_pipe(&mut OperatorIdGen::new())?;
+        self.statements = statements;
+        self.pipeline = Some(pipeline);
+        Ok(Output::Nothing)
+    }
+
+    /// Sends the current event through the query
+    fn send(&mut self) -> Result<Output> {
+        let pipeline = self
+            .pipeline
+            .as_mut()
+            .ok_or_else(|| Error::from("There is no query yet, switch to it with `:query`"))?;
+        let (event, meta) = (self.event.clone(), self.meta.clone());
+        let data = EventPayload::new(vec![], |_| ValueAndMeta::from_parts(event, meta));
+        let mut continuation = vec![];
+        async_std::task::block_on(pipeline.enqueue(
+            "in",
+            Event {
+                id: EventId::from_id(0, 0, self.event_id),
+                data,
+                ingest_ns: nanotime(),
+                ..Event::default()
+            },
+            &mut continuation,
+        ))?;
+        self.event_id += 1;
+        Ok(Output::Emitted(
+            continuation
+                .into_iter()
+                .map(|(port, e)| (port.to_string(), e.data.suffix().value().clone_static()))
+                .collect(),
+        ))
+    }
+}
+
+/// Prints the warnings that are relevant for interactive use
+fn print_warnings<'w>(
+    aid: arena::Index,
+    warnings: impl Iterator<Item = &'w Warning>,
+) -> Result<()> {
+    let mut h = TermHighlighter::stderr();
+    for w in warnings.filter(|w| !QUIET.contains(&w.rule)) {
+        let tokens: Vec<_> = Lexer::new(Arena::io_get(aid)?, aid)
+            .tokenize_until_err()
+            .collect();
+        h.highlight_error(None, &tokens, "", true, Some(w.outer), Some(w.into()))?;
+    }
+    h.finalize()?;
+    Ok(())
+}
+
+/// Tests if an error is caused by input that is not finished yet
+fn is_incomplete(e: &Error) -> bool {
+    match e.kind() {
+        crate::errors::ErrorKind::Script(kind) => {
+            matches!(
+                kind,
+                ScriptErrorKind::UnexpectedEndOfStream(_)
+                    | ScriptErrorKind::UnterminatedHereDoc(..)
+                    | ScriptErrorKind::UnterminatedStringLiteral(..)
+                    | ScriptErrorKind::UnterminatedInterpolation(..)
+            ) || matches!(kind, ScriptErrorKind::ParserError(msg) if msg.starts_with("UnrecognizedEOF"))
+        }
+        _ => false,
+    }
+}
+
+fn print_error(e: Error) {
+    match e {
+        Error(crate::errors::ErrorKind::Script(kind), _) => {
+            let e = ScriptError::from(kind);
+            if let Err(highlight_error) = TermHighlighter::stderr().format_error(&e) {
+                eprintln!("error: {} ({})", e, highlight_error);
+            }
+        }
+        e => eprintln!("error: {}", e),
+    }
+}
+
+fn print(output: Output) -> Result<()> {
+    match output {
+        Output::Value(value) => highlight(true, &value)?,
+        Output::Emitted(values) => {
+            for (port, value) in values {
+                println!("{}:", port);
+                highlight(true, &value)?;
+            }
+        }
+        Output::Drop => println!("dropped"),
+        Output::Info(msg) => println!("{}", msg),
+        Output::Nothing | Output::Quit => (),
+    }
+    Ok(())
+}
+
+/// Highlights the input while it is typed
+struct InputHighlighter;
+
+impl LineHighlighter for InputHighlighter {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        highlight_input(line, &mut TermHighlighter::stdout())
+            .map_or(Cow::Borrowed(line), Cow::Owned)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+impl Completer for InputHighlighter {
+    type Candidate = String;
+}
+
+impl Hinter for InputHighlighter {
+    type Hint = String;
+}
+
+impl Validator for InputHighlighter {}
+
+impl Helper for InputHighlighter {}
+
+/// Highlights a line of input with `h`, commands and input that can't be
+/// tokenized yet are left alone
+fn highlight_input<H: Highlighter + ToString>(line: &str, h: &mut H) -> Option<String> {
+    if line.trim_start().starts_with(':') {
+        return None;
+    }
+    let tokens = Lexer::new(line, arena::Index::INVALID)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .ok()?;
+    // the editor relies on the highlighted line showing exactly the input
+    let mut plain = DumbHighlighter::new();
+    plain.highlight(None, &tokens, "", false, None).ok()?;
+    if plain.to_string().strip_suffix('\n') != Some(line) {
+        return None;
+    }
+    h.highlight(None, &tokens, "", false, None).ok()?;
+    let highlighted = h.to_string();
+    Some(
+        highlighted
+            .strip_suffix('\n')
+            .unwrap_or(&highlighted)
+            .to_string(),
+    )
+}
+
+fn history_file() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(".tremor_history"))
+}
+
+impl Repl {
+    pub(crate) fn run(&self) -> Result<()> {
+        let mode = if self.query {
+            Mode::Query
+        } else {
+            Mode::Script
+        };
+        let mut session = Session::new(env::setup()?, mode);
+        let mut editor = Editor::new();
+        editor.set_helper(Some(InputHighlighter));
+        let history = history_file();
+        if let Some(history) = &history {
+            if let Err(e) = editor.load_history(history) {
+                // there is no history the first time around
+                debug!("No repl history loaded: {}", e);
+            }
+        }
+        println!(
+            "tremor {} - type `:help` for help",
+            env!("CARGO_PKG_VERSION")
+        );
+
+        let mut input = String::new();
+        loop {
+            let prompt = if input.is_empty() {
+                session.prompt()
+            } else {
+                "...> "
+            };
+            let line = match editor.readline(prompt) {
+                Ok(line) => line,
+                // ctrl-c discards the current input
+                Err(ReadlineError::Interrupted) => {
+                    input.clear();
+                    continue;
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(Error::from(e.to_string())),
+            };
+            if !input.is_empty() {
+                input.push('\n');
+            }
+            input.push_str(&line);
+            match session.eval(&input) {
+                Ok(Output::Quit) => break,
+                Ok(output) => print(output)?,
+                Err(e) if is_incomplete(&e) && !line.trim().is_empty() => continue,
+                Err(e) => print_error(e),
+            }
+            editor.add_history_entry(input.as_str());
+            input.clear();
+        }
+
+        if let Some(history) = &history {
+            if let Err(e) = editor.save_history(history) {
+                warn!("Failed to save repl history: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Result<Session> {
+        Ok(Session::new(env::setup()?, Mode::Script))
+    }
+
+    #[test]
+    fn bindings_and_state_persist() -> Result<()> {
+        let mut s = session()?;
+        assert_eq!(Output::Value(Value::from(2)), s.eval("let a = 2; a")?);
+        assert_eq!(Output::Value(Value::from(3)), s.eval("a + 1")?);
+        s.eval("let state = {\"count\": a}")?;
+        assert_eq!(Output::Value(Value::from(2)), s.eval("state.count")?);
+        s.eval(":reset")?;
+        assert!(s.eval("a").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn event_and_meta() -> Result<()> {
+        let mut s = session()?;
+        s.eval(":event {\"snot\": \"badger\"}")?;
+        s.eval(":meta {\"key\": 1}")?;
+        assert_eq!(Output::Value(Value::from("badger")), s.eval("event.snot")?);
+        assert_eq!(Output::Value(Value::from(1)), s.eval("$key")?);
+        assert_eq!(Output::Drop, s.eval("drop")?);
+        Ok(())
+    }
+
+    #[test]
+    fn query() -> Result<()> {
+        let mut s = session()?;
+        assert!(s.eval(":send").is_err());
+        s.eval(":query")?;
+        s.eval("select event.x from in into out;")?;
+        s.eval(":event {\"x\": 42}")?;
+        assert_eq!(
+            Output::Emitted(vec![("out".to_string(), Value::from(42))]),
+            s.eval(":send")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn incomplete() -> Result<()> {
+        let s = session()?;
+        let incomplete = |src| {
+            s.parse_script(src)
+                .map_or_else(|e| is_incomplete(&e), |_| false)
+        };
+        assert!(incomplete("match event of"));
+        assert!(incomplete("1 +"));
+        assert!(incomplete("\"snot"));
+        assert!(!incomplete("1 + )"));
+        assert!(!incomplete("1 + 1"));
+        Ok(())
+    }
+
+    #[test]
+    fn input_highlighting() {
+        let highlight = |line| highlight_input(line, &mut DumbHighlighter::new());
+        for line in [
+            "let a = 1 + 2;",
+            "event.snot",
+            "select event from in into out;",
+        ] {
+            assert_eq!(Some(line.to_string()), highlight(line));
+        }
+        // commands, unfinished input and input the highlighter doesn't reproduce
+        assert_eq!(None, highlight(":event {}"));
+        assert_eq!(None, highlight("\"snot"));
+        assert_eq!(None, highlight("  # comment"));
+    }
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use crate::cli::Run;
+use crate::dap;
 use crate::env;
 use crate::errors::Result;
 use crate::util::{get_source_kind, highlight, slurp_string, SourceKind};
@@ -33,11 +34,15 @@ use tremor_script::{
     highlighter::{Error as HighlighterError, Highlighter, Term as TermHighlighter},
     lexer::Lexer,
     prelude::*,
+    profile,
     query::Query,
     script::{AggrType, Return, Script},
     EventPayload, Value, ValueAndMeta,
 };
 
+/// Number of frames listed in the profile summary
+const PROFILE_SUMMARY: usize = 10;
+
 struct Ingress {
     is_interactive: bool,
     is_pretty: bool,
@@ -238,7 +243,15 @@ impl Run {
         match Script::parse(raw, &env.fun) {
             Ok(mut script) => {
                 script.format_warnings_with(&mut h)?;
+                if profile::is_enabled() {
+                    profile::register_file(script.aid, &self.script)?;
+                }
 
+                let dap = match self.debug {
+                    Some(port) => Some(dap::Server::listen(port, &self.script, &script)?),
+                    None => None,
+                };
+                let stepper = dap.clone();
                 let mut ingress = Ingress::from_args(self)?;
                 let mut egress = Egress::from_args(self)?;
                 let id = 0_u64;
@@ -248,6 +261,9 @@ impl Run {
                     id,
                     &mut egress,
                     &move |runnable, _id, egress, state, at, event| {
+                        if let Some(stepper) = &stepper {
+                            stepper.new_event()?;
+                        }
                         let mut global_map = Value::object();
                         let mut event = event.clone_static();
                         match runnable.run(
@@ -290,6 +306,9 @@ impl Run {
                     },
                 )?;
 
+                if let Some(dap) = dap {
+                    dap.finish()?;
+                }
                 Ok(())
             }
             Err(e) => {
@@ -334,6 +353,9 @@ impl Run {
         h: &mut TermHighlighter,
     ) -> Result<()> {
         runnable.format_warnings_with(h)?;
+        if profile::is_enabled() {
+            profile::register_file(runnable.aid, &file)?;
+        }
 
         let mut ingress = Ingress::from_args(self)?;
         let mut egress = Egress::from_args(self)?;
@@ -430,15 +452,48 @@ impl Run {
     }
 
     pub(crate) async fn run(&self) -> Result<()> {
-        match get_source_kind(&self.script) {
+        let kind = get_source_kind(&self.script);
+        if self.debug.is_some() && kind != SourceKind::Tremor {
+            return Err("Only tremor scripts can be debugged".into());
+        }
+        if self.profile.is_some() {
+            profile::enable();
+        }
+        let res = match kind {
             SourceKind::Troy => self.run_troy_source().await,
             SourceKind::Trickle => self.run_trickle_source(),
             SourceKind::Tremor => self.run_tremor_source(),
             SourceKind::Json | SourceKind::Unsupported(_) => {
                 Err(format!("Error: Unable to execute source: {}", &self.script).into())
             }
+        };
+        if let Some(path) = &self.profile {
+            profile::disable();
+            write_profile(path)?;
         }
+        res
+    }
+}
+
+/// Writes the folded stacks to `path` and prints the frames with the most
+/// self time to stderr
+fn write_profile(path: &str) -> Result<()> {
+    let mut file = file::create(path)?;
+    file.write_all(profile::folded()?.as_bytes())?;
+    let mut frames: Vec<_> = profile::frames()?.into_iter().collect();
+    frames.sort_by(|(_, a), (_, b)| b.self_ns.cmp(&a.self_ns));
+    eprintln!(
+        "{:>12} {:>14} {:>14}  frame",
+        "count", "total ns", "self ns"
+    );
+    for (frame, sample) in frames.iter().take(PROFILE_SUMMARY) {
+        eprintln!(
+            "{:>12} {:>14} {:>14}  {}",
+            sample.count, sample.total_ns, sample.self_ns, frame
+        );
     }
+    eprintln!("Wrote profile to {}", path);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -460,6 +515,8 @@ mod test {
             preprocessor: String::new(),
             postprocessor: String::new(),
             port: None,
+            debug: None,
+            profile: None,
         };
         r.run().timeout(Duration::from_secs(1)).await?
     }
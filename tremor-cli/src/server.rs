@@ -24,6 +24,7 @@ use signal_hook::low_level::signal_name;
 use signal_hook_async_std::Signals;
 use std::io::Write;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 use tremor_api as api;
 use tremor_common::file;
 use tremor_runtime::system::{ShutdownMode, World};
@@ -67,10 +68,23 @@ async fn handle_signals(signals: Signals, world: World) {
         }
     }
 }
+/// parse a flow specific drain timeout given as `<flow>=<seconds>`
+fn parse_flow_drain_timeout(s: &str) -> Result<(String, Duration)> {
+    let (flow, secs) = s.split_once('=').ok_or_else(|| {
+        Error::from(format!(
+            "Invalid flow drain timeout `{s}`, expected `<flow>=<seconds>`"
+        ))
+    })?;
+    let secs: u64 = secs
+        .parse()
+        .map_err(|e| Error::from(format!("Invalid flow drain timeout `{s}`: {e}")))?;
+    Ok((flow.to_string(), Duration::from_secs(secs)))
+}
+
 impl ServerRun {
     #[allow(clippy::too_many_lines)]
     async fn run_dun(&self) -> Result<i32> {
-        use tremor_runtime::system::WorldConfig;
+        use tremor_runtime::system::{shutdown::DrainConfig, WorldConfig};
 
         let mut result = 0;
 
@@ -98,10 +112,21 @@ impl ServerRun {
         }
 
         tremor_script::RECURSION_LIMIT.store(self.recursion_limit, Ordering::Relaxed);
+        tremor_script::set_type_check(self.type_check);
+
+        let drain = DrainConfig {
+            timeout: Duration::from_secs(self.drain_timeout),
+            flow_timeouts: self
+                .flow_drain_timeout
+                .iter()
+                .map(|s| parse_flow_drain_timeout(s))
+                .collect::<Result<_>>()?,
+        };
 
         // TODO: Allow configuring this for offramps and pipelines
         let config = WorldConfig {
             debug_connectors: self.debug_connectors,
+            drain,
             ..WorldConfig::default()
         };
 
@@ -139,9 +164,21 @@ impl ServerRun {
                 Ok(())
             })
         } else {
-            eprintln!("Listening at: http://{}", &self.api_host);
-            info!("Listening at: http://{}", &self.api_host);
-            api::serve(self.api_host.clone(), &world)
+            let security = if let Some(path) = &self.api_security {
+                api::security::Config::from_file(path).map_err(|e| {
+                    Error::from(format!("Failed to load API security config `{path}`: {e}"))
+                })?
+            } else {
+                api::security::Config::default()
+            };
+            let scheme = if security.tls.is_some() {
+                "https"
+            } else {
+                "http"
+            };
+            eprintln!("Listening at: {scheme}://{}", &self.api_host);
+            info!("Listening at: {scheme}://{}", &self.api_host);
+            api::serve(self.api_host.clone(), &world, security)
         };
         // waiting for either
         match future::select(handle, api_handle).await {
@@ -37,7 +37,9 @@ mod assert;
 mod before;
 mod command;
 mod metadata;
+mod pipeline;
 mod process;
+mod property;
 pub mod stats;
 pub mod tag;
 mod unit;
@@ -267,6 +269,10 @@ impl TestConfig {
 impl Test {
     #[allow(clippy::too_many_lines)]
     pub(crate) async fn run(&self) -> Result<()> {
+        tremor_script::set_type_check(self.type_check);
+        if self.coverage.is_some() {
+            tremor_script::coverage::enable();
+        }
         let base_directory = tremor_common::file::canonicalize(&self.path)?;
         let mut config = TestConfig {
             verbose: self.verbose,
@@ -288,6 +294,8 @@ impl Test {
         let mut unit_stats = stats::Stats::new();
         let mut cmd_stats = stats::Stats::new();
         let mut integration_stats = stats::Stats::new();
+        let mut pipeline_stats = stats::Stats::new();
+        let mut property_stats = stats::Stats::new();
 
         let found: Vec<_> = found.filter_map(std::result::Result::ok).collect();
         let start = nanotime();
@@ -296,11 +304,14 @@ impl Test {
             // No meta.yaml was found, therefore we might have the path to a
             // specific folder. Let's apply some heuristics to see if we have
             // something runnable.
-            let files = GlobWalkerBuilder::from_patterns(&config.base_directory, &["*.{troy}"])
-                .case_insensitive(true)
-                .max_depth(1)
-                .build()?
-                .filter_map(std::result::Result::ok);
+            let files = GlobWalkerBuilder::from_patterns(
+                &config.base_directory,
+                &["*.{troy,trickle}", "property.yaml"],
+            )
+            .case_insensitive(true)
+            .max_depth(1)
+            .build()?
+            .filter_map(std::result::Result::ok);
 
             if files.count() >= 1 {
                 let stats = stats::Stats::new();
@@ -353,6 +364,19 @@ impl Test {
                         unit_stats.merge(&s);
                         t
                     }
+                    TestMode::Pipeline => {
+                        let (s, t) =
+                            pipeline::suite_pipeline(PathBuf::from(&self.path).as_path(), &config)
+                                .await?;
+                        pipeline_stats.merge(&s);
+                        t
+                    }
+                    TestMode::Property => {
+                        let (s, t) =
+                            property::suite_property(PathBuf::from(&self.path).as_path(), &config)?;
+                        property_stats.merge(&s);
+                        t
+                    }
                     TestMode::All => {
                         eprintln!("No tests run: Don't know how to run test of kind All");
                         Vec::new()
@@ -401,6 +425,16 @@ impl Test {
                             unit_stats.merge(&s);
                             t
                         }
+                        TestMode::Pipeline => {
+                            let (s, t) = pipeline::suite_pipeline(root, &config).await?;
+                            pipeline_stats.merge(&s);
+                            t
+                        }
+                        TestMode::Property => {
+                            let (s, t) = property::suite_property(root, &config)?;
+                            property_stats.merge(&s);
+                            t
+                        }
                         TestMode::All => continue,
                     };
                     reports.insert(config.meta.mode.to_string(), test_reports);
@@ -416,11 +450,15 @@ impl Test {
         status::rollups("All Integration", &integration_stats)?;
         status::rollups("All Command", &cmd_stats)?;
         status::rollups("All Unit", &unit_stats)?;
+        status::rollups("All Pipeline", &pipeline_stats)?;
+        status::rollups("All Property", &property_stats)?;
         let mut all_stats = stats::Stats::new();
         all_stats.merge(&bench_stats);
         all_stats.merge(&integration_stats);
         all_stats.merge(&cmd_stats);
         all_stats.merge(&unit_stats);
+        all_stats.merge(&pipeline_stats);
+        all_stats.merge(&property_stats);
         status::rollups("Total", &all_stats)?;
         let mut stats_map = HashMap::new();
         stats_map.insert("all".to_string(), all_stats.clone());
@@ -428,6 +466,8 @@ impl Test {
         stats_map.insert("integration".to_string(), integration_stats);
         stats_map.insert("command".to_string(), cmd_stats);
         stats_map.insert("unit".to_string(), unit_stats);
+        stats_map.insert("pipeline".to_string(), pipeline_stats);
+        stats_map.insert("property".to_string(), property_stats);
         status::total_duration(elapsed)?;
 
         let test_run = report::TestRun {
@@ -444,6 +484,13 @@ impl Test {
                 Error::from(format!("Failed to write report to `{}`: {}", report, e))
             })?;
         }
+        if let Some(coverage) = &self.coverage {
+            let mut file = file::create(coverage)?;
+            let lcov = tremor_script::coverage::to_lcov()?;
+            file.write_all(lcov.as_bytes()).map_err(|e| {
+                Error::from(format!("Failed to write coverage to `{}`: {}", coverage, e))
+            })?;
+        }
 
         if all_stats.fail > 0 {
             Err(ErrorKind::TestFailures(all_stats).into())
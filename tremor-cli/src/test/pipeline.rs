@@ -0,0 +1,339 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-process pipeline tests
+//!
+//! A pipeline test is a directory with a `fixture.yaml` and a trickle query.
+//! The fixture lists the events (and ticks) fed into the query, in order and
+//! with explicit ingest timestamps so time based windows are deterministic,
+//! and the values expected on each output port:
+//!
+//! ```yaml
+//! query: query.trickle # the default
+//! input:
+//!   - value: {"snot": "badger"}
+//!     meta: {"key": 1}   # optional
+//!     ingest_ns: 100     # optional, defaults to one after the previous input
+//!     port: in           # optional
+//!   - tick: 200          # a tick signal at the given ingest timestamp
+//! expected:
+//!   out:
+//!     - {"snot": "badger"}
+//! ```
+//!
+//! Ports that are not listed in `expected` must not receive any events.
+
+use crate::env;
+use crate::errors::{Error, Result};
+use crate::report::{self, TestElement, TestSuite};
+use crate::status;
+use crate::test::{stats, tag, TestConfig};
+use crate::util::{basename, slurp_string};
+use globwalk::{FileType, GlobWalkerBuilder};
+use simd_json::OwnedValue;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use tremor_common::{ids::OperatorIdGen, time::nanotime};
+use tremor_pipeline::{Event, EventId, SignalKind};
+use tremor_script::{prelude::*, query::Query, EventPayload, Value, ValueAndMeta};
+
+/// Name of the fixture file in a test directory
+const FIXTURE: &str = "fixture.yaml";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Fixture {
+    #[serde(default = "default_query")]
+    query: String,
+    #[serde(default)]
+    input: Vec<Input>,
+    #[serde(default)]
+    expected: BTreeMap<String, Vec<OwnedValue>>,
+}
+
+fn default_query() -> String {
+    "query.trickle".to_string()
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Input {
+    Tick {
+        tick: u64,
+    },
+    Event {
+        value: OwnedValue,
+        #[serde(default)]
+        meta: Option<OwnedValue>,
+        #[serde(default)]
+        ingest_ns: Option<u64>,
+        #[serde(default)]
+        port: Option<String>,
+    },
+}
+
+/// Outputs of a pipeline per port, in the order they were emitted
+type Outputs = BTreeMap<String, Vec<Value<'static>>>;
+
+/// Runs the inputs of a fixture through the query and collects the outputs
+async fn run_fixture(dir: &Path, fixture: Fixture) -> Result<(Outputs, Outputs)> {
+    let path = dir.join(&fixture.query);
+    let src = slurp_string(&path)?;
+    let env = env::setup()?;
+    let query = Query::parse(&src, &env.fun, &env.aggr)?;
+    if tremor_script::coverage::is_enabled() {
+        tremor_script::coverage::register_file(query.aid, &path.to_string_lossy())?;
+    }
+    let mut pipeline = tremor_pipeline::query::Query(query).to_pipe(&mut OperatorIdGen::new())?;
+
+    let mut outputs = Outputs::new();
+    let mut ingest_ns = 0;
+    let mut id = 0;
+    for input in fixture.input {
+        let mut returns = vec![];
+        match input {
+            Input::Tick { tick } => {
+                ingest_ns = tick;
+                let signal = Event {
+                    ingest_ns,
+                    kind: Some(SignalKind::Tick),
+                    ..Event::default()
+                };
+                pipeline.enqueue_signal(signal, &mut returns)?;
+            }
+            Input::Event {
+                value,
+                meta,
+                ingest_ns: at,
+                port,
+            } => {
+                ingest_ns = at.unwrap_or(ingest_ns + 1);
+                let value = Value::from(value);
+                let meta = meta.map_or_else(Value::object, Value::from);
+                let event = Event {
+                    id: EventId::from_id(0, 0, id),
+                    data: EventPayload::new(vec![], |_| ValueAndMeta::from_parts(value, meta)),
+                    ingest_ns,
+                    ..Event::default()
+                };
+                pipeline
+                    .enqueue(port.as_deref().unwrap_or("in"), event, &mut returns)
+                    .await?;
+                id += 1;
+            }
+        }
+        for (port, event) in returns {
+            outputs
+                .entry(port.to_string())
+                .or_default()
+                .extend(event.value_iter().map(|v| v.clone_static()));
+        }
+    }
+    let expected = fixture
+        .expected
+        .into_iter()
+        .map(|(port, values)| (port, values.into_iter().map(Value::from).collect()))
+        .collect();
+    Ok((expected, outputs))
+}
+
+/// Describes how the values on a port differ from the expected ones
+fn diff(expected: &[Value], got: &[Value]) -> Option<String> {
+    if expected == got {
+        return None;
+    }
+    let mut info = String::new();
+    for i in 0..expected.len().max(got.len()) {
+        let line = match (expected.get(i), got.get(i)) {
+            (Some(e), Some(g)) if e == g => continue,
+            (Some(e), Some(g)) => format!("#{}: expected {} but got {}", i, e, g),
+            (Some(e), None) => format!("#{}: expected {} but got nothing", i, e),
+            (None, Some(g)) => format!("#{}: unexpected {}", i, g),
+            (None, None) => continue,
+        };
+        info.push_str(&line);
+        info.push('\n');
+    }
+    Some(info)
+}
+
+async fn run_test(dir: &Path, config: &TestConfig) -> Result<TestSuite> {
+    let name = basename(&dir.to_string_lossy());
+    let start = nanotime();
+    let mut stats = stats::Stats::new();
+    let mut elements = Vec::new();
+
+    let fixture = slurp_string(dir.join(FIXTURE))?;
+    let result = match serde_yaml::from_str::<Fixture>(&fixture) {
+        Ok(fixture) => run_fixture(dir, fixture).await,
+        Err(e) => Err(Error::from(format!(
+            "Invalid fixture in `{}`: {}",
+            dir.display(),
+            e
+        ))),
+    };
+
+    match result {
+        Ok((expected, got)) => {
+            let ports: BTreeSet<&String> = expected.keys().chain(got.keys()).collect();
+            for port in ports {
+                let expected = expected.get(port).map_or(&[][..], Vec::as_slice);
+                let got = got.get(port).map_or(&[][..], Vec::as_slice);
+                let info = diff(expected, got);
+                let success = info.is_none();
+                let status = stats.report(success, &format!("{}:{}", name, port));
+                stats.assert();
+                status::assert_has(
+                    "  ",
+                    &format!("Port `{}`", port),
+                    &format!("{} event(s)", got.len()),
+                    info.as_ref(),
+                    success,
+                )?;
+                elements.push(TestElement {
+                    description: format!("Outputs on port `{}`", port),
+                    keyword: report::KeywordKind::Predicate,
+                    result: report::ResultKind {
+                        status,
+                        duration: 0,
+                    },
+                    info,
+                    hidden: success && !config.verbose,
+                });
+            }
+        }
+        Err(e) => {
+            status::text("    ", &format!("Error: {}", e))?;
+            let status = stats.report(false, &name);
+            elements.push(TestElement {
+                description: "Running the pipeline".to_string(),
+                keyword: report::KeywordKind::Test,
+                result: report::ResultKind {
+                    status,
+                    duration: 0,
+                },
+                info: Some(e.to_string()),
+                hidden: false,
+            });
+        }
+    }
+
+    Ok(TestSuite {
+        name: name.clone(),
+        description: format!("pipeline test {}", name),
+        elements,
+        evidence: None,
+        stats,
+        duration: nanotime() - start,
+    })
+}
+
+pub(crate) async fn suite_pipeline(
+    root: &Path,
+    config: &TestConfig,
+) -> Result<(stats::Stats, Vec<report::TestReport>)> {
+    let base = config.base_directory.as_path();
+    let tests = GlobWalkerBuilder::new(root, &config.meta.includes)
+        .case_insensitive(true)
+        .file_type(FileType::DIR)
+        .build()
+        .map_err(|e| format!("Unable to walk test path for pipeline tests: {}", e))?;
+
+    let report_start = nanotime();
+    let mut suites = HashMap::new();
+    let mut stats = stats::Stats::new();
+
+    status::h0("Framework", "Finding pipeline test scenarios")?;
+
+    for test in tests.filter_map(std::result::Result::ok) {
+        let dir = test.path();
+        if !dir.join(FIXTURE).is_file() {
+            continue;
+        }
+        let name = basename(&dir.to_string_lossy());
+        let tags = tag::resolve(base, dir)?;
+        let (matched, is_match) = config.matches(&tags);
+        if is_match {
+            status::h1("Pipeline", &format!("Running {}", name))?;
+            status::tags(&tags, Some(&matched), Some(&config.excludes))?;
+            let suite = run_test(dir, config).await?;
+            stats.merge(&suite.stats);
+            status::duration(suite.duration, "    ")?;
+            suites.insert(name, suite);
+        } else {
+            stats.skip();
+            status::h1("Pipeline", &format!("Skipping {}", name))?;
+            status::tags(&tags, Some(&matched), Some(&config.excludes))?;
+        }
+    }
+
+    status::rollups("\n  Pipeline", &stats)?;
+
+    let report = report::TestReport {
+        description: "pipeline tests".into(),
+        elements: suites,
+        stats: stats.clone(),
+        duration: nanotime() - report_start,
+    };
+    Ok((stats, vec![report]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_value::literal;
+
+    #[test]
+    fn fixture() -> Result<()> {
+        let fixture: Fixture = serde_yaml::from_str(
+            r#"
+input:
+  - value: {"snot": "badger"}
+    meta: {"key": 1}
+    ingest_ns: 10
+  - tick: 20
+expected:
+  out:
+    - 1
+"#,
+        )
+        .map_err(|e| Error::from(e.to_string()))?;
+        assert_eq!("query.trickle", fixture.query);
+        assert_eq!(2, fixture.input.len());
+        assert!(matches!(
+            fixture.input.get(0),
+            Some(Input::Event {
+                ingest_ns: Some(10),
+                ..
+            })
+        ));
+        assert!(matches!(
+            fixture.input.get(1),
+            Some(Input::Tick { tick: 20 })
+        ));
+        assert_eq!(Some(1), fixture.expected.get("out").map(Vec::len));
+        Ok(())
+    }
+
+    #[test]
+    fn diffs() {
+        let expected = vec![literal!(1), literal!(2)];
+        assert_eq!(None, diff(&expected, &expected));
+        let got = vec![literal!(1), literal!(3), literal!(4)];
+        assert_eq!(
+            Some("#1: expected 2 but got 3\n#2: unexpected 4\n".to_string()),
+            diff(&expected, &got)
+        );
+    }
+}
@@ -0,0 +1,554 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Property based tests for tremor scripts
+//!
+//! A property test is a directory with a `property.yaml` and a tremor script.
+//! The runner generates random events, runs them through the script and
+//! checks every invariant against the outcome. Failing events are shrunk to a
+//! minimal counterexample before they are reported.
+//!
+//! ```yaml
+//! script: script.tremor # the default
+//! cases: 256            # the default
+//! seed: 42              # optional, makes runs reproducible
+//! generator:
+//!   # events shaped like an example ...
+//!   example: {"name": "snot", "count": 1, "tags": ["badger"]}
+//!   # ... or described by a schema
+//!   # schema:
+//!   #   type: record
+//!   #   fields:
+//!   #     count: {type: integer, min: 0, max: 100}
+//!   #     name: {type: string, pattern: "[a-z]{1,8}"}
+//!   #   optional: [name]
+//! invariants:
+//!   - name: count is preserved
+//!     check: event.output.count == event.input.count
+//! ```
+//!
+//! Invariants are tremor-script expressions evaluated against an `event` of
+//! the form `{"input": ..., "output": ..., "port": ...}`. `output` is the
+//! emitted value and `port` the port it was emitted on, `"err"` with the error
+//! message as `output` if the script failed, or `null` for both if the event
+//! was dropped. An invariant holds if it evaluates to `true`.
+
+use crate::env;
+use crate::errors::{Error, Result};
+use crate::report::{self, TestElement, TestSuite};
+use crate::status;
+use crate::test::{stats, tag, TestConfig};
+use crate::util::{basename, slurp_string};
+use globwalk::{FileType, GlobWalkerBuilder};
+use proptest::prelude::*;
+use proptest::test_runner::{
+    Config as RunnerConfig, RngAlgorithm, TestCaseError, TestError, TestRng, TestRunner,
+};
+use simd_json::{OwnedValue, StaticNode};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tremor_common::time::nanotime;
+use tremor_script::{
+    prelude::*,
+    script::{AggrType, Return, Script},
+    Value,
+};
+
+/// Name of the property file in a test directory
+const PROPERTY: &str = "property.yaml";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Property {
+    #[serde(default = "default_script")]
+    script: String,
+    #[serde(default = "default_cases")]
+    cases: u32,
+    #[serde(default)]
+    seed: Option<u64>,
+    generator: Generator,
+    invariants: Vec<Invariant>,
+}
+
+fn default_script() -> String {
+    "script.tremor".to_string()
+}
+
+fn default_cases() -> u32 {
+    256
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Generator {
+    /// Events of the same shape as the example
+    Example(OwnedValue),
+    /// Events described by a schema
+    Schema(Schema),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Invariant {
+    name: String,
+    check: String,
+}
+
+/// Describes the events to generate
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Schema {
+    Null,
+    Bool,
+    Integer {
+        #[serde(default)]
+        min: Option<i64>,
+        #[serde(default)]
+        max: Option<i64>,
+    },
+    Float {
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+    String {
+        #[serde(default = "default_pattern")]
+        pattern: String,
+    },
+    Array {
+        items: Box<Schema>,
+        #[serde(default)]
+        min_len: usize,
+        #[serde(default = "default_max_len")]
+        max_len: usize,
+    },
+    Record {
+        #[serde(default)]
+        fields: BTreeMap<String, Schema>,
+        /// fields that may be missing
+        #[serde(default)]
+        optional: Vec<String>,
+    },
+    OneOf {
+        variants: Vec<Schema>,
+    },
+    Const {
+        value: OwnedValue,
+    },
+}
+
+fn default_pattern() -> String {
+    ".*".to_string()
+}
+
+fn default_max_len() -> usize {
+    8
+}
+
+type ValueStrategy = BoxedStrategy<Value<'static>>;
+
+fn any_float() -> ValueStrategy {
+    (prop::num::f64::NORMAL | prop::num::f64::ZERO)
+        .prop_map(Value::from)
+        .boxed()
+}
+
+/// Generates values of the same shape as the example: leaves keep their
+/// type, records their keys and arrays hold up to twice as many elements
+/// shaped like any of the example elements
+fn from_example(example: &Value<'static>) -> ValueStrategy {
+    match example {
+        Value::Static(StaticNode::Null) => Just(Value::null()).boxed(),
+        Value::Static(StaticNode::Bool(_)) => any::<bool>().prop_map(Value::from).boxed(),
+        Value::Static(StaticNode::I64(_)) => any::<i64>().prop_map(Value::from).boxed(),
+        Value::Static(StaticNode::U64(_)) => any::<u64>().prop_map(Value::from).boxed(),
+        Value::Static(StaticNode::F64(_)) => any_float(),
+        Value::String(_) => ".*".prop_map(Value::from).boxed(),
+        Value::Bytes(_) => any::<Vec<u8>>()
+            .prop_map(|b| Value::Bytes(b.into()))
+            .boxed(),
+        Value::Array(elements) if elements.is_empty() => Just(Value::array()).boxed(),
+        Value::Array(elements) => {
+            let shapes: Vec<_> = elements.iter().map(from_example).collect();
+            prop::collection::vec(prop::strategy::Union::new(shapes), 0..=elements.len() * 2)
+                .prop_map(Value::from)
+                .boxed()
+        }
+        Value::Object(fields) => {
+            let (keys, values): (Vec<_>, Vec<_>) = fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), from_example(v)))
+                .unzip();
+            values
+                .prop_map(move |values| keys.clone().into_iter().zip(values).collect::<Value>())
+                .boxed()
+        }
+    }
+}
+
+/// Generates values matching the schema
+fn from_schema(schema: &Schema) -> Result<ValueStrategy> {
+    Ok(match schema {
+        Schema::Null => Just(Value::null()).boxed(),
+        Schema::Bool => any::<bool>().prop_map(Value::from).boxed(),
+        Schema::Integer { min, max } => {
+            let (min, max) = (min.unwrap_or(i64::MIN), max.unwrap_or(i64::MAX));
+            if min > max {
+                return Err(format!("Invalid integer range {}..={}", min, max).into());
+            }
+            (min..=max).prop_map(Value::from).boxed()
+        }
+        Schema::Float {
+            min: None,
+            max: None,
+        } => any_float(),
+        Schema::Float { min, max } => {
+            // halved so the width of the range stays finite
+            let (min, max) = (min.unwrap_or(f64::MIN / 2.0), max.unwrap_or(f64::MAX / 2.0));
+            if min > max {
+                return Err(format!("Invalid float range {}..={}", min, max).into());
+            }
+            (min..=max).prop_map(Value::from).boxed()
+        }
+        Schema::String { pattern } => proptest::string::string_regex(pattern)
+            .map_err(|e| Error::from(format!("Invalid string pattern `{}`: {}", pattern, e)))?
+            .prop_map(Value::from)
+            .boxed(),
+        Schema::Array {
+            items,
+            min_len,
+            max_len,
+        } => {
+            if min_len > max_len {
+                return Err(format!("Invalid array length {}..={}", min_len, max_len).into());
+            }
+            prop::collection::vec(from_schema(items)?, *min_len..=*max_len)
+                .prop_map(Value::from)
+                .boxed()
+        }
+        Schema::Record { fields, optional } => {
+            let mut keys = Vec::with_capacity(fields.len());
+            let mut values = Vec::with_capacity(fields.len());
+            for (k, v) in fields {
+                let value = from_schema(v)?;
+                let value = if optional.contains(k) {
+                    prop::option::of(value).boxed()
+                } else {
+                    value.prop_map(Some).boxed()
+                };
+                keys.push(k.clone());
+                values.push(value);
+            }
+            values
+                .prop_map(move |values| {
+                    keys.iter()
+                        .cloned()
+                        .zip(values)
+                        .filter_map(|(k, v)| Some((k, v?)))
+                        .collect::<Value>()
+                })
+                .boxed()
+        }
+        Schema::OneOf { variants } if variants.is_empty() => {
+            return Err("A `one_of` schema needs at least one variant".into());
+        }
+        Schema::OneOf { variants } => prop::strategy::Union::new(
+            variants
+                .iter()
+                .map(from_schema)
+                .collect::<Result<Vec<_>>>()?,
+        )
+        .boxed(),
+        Schema::Const { value } => Just(Value::from(value.clone())).boxed(),
+    })
+}
+
+/// Runs the script for one input and describes the outcome to the invariants
+fn outcome(script: &Script, input: &Value<'static>) -> Value<'static> {
+    let mut event = input.clone();
+    let mut state = Value::null();
+    let mut meta = Value::object();
+    let result = script.run(
+        &EventContext::new(nanotime(), None),
+        AggrType::Emit,
+        &mut event,
+        &mut state,
+        &mut meta,
+    );
+    let (output, port) = match result {
+        Ok(Return::Emit { value, port }) => (value, Value::from(port.unwrap_or_else(out))),
+        Ok(Return::EmitEvent { port }) => (event, Value::from(port.unwrap_or_else(out))),
+        Ok(Return::Drop) => (Value::null(), Value::null()),
+        Err(e) => (Value::from(e.to_string()), Value::from("err")),
+    };
+    vec![
+        ("input", input.clone()),
+        ("output", output.into_static()),
+        ("port", port),
+    ]
+    .into_iter()
+    .collect()
+}
+
+fn out() -> String {
+    "out".to_string()
+}
+
+/// Checks a single invariant against an outcome
+fn check(invariant: &Script, outcome: &Value<'static>) -> std::result::Result<(), String> {
+    let mut event = outcome.clone();
+    let mut state = Value::null();
+    let mut meta = Value::object();
+    match invariant.run(
+        &EventContext::new(nanotime(), None),
+        AggrType::Emit,
+        &mut event,
+        &mut state,
+        &mut meta,
+    ) {
+        Ok(Return::Emit { value, .. }) if value == true => Ok(()),
+        Ok(Return::Emit { value, .. }) => Err(format!("evaluated to {}", value)),
+        Ok(Return::EmitEvent { .. } | Return::Drop) => Err("did not evaluate to a value".into()),
+        Err(e) => Err(format!("failed: {}", e)),
+    }
+}
+
+fn runner(property: &Property) -> TestRunner {
+    let config = RunnerConfig {
+        cases: property.cases,
+        // counterexamples are reported, not persisted
+        failure_persistence: None,
+        ..RunnerConfig::default()
+    };
+    if let Some(seed) = property.seed {
+        let mut bytes = [0_u8; 32];
+        bytes[..8].copy_from_slice(&seed.to_le_bytes());
+        TestRunner::new_with_rng(config, TestRng::from_seed(RngAlgorithm::ChaCha, &bytes))
+    } else {
+        TestRunner::new(config)
+    }
+}
+
+/// Checks an invariant for generated inputs, returns the minimal
+/// counterexample if it does not hold
+fn run_invariant(
+    property: &Property,
+    strategy: &ValueStrategy,
+    script: &Script,
+    invariant: &Script,
+) -> Option<String> {
+    let result = runner(property).run(strategy, |input| {
+        check(invariant, &outcome(script, &input)).map_err(TestCaseError::fail)
+    });
+    match result {
+        Ok(()) => None,
+        Err(TestError::Fail(reason, input)) => Some(format!(
+            "Minimal counterexample: {}\nOutcome: {}\nThe invariant {}",
+            input,
+            outcome(script, &input),
+            reason
+        )),
+        Err(TestError::Abort(reason)) => Some(format!("Aborted: {}", reason)),
+    }
+}
+
+fn run_test(dir: &Path, config: &TestConfig) -> Result<TestSuite> {
+    let name = basename(&dir.to_string_lossy());
+    let start = nanotime();
+    let mut stats = stats::Stats::new();
+    let mut elements = Vec::new();
+
+    let property = slurp_string(dir.join(PROPERTY))?;
+    let property: Property = serde_yaml::from_str(&property)
+        .map_err(|e| Error::from(format!("Invalid property in `{}`: {}", dir.display(), e)))?;
+    let env = env::setup()?;
+    let script = Script::parse(&slurp_string(dir.join(&property.script))?, &env.fun)?;
+    let strategy = match &property.generator {
+        Generator::Example(example) => from_example(&Value::from(example.clone())),
+        Generator::Schema(schema) => from_schema(schema)?,
+    };
+
+    for invariant in &property.invariants {
+        let info = match Script::parse(&invariant.check, &env.fun) {
+            Ok(check) => run_invariant(&property, &strategy, &script, &check),
+            Err(e) => Some(format!("Invalid invariant: {}", e)),
+        };
+        let success = info.is_none();
+        let status = stats.report(success, &format!("{}:{}", name, invariant.name));
+        stats.assert();
+        status::assert_has(
+            "  ",
+            &invariant.name,
+            &format!("{} case(s)", property.cases),
+            info.as_ref(),
+            success,
+        )?;
+        elements.push(TestElement {
+            description: format!("Invariant `{}`", invariant.name),
+            keyword: report::KeywordKind::Predicate,
+            result: report::ResultKind {
+                status,
+                duration: 0,
+            },
+            info,
+            hidden: success && !config.verbose,
+        });
+    }
+
+    Ok(TestSuite {
+        name: name.clone(),
+        description: format!("property test {}", name),
+        elements,
+        evidence: None,
+        stats,
+        duration: nanotime() - start,
+    })
+}
+
+pub(crate) fn suite_property(
+    root: &Path,
+    config: &TestConfig,
+) -> Result<(stats::Stats, Vec<report::TestReport>)> {
+    let base = config.base_directory.as_path();
+    let tests = GlobWalkerBuilder::new(root, &config.meta.includes)
+        .case_insensitive(true)
+        .file_type(FileType::DIR)
+        .build()
+        .map_err(|e| format!("Unable to walk test path for property tests: {}", e))?;
+
+    let report_start = nanotime();
+    let mut suites = HashMap::new();
+    let mut stats = stats::Stats::new();
+
+    status::h0("Framework", "Finding property test scenarios")?;
+
+    for test in tests.filter_map(std::result::Result::ok) {
+        let dir = test.path();
+        if !dir.join(PROPERTY).is_file() {
+            continue;
+        }
+        let name = basename(&dir.to_string_lossy());
+        let tags = tag::resolve(base, dir)?;
+        let (matched, is_match) = config.matches(&tags);
+        if is_match {
+            status::h1("Property", &format!("Running {}", name))?;
+            status::tags(&tags, Some(&matched), Some(&config.excludes))?;
+            let suite = run_test(dir, config)?;
+            stats.merge(&suite.stats);
+            status::duration(suite.duration, "    ")?;
+            suites.insert(name, suite);
+        } else {
+            stats.skip();
+            status::h1("Property", &format!("Skipping {}", name))?;
+            status::tags(&tags, Some(&matched), Some(&config.excludes))?;
+        }
+    }
+
+    status::rollups("\n  Property", &stats)?;
+
+    let report = report::TestReport {
+        description: "property tests".into(),
+        elements: suites,
+        stats: stats.clone(),
+        duration: nanotime() - report_start,
+    };
+    Ok((stats, vec![report]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::strategy::ValueTree;
+    use tremor_value::literal;
+
+    fn property(invariant: &str) -> Result<Property> {
+        serde_yaml::from_str(&format!(
+            r#"
+seed: 23
+cases: 64
+generator:
+  schema:
+    type: record
+    fields:
+      count: {{type: integer, min: 0, max: 1000}}
+      name: {{type: string, pattern: "[a-z]{{1,4}}"}}
+    optional: [name]
+invariants:
+  - name: test
+    check: {}
+"#,
+            invariant
+        ))
+        .map_err(|e| Error::from(e.to_string()))
+    }
+
+    #[test]
+    fn schema_values() -> Result<()> {
+        let property = property("'true'")?;
+        let strategy = match &property.generator {
+            Generator::Schema(schema) => from_schema(schema)?,
+            Generator::Example(_) => return Err("expected a schema".into()),
+        };
+        let mut runner = runner(&property);
+        for _ in 0..32 {
+            let value = strategy
+                .new_tree(&mut runner)
+                .map_err(|e| Error::from(e.to_string()))?
+                .current();
+            let count = value.get_u64("count").ok_or("count missing")?;
+            assert!(count <= 1000);
+            if let Some(name) = value.get_str("name") {
+                assert!((1..=4).contains(&name.len()));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn shrinks_counterexample() -> Result<()> {
+        let env = env::setup()?;
+        let property = property("event.output < 10")?;
+        let strategy = match &property.generator {
+            Generator::Schema(schema) => from_schema(schema)?,
+            Generator::Example(_) => return Err("expected a schema".into()),
+        };
+        let script = Script::parse("event.count", &env.fun)?;
+        let check = Script::parse(&property.invariants[0].check, &env.fun)?;
+        let info = run_invariant(&property, &strategy, &script, &check).ok_or("should fail")?;
+        assert!(
+            info.starts_with(r#"Minimal counterexample: {"count":10}"#),
+            "{}",
+            info
+        );
+
+        let check = Script::parse("event.output >= 0", &env.fun)?;
+        assert_eq!(None, run_invariant(&property, &strategy, &script, &check));
+        Ok(())
+    }
+
+    #[test]
+    fn example_shape() -> Result<()> {
+        let example = literal!({"snot": "badger", "n": 1, "list": [true]});
+        let mut runner = TestRunner::deterministic();
+        let value = from_example(&example)
+            .new_tree(&mut runner)
+            .map_err(|e| Error::from(e.to_string()))?
+            .current();
+        assert!(value.get_str("snot").is_some());
+        assert!(value.get_i64("n").is_some());
+        assert!(value.get_array("list").is_some());
+        Ok(())
+    }
+}
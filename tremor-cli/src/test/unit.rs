@@ -218,6 +218,9 @@ pub(crate) fn run_suite(
     let mut stats = stats::Stats::new();
     match tremor_script::Script::parse(&raw, &env.fun) {
         Ok(runnable) => {
+            if tremor_script::coverage::is_enabled() {
+                tremor_script::coverage::register_file(runnable.aid, &script)?;
+            }
             let local = LocalStack::default();
 
             let mut h = TermHighlighter::default();
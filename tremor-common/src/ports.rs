@@ -25,3 +25,6 @@ pub const ERR: Cow<'static, str> = Cow::const_str("err");
 
 /// standard metrics port
 pub const METRICS: Cow<'static, str> = Cow::const_str("metrics");
+
+/// standard port for connection lifecycle events
+pub const CONNECTIONS: Cow<'static, str> = Cow::const_str("connections");
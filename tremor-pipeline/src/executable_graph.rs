@@ -26,7 +26,7 @@ use crate::{op::EventAndInsights, Event, NodeKind, Operator};
 use beef::Cow;
 use halfbrown::HashMap;
 use tremor_common::{ids::OperatorId, stry};
-use tremor_script::{ast::Helper, ast::Stmt, Value};
+use tremor_script::{ast::Helper, ast::Stmt, profile, Value};
 
 /// Configuration for a node
 #[derive(Debug, Clone, Default)]
@@ -238,13 +238,50 @@ impl NodeMetrics {
     }
 }
 
+/// A node within the topology of an executable graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopologyNode {
+    /// ID of the node
+    pub id: String,
+    /// kind of the node: `input`, `output`, `operator`, `select` or `script`
+    pub kind: String,
+    /// operator namespace, e.g. `trickle::select`
+    pub op_type: String,
+}
+
+/// A connection between the ports of two nodes within an executable graph
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TopologyEdge {
+    /// ID of the node the connection starts at
+    pub from: String,
+    /// output port of the `from` node
+    pub from_port: String,
+    /// ID of the node the connection ends at
+    pub to: String,
+    /// input port of the `to` node
+    pub to_port: String,
+}
+
+/// The nodes and connections of an executable graph
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphTopology {
+    /// all nodes of the graph
+    pub nodes: Vec<TopologyNode>,
+    /// all connections between nodes, ordered
+    pub edges: Vec<TopologyEdge>,
+}
+
 /// An executable graph, this is the executable
 /// form of a pipeline
 #[derive(Debug)]
 pub struct ExecutableGraph {
     /// ID of the graph
     pub id: String,
+    /// profiled frame of the flow the graph is deployed in, if any
+    pub(crate) flow_frame: Option<profile::Frame>,
     pub(crate) graph: Vec<OperatorNode>,
+    /// profiled frame of every node, named once instead of per event
+    pub(crate) operator_frames: Vec<profile::Frame>,
     pub(crate) state: State,
     pub(crate) inputs: HashMap<Cow<'static, str>, usize>,
     pub(crate) stack: Vec<(usize, Cow<'static, str>, Event)>,
@@ -264,7 +301,59 @@ pub struct ExecutableGraph {
 /// The return of a graph execution
 pub type Returns = Vec<(Cow<'static, str>, Event)>;
 
+/// The profiled frames of the nodes of the pipeline with the given id
+pub(crate) fn operator_frames(id: &str, graph: &[OperatorNode]) -> Vec<profile::Frame> {
+    graph
+        .iter()
+        .map(|node| profile::Frame::Operator(format!("{}/{}", id, node.id).into()))
+        .collect()
+}
+
 impl ExecutableGraph {
+    /// Marks the graph as deployed in the flow with the given id
+    pub fn set_flow(&mut self, flow_id: &str) {
+        self.flow_frame = Some(profile::Frame::Flow(flow_id.into()));
+    }
+
+    /// The nodes and connections of this graph
+    ///
+    /// This should be called before `optimize`, which rewires connections around skippable nodes.
+    #[must_use]
+    pub fn topology(&self) -> GraphTopology {
+        let nodes = self
+            .graph
+            .iter()
+            .map(|node| TopologyNode {
+                id: node.id.clone(),
+                kind: match node.kind {
+                    NodeKind::Input => "input",
+                    NodeKind::Output(_) => "output",
+                    NodeKind::Operator => "operator",
+                    NodeKind::Select => "select",
+                    NodeKind::Script => "script",
+                }
+                .to_string(),
+                op_type: node.op_type.clone(),
+            })
+            .collect();
+        let mut edges: Vec<_> = self
+            .port_indexes
+            .iter()
+            .flat_map(|((from, from_port), tos)| {
+                tos.iter().filter_map(move |(to, to_port)| {
+                    Some(TopologyEdge {
+                        from: self.graph.get(*from)?.id.clone(),
+                        from_port: from_port.to_string(),
+                        to: self.graph.get(*to)?.id.clone(),
+                        to_port: to_port.to_string(),
+                    })
+                })
+            })
+            .collect();
+        edges.sort();
+        GraphTopology { nodes, edges }
+    }
+
     /// Tries to optimise a pipeline
     pub fn optimize(&mut self) -> Option<()> {
         let mut i = 0;
@@ -445,6 +534,11 @@ impl ExecutableGraph {
 
     #[inline]
     fn run(&mut self, returns: &mut Returns) -> Result<()> {
+        let _profile = self
+            .flow_frame
+            .as_ref()
+            .filter(|_| profile::is_enabled())
+            .map(|frame| profile::enter(frame.clone()));
         while match self.next(returns) {
             Ok(res) => res,
             Err(e) => {
@@ -474,8 +568,14 @@ impl ExecutableGraph {
                 } else {
                     // ALLOW: We know the state was initiated
                     let state = unsafe { self.state.ops.get_unchecked_mut(idx) };
-                    let EventAndInsights { events, insights } =
-                        stry!(node.on_event(node.uid, &port, state, event));
+                    let EventAndInsights { events, insights } = {
+                        let _profile = self
+                            .operator_frames
+                            .get(idx)
+                            .filter(|_| profile::is_enabled())
+                            .map(|frame| profile::enter(frame.clone()));
+                        stry!(node.on_event(node.uid, &port, state, event))
+                    };
 
                     for (out_port, _) in &events {
                         unsafe { self.metrics.get_unchecked_mut(idx) }.inc_output(out_port);
@@ -806,6 +906,8 @@ mod test {
         let mut rx = METRICS_CHANNEL.rx();
         let mut g = ExecutableGraph {
             id: "test".into(),
+            flow_frame: None,
+            operator_frames: operator_frames("test", &graph),
             graph,
             state,
             inputs,
@@ -903,6 +1005,8 @@ mod test {
         };
         let mut g = ExecutableGraph {
             id: "test".into(),
+            flow_frame: None,
+            operator_frames: operator_frames("test", &graph),
             graph,
             state,
             inputs,
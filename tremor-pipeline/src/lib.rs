@@ -65,8 +65,10 @@ pub(crate) mod op;
 /// Tools to turn tremor query into pipelines
 pub mod query;
 pub use crate::event::{Event, ValueIter, ValueMetaIter};
-pub use crate::executable_graph::{ExecutableGraph, OperatorNode};
-pub(crate) use crate::executable_graph::{NodeMetrics, State};
+pub(crate) use crate::executable_graph::{operator_frames, NodeMetrics, State};
+pub use crate::executable_graph::{
+    ExecutableGraph, GraphTopology, OperatorNode, TopologyEdge, TopologyNode,
+};
 pub use op::{ConfigImpl, InitializableOperator, Operator};
 pub use tremor_script::prelude::EventOriginUri;
 pub(crate) type ExecPortIndexMap =
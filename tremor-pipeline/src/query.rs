@@ -21,8 +21,8 @@ use crate::{
         prelude::{IN, OUT},
         trickle::{operator::TrickleOperator, select::Select, simple_select::SimpleSelect, window},
     },
-    ConfigGraph, Connection, ExecPortIndexMap, ExecutableGraph, NodeConfig, NodeKind, NodeMetrics,
-    Operator, OperatorNode, State, METRICS_CHANNEL,
+    operator_frames, ConfigGraph, Connection, ExecPortIndexMap, ExecutableGraph, NodeConfig,
+    NodeKind, NodeMetrics, Operator, OperatorNode, State, METRICS_CHANNEL,
 };
 use beef::Cow;
 use halfbrown::HashMap;
@@ -649,6 +649,8 @@ impl Query {
                     .collect(),
                 stack: Vec::with_capacity(graph.len()),
                 id: pipeline_id.to_string(), // TODO make configurable
+                flow_frame: None,
+                operator_frames: operator_frames(pipeline_id, &graph),
                 last_metrics: 0,
                 state: State::new(iter::repeat(Value::null()).take(graph.len()).collect()),
                 graph,
@@ -30,7 +30,7 @@ mod node_id;
 pub mod query;
 pub(crate) mod raw;
 mod support;
-mod upable;
+pub(crate) mod upable;
 /// collection of AST visitors
 pub mod visitors;
 
@@ -276,6 +276,9 @@ pub struct Script<'script> {
     pub exprs: Exprs<'script>,
     /// Locals
     pub locals: usize,
+    /// Names of the top level locals and their index on the local stack
+    #[serde(skip)]
+    pub(crate) local_names: Vec<(String, usize)>,
     #[serde(skip)]
     /// Documentation from the script
     pub docs: docs::Docs,
@@ -283,6 +286,12 @@ pub struct Script<'script> {
 impl_expr!(Script);
 
 impl<'script> Script<'script> {
+    /// Names of the top level locals and their index on the local stack
+    #[must_use]
+    pub fn local_names(&self) -> &[(String, usize)] {
+        &self.local_names
+    }
+
     /// Runs the script and evaluates to a resulting event.
     /// This expects the script to be imutable!
     ///
@@ -336,7 +345,53 @@ impl<'script> Script<'script> {
         'script: 'event,
     {
         let mut local = LocalStack::with_size(self.locals);
+        self.run_with_stack(context, aggr, event, state, meta, &mut local)
+    }
 
+    /// Runs the script with its top level locals seeded from `locals`, the values
+    /// of the top level locals after the run are written back to `locals`
+    ///
+    /// # Errors
+    /// on runtime errors
+    pub fn run_with_locals<'event>(
+        &self,
+        context: &crate::EventContext,
+        aggr: AggrType,
+        event: &mut Value<'event>,
+        state: &mut Value<'static>,
+        meta: &mut Value<'event>,
+        locals: &mut HashMap<String, Value<'event>>,
+    ) -> Result<Return<'event>>
+    where
+        'script: 'event,
+    {
+        let mut local = LocalStack::with_size(self.locals);
+        for (name, idx) in &self.local_names {
+            if let (Some(value), Some(slot)) = (locals.get(name), local.values.get_mut(*idx)) {
+                *slot = Some(value.clone());
+            }
+        }
+        let res = self.run_with_stack(context, aggr, event, state, meta, &mut local);
+        for (name, idx) in &self.local_names {
+            if let Some(value) = local.values.get_mut(*idx).and_then(Option::take) {
+                locals.insert(name.clone(), value);
+            }
+        }
+        res
+    }
+
+    fn run_with_stack<'event>(
+        &self,
+        context: &crate::EventContext,
+        aggr: AggrType,
+        event: &mut Value<'event>,
+        state: &mut Value<'static>,
+        meta: &mut Value<'event>,
+        local: &mut LocalStack<'event>,
+    ) -> Result<Return<'event>>
+    where
+        'script: 'event,
+    {
         let mut exprs = self.exprs.iter().peekable();
         let opts = ExecOpts {
             result_needed: true,
@@ -350,7 +405,7 @@ impl<'script> Script<'script> {
 
         while let Some(expr) = exprs.next() {
             if exprs.peek().is_none() {
-                match stry!(expr.run(opts.with_result(), &env, event, state, meta, &mut local)) {
+                match stry!(expr.run(opts.with_result(), &env, event, state, meta, local)) {
                     Cont::Drop => return Ok(Return::Drop),
                     Cont::Emit(value, port) => return Ok(Return::Emit { value, port }),
                     Cont::EmitEvent(port) => {
@@ -364,7 +419,7 @@ impl<'script> Script<'script> {
                     }
                 }
             }
-            match stry!(expr.run(opts.without_result(), &env, event, state, meta, &mut local)) {
+            match stry!(expr.run(opts.without_result(), &env, event, state, meta, local)) {
                 Cont::Drop => return Ok(Return::Drop),
                 Cont::Emit(value, port) => return Ok(Return::Emit { value, port }),
                 Cont::EmitEvent(port) => {
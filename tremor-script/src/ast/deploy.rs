@@ -41,10 +41,65 @@ pub struct Deploy<'script> {
 
 impl<'script> Deploy<'script> {
     /// Provides a `GraphViz` dot file representation of the deployment graph
+    ///
+    /// Each deployed flow is rendered as a cluster of its connectors and pipelines
+    /// and the connections between them.
     #[must_use]
-    #[allow(clippy::unused_self)]
     pub fn dot(&self) -> String {
-        "todo".to_string() // TODO convert to graphviz dot file
+        use std::fmt::Write;
+        let quote = |s: &str| format!("\"{}\"", s.replace('"', "\\\""));
+        let mut dot = String::from("digraph deploy {\n  rankdir=LR;\n");
+        // writing to a String never fails
+        for stmt in &self.stmts {
+            let flow = if let DeployStmt::DeployFlowStmt(flow) = stmt {
+                flow
+            } else {
+                continue;
+            };
+            let alias = &flow.instance_alias;
+            let node = |instance: &str| quote(&format!("{alias}/{instance}"));
+            let _ = writeln!(dot, "  subgraph {} {{", quote(&format!("cluster_{alias}")));
+            let _ = writeln!(dot, "    label={};", quote(&format!("flow {alias}")));
+            for create in &flow.defn.creates {
+                let (shape, kind) = match &create.defn {
+                    CreateTargetDefinition::Connector(c) => ("box", c.builtin_kind.as_str()),
+                    CreateTargetDefinition::Pipeline(_) => ("ellipse", "pipeline"),
+                };
+                let _ = writeln!(
+                    dot,
+                    "    {} [shape={shape}, label={}];",
+                    node(&create.instance_alias),
+                    quote(&format!("{}\\n{kind}", create.instance_alias))
+                );
+            }
+            for connect in &flow.defn.connections {
+                let (from, to) = match connect {
+                    ConnectStmt::ConnectorToPipeline { from, to, .. }
+                    | ConnectStmt::PipelineToConnector { from, to, .. }
+                    | ConnectStmt::PipelineToPipeline { from, to, .. } => (from, to),
+                };
+                let _ = writeln!(
+                    dot,
+                    "    {} -> {} [label={}];",
+                    node(from.alias()),
+                    node(to.alias()),
+                    quote(&format!("{} -> {}", from.port(), to.port()))
+                );
+            }
+            if let Some(dead_letter) = &flow.defn.dead_letter {
+                let _ = writeln!(dot, "    {} [shape=point];", node("flow/err"));
+                let _ = writeln!(
+                    dot,
+                    "    {} -> {} [style=dashed, label={}];",
+                    node("flow/err"),
+                    node(dead_letter.alias()),
+                    quote(&format!("err -> {}", dead_letter.port()))
+                );
+            }
+            dot.push_str("  }\n");
+        }
+        dot.push_str("}\n");
+        dot
     }
 }
 
@@ -236,6 +291,8 @@ pub struct FlowDefinition<'script> {
     pub connections: Vec<ConnectStmt>,
     /// Deployment atoms
     pub creates: Vec<CreateStmt<'script>>,
+    /// Connector receiving all error events not explicitly routed within the flow
+    pub dead_letter: Option<DeployEndpoint>,
     /// Documentation comments
     #[serde(skip)]
     pub docs: Option<String>,
@@ -36,6 +36,7 @@ use crate::{
     },
     errors::{Error, Kind as ErrorKind, Result},
     impl_expr,
+    lint::{self, Rule},
     module::Manager,
     AggrType, EventContext, Return,
 };
@@ -82,6 +83,14 @@ impl<'script> DeployRaw<'script> {
                 stmts.push(stmt);
             }
         }
+        let deployed = lint::local_targets(stmts.iter().filter_map(|stmt| match stmt {
+            DeployStmt::DeployFlowStmt(d) => Some(&d.from_target),
+            _ => None,
+        }));
+        for (span, msg) in lint::unused_definitions("flow", &helper.scope.content.flows, &deployed)
+        {
+            helper.warn_with_scope(Rule::UnusedDefine, span, &msg);
+        }
 
         helper.docs.module = Some(ModDoc {
             name: "self".into(),
@@ -272,6 +281,34 @@ impl<'script> Upable<'script> for ConnectStmtRaw<'script> {
     }
 }
 
+/// we're forced to make this pub because of lalrpop
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct DeadLetterRaw<'script> {
+    /// The port of the flow, only `err` is supported
+    pub(crate) port: IdentRaw<'script>,
+    /// The connector receiving all unrouted error events
+    pub(crate) to: DeployEndpointRaw<'script>,
+    pub(crate) mid: Box<NodeMeta>,
+}
+impl_expr!(DeadLetterRaw);
+
+impl<'script> Upable<'script> for DeadLetterRaw<'script> {
+    type Target = DeployEndpoint;
+    fn up<'registry>(self, helper: &mut Helper<'script, 'registry>) -> Result<Self::Target> {
+        if &*self.port.id != "err" {
+            return error_generic(
+                &self,
+                &self.port,
+                &format!(
+                    "Only the `err` port of a flow can be connected, not `{}`",
+                    self.port.id
+                ),
+            );
+        }
+        self.to.up(helper)
+    }
+}
+
 /// we're forced to make this pub because of lalrpop
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct FlowDefinitionRaw<'script> {
@@ -310,6 +347,7 @@ impl<'script> Upable<'script> for FlowDefinitionRaw<'script> {
 
         let mut connections = Vec::new();
         let mut creates = Vec::new();
+        let mut dead_letter = None;
         for stmt in self.stmts {
             match stmt {
                 FlowStmtRaw::Use(UseRaw { alias, module, mid }) => {
@@ -335,6 +373,16 @@ impl<'script> Upable<'script> for FlowDefinitionRaw<'script> {
                 FlowStmtRaw::Connect(connect) => {
                     connections.push(connect.up(helper)?);
                 }
+                FlowStmtRaw::DeadLetter(connect) => {
+                    if dead_letter.is_some() {
+                        return error_generic(
+                            &connect,
+                            &connect,
+                            &"The `err` port of a flow can only be connected once",
+                        );
+                    }
+                    dead_letter = Some(connect.up(helper)?);
+                }
                 FlowStmtRaw::Create(stmt) => {
                     creates.push(stmt.up(helper)?);
                 }
@@ -345,6 +393,17 @@ impl<'script> Upable<'script> for FlowDefinitionRaw<'script> {
             .doc
             .map(|d| d.iter().map(|l| l.trim()).collect::<Vec<_>>().join("\n"));
         let params = self.params.up(helper)?;
+        let targets = lint::local_targets(creates.iter().map(|c: &CreateStmt| &c.from_target));
+        let content = &helper.scope.content;
+        let mut unused = lint::unused_definitions("connector", &content.connectors, &targets);
+        unused.append(&mut lint::unused_definitions(
+            "pipeline",
+            &content.pipelines,
+            &targets,
+        ));
+        for (span, msg) in unused {
+            helper.warn_with_scope(Rule::UnusedDefine, span, &msg);
+        }
         helper.leave_scope()?;
 
         let flow_defn = FlowDefinition {
@@ -353,6 +412,7 @@ impl<'script> Upable<'script> for FlowDefinitionRaw<'script> {
             params,
             connections,
             creates,
+            dead_letter,
             docs,
         };
         Ok(flow_defn)
@@ -366,6 +426,7 @@ pub(crate) enum FlowStmtRaw<'script> {
     ConnectorDefinition(ConnectorDefinitionRaw<'script>),
     PipelineDefinition(PipelineDefinitionRaw<'script>),
     Connect(ConnectStmtRaw<'script>),
+    DeadLetter(DeadLetterRaw<'script>),
     Create(CreateStmtRaw<'script>),
     Use(UseRaw),
 }
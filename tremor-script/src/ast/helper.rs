@@ -21,6 +21,7 @@ use super::{
 };
 use crate::{
     errors::Result,
+    lint::Rule,
     pos::Span,
     prelude::*,
     registry::{Aggr as AggrRegistry, Registry},
@@ -41,18 +42,21 @@ pub struct Warning {
     pub inner: Span,
     /// Warning message
     pub msg: String,
+    /// Lint rule that raised the warning
+    pub rule: Rule,
 }
 
 impl Warning {
-    fn new<T: ToString>(inner: Span, outer: Span, msg: &T) -> Self {
+    fn new<T: ToString>(rule: Rule, inner: Span, outer: Span, msg: &T) -> Self {
         Self {
             outer,
             inner,
             msg: msg.to_string(),
+            rule,
         }
     }
-    fn new_with_scope<T: ToString>(warning_scope: Span, msg: &T) -> Self {
-        Self::new(warning_scope, warning_scope, msg)
+    fn new_with_scope<T: ToString>(rule: Rule, warning_scope: Span, msg: &T) -> Self {
+        Self::new(rule, warning_scope, warning_scope, msg)
     }
 }
 
@@ -308,11 +312,25 @@ where
         })
     }
 
-    pub(crate) fn warn<S: ToString>(&mut self, inner: Span, outer: Span, msg: &S) {
-        self.warnings.insert(Warning::new(inner, outer, msg));
+    /// Names and indexes of the locals that are not shadow variables
+    pub(crate) fn named_locals(&self) -> Vec<(String, usize)> {
+        self.locals
+            .iter()
+            .filter(|(id, _)| !id.starts_with(" __SHADOW "))
+            .map(|(id, idx)| (id.clone(), *idx))
+            .collect()
+    }
+
+    /// Tests if a local variable or shadowed variable with the given name is in scope
+    pub(crate) fn is_bound(&self, id: &str) -> bool {
+        self.find_shadow_var(id).is_some() || self.locals.contains_key(id)
+    }
+
+    pub(crate) fn warn<S: ToString>(&mut self, rule: Rule, inner: Span, outer: Span, msg: &S) {
+        self.warnings.insert(Warning::new(rule, inner, outer, msg));
     }
-    pub(crate) fn warn_with_scope<S: ToString>(&mut self, r: Span, msg: &S) {
-        self.warnings.insert(Warning::new_with_scope(r, msg));
+    pub(crate) fn warn_with_scope<S: ToString>(&mut self, rule: Rule, r: Span, msg: &S) {
+        self.warnings.insert(Warning::new_with_scope(rule, r, msg));
     }
 }
 
@@ -27,15 +27,17 @@ use super::{
 };
 use crate::{
     arena::{self, Arena},
+    coverage,
     errors::{already_defined_err, Error, Kind as ErrorKind, Result},
     impl_expr,
     lexer::{Lexer, Span},
     path::ModulePath,
-    FN_REGISTRY,
+    profile, FN_REGISTRY,
 };
 use beef::Cow;
 use sha2::Digest;
 use std::collections::hash_map::Entry;
+use std::path::PathBuf;
 use std::{collections::HashMap, fmt::Debug};
 
 use std::sync::RwLock;
@@ -162,7 +164,10 @@ impl<'script> Content<'script> {
             Err(already_defined_err(&elem, "const"))
         }
     }
-    pub(crate) fn insert_function(&mut self, elem: FnDefn<'script>) -> Result<()> {
+    pub(crate) fn insert_function(&mut self, mut elem: FnDefn<'script>) -> Result<()> {
+        if coverage::is_enabled() {
+            coverage::register_fn(&mut elem)?;
+        }
         let name = elem.name.clone();
         if let Entry::Vacant(e) = self.functions.entry(name) {
             e.insert(elem);
@@ -419,6 +424,13 @@ impl Manager {
         MODULES.write()?.path.add(path);
         Ok(())
     }
+    /// Resolves the file a module is loaded from, using the module path
+    /// # Errors
+    /// if the module global can't be aquired
+    pub fn resolve(node_id: &NodeId) -> Result<Option<PathBuf>> {
+        Ok(MODULES.read()?.path.resolve_id(node_id))
+    }
+
     /// shows modules
     pub(crate) fn modules(&self) -> &[Module] {
         &self.modules
@@ -484,6 +496,12 @@ impl Manager {
             Ok(Index(id))
         } else {
             let (arena_idx, src) = Arena::insert(&src)?;
+            if coverage::is_enabled() {
+                coverage::register_file(arena_idx, &p.to_string_lossy())?;
+            }
+            if profile::is_enabled() {
+                profile::register_file(arena_idx, &p.to_string_lossy())?;
+            }
             let m = Module::load(id, ids, arena_idx, src)?;
 
             let mut mm = MODULES.write()?;
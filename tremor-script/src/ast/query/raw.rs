@@ -30,14 +30,17 @@ use super::{
 use crate::{ast::NodeMeta, impl_expr};
 use crate::{
     ast::{
+        base_expr::Ranged,
         node_id::NodeId,
         raw::UseRaw,
         visitors::{ConstFolder, GroupByExprExtractor, TargetEventRef},
         walkers::{ImutExprWalker, QueryWalker},
         Consts, Ident,
     },
+    coverage,
     errors::{Error, Kind as ErrorKind},
     impl_expr_no_lt,
+    lint::{self, Rule},
     module::Manager,
 };
 use beef::Cow;
@@ -64,6 +67,12 @@ impl<'script> QueryRaw<'script> {
             .collect::<Result<_>>()?;
         for stmt in &mut stmts {
             ConstFolder::new(helper).walk_stmt(stmt)?;
+            if coverage::is_enabled() {
+                coverage::register_stmt(stmt)?;
+            }
+        }
+        for (span, msg) in lint::unused_query_definitions(&helper.scope.content, &stmts) {
+            helper.warn_with_scope(Rule::UnusedDefine, span, &msg);
         }
         let mut from = Vec::new();
         let mut into = Vec::new();
@@ -216,6 +225,17 @@ impl<'script> Upable<'script> for OperatorDefinitionRaw<'script> {
             kind: self.kind.up(helper)?,
             params: self.params.up(helper)?,
         };
+        let kind = &operator_defn.kind;
+        if let Some(replacement) = lint::deprecated_operator(&kind.module, &kind.operation) {
+            helper.warn_with_scope(
+                Rule::Deprecated,
+                kind.extent(),
+                &format!(
+                    "The {}::{} operator is deprecated, please use {} instead.",
+                    kind.module, kind.operation, replacement
+                ),
+            );
+        }
         helper.add_query_doc(&operator_defn.id, self.doc);
         Ok(operator_defn)
     }
@@ -18,19 +18,24 @@
 
 use crate::{
     ast::{
-        base_expr, query, upable::Upable, visitors::ConstFolder, walkers::ExprWalker, ArrayPattern,
-        ArrayPredicatePattern, AssignPattern, BinExpr, BinOpKind, Bytes, BytesPart, ClauseGroup,
-        Comprehension, ComprehensionCase, Costly, DefaultCase, EmitExpr, EventPath, Expr, ExprPath,
-        Expression, Field, FnDefn, Helper, Ident, IfElse, ImutExpr, Invocable, Invoke, InvokeAggr,
-        InvokeAggrFn, List, Literal, LocalPath, Match, Merge, MetadataPath, Patch, PatchOperation,
-        Path, Pattern, PredicateClause, PredicatePattern, Record, RecordPattern, Recur,
-        ReservedPath, Script, Segment, StatePath, StrLitElement, StringLit, TestExpr, TuplePattern,
-        UnaryExpr, UnaryOpKind,
+        base_expr, query,
+        upable::Upable,
+        visitors::{ConstFolder, TypeChecker},
+        walkers::{ExprWalker, ImutExprWalker},
+        ArrayPattern, ArrayPredicatePattern, AssignPattern, BinExpr, BinOpKind, Bytes, BytesPart,
+        ClauseGroup, Comprehension, ComprehensionCase, Costly, DefaultCase, EmitExpr, EventPath,
+        Expr, ExprPath, Expression, Field, FnDefn, Helper, Ident, IfElse, ImutExpr, Invocable,
+        Invoke, InvokeAggr, InvokeAggrFn, List, Literal, LocalPath, Match, Merge, MetadataPath,
+        Patch, PatchOperation, Path, Pattern, PredicateClause, PredicatePattern, Record,
+        RecordPattern, Recur, ReservedPath, Script, Segment, StatePath, StrLitElement, StringLit,
+        TestExpr, TuplePattern, UnaryExpr, UnaryOpKind,
     },
+    coverage,
     errors::{
         err_generic, error_generic, error_missing_effector, Error, Kind as ErrorKind, Result,
     },
     impl_expr, impl_expr_exraw, impl_expr_no_lt,
+    lint::{Rule, UnusedLets},
     prelude::*,
     tilde::Extractor,
     KnownKey, Value,
@@ -59,9 +64,9 @@ impl_expr_no_lt!(UseRaw);
 /// A raw script we got to put this here because of silly lalrpoop focing it to be public
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ScriptRaw<'script> {
-    mid: Box<NodeMeta>,
-    exprs: TopLevelExprsRaw<'script>,
-    doc: Option<Vec<Cow<'script, str>>>,
+    pub(crate) mid: Box<NodeMeta>,
+    pub(crate) exprs: TopLevelExprsRaw<'script>,
+    pub(crate) doc: Option<Vec<Cow<'script, str>>>,
 }
 
 impl<'script> ScriptRaw<'script> {
@@ -105,6 +110,7 @@ impl<'script> ScriptRaw<'script> {
                 TopLevelExprRaw::FnDefn(f) => {
                     let mut f = f.up(helper)?;
                     ExprWalker::walk_fn_defn(&mut ConstFolder::new(helper), &mut f)?;
+                    TypeChecker::check_fn(helper, &mut f, crate::type_check())?;
                     helper.scope.insert_function(f)?;
                 }
                 TopLevelExprRaw::Expr(expr) => {
@@ -139,6 +145,12 @@ impl<'script> ScriptRaw<'script> {
             exprs.push(Expr::Emit(Box::new(expr)));
         }
 
+        UnusedLets::check(helper, &mut exprs)?;
+        TypeChecker::check_exprs(helper, &mut exprs, crate::type_check())?;
+        if coverage::is_enabled() {
+            coverage::register_exprs(&mut exprs)?;
+        }
+
         helper.docs.module = Some(ModDoc {
             name: "self".into(),
             doc: self
@@ -150,6 +162,7 @@ impl<'script> ScriptRaw<'script> {
             mid: self.mid,
             exprs,
             locals: helper.locals.len(),
+            local_names: helper.named_locals(),
             docs: helper.docs.clone(),
         })
     }
@@ -990,6 +1003,15 @@ where
     fn up<'registry>(self, helper: &mut Helper<'script, 'registry>) -> Result<Self::Target> {
         let was_leaf = helper.possible_leaf;
         helper.possible_leaf = false;
+        if let PatternRaw::Assign(AssignPatternRaw { id, .. }) = &self.pattern {
+            if helper.is_bound(id) {
+                helper.warn_with_scope(
+                    Rule::ShadowedLocal,
+                    self.mid.range,
+                    &format!("The binding `{}` shadows a local of the same name", id),
+                );
+            }
+        }
         // We run the pattern first as this might reserve a local shadow
         let pattern = self.pattern.up(helper)?;
         let guard = self.guard.up(helper)?;
@@ -1277,6 +1299,15 @@ where
 {
     type Target = ComprehensionCase<'script, Ex::Target>;
     fn up<'registry>(self, helper: &mut Helper<'script, 'registry>) -> Result<Self::Target> {
+        for name in [&self.key_name, &self.value_name] {
+            if helper.is_bound(name) {
+                helper.warn_with_scope(
+                    Rule::ShadowedLocal,
+                    self.mid.range,
+                    &format!("The binding `{}` shadows a local of the same name", name),
+                );
+            }
+        }
         // regiter key and value as shadowed variables
         let key_idx = helper.register_shadow_var(&self.key_name);
         let val_idx = helper.register_shadow_var(&self.value_name);
@@ -1498,6 +1529,7 @@ impl<'script> Upable<'script> for RecordPatternRaw<'script> {
             });
             if duplicated {
                 helper.warn(
+                    Rule::RedundantPattern,
                     self.mid.range,
                     self.mid.range.expand_lines(2),
                     &format!("The field {} is checked with both present and another extractor, this is redundant as extractors imply presence. It may also overwrite the result of the extractor.", present),
@@ -1515,6 +1547,7 @@ impl<'script> Upable<'script> for RecordPatternRaw<'script> {
             });
             if duplicated {
                 helper.warn(
+                    Rule::UnreachableCase,
                     self.mid.range,
                     self.mid.range.expand_lines(2),
                     &format!("The field {} is checked with both absence and another extractor, this test can never be true.", absent),
@@ -2027,6 +2060,58 @@ fn sort_clauses<Ex: Expression>(patterns: &mut [PredicateClause<Ex>]) {
 
 const NO_DFLT: &str = "This match expression has no default clause, if the other clauses do not cover all possibilities this will lead to events being discarded with runtime errors.";
 const MULT_DFLT: &str = "A match statement with more then one default clause will never reach any but the first default clause.";
+const UNREACHABLE: &str = "This case can never be reached as a previous case matches everything.";
+const TRUE_GUARD: &str = "This guard is always true and can be removed.";
+const FALSE_GUARD: &str = "This guard is always false, the case can never be reached.";
+/// lints for the clauses of a match: missing or multiple defaults, unreachable cases and constant guards
+fn lint_clauses<'script, Ex: Expression>(
+    helper: &mut Helper<'script, '_>,
+    mid: &NodeMeta,
+    patterns: &[PredicateClause<'script, Ex>],
+) {
+    let defaults = patterns
+        .iter()
+        .filter(|p| p.pattern.is_default() && p.guard.is_none())
+        .count();
+    match defaults {
+        0 => helper.warn_with_scope(Rule::MissingDefault, mid.range, &NO_DFLT),
+        x if x > 1 => helper.warn_with_scope(Rule::UnreachableCase, mid.range, &MULT_DFLT),
+        _ => (),
+    };
+    // cases after one that matches everything can never be taken
+    let catch_all = patterns
+        .iter()
+        .position(|p| p.pattern.is_default() && p.guard.is_none());
+    if let Some(catch_all) = catch_all {
+        for p in patterns.iter().skip(catch_all + 1) {
+            if !(p.pattern.is_default() && p.guard.is_none()) {
+                helper.warn_with_scope(Rule::UnreachableCase, p.extent(), &UNREACHABLE);
+            }
+        }
+    }
+    for p in patterns {
+        if let Some(guard) = &p.guard {
+            let span = guard.extent();
+            let mut guard = guard.clone();
+            // a guard that fails to fold is not constant, so we can ignore the error here
+            if ImutExprWalker::walk_expr(&mut ConstFolder::new(helper), &mut guard).is_err() {
+                continue;
+            }
+            if let ImutExpr::Literal(Literal { value, .. }) = &guard {
+                match value.as_bool() {
+                    Some(true) => {
+                        helper.warn_with_scope(Rule::AlwaysTrueGuard, span, &TRUE_GUARD);
+                    }
+                    Some(false) => {
+                        helper.warn_with_scope(Rule::UnreachableCase, span, &FALSE_GUARD);
+                    }
+                    None => (),
+                }
+            }
+        }
+    }
+}
+
 impl<'script, Ex> Upable<'script> for MatchRaw<'script, Ex>
 where
     <Ex as Upable<'script>>::Target: Expression + 'script,
@@ -2040,15 +2125,8 @@ where
             .map(|v| v.up(helper))
             .collect::<Result<_>>()?;
 
-        let defaults = patterns
-            .iter()
-            .filter(|p| p.pattern.is_default() && p.guard.is_none())
-            .count();
-        match defaults {
-            0 => helper.warn_with_scope(self.mid.range, &NO_DFLT),
-            x if x > 1 => helper.warn_with_scope(self.mid.range, &MULT_DFLT),
-            _ => (),
-        };
+        lint_clauses(helper, &self.mid, &patterns);
+
         // If the last statement is a global default we can simply remove it
         let default = if let Some(PredicateClause {
             pattern: Pattern::Default,
@@ -2259,7 +2337,7 @@ impl<'script> Upable<'script> for InvokeAggrRaw<'script> {
             .into());
         }
         if let Some(warning) = invocable.warning() {
-            helper.warn_with_scope(self.extent(), &warning);
+            helper.warn_with_scope(Rule::Deprecated, self.extent(), &warning);
         }
         let aggr_id = helper.aggregates.len();
         let args = self.args.up(helper)?.into_iter().collect();
@@ -380,3 +380,38 @@ fn pp_is_exclusive() {
         "~= is not exclusive to eq if the patterns are not exclusive"
     );
 }
+
+#[test]
+fn run_with_locals() -> crate::errors::Result<()> {
+    let reg = crate::registry::registry();
+    let ctx = crate::EventContext::new(0, None);
+    let mut locals = HashMap::new();
+    let mut event = Value::object();
+    let mut state = Value::null();
+    let mut meta = Value::object();
+
+    let script = crate::Script::parse("let a = 1; a", &reg)?;
+    script.run_with_locals(
+        &ctx,
+        AggrType::Emit,
+        &mut event,
+        &mut state,
+        &mut meta,
+        &mut locals,
+    )?;
+    assert_eq!(Some(1), locals.get("a").and_then(Value::as_i64));
+
+    let script = crate::Script::parse("let b = a + 1; b", &reg)?;
+    let res = script.run_with_locals(
+        &ctx,
+        AggrType::Emit,
+        &mut event,
+        &mut state,
+        &mut meta,
+        &mut locals,
+    )?;
+    assert_matches!(res, Return::Emit { value, .. } if value.as_i64() == Some(2));
+    assert_eq!(Some(1), locals.get("a").and_then(Value::as_i64));
+    assert_eq!(Some(2), locals.get("b").and_then(Value::as_i64));
+    Ok(())
+}
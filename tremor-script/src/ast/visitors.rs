@@ -30,6 +30,7 @@ pub use impls::const_folder::ConstFolder;
 pub(crate) use impls::group_by_extractor::GroupByExprExtractor;
 pub(crate) use impls::is_const::IsConstFn;
 pub(crate) use impls::target_event_ref::TargetEventRef;
+pub(crate) use impls::type_checker::TypeChecker;
 
 pub(crate) use deploy::Visitor as DeployVisitor;
 pub(crate) use expr::Visitor as ExprVisitor;
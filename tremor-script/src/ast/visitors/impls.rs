@@ -17,3 +17,4 @@ pub(crate) mod const_folder;
 pub(crate) mod group_by_extractor;
 pub(crate) mod is_const;
 pub(crate) mod target_event_ref;
+pub(crate) mod type_checker;
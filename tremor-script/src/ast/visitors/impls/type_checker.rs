@@ -0,0 +1,515 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::ast::base_expr::Ranged;
+use crate::ast::visitors::prelude::*;
+use crate::ast::{BinOpKind, Invocable, UnaryOpKind};
+use crate::errors::{Kind as ErrorKind, Result};
+use crate::lint::Rule;
+use crate::pos::Span;
+use crate::registry::Types;
+use crate::{TypeCheck, Value};
+use simd_json::StaticNode;
+use std::collections::HashMap;
+
+/// A type mismatch found by the `TypeChecker`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mismatch {
+    pub(crate) outer: Span,
+    pub(crate) inner: Span,
+    pub(crate) msg: String,
+    /// the value can never have the expected type, as opposed to
+    /// only possibly having a different one
+    pub(crate) definite: bool,
+}
+
+/// Infers types of expressions bottom up and reports values passed to
+/// functions and operators that can not, or might not, have the type
+/// they require.
+///
+/// Locals are tracked in the order they are assigned, assignments inside
+/// of branches only widen the type of a local.
+#[derive(Default)]
+pub(crate) struct TypeChecker {
+    locals: HashMap<usize, Types>,
+    branches: usize,
+    pub(crate) mismatches: Vec<Mismatch>,
+}
+
+impl TypeChecker {
+    /// Checks the expressions of a script, reporting mismatches to the helper
+    pub(crate) fn check_exprs<'script>(
+        helper: &mut Helper<'script, '_>,
+        exprs: &mut Exprs<'script>,
+        mode: TypeCheck,
+    ) -> Result<()> {
+        if mode == TypeCheck::Off {
+            return Ok(());
+        }
+        let mut checker = Self::default();
+        for e in exprs {
+            ExprWalker::walk_expr(&mut checker, e)?;
+        }
+        checker.report(helper, mode)
+    }
+
+    /// Checks the body of a function, reporting mismatches to the helper
+    pub(crate) fn check_fn<'script>(
+        helper: &mut Helper<'script, '_>,
+        f: &mut FnDefn<'script>,
+        mode: TypeCheck,
+    ) -> Result<()> {
+        if mode == TypeCheck::Off {
+            return Ok(());
+        }
+        let mut checker = Self::default();
+        ExprWalker::walk_fn_defn(&mut checker, f)?;
+        checker.report(helper, mode)
+    }
+
+    fn report(self, helper: &mut Helper, mode: TypeCheck) -> Result<()> {
+        for m in self.mismatches {
+            if m.definite && mode == TypeCheck::Strict {
+                return Err(ErrorKind::TypeMismatch(m.outer, m.inner, m.msg).into());
+            }
+            helper.warn(Rule::TypeMismatch, m.inner, m.outer, &m.msg);
+        }
+        Ok(())
+    }
+
+    fn literal(value: &Value) -> Types {
+        match value {
+            Value::Static(StaticNode::Null) => Types::NULL,
+            Value::Static(StaticNode::Bool(_)) => Types::BOOL,
+            Value::Static(StaticNode::F64(_)) => Types::FLOAT,
+            Value::Static(_) => Types::INTEGER,
+            Value::String(_) => Types::STRING,
+            Value::Array(_) => Types::ARRAY,
+            Value::Object(_) => Types::RECORD,
+            Value::Bytes(_) => Types::BYTES,
+        }
+    }
+
+    /// Numeric result of an arithmetic operation
+    fn numeric(lhs: Types, rhs: Types) -> Types {
+        if lhs.is_subset(Types::INTEGER) && rhs.is_subset(Types::INTEGER) {
+            Types::INTEGER
+        } else if lhs.is_subset(Types::FLOAT) || rhs.is_subset(Types::FLOAT) {
+            Types::FLOAT
+        } else {
+            Types::NUMBER
+        }
+    }
+
+    /// The types an expression can evaluate to
+    fn infer(&self, e: &ImutExpr) -> Types {
+        match e {
+            ImutExpr::Record(_) | ImutExpr::Patch(_) | ImutExpr::Merge(_) => Types::RECORD,
+            ImutExpr::List(_) | ImutExpr::Comprehension(_) => Types::ARRAY,
+            ImutExpr::String(_) => Types::STRING,
+            ImutExpr::Bytes(_) => Types::BYTES,
+            ImutExpr::Present { .. } => Types::BOOL,
+            ImutExpr::Literal(l) => Self::literal(&l.value),
+            ImutExpr::Local { idx, .. } => self.locals.get(idx).copied().unwrap_or(Types::ANY),
+            ImutExpr::Binary(b) => self.infer_binary(b),
+            ImutExpr::Unary(u) => match u.kind {
+                UnaryOpKind::Not => Types::BOOL,
+                UnaryOpKind::BitNot => Types::INTEGER.union(Types::BOOL),
+                UnaryOpKind::Plus | UnaryOpKind::Minus => {
+                    let t = self.infer(&u.expr).intersection(Types::NUMBER);
+                    if t.is_none() {
+                        Types::NUMBER
+                    } else {
+                        t
+                    }
+                }
+            },
+            ImutExpr::Invoke(i)
+            | ImutExpr::Invoke1(i)
+            | ImutExpr::Invoke2(i)
+            | ImutExpr::Invoke3(i) => match &i.invocable {
+                Invocable::Intrinsic(f) => f.signature().map_or(Types::ANY, |s| s.result),
+                Invocable::Tremor(_) => Types::ANY,
+            },
+            ImutExpr::Match(_)
+            | ImutExpr::Path(_)
+            | ImutExpr::InvokeAggr(_)
+            | ImutExpr::Recur(_) => Types::ANY,
+        }
+    }
+
+    fn infer_binary(&self, b: &BinExpr) -> Types {
+        use BinOpKind::{
+            Add, And, BitAnd, BitXor, Div, Eq, Gt, Gte, LBitShift, Lt, Lte, Mod, Mul, NotEq, Or,
+            RBitShiftSigned, RBitShiftUnsigned, Sub, Xor,
+        };
+        let lhs = self.infer(&b.lhs);
+        let rhs = self.infer(&b.rhs);
+        match b.kind {
+            Or | Xor | And | Eq | NotEq | Gte | Gt | Lte | Lt => Types::BOOL,
+            BitXor | BitAnd => Types::INTEGER.union(Types::BOOL),
+            RBitShiftSigned | RBitShiftUnsigned | LBitShift | Mod => Types::INTEGER,
+            Div => Types::FLOAT,
+            Sub | Mul => Self::numeric(lhs, rhs),
+            Add => {
+                if lhs.is_subset(Types::STRING) || rhs.is_subset(Types::STRING) {
+                    Types::STRING
+                } else if lhs.is_subset(Types::NUMBER) || rhs.is_subset(Types::NUMBER) {
+                    Self::numeric(lhs, rhs)
+                } else {
+                    Types::NUMBER.union(Types::STRING)
+                }
+            }
+        }
+    }
+
+    /// Records a mismatch if `actual` is known and not within `expected`
+    fn expect(
+        &mut self,
+        outer: Span,
+        inner: Span,
+        what: &str,
+        expected: Types,
+        actual: Types,
+    ) -> bool {
+        if actual.is_any() || actual.is_subset(expected) {
+            return false;
+        }
+        let definite = actual.intersection(expected).is_none();
+        let msg = if definite {
+            format!("{} expects {} but got {}", what, expected, actual)
+        } else {
+            format!("{} expects {} but might get {}", what, expected, actual)
+        };
+        self.mismatches.push(Mismatch {
+            outer,
+            inner,
+            msg,
+            definite,
+        });
+        true
+    }
+
+    fn bind(&mut self, idx: usize, t: Types) {
+        let t = if self.branches > 0 {
+            self.locals.get(&idx).map_or(t, |old| old.union(t))
+        } else {
+            t
+        };
+        self.locals.insert(idx, t);
+    }
+}
+
+impl<'script> walkers::imut_expr::Walker<'script> for TypeChecker {}
+impl<'script> visitors::imut_expr::Visitor<'script> for TypeChecker {
+    fn visit_invoke(&mut self, invoke: &mut Invoke<'script>) -> Result<VisitRes> {
+        if let Invocable::Intrinsic(f) = &invoke.invocable {
+            if let Some(sig) = f.signature() {
+                let outer = invoke.extent();
+                // the registered name, e.g. `string::len`, not the path of the wrapping module
+                let name = format!("{}::{}", f.module(), f.name());
+                for (n, arg) in invoke.args.iter().enumerate() {
+                    if let Some(expected) = sig.arg(n) {
+                        let actual = self.infer(arg);
+                        let what = format!("argument {} of `{}`", n + 1, name);
+                        self.expect(outer, arg.extent(), &what, expected, actual);
+                    }
+                }
+            }
+        }
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_binary(&mut self, binary: &mut BinExpr<'script>) -> Result<VisitRes> {
+        use BinOpKind::{
+            Add, And, BitAnd, BitXor, Div, Eq, Gt, Gte, LBitShift, Lt, Lte, Mod, Mul, NotEq, Or,
+            RBitShiftSigned, RBitShiftUnsigned, Sub, Xor,
+        };
+        let expected = match binary.kind {
+            Or | Xor | And => Types::BOOL,
+            Sub | Mul | Div => Types::NUMBER,
+            RBitShiftSigned | RBitShiftUnsigned | LBitShift | Mod => Types::INTEGER,
+            Add => Types::NUMBER.union(Types::STRING),
+            BitXor | BitAnd | Eq | NotEq | Gte | Gt | Lte | Lt => return Ok(VisitRes::Walk),
+        };
+        let outer = binary.extent();
+        let what = format!("operator `{}`", binary.kind);
+        let lhs = self.infer(&binary.lhs);
+        let rhs = self.infer(&binary.rhs);
+        let reported = self.expect(outer, binary.lhs.extent(), &what, expected, lhs)
+            | self.expect(outer, binary.rhs.extent(), &what, expected, rhs);
+        // `+` concatenates strings or adds numbers, but never mixes them
+        let mixed = (lhs.is_subset(Types::STRING) && rhs.is_subset(Types::NUMBER))
+            || (lhs.is_subset(Types::NUMBER) && rhs.is_subset(Types::STRING));
+        if binary.kind == Add && !reported && mixed {
+            self.mismatches.push(Mismatch {
+                outer,
+                inner: binary.rhs.extent(),
+                msg: format!("operator `+` can not combine {} and {}", lhs, rhs),
+                definite: true,
+            });
+        }
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_unary(&mut self, unary: &mut UnaryExpr<'script>) -> Result<VisitRes> {
+        let expected = match unary.kind {
+            UnaryOpKind::Plus | UnaryOpKind::Minus => Types::NUMBER,
+            UnaryOpKind::Not => Types::BOOL,
+            UnaryOpKind::BitNot => Types::INTEGER.union(Types::BOOL),
+        };
+        let actual = self.infer(&unary.expr);
+        let what = format!("operator `{}`", unary.kind);
+        self.expect(unary.extent(), unary.expr.extent(), &what, expected, actual);
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_match_pattern(&mut self, pattern: &mut Pattern<'script>) -> Result<VisitRes> {
+        if let Pattern::Assign(a) = pattern {
+            self.locals.insert(a.idx, Types::ANY);
+        }
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_comprehension(
+        &mut self,
+        comp: &mut Comprehension<'script, ImutExpr<'script>>,
+    ) -> Result<VisitRes> {
+        self.locals.insert(comp.key_id, Types::ANY);
+        self.locals.insert(comp.val_id, Types::ANY);
+        Ok(VisitRes::Walk)
+    }
+}
+
+impl<'script> walkers::expr::Walker<'script> for TypeChecker {}
+impl<'script> visitors::expr::Visitor<'script> for TypeChecker {
+    fn leave_expr(&mut self, e: &mut Expr<'script>) -> Result<()> {
+        match e {
+            Expr::Assign {
+                path: Path::Local(LocalPath { idx, segments, .. }),
+                expr,
+                ..
+            } if segments.is_empty() => {
+                let t = if let Expr::Imut(e) = expr.as_ref() {
+                    self.infer(e)
+                } else {
+                    Types::ANY
+                };
+                self.bind(*idx, t);
+            }
+            Expr::AssignMoveLocal {
+                path: Path::Local(LocalPath { idx, segments, .. }),
+                idx: from,
+                ..
+            } if segments.is_empty() => {
+                let t = self.locals.get(from).copied().unwrap_or(Types::ANY);
+                self.bind(*idx, t);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn visit_fn_defn(&mut self, _e: &mut FnDefn<'script>) -> Result<VisitRes> {
+        self.locals.clear();
+        self.branches = 0;
+        Ok(VisitRes::Walk)
+    }
+
+    fn visit_comprehension(
+        &mut self,
+        comp: &mut Comprehension<'script, Expr<'script>>,
+    ) -> Result<VisitRes> {
+        self.locals.insert(comp.key_id, Types::ANY);
+        self.locals.insert(comp.val_id, Types::ANY);
+        self.branches += 1;
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_comprehension(
+        &mut self,
+        _comp: &mut Comprehension<'script, Expr<'script>>,
+    ) -> Result<()> {
+        self.branches -= 1;
+        Ok(())
+    }
+
+    fn visit_ifelse(&mut self, _ifelse: &mut IfElse<'script, Expr<'script>>) -> Result<VisitRes> {
+        self.branches += 1;
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_ifelse(&mut self, _ifelse: &mut IfElse<'script, Expr<'script>>) -> Result<()> {
+        self.branches -= 1;
+        Ok(())
+    }
+
+    fn visit_mmatch(&mut self, _mmatch: &mut Match<'script, Expr<'script>>) -> Result<VisitRes> {
+        self.branches += 1;
+        Ok(VisitRes::Walk)
+    }
+
+    fn leave_mmatch(&mut self, _mmatch: &mut Match<'script, Expr<'script>>) -> Result<()> {
+        self.branches -= 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+
+    fn check(src: &str) -> Result<Vec<(bool, String)>> {
+        let mut reg = registry();
+        crate::std_lib::load(&mut reg);
+        let script = crate::script::Script::parse(src, &reg)?;
+        let mut exprs = script.script.exprs.clone();
+        let mut checker = TypeChecker::default();
+        for e in &mut exprs {
+            ExprWalker::walk_expr(&mut checker, e)?;
+        }
+        Ok(checker
+            .mismatches
+            .into_iter()
+            .map(|m| (m.definite, m.msg))
+            .collect())
+    }
+
+    #[test]
+    fn types_display() {
+        assert_eq!("any", Types::ANY.to_string());
+        assert_eq!("string", Types::STRING.to_string());
+        assert_eq!("integer or float", Types::NUMBER.to_string());
+        assert_eq!(
+            "integer, float or string",
+            Types::NUMBER.union(Types::STRING).to_string()
+        );
+    }
+
+    #[test]
+    fn definite_fn_arg() -> Result<()> {
+        let m = check("use std::string; let x = 42; string::len(x)")?;
+        assert_eq!(
+            vec![(
+                true,
+                "argument 1 of `string::len` expects string but got integer".to_string()
+            )],
+            m
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn nested_fn_result() -> Result<()> {
+        let m = check("use std::string; use std::array; array::len(string::len(event))")?;
+        assert_eq!(
+            vec![(
+                true,
+                "argument 1 of `array::len` expects array but got integer".to_string()
+            )],
+            m
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn probable_after_branch() -> Result<()> {
+        let m = check(
+            r#"
+            use std::string;
+            let x = "snot";
+            match event of
+              case 1 => let x = 1
+              default => null
+            end;
+            string::len(x)
+            "#,
+        )?;
+        assert_eq!(
+            vec![(
+                false,
+                "argument 1 of `string::len` expects string but might get integer or string"
+                    .to_string()
+            )],
+            m
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_is_fine() -> Result<()> {
+        let m = check("use std::string; let x = event.x; string::len(x) + string::len(event)")?;
+        assert!(m.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn modulo_is_integer() -> Result<()> {
+        let m = check("use std::string; let x = 1.5; let y = 7 % 2; [x % 2, string::len(y)]")?;
+        assert_eq!(
+            vec![
+                (
+                    true,
+                    "operator `%` expects integer but got float".to_string()
+                ),
+                (
+                    true,
+                    "argument 1 of `string::len` expects string but got integer".to_string()
+                ),
+            ],
+            m
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn operators() -> Result<()> {
+        let m = check(r#"let x = "snot"; let y = 1; [x - 1, x + y, -x, y + 2]"#)?;
+        assert_eq!(
+            vec![
+                (
+                    true,
+                    "operator `-` expects integer or float but got string".to_string()
+                ),
+                (
+                    true,
+                    "operator `+` can not combine string and integer".to_string()
+                ),
+                (
+                    true,
+                    "operator `-` expects integer or float but got string".to_string()
+                ),
+            ],
+            m
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pattern_bindings_are_unknown() -> Result<()> {
+        let m = check(
+            r#"
+            use std::string;
+            let x = 1;
+            match event of
+              case x = %{} => string::len(x)
+              default => null
+            end
+            "#,
+        )?;
+        assert!(m.is_empty());
+        Ok(())
+    }
+}
@@ -42,6 +42,9 @@ pub trait Walker<'script>: QueryWalker<'script> + DeployVisitor<'script> {
         for connection in &mut defn.connections {
             self.walk_connect_stmt(connection)?;
         }
+        if let Some(dead_letter) = &mut defn.dead_letter {
+            self.walk_deploy_edpoint(dead_letter)?;
+        }
         self.leave_flow_definition(defn)
     }
 
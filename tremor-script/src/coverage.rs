@@ -0,0 +1,383 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Expression and branch coverage for tremor-script and trickle.
+//!
+//! Coverage is off by default. Once [`enable`]d, every script, query and
+//! function compiled afterwards registers its expressions and the clauses of
+//! its `match` and `if` expressions, keyed by the span of their node. The
+//! interpreter then counts how often each expression runs, every thread in
+//! its own counters so evaluation never contends on a shared lock, and
+//! [`to_lcov`] sums the counts of all threads into an lcov tracefile.
+
+use crate::arena;
+use crate::ast::base_expr::Ranged;
+use crate::ast::visitors::prelude::*;
+use crate::ast::{Expression, FnDefn};
+use crate::interpreter::instrumentation;
+use crate::pos::Span;
+use lazy_static::lazy_static;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Execution counts of the expressions run by a single thread
+type Counts = HashMap<Span, u64>;
+
+lazy_static! {
+    static ref COVERAGE: Mutex<Coverage> = Mutex::new(Coverage::default());
+    /// the counts of every thread that ran an expression, they outlive their thread
+    static ref COUNTS: Mutex<Vec<Arc<Mutex<Counts>>>> = Mutex::new(Vec::new());
+}
+
+thread_local! {
+    /// the counts of the current thread, only contended while a report is rendered
+    static LOCAL_COUNTS: Arc<Mutex<Counts>> = {
+        let counts = Arc::default();
+        if let Ok(mut all) = COUNTS.lock() {
+            all.push(Arc::clone(&counts));
+        }
+        counts
+    };
+}
+
+/// Collected coverage of a single source
+#[derive(Debug, Default)]
+struct FileCoverage {
+    /// path of the source, sources without one are not reported
+    path: Option<String>,
+    /// every registered expression
+    exprs: BTreeSet<Span>,
+    /// the bodies of the clauses of a `match` or `if`, keyed by its span
+    branches: BTreeMap<Span, Vec<Span>>,
+}
+
+#[derive(Debug, Default)]
+struct Coverage {
+    files: BTreeMap<arena::Index, FileCoverage>,
+}
+
+impl Coverage {
+    fn file(&mut self, aid: arena::Index) -> &mut FileCoverage {
+        self.files.entry(aid).or_default()
+    }
+}
+
+/// Turns on coverage collection for everything compiled from now on
+pub fn enable() {
+    instrumentation::switch_on(&ENABLED);
+}
+
+/// Turns off coverage collection, already collected coverage is kept
+pub fn disable() {
+    instrumentation::switch_off(&ENABLED);
+}
+
+/// Returns true if coverage is being collected
+#[inline]
+#[must_use]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Discards all collected coverage
+///
+/// # Errors
+/// if the coverage lock is poisoned
+pub fn reset() -> Result<()> {
+    COVERAGE.lock()?.files.clear();
+    for counts in COUNTS.lock()?.iter() {
+        counts.lock()?.clear();
+    }
+    Ok(())
+}
+
+/// Associates the source with the given arena index with a file path, only
+/// sources with a path are reported
+///
+/// # Errors
+/// if the coverage lock is poisoned
+pub fn register_file(aid: arena::Index, path: &str) -> Result<()> {
+    COVERAGE.lock()?.file(aid).path = Some(path.to_string());
+    Ok(())
+}
+
+/// Records one execution of the expression with the given span
+#[inline]
+pub(crate) fn hit(span: Span) {
+    LOCAL_COUNTS.with(|counts| {
+        if let Ok(mut counts) = counts.lock() {
+            *counts.entry(span).or_default() += 1;
+        }
+    });
+}
+
+/// Sums the counts of all threads
+fn counts() -> Result<Counts> {
+    let mut total = Counts::new();
+    for counts in COUNTS.lock()?.iter() {
+        for (span, hits) in counts.lock()?.iter() {
+            *total.entry(*span).or_default() += hits;
+        }
+    }
+    Ok(total)
+}
+
+/// Registers the expressions of a script
+pub(crate) fn register_exprs(exprs: &mut [Expr]) -> Result<()> {
+    let mut registrar = Registrar::default();
+    for e in exprs {
+        ExprWalker::walk_expr(&mut registrar, e)?;
+    }
+    registrar.commit()
+}
+
+/// Registers the expressions of a query statement
+pub(crate) fn register_stmt(stmt: &mut Stmt) -> Result<()> {
+    let mut registrar = Registrar::default();
+    registrar.walk_stmt(stmt)?;
+    registrar.commit()
+}
+
+/// Registers the body of a function
+pub(crate) fn register_fn(defn: &mut FnDefn) -> Result<()> {
+    register_exprs(&mut defn.body)
+}
+
+/// Renders the collected coverage as an lcov tracefile
+///
+/// Every `match` or `if` is a block, every clause of it a branch. Lines are
+/// attributed to the line an expression starts on.
+///
+/// # Errors
+/// if the coverage lock is poisoned
+pub fn to_lcov() -> Result<String> {
+    let counts = counts()?;
+    let hits_of = |span: &Span| counts.get(span).copied().unwrap_or_default();
+    let coverage = COVERAGE.lock()?;
+    let mut out = String::new();
+    for file in coverage.files.values() {
+        let path = if let Some(path) = &file.path {
+            path
+        } else {
+            continue;
+        };
+        // writing to a string can not fail
+        let _ = writeln!(out, "TN:\nSF:{}", path);
+
+        let (mut found, mut taken) = (0, 0);
+        for (block, (outer, bodies)) in file.branches.iter().enumerate() {
+            let reached = hits_of(outer) > 0;
+            for (branch, body) in bodies.iter().enumerate() {
+                let hits = hits_of(body);
+                found += 1;
+                if hits > 0 {
+                    taken += 1;
+                }
+                let hits = if reached {
+                    hits.to_string()
+                } else {
+                    "-".to_string()
+                };
+                let _ = writeln!(
+                    out,
+                    "BRDA:{},{},{},{}",
+                    outer.start().line(),
+                    block,
+                    branch,
+                    hits
+                );
+            }
+        }
+        let _ = writeln!(out, "BRF:{}\nBRH:{}", found, taken);
+
+        let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+        for span in &file.exprs {
+            let line = lines.entry(span.start().line()).or_default();
+            *line = (*line).max(hits_of(span));
+        }
+        for (line, hits) in &lines {
+            let _ = writeln!(out, "DA:{},{}", line, hits);
+        }
+        let hit = lines.values().filter(|h| **h > 0).count();
+        let _ = writeln!(out, "LF:{}\nLH:{}\nend_of_record", lines.len(), hit);
+    }
+    Ok(out)
+}
+
+/// Collects the expressions and branches of an AST before adding them to
+/// the global coverage in one go
+#[derive(Default)]
+struct Registrar {
+    exprs: Vec<Span>,
+    branches: Vec<(Span, Vec<Span>)>,
+}
+
+impl Registrar {
+    fn commit(self) -> Result<()> {
+        let mut coverage = COVERAGE.lock()?;
+        for span in self.exprs {
+            coverage.file(span.aid()).exprs.insert(span);
+        }
+        for (span, bodies) in self.branches {
+            coverage.file(span.aid()).branches.insert(span, bodies);
+        }
+        Ok(())
+    }
+
+    fn add_clause<Ex: Expression + BaseExpr>(bodies: &mut Vec<Span>, clause: &PredicateClause<Ex>) {
+        bodies.push(clause.last_expr.extent());
+    }
+
+    fn add_group<Ex: Expression + BaseExpr>(bodies: &mut Vec<Span>, group: &ClauseGroup<Ex>) {
+        match group {
+            ClauseGroup::Single { pattern, .. } => Self::add_clause(bodies, pattern),
+            ClauseGroup::Simple { patterns, .. } => {
+                for p in patterns {
+                    Self::add_clause(bodies, p);
+                }
+            }
+            ClauseGroup::SearchTree { tree, rest, .. } => {
+                for (_, last) in tree.values() {
+                    bodies.push(last.extent());
+                }
+                for p in rest {
+                    Self::add_clause(bodies, p);
+                }
+            }
+            ClauseGroup::Combined { groups, .. } => {
+                for g in groups {
+                    Self::add_group(bodies, g);
+                }
+            }
+        }
+    }
+
+    /// the default case is only a branch if it has a body we can count
+    fn add_default<Ex: Expression + BaseExpr>(bodies: &mut Vec<Span>, default: &DefaultCase<Ex>) {
+        match default {
+            DefaultCase::None | DefaultCase::Null => (),
+            DefaultCase::Many { last_expr, .. } => bodies.push(last_expr.extent()),
+            DefaultCase::One(last_expr) => bodies.push(last_expr.extent()),
+        }
+    }
+
+    fn add_match<Ex: Expression + BaseExpr>(&mut self, mmatch: &Match<Ex>) {
+        let mut bodies = Vec::new();
+        for group in &mmatch.patterns {
+            Self::add_group(&mut bodies, group);
+        }
+        Self::add_default(&mut bodies, &mmatch.default);
+        self.branches.push((mmatch.extent(), bodies));
+    }
+}
+
+impl<'script> ImutExprWalker<'script> for Registrar {}
+impl<'script> ImutExprVisitor<'script> for Registrar {
+    fn visit_expr(&mut self, e: &mut ImutExpr<'script>) -> Result<VisitRes> {
+        self.exprs.push(e.extent());
+        Ok(VisitRes::Walk)
+    }
+    fn visit_mmatch(&mut self, mmatch: &mut Match<'script, ImutExpr>) -> Result<VisitRes> {
+        self.add_match(mmatch);
+        Ok(VisitRes::Walk)
+    }
+}
+
+impl<'script> ExprWalker<'script> for Registrar {}
+impl<'script> ExprVisitor<'script> for Registrar {
+    fn visit_expr(&mut self, e: &mut Expr<'script>) -> Result<VisitRes> {
+        self.exprs.push(e.extent());
+        Ok(VisitRes::Walk)
+    }
+    fn visit_mmatch(&mut self, mmatch: &mut Match<'script, Expr<'script>>) -> Result<VisitRes> {
+        self.add_match(mmatch);
+        Ok(VisitRes::Walk)
+    }
+    fn visit_ifelse(&mut self, ifelse: &mut IfElse<'script, Expr<'script>>) -> Result<VisitRes> {
+        let mut bodies = Vec::new();
+        Self::add_clause(&mut bodies, &ifelse.if_clause);
+        Self::add_default(&mut bodies, &ifelse.else_clause);
+        self.branches.push((ifelse.extent(), bodies));
+        Ok(VisitRes::Walk)
+    }
+}
+
+impl<'script> QueryWalker<'script> for Registrar {}
+impl<'script> QueryVisitor<'script> for Registrar {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, registry, Script};
+
+    /// disables coverage again once the test is done, even if it fails
+    struct Enabled;
+    impl Enabled {
+        fn new() -> Self {
+            enable();
+            Self
+        }
+    }
+    impl Drop for Enabled {
+        fn drop(&mut self) {
+            disable();
+        }
+    }
+
+    #[test]
+    fn lcov() -> Result<()> {
+        let _enabled = Enabled::new();
+        let src = r#"
+match event of
+  case 1 =>
+    "one"
+  case 2 =>
+    "two"
+  case _ =>
+    "many"
+end
+"#;
+        let script = Script::parse(src, &registry::registry())?;
+        register_file(script.aid, "coverage.tremor")?;
+        for event in [1_u64, 3, 4] {
+            let mut event = crate::Value::from(event);
+            let mut state = crate::Value::null();
+            let mut meta = crate::Value::object();
+            script.run(
+                &crate::EventContext::new(0, None),
+                crate::AggrType::Emit,
+                &mut event,
+                &mut state,
+                &mut meta,
+            )?;
+        }
+        let lcov = to_lcov()?;
+        let ours = lcov
+            .split("end_of_record")
+            .find(|r| r.contains("SF:coverage.tremor"))
+            .ok_or("no record for the script")?;
+        assert!(ours.contains("BRDA:2,0,0,1\n"));
+        assert!(ours.contains("BRDA:2,0,1,0\n"));
+        assert!(ours.contains("BRDA:2,0,2,2\n"));
+        assert!(ours.contains("BRF:3\nBRH:2\n"));
+        assert!(ours.contains("DA:4,1\n"));
+        assert!(ours.contains("DA:6,0\n"));
+        assert!(ours.contains("DA:8,2\n"));
+        Ok(())
+    }
+}
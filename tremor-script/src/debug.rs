@@ -0,0 +1,192 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hooks for stepping through tremor-script evaluation.
+//!
+//! An attached [`Debugger`] is called by the interpreter before every
+//! expression is evaluated and gets to look at the `event`, `state`, `$`
+//! metadata and the locals at that point. Evaluation only continues once the
+//! debugger returns, so blocking in [`Debugger::step`] pauses the script.
+//! Every [`Frame`] carries the number of tremor functions being evaluated, so
+//! a debugger can step into, over and out of function calls.
+
+use crate::arena;
+use crate::interpreter::{instrumentation, LocalStack};
+use crate::pos::Span;
+use crate::Value;
+use lazy_static::lazy_static;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+static ATTACHED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref DEBUGGER: RwLock<Option<Arc<dyn Debugger>>> = RwLock::new(None);
+}
+
+thread_local! {
+    /// number of tremor functions currently evaluated on this thread
+    static DEPTH: Cell<usize> = Cell::new(0);
+}
+
+/// The state of the interpreter right before an expression is evaluated
+pub struct Frame<'run, 'event> {
+    /// span of the expression about to be evaluated
+    pub span: Span,
+    /// the event
+    pub event: &'run Value<'event>,
+    /// the state
+    pub state: &'run Value<'static>,
+    /// the `$` metadata
+    pub meta: &'run Value<'event>,
+    /// number of tremor functions being evaluated, 0 for the script itself
+    pub depth: usize,
+    locals: &'run LocalStack<'event>,
+}
+
+impl<'run, 'event> Frame<'run, 'event> {
+    /// The arena index of the source the expression belongs to
+    #[must_use]
+    pub fn aid(&self) -> arena::Index {
+        self.span.aid()
+    }
+
+    /// The value of the local with the given index, if it is set
+    #[must_use]
+    pub fn local(&self, idx: usize) -> Option<&'run Value<'event>> {
+        self.locals.values.get(idx).and_then(Option::as_ref)
+    }
+}
+
+/// A debugger driving the evaluation of scripts
+pub trait Debugger: Send + Sync {
+    /// Called before an expression is evaluated, evaluation continues once
+    /// this returns
+    fn step(&self, frame: &Frame);
+}
+
+/// Attaches a debugger to all scripts evaluated from now on, replacing a
+/// previously attached one
+///
+/// # Errors
+/// if the debugger lock is poisoned
+pub fn attach(debugger: Arc<dyn Debugger>) -> crate::errors::Result<()> {
+    *DEBUGGER.write()? = Some(debugger);
+    instrumentation::switch_on(&ATTACHED);
+    Ok(())
+}
+
+/// Detaches the current debugger
+///
+/// # Errors
+/// if the debugger lock is poisoned
+pub fn detach() -> crate::errors::Result<()> {
+    instrumentation::switch_off(&ATTACHED);
+    *DEBUGGER.write()? = None;
+    Ok(())
+}
+
+/// Returns true if a debugger is attached
+#[inline]
+#[must_use]
+pub fn is_attached() -> bool {
+    ATTACHED.load(Ordering::Acquire)
+}
+
+/// Marks the evaluation of a tremor function for as long as it is alive
+pub(crate) struct Call;
+
+impl Call {
+    pub(crate) fn enter() -> Self {
+        DEPTH.with(|depth| depth.set(depth.get() + 1));
+        Self
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get().saturating_sub(1)));
+    }
+}
+
+/// Hands the current interpreter state to the attached debugger
+pub(crate) fn step<'run, 'event>(
+    span: Span,
+    event: &'run Value<'event>,
+    state: &'run Value<'static>,
+    meta: &'run Value<'event>,
+    locals: &'run LocalStack<'event>,
+) {
+    // clone the debugger so it isn't locked while it blocks
+    let debugger = DEBUGGER.read().ok().and_then(|d| d.clone());
+    if let Some(debugger) = debugger {
+        debugger.step(&Frame {
+            span,
+            event,
+            state,
+            meta,
+            depth: DEPTH.with(Cell::get),
+            locals,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::{registry, Script};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        steps: Mutex<Vec<(usize, Option<Value<'static>>)>>,
+    }
+
+    impl Debugger for Recorder {
+        fn step(&self, frame: &Frame) {
+            if let Ok(mut steps) = self.steps.lock() {
+                steps.push((
+                    frame.span.start().line(),
+                    frame.local(0).map(Value::clone_static),
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn steps() -> crate::errors::Result<()> {
+        let script = Script::parse("let a = event;\nlet b = a + 1;\nb", &registry::registry())?;
+        let recorder = Arc::new(Recorder::default());
+        attach(recorder.clone())?;
+        let mut event = Value::from(41);
+        let mut state = Value::null();
+        let mut meta = Value::object();
+        let res = script.run(
+            &EventContext::new(0, None),
+            AggrType::Emit,
+            &mut event,
+            &mut state,
+            &mut meta,
+        );
+        detach()?;
+        res?;
+        let steps = recorder.steps.lock()?;
+        // other tests may run scripts while we are attached
+        assert!(steps.contains(&(1, None)));
+        assert!(steps.contains(&(2, Some(Value::from(41)))));
+        Ok(())
+    }
+}
@@ -172,4 +172,34 @@ mod test {
             r#"define flow test flow define pipeline passthrough pipeline select args from in into out end; end;"#,
         );
     }
+
+    #[test]
+    fn dot() -> Result<()> {
+        let reg = crate::registry();
+        let aggr_reg = crate::aggr_registry();
+        let deploy = Deploy::parse(
+            r#"
+            define flow test
+            flow
+                define connector metronome from metronome;
+                define pipeline passthrough
+                pipeline
+                    select event from in into out;
+                end;
+                create connector metronome;
+                create pipeline passthrough;
+                connect /connector/metronome to /pipeline/passthrough;
+            end;
+            deploy flow test;
+            "#,
+            &reg,
+            &aggr_reg,
+        )?;
+        let dot = deploy.dot();
+        assert!(dot.starts_with("digraph deploy {"));
+        assert!(dot.contains("subgraph \"cluster_test\" {"));
+        assert!(dot.contains("\"test/metronome\" [shape=box, label=\"metronome\\nmetronome\"];"));
+        assert!(dot.contains("\"test/metronome\" -> \"test/passthrough\" [label=\"out -> in\"];"));
+        Ok(())
+    }
 }
@@ -192,10 +192,11 @@ impl ErrorKind {
             NoEventReferencesAllowed, NoLocalsAllowed, NoObjectError, NotConstant, NotFound, Oops,
             ParseIntError, ParserError, PatchKeyExists, PipelineUnknownPort,
             QueryNodeDuplicateName, QueryNodeReservedName, QueryStreamNotDefined, RecursionLimit,
-            RuntimeError, TailingHereDoc, TypeConflict, UnexpectedCharacter, UnexpectedEndOfStream,
-            UnexpectedEscapeCode, UnknownLocal, UnrecognizedToken, UnterminatedExtractor,
-            UnterminatedHereDoc, UnterminatedIdentLiteral, UnterminatedInterpolation,
-            UnterminatedStringLiteral, UpdateKeyMissing, Utf8Error, ValueError, WithParamNoArg,
+            RuntimeError, TailingHereDoc, TypeConflict, TypeMismatch, UnexpectedCharacter,
+            UnexpectedEndOfStream, UnexpectedEscapeCode, UnknownLocal, UnrecognizedToken,
+            UnterminatedExtractor, UnterminatedHereDoc, UnterminatedIdentLiteral,
+            UnterminatedInterpolation, UnterminatedStringLiteral, UpdateKeyMissing, Utf8Error,
+            ValueError, WithParamNoArg,
         };
         match self {
             NoClauseHit(outer)
@@ -258,6 +259,7 @@ impl ErrorKind {
             | RuntimeError(outer, inner, _, _, _, _)
             | TailingHereDoc(outer, inner, _, _)
             | TypeConflict(outer, inner, _, _)
+            | TypeMismatch(outer, inner, _)
             | UnexpectedCharacter(outer, inner, _, _)
             | UnexpectedEscapeCode(outer, inner, _, _)
             | UnrecognizedToken(outer, inner, _, _)
@@ -402,7 +404,9 @@ impl Error {
         self.0.aid()
     }
 
-    pub(crate) fn hint(&self) -> Option<String> {
+    /// A hint on how to resolve the error, if there is one
+    #[must_use]
+    pub fn hint(&self) -> Option<String> {
         self.0.hint()
     }
     pub(crate) fn token(&self) -> Option<UnfinishedToken> {
@@ -544,6 +548,10 @@ error_chain! {
             description("Conflicting types")
                 display("Conflicting types, got {} but expected {}", t2s(*got), choices(&expected.iter().map(|v| t2s(*v).to_string()).collect::<Vec<String>>()))
         }
+        TypeMismatch(expr: Span, inner: Span, msg: String) {
+            description("Type mismatch")
+                display("Type mismatch: {}", msg)
+        }
         Oops(expr: Span, id: u64, msg: String) {
             description("Something went wrong and we're not sure what it was")
                 display("Something went wrong and we're not sure what it was: {}", msg)
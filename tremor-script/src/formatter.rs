@@ -0,0 +1,782 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Canonical formatter for tremor-script, trickle and troy
+//!
+//! The source is parsed into its raw AST which is printed in a canonical layout:
+//!
+//! * every nesting level is indented by two spaces
+//! * records, lists, bytes, invocations, patches, merges and selects are printed on a
+//!   single line if they fit into 80 columns and contain no comments, otherwise they
+//!   are broken up into one entry per line
+//! * `match`, `for` and function definitions always span multiple lines
+//! * blank lines between statements and entries are kept, but collapsed into one
+//! * doc and module comments are printed from the AST, all other comments are printed
+//!   before the statement, entry or clause following them, comments within expressions
+//!   that are kept on a single line are moved behind the expression
+//!
+//! Formatting is idempotent and does not change the raw AST of the source.
+
+mod deploy;
+mod query;
+mod script;
+
+use crate::{
+    arena::Arena,
+    ast::{
+        base_expr::Ranged,
+        deploy::raw::DeployRaw,
+        module::ModuleRaw,
+        query::raw::QueryRaw,
+        raw::{ImutExprRaw, ScriptRaw},
+    },
+    errors::Result,
+    lexer::{ident_to_token, Lexer, Token},
+    parser::g,
+    pos::{Location, Spanned},
+};
+use unicode_xid::UnicodeXID;
+
+/// indentation per nesting level
+const INDENT: &str = "  ";
+/// the width up to which groups are kept on a single line
+const MAX_WIDTH: usize = 80;
+
+/// binding power of `match` and `for` expressions, they need to be parenthesized almost anywhere
+const COMPLEX: u8 = 0;
+/// binding power of the loosest binary operator, `or`
+const BINARY: u8 = 1;
+/// binding power of unary `+` and `-`
+const SIGN: u8 = 11;
+/// binding power of `not` and `!`
+const NOT: u8 = 12;
+/// binding power of `present` and `absent`
+const PRESENCE: u8 = 13;
+/// binding power of everything that never needs parentheses
+const SIMPLE: u8 = 14;
+
+/// The language of a source to format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    /// tremor-script, also covers module files
+    Script,
+    /// trickle
+    Query,
+    /// troy
+    Deploy,
+}
+
+/// Format the given `src` canonically
+///
+/// # Errors
+/// if the source can not be lexed or parsed in the given `language`
+pub fn format(src: &str, language: Language) -> Result<String> {
+    let (aid, src) = Arena::insert(src)?;
+    let tokens = Lexer::new(src, aid).collect::<Result<Vec<_>>>()?;
+    let raw = parse(&tokens, language)?;
+    let mut printer = Printer::new(src, &tokens);
+    match &raw {
+        Raw::Script(script) => printer.script(script),
+        Raw::Module(module) => printer.module(module),
+        Raw::Query(query) => printer.query(query),
+        Raw::Deploy(deploy) => printer.deploy(deploy),
+    }
+    Ok(printer.finish())
+}
+
+/// Checks if the given `src` is already formatted canonically
+///
+/// # Errors
+/// if the source can not be lexed or parsed in the given `language`
+pub fn is_formatted(src: &str, language: Language) -> Result<bool> {
+    Ok(format(src, language)? == src)
+}
+
+/// The raw AST of a source
+enum Raw<'src> {
+    Script(ScriptRaw<'src>),
+    Module(ModuleRaw<'src>),
+    Query(QueryRaw<'src>),
+    Deploy(DeployRaw<'src>),
+}
+
+fn parse<'src>(tokens: &[Spanned<'src>], language: Language) -> Result<Raw<'src>> {
+    let filtered = || tokens.iter().filter(|t| !t.value.is_ignorable()).cloned();
+    Ok(match language {
+        Language::Script => match g::ScriptParser::new().parse(filtered()) {
+            Ok(script) => Raw::Script(script),
+            // .tremor files can also be modules
+            Err(e) => Raw::Module(
+                g::ModuleFileParser::new()
+                    .parse(filtered())
+                    .map_err(|_| e)?,
+            ),
+        },
+        Language::Query => Raw::Query(g::QueryParser::new().parse(filtered())?),
+        Language::Deploy => match g::DeployParser::new().parse(filtered()) {
+            Ok(deploy) => Raw::Deploy(deploy),
+            Err(e) => Raw::Module(
+                g::ModuleFileParser::new()
+                    .parse(filtered())
+                    .map_err(|_| e)?,
+            ),
+        },
+    })
+}
+
+/// A `#` comment, these are not part of the AST
+#[derive(Debug, Clone, Copy)]
+struct Comment<'src> {
+    /// byte offset of the comment
+    at: usize,
+    line: usize,
+    /// the comment including its `#`
+    text: &'src str,
+    /// if there is code before the comment on its line
+    trailing: bool,
+}
+
+/// The extent of an AST node that is printed on lines of its own
+trait Layout {
+    /// start and end of the node, as recorded by the parser
+    fn bounds(&self) -> (Location, Location);
+    /// doc comment lines printed before the node but not covered by its start
+    fn doc_lines(&self) -> usize {
+        0
+    }
+}
+
+impl<T: Layout> Layout for &T {
+    fn bounds(&self) -> (Location, Location) {
+        (*self).bounds()
+    }
+    fn doc_lines(&self) -> usize {
+        (*self).doc_lines()
+    }
+}
+
+/// Implements `Layout` for nodes with a meta that covers them
+macro_rules! layout {
+    ($($node:ident),*) => {
+        $(impl Layout for $node<'_> {
+            fn bounds(&self) -> (Location, Location) {
+                (self.s(), self.e())
+            }
+        })*
+    };
+}
+use layout;
+
+layout!(ImutExprRaw);
+
+/// expressions that form the body of `case` clauses
+trait Body: Layout {
+    /// the separator of the expressions in a body
+    const SEP: &'static str;
+    fn print(&self, p: &mut Printer);
+}
+
+impl Body for ImutExprRaw<'_> {
+    const SEP: &'static str = ",";
+    fn print(&self, p: &mut Printer) {
+        p.imut(self, COMPLEX);
+    }
+}
+
+/// Prints a raw AST while interleaving the comments of its source
+struct Printer<'src> {
+    src: &'src str,
+    /// start offset and end of the tokens that are part of the AST
+    tokens: Vec<(usize, Location)>,
+    comments: Vec<Comment<'src>>,
+    /// the next comment to print
+    next: usize,
+    /// start of the module comment, comments before it stay in front of it
+    mod_doc: Option<Location>,
+    out: String,
+    indent: usize,
+    /// if we are at the beginning of a line
+    bol: bool,
+    /// the last source line printed, to carry over blank lines
+    last_line: Option<usize>,
+    /// if everything has to be printed on a single line
+    flat: bool,
+}
+
+impl<'src> Printer<'src> {
+    fn new(src: &'src str, spanned: &[Spanned<'src>]) -> Self {
+        let mut tokens = Vec::new();
+        let mut comments = Vec::new();
+        let mut mod_doc = None;
+        let mut code_line = None;
+        for Spanned { span, value } in spanned {
+            let (start, end) = (span.start(), span.end());
+            match value {
+                Token::SingleLineComment(_) => comments.push(Comment {
+                    at: start.absolute(),
+                    line: start.line(),
+                    text: src
+                        .get(start.absolute()..end.absolute())
+                        .unwrap_or_default()
+                        .trim_end(),
+                    trailing: code_line == Some(start.line()),
+                }),
+                token if token.is_ignorable() => (),
+                token => {
+                    if mod_doc.is_none() && matches!(token, Token::ModComment(_)) {
+                        mod_doc = Some(start);
+                    }
+                    tokens.push((start.absolute(), end));
+                    code_line = Some(end.line());
+                }
+            }
+        }
+        Self {
+            src,
+            tokens,
+            comments,
+            next: 0,
+            mod_doc,
+            out: String::new(),
+            indent: 0,
+            bol: true,
+            last_line: None,
+            flat: false,
+        }
+    }
+
+    /// a printer rendering into a single line without any comments
+    fn flat_printer(&self) -> Self {
+        Self {
+            src: self.src,
+            tokens: Vec::new(),
+            comments: Vec::new(),
+            next: 0,
+            mod_doc: None,
+            out: String::new(),
+            indent: 0,
+            bol: false,
+            last_line: None,
+            flat: true,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.comments_before(usize::MAX);
+        if !self.bol {
+            self.nl();
+        }
+        self.out
+    }
+
+    fn write(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        if self.bol {
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+            self.bol = false;
+        }
+        self.out.push_str(s);
+    }
+
+    fn nl(&mut self) {
+        self.out.push('\n');
+        self.bol = true;
+    }
+
+    fn column(&self) -> usize {
+        if self.bol {
+            self.indent * INDENT.len()
+        } else {
+            let line = self.out.rfind('\n').map_or(0, |i| i + 1);
+            self.out.get(line..).unwrap_or_default().chars().count()
+        }
+    }
+
+    /// if `s` fits onto the current line
+    fn fits(&self, s: &str) -> bool {
+        !s.contains('\n') && self.column() + s.chars().count() <= MAX_WIDTH
+    }
+
+    /// start offset and end of the last token of a node ending at `end`
+    ///
+    /// the parser records the end of a node as the start of the token following it
+    fn last_token(&self, end: Location) -> (usize, Location) {
+        let idx = self
+            .tokens
+            .partition_point(|(start, _)| *start < end.absolute());
+        idx.checked_sub(1)
+            .and_then(|idx| self.tokens.get(idx))
+            .copied()
+            .unwrap_or((end.absolute(), end))
+    }
+
+    /// if there are comments left to print before `pos`
+    fn pending_before(&self, pos: usize) -> bool {
+        self.comments.get(self.next).map_or(false, |c| c.at < pos)
+    }
+
+    /// if there are comments left to print before the last token of a node ending at `end`
+    fn comments_within(&self, end: Location) -> bool {
+        self.pending_before(self.last_token(end).0)
+    }
+
+    /// prints all pending comments before `pos`, each ends its line
+    fn comments_before(&mut self, pos: usize) {
+        while let Some(comment) = self.comments.get(self.next).copied() {
+            if comment.at >= pos {
+                break;
+            }
+            self.next += 1;
+            if comment.trailing && !self.bol {
+                self.write(" ");
+            } else {
+                if !self.bol {
+                    self.nl();
+                }
+                if self.last_line.map_or(false, |l| comment.line > l + 1) {
+                    self.nl();
+                }
+            }
+            self.write(comment.text);
+            self.nl();
+            self.last_line = Some(self.last_line.map_or(comment.line, |l| l.max(comment.line)));
+        }
+    }
+
+    /// starts a new line for a node starting at `start`, preceded by `docs` lines of doc comments
+    fn line_before(&mut self, start: Location, docs: usize) {
+        self.comments_before(start.absolute());
+        if !self.bol {
+            self.nl();
+        }
+        if self
+            .last_line
+            .map_or(false, |l| start.line() > l + docs + 1)
+        {
+            self.nl();
+        }
+    }
+
+    /// prints `items` on lines of their own, separated by `sep` and terminated by it if `terminate` is set
+    fn entries<T: Layout>(
+        &mut self,
+        items: &[T],
+        sep: &str,
+        terminate: bool,
+        item: &dyn Fn(&mut Self, &T),
+    ) {
+        for (i, e) in items.iter().enumerate() {
+            if i > 0 {
+                self.write(sep);
+            }
+            let (start, end) = e.bounds();
+            self.line_before(start, e.doc_lines());
+            item(self, e);
+            self.last_line = Some(self.last_token(end).1.line());
+        }
+        if terminate && !items.is_empty() {
+            self.write(sep);
+        }
+    }
+
+    /// prints `items` like `entries` but one level deeper
+    fn indented<T: Layout>(
+        &mut self,
+        items: &[T],
+        sep: &str,
+        terminate: bool,
+        item: &dyn Fn(&mut Self, &T),
+    ) {
+        self.indent += 1;
+        self.last_line = None;
+        self.entries(items, sep, terminate, item);
+        self.indent -= 1;
+    }
+
+    /// starts a clause like `with` on a line of its own
+    fn clause(&mut self, keyword: &str) {
+        if !self.bol {
+            self.nl();
+        }
+        self.write(keyword);
+    }
+
+    /// closes a block of a node ending at `end` with `token` on a line of its own,
+    /// comments left in the block are printed before it
+    fn close(&mut self, end: Location, token: &str) {
+        self.indent += 1;
+        self.comments_before(self.last_token(end).0);
+        self.indent -= 1;
+        self.clause(token);
+    }
+
+    /// prints delimited `items` on a single line if they fit and contain no comments,
+    /// otherwise one entry per line
+    #[allow(clippy::too_many_arguments)]
+    fn group<T: Layout>(
+        &mut self,
+        end: Location,
+        open: &dyn Fn(&mut Self),
+        items: &[T],
+        sep: &str,
+        close: &str,
+        pad: bool,
+        item: &dyn Fn(&mut Self, &T),
+    ) {
+        let flat = |p: &mut Self| {
+            open(p);
+            let pad = pad && !items.is_empty();
+            if pad {
+                p.write(" ");
+            }
+            for (i, e) in items.iter().enumerate() {
+                if i > 0 {
+                    p.write(sep);
+                    p.write(" ");
+                }
+                item(p, e);
+            }
+            if pad {
+                p.write(" ");
+            }
+            p.write(close);
+        };
+        if self.flat {
+            flat(self);
+            return;
+        }
+        if !self.comments_within(end) {
+            let mut p = self.flat_printer();
+            flat(&mut p);
+            if self.fits(&p.out) {
+                self.write(&p.out);
+                return;
+            }
+        }
+        open(self);
+        self.indented(items, sep, false, item);
+        self.close(end, close);
+    }
+
+    /// prints doc comments, one per line
+    fn docs(&mut self, docs: Option<&[beef::Cow<str>]>) {
+        for line in docs.unwrap_or_default() {
+            self.write("##");
+            self.write(line);
+            self.nl();
+        }
+    }
+
+    /// prints the module comment, followed by a blank line
+    fn mod_doc(&mut self, docs: Option<&[beef::Cow<str>]>) {
+        if let Some(docs) = docs {
+            if let Some(start) = self.mod_doc {
+                self.line_before(start, 0);
+            }
+            for line in docs {
+                self.write("###");
+                self.write(line);
+                self.nl();
+            }
+            self.nl();
+            self.last_line = None;
+        }
+    }
+}
+
+/// an identifier, quoted in backticks if it would not lex as one otherwise
+fn ident(id: &str) -> String {
+    let mut chars = id.chars();
+    let plain = chars.next().map_or(false, UnicodeXID::is_xid_start)
+        && chars.all(|c| UnicodeXID::is_xid_continue(c) || c == '_')
+        && matches!(ident_to_token(id), Token::Ident(..));
+    if plain {
+        id.to_string()
+    } else {
+        format!("`{}`", id)
+    }
+}
+
+/// the number of doc comment lines
+fn doc_lines<T>(docs: &Option<Vec<T>>) -> usize {
+    docs.as_ref().map_or(0, Vec::len)
+}
+
+/// a `::` separated path of identifiers
+fn ident_path<'a>(segments: impl IntoIterator<Item = &'a str>) -> String {
+    segments
+        .into_iter()
+        .map(ident)
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{arena, errors::Error};
+    use simd_json::{prelude::*, OwnedValue};
+
+    fn fmt(src: &str, language: Language) -> Result<String> {
+        let formatted = format(src, language)?;
+        // formatting is idempotent
+        assert_eq!(formatted, format(&formatted, language)?);
+        Ok(formatted)
+    }
+
+    /// the raw AST without locations, adjacent string literal parts are joined
+    fn ast(src: &str, language: Language) -> Result<OwnedValue> {
+        fn normalize(value: &mut OwnedValue) {
+            if let Some(array) = value.as_array_mut() {
+                array.iter_mut().for_each(normalize);
+            } else if let Some(object) = value.as_object_mut() {
+                object.remove("mid");
+                object.values_mut().for_each(normalize);
+                if let Some(elements) = object.get_mut("elements").and_then(Mutable::as_array_mut) {
+                    let mut joined: Vec<OwnedValue> = Vec::new();
+                    for element in elements.drain(..) {
+                        let lit = element.get_str("Lit").map(ToString::to_string);
+                        let last = joined.last_mut().and_then(|l| l.get_mut("Lit"));
+                        match (lit, last) {
+                            // the elements are stored in reverse
+                            (Some(lit), Some(OwnedValue::String(last))) => last.insert_str(0, &lit),
+                            _ => joined.push(element),
+                        }
+                    }
+                    joined.retain(|e| e.get_str("Lit") != Some(""));
+                    *elements = joined;
+                }
+            }
+        }
+        let tokens = Lexer::new(src, arena::Index::INVALID).collect::<Result<Vec<_>>>()?;
+        let mut value = match parse(&tokens, language)? {
+            Raw::Script(raw) => simd_json::serde::to_owned_value(raw),
+            Raw::Module(raw) => simd_json::serde::to_owned_value(raw),
+            Raw::Query(raw) => simd_json::serde::to_owned_value(raw),
+            Raw::Deploy(raw) => simd_json::serde::to_owned_value(raw),
+        }
+        .map_err(|e| e.to_string())?;
+        normalize(&mut value);
+        Ok(value)
+    }
+
+    fn comments(src: &str) -> Result<Vec<String>> {
+        Ok(Lexer::new(src, arena::Index::INVALID)
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .filter_map(|t| match t.value {
+                Token::SingleLineComment(c) => Some(c.trim_end().to_string()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    #[test]
+    fn script() -> Result<()> {
+        let src = r##"
+### module docs
+# leading comment
+let   a = {"snot":"badger",   "list": [1,2, 3]}; # trailing
+
+
+## docs
+fn foo(x) with
+let y = x + 1; # add one
+y * (2 + 3)
+end;
+match event of case %{present a} => foo(a) case _ when a > 1 => "#{a}" default => drop end;
+emit event => "out"
+"##;
+        assert_eq!(
+            fmt(src, Language::Script)?,
+            r##"### module docs
+
+# leading comment
+let a = {"snot": "badger", "list": [1, 2, 3]}; # trailing
+
+## docs
+fn foo(x) with
+  let y = x + 1; # add one
+  y * (2 + 3)
+end;
+match event of
+  case %{present a} => foo(a)
+  case _ when a > 1 => "#{a}"
+  default => drop
+end;
+emit => "out"
+"##
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn breaks_long_groups() -> Result<()> {
+        let src = r#"let a = {"first": "a rather long value", "second": [1, 2, 3], "third": {"nested": true}}"#;
+        assert_eq!(
+            fmt(src, Language::Script)?,
+            r#"let a = {
+  "first": "a rather long value",
+  "second": [1, 2, 3],
+  "third": {"nested": true}
+}
+"#
+        );
+        let src = "let a = [\n  1, # one\n  2\n]";
+        assert_eq!(
+            fmt(src, Language::Script)?,
+            "let a = [\n  1, # one\n  2\n]\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parenthesizes_by_binding() -> Result<()> {
+        assert_eq!(
+            fmt("let a = ((1 + 2) * 3) - (4 - 5);", Language::Script)?,
+            "let a = (1 + 2) * 3 - (4 - 5)\n"
+        );
+        assert_eq!(
+            fmt("let a = not (present event.a);", Language::Script)?,
+            "let a = absent event.a\n"
+        );
+        assert_eq!(
+            fmt(
+                r#"let a = (match event of default => 1 end)["x"];"#,
+                Language::Script
+            )?,
+            "let a = (match event of\n  default => 1\nend)[\"x\"]\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn query() -> Result<()> {
+        let src = r#"
+define window w from tumbling with size = 3 end;
+# the pipeline
+define pipeline p pipeline
+select {"count": aggr::stats::count()} from in[w] group by set(event.a, each(event.b)) into out having event.count > 0;
+end;
+create pipeline p;
+"#;
+        assert_eq!(
+            fmt(src, Language::Query)?,
+            r#"define window w from tumbling
+with
+  size = 3
+end;
+# the pipeline
+define pipeline p
+pipeline
+  select {"count": aggr::stats::count()}
+  from in[w]
+  group by set(event.a, each(event.b))
+  into out
+  having event.count > 0;
+end;
+create pipeline p;
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deploy() -> Result<()> {
+        let src = r#"
+define flow main
+flow
+  define connector metronome from metronome with config = {"interval": 1} end;
+  create connector metronome;
+  create pipeline main from pipelines::main;
+  connect /connector/metronome/out to /pipeline/main/in; # default ports
+  connect /pipeline/main/err to /connector/exit;
+end;
+deploy flow main;
+"#;
+        assert_eq!(
+            fmt(src, Language::Deploy)?,
+            r#"define flow main
+flow
+  define connector metronome from metronome
+  with
+    config = {"interval": 1}
+  end;
+  create connector metronome;
+  create pipeline main from pipelines::main;
+  connect /connector/metronome to /pipeline/main; # default ports
+  connect /pipeline/main/err to /connector/exit;
+end;
+deploy flow main;
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn strings() -> Result<()> {
+        let src = "let a = \"\\\\ \\\" \\# #\\#{x} \\n #{event.a + 1}\";\nlet b = \"\"\"\n  heredoc \" \\#{}\n  \"\"\"\n";
+        let formatted = fmt(src, Language::Script)?;
+        assert_eq!(
+            formatted,
+            "let a = \"\\\\ \\\" # #\\#{x} \\n #{event.a + 1}\";\nlet b = \"\"\"\n  heredoc \" \\#{}\n  \"\"\"\n"
+        );
+        assert_eq!(
+            ast(src, Language::Script)?,
+            ast(&formatted, Language::Script)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fixtures() -> Result<()> {
+        let tests = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests");
+        for (dir, file, language) in [
+            ("scripts", "script.tremor", Language::Script),
+            ("queries", "query.trickle", Language::Query),
+            ("flows", "flow.troy", Language::Deploy),
+        ] {
+            for entry in std::fs::read_dir(tests.join(dir))? {
+                let path = entry?.path().join(file);
+                let src = if let Ok(src) = std::fs::read_to_string(&path) {
+                    src
+                } else {
+                    continue;
+                };
+                // some fixtures are deliberately invalid, only valid sources need to format
+                let original = if let Ok(ast) = ast(&src, language) {
+                    ast
+                } else {
+                    continue;
+                };
+                let context = |e: Error| Error::from(format!("{}: {e}", path.display()));
+                let formatted = fmt(&src, language).map_err(context)?;
+                let formatted_ast = ast(&formatted, language).map_err(context)?;
+                assert_eq!(original, formatted_ast, "{}", path.display());
+                assert_eq!(comments(&src)?, comments(&formatted)?, "{}", path.display());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(format("let a = ;", Language::Script).is_err());
+        assert!(format("select from;", Language::Query).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Printing of troy deployments
+
+use super::{doc_lines, ident, Layout, Printer};
+use crate::{
+    ast::{
+        base_expr::Ranged,
+        deploy::raw::{
+            ConnectStmtRaw, ConnectorDefinitionRaw, CreateKind, CreateStmtRaw, DeployEndpointRaw,
+            DeployFlowRaw, DeployRaw, DeployStmtRaw, FlowDefinitionRaw, FlowStmtRaw,
+        },
+    },
+    pos::Location,
+};
+
+impl Layout for DeployStmtRaw<'_> {
+    fn bounds(&self) -> (Location, Location) {
+        match self {
+            DeployStmtRaw::DeployFlow(d) => (d.s(), d.e()),
+            DeployStmtRaw::FlowDefinition(f) => (f.s(), f.e()),
+            DeployStmtRaw::Use(u) => (u.s(), u.e()),
+        }
+    }
+}
+
+impl Layout for FlowStmtRaw<'_> {
+    fn bounds(&self) -> (Location, Location) {
+        match self {
+            FlowStmtRaw::ConnectorDefinition(c) => (c.s(), c.e()),
+            FlowStmtRaw::PipelineDefinition(p) => (p.s(), p.e()),
+            FlowStmtRaw::Connect(
+                ConnectStmtRaw::ConnectorToPipeline { mid, .. }
+                | ConnectStmtRaw::PipelineToConnector { mid, .. }
+                | ConnectStmtRaw::PipelineToPipeline { mid, .. },
+            ) => (mid.start(), mid.end()),
+            FlowStmtRaw::DeadLetter(d) => (d.s(), d.e()),
+            FlowStmtRaw::Create(c) => (c.s(), c.e()),
+            FlowStmtRaw::Use(u) => (u.s(), u.e()),
+        }
+    }
+    fn doc_lines(&self) -> usize {
+        // connector docs are part of the statement, pipeline docs precede it
+        match self {
+            FlowStmtRaw::PipelineDefinition(p) => doc_lines(&p.doc),
+            FlowStmtRaw::ConnectorDefinition(_)
+            | FlowStmtRaw::Connect(_)
+            | FlowStmtRaw::DeadLetter(_)
+            | FlowStmtRaw::Create(_)
+            | FlowStmtRaw::Use(_) => 0,
+        }
+    }
+}
+
+impl<'src> Printer<'src> {
+    pub(super) fn deploy(&mut self, deploy: &DeployRaw) {
+        self.config(&deploy.config);
+        self.mod_doc(deploy.doc.as_deref());
+        self.entries(&deploy.stmts, ";", true, &|p, stmt| match stmt {
+            DeployStmtRaw::DeployFlow(d) => p.deploy_flow(d),
+            DeployStmtRaw::FlowDefinition(f) => p.define_flow(f),
+            DeployStmtRaw::Use(u) => p.use_stmt(u),
+        });
+    }
+
+    fn deploy_flow(&mut self, d: &DeployFlowRaw) {
+        self.docs(d.docs.as_deref());
+        self.write("deploy flow ");
+        self.write(&ident(&d.id.id));
+        self.target(&d.id.id, &d.target);
+        if !d.params.with.exprs.is_empty() {
+            self.with(&d.params.with);
+            self.close(d.mid.end(), "end");
+        }
+    }
+
+    pub(super) fn define_flow(&mut self, f: &FlowDefinitionRaw) {
+        self.docs(f.doc.as_deref());
+        self.write("define flow ");
+        self.write(&ident(&f.id));
+        self.args(&f.params.args);
+        self.clause("flow");
+        self.indented(&f.stmts, ";", true, &Self::flow_stmt);
+        self.close(f.mid.end(), "end");
+    }
+
+    pub(super) fn define_connector(&mut self, c: &ConnectorDefinitionRaw) {
+        self.docs(c.docs.as_deref());
+        self.write("define connector ");
+        self.write(&ident(&c.id));
+        self.write(" from ");
+        self.write(&ident(&c.kind.id));
+        self.args_with(&c.params, c.mid.end());
+    }
+
+    fn flow_stmt(&mut self, stmt: &FlowStmtRaw) {
+        match stmt {
+            FlowStmtRaw::ConnectorDefinition(c) => self.define_connector(c),
+            FlowStmtRaw::PipelineDefinition(p) => self.define_pipeline(p),
+            FlowStmtRaw::Connect(ConnectStmtRaw::ConnectorToPipeline { from, to, .. }) => {
+                self.connect("connector", from, "pipeline", to);
+            }
+            FlowStmtRaw::Connect(ConnectStmtRaw::PipelineToConnector { from, to, .. }) => {
+                self.connect("pipeline", from, "connector", to);
+            }
+            FlowStmtRaw::Connect(ConnectStmtRaw::PipelineToPipeline { from, to, .. }) => {
+                self.connect("pipeline", from, "pipeline", to);
+            }
+            FlowStmtRaw::DeadLetter(d) => {
+                self.write("connect /flow/");
+                self.write(&ident(&d.port.id));
+                self.write(" to ");
+                self.endpoint("connector", &d.to, "in");
+            }
+            FlowStmtRaw::Create(c) => self.create_stmt(c),
+            FlowStmtRaw::Use(u) => self.use_stmt(u),
+        }
+    }
+
+    fn create_stmt(&mut self, c: &CreateStmtRaw) {
+        let kind = match c.kind {
+            CreateKind::Connector => "connector",
+            CreateKind::Pipeline => "pipeline",
+        };
+        self.create(kind, &c.id.id, &c.target, &c.params, c.mid.end());
+    }
+
+    fn connect(
+        &mut self,
+        from_kind: &str,
+        from: &DeployEndpointRaw,
+        to_kind: &str,
+        to: &DeployEndpointRaw,
+    ) {
+        self.write("connect ");
+        self.endpoint(from_kind, from, "out");
+        self.write(" to ");
+        self.endpoint(to_kind, to, "in");
+    }
+
+    /// `/kind/alias[/port]`, the port is omitted if it is the `default`
+    fn endpoint(&mut self, kind: &str, endpoint: &DeployEndpointRaw, default: &str) {
+        self.write("/");
+        self.write(kind);
+        self.write("/");
+        self.write(&ident(&endpoint.alias.id));
+        if endpoint.port.id != default {
+            self.write("/");
+            self.write(&ident(&endpoint.port.id));
+        }
+    }
+}
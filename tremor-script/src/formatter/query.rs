@@ -0,0 +1,326 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Printing of trickle queries
+
+use super::{doc_lines, ident, Layout, Printer, BINARY, COMPLEX};
+use crate::{
+    ast::{
+        base_expr::Ranged,
+        query::{
+            raw::{
+                ArgsExprsRaw, ConfigRaw, CreationalWithRaw, DefinitionalArgsWithRaw, GroupByRaw,
+                OperatorDefinitionRaw, PipelineDefinitionRaw, QueryRaw, ScriptDefinitionRaw,
+                SelectRaw, StmtRaw, WindowDefinitionRaw, WithExprsRaw,
+            },
+            WindowKind,
+        },
+        raw::{IdentRaw, ImutExprRaw},
+        NodeId,
+    },
+    pos::Location,
+};
+
+impl Layout for StmtRaw<'_> {
+    fn bounds(&self) -> (Location, Location) {
+        (self.s(), self.e())
+    }
+    fn doc_lines(&self) -> usize {
+        match self {
+            StmtRaw::WindowDefinition(w) => doc_lines(&w.doc),
+            StmtRaw::OperatorDefinition(o) => doc_lines(&o.doc),
+            StmtRaw::ScriptDefinition(s) => doc_lines(&s.doc),
+            StmtRaw::PipelineDefinition(p) => doc_lines(&p.doc),
+            StmtRaw::PipelineCreate(_)
+            | StmtRaw::StreamStmt(_)
+            | StmtRaw::OperatorCreate(_)
+            | StmtRaw::ScriptCreate(_)
+            | StmtRaw::SelectStmt(_)
+            | StmtRaw::Use(_) => 0,
+        }
+    }
+}
+
+impl Layout for (IdentRaw<'_>, ImutExprRaw<'_>) {
+    fn bounds(&self) -> (Location, Location) {
+        (self.0.s(), self.1.e())
+    }
+}
+
+impl Layout for (IdentRaw<'_>, Option<ImutExprRaw<'_>>) {
+    fn bounds(&self) -> (Location, Location) {
+        let end = self.1.as_ref().map_or_else(|| self.0.e(), Ranged::e);
+        (self.0.s(), end)
+    }
+}
+
+impl<'src> Printer<'src> {
+    pub(super) fn query(&mut self, query: &QueryRaw) {
+        self.config(&query.config);
+        self.entries(&query.stmts, ";", true, &Self::stmt);
+    }
+
+    /// `#!config` directives
+    pub(super) fn config(&mut self, config: &ConfigRaw) {
+        self.entries(config, "", false, &|p, (name, value)| {
+            p.write("#!config ");
+            p.write(&ident(&name.id));
+            p.write(" = ");
+            p.imut(value, BINARY);
+        });
+    }
+
+    fn stmt(&mut self, stmt: &StmtRaw) {
+        match stmt {
+            StmtRaw::WindowDefinition(w) => self.define_window(w),
+            StmtRaw::OperatorDefinition(o) => self.define_operator(o),
+            StmtRaw::ScriptDefinition(s) => self.define_script(s),
+            StmtRaw::PipelineDefinition(p) => self.define_pipeline(p),
+            StmtRaw::PipelineCreate(c) => {
+                self.create("pipeline", &c.alias, &c.target, &c.params, c.mid.end());
+            }
+            StmtRaw::StreamStmt(s) => {
+                self.write("create stream ");
+                self.write(&ident(&s.id));
+            }
+            StmtRaw::OperatorCreate(c) => {
+                self.create("operator", &c.id, &c.target, &c.params, c.mid.end());
+            }
+            StmtRaw::ScriptCreate(c) => {
+                self.create("script", &c.id, &c.target, &c.params, c.mid.end());
+            }
+            StmtRaw::SelectStmt(s) => self.select(s),
+            StmtRaw::Use(u) => self.use_stmt(u),
+        }
+    }
+
+    pub(super) fn define_window(&mut self, w: &WindowDefinitionRaw) {
+        self.docs(w.doc.as_deref());
+        self.write("define window ");
+        self.write(&ident(&w.id));
+        self.write(match w.kind {
+            WindowKind::Sliding => " from sliding",
+            WindowKind::Tumbling => " from tumbling",
+        });
+        self.with(&w.params.with);
+        if let Some(script) = &w.script {
+            self.clause("script");
+            self.indented(&script.exprs, "", false, &Self::top_level_expr);
+        }
+        if w.params.with.exprs.is_empty() && w.script.is_none() {
+            self.write(" end");
+        } else {
+            self.close(w.mid.end(), "end");
+        }
+    }
+
+    pub(super) fn define_operator(&mut self, o: &OperatorDefinitionRaw) {
+        self.docs(o.doc.as_deref());
+        self.write("define operator ");
+        self.write(&ident(&o.id));
+        self.write(" from ");
+        self.write(&ident(&o.kind.module));
+        self.write("::");
+        self.write(&ident(&o.kind.operation));
+        self.args_with(&o.params, o.mid.end());
+    }
+
+    pub(super) fn define_script(&mut self, s: &ScriptDefinitionRaw) {
+        self.docs(s.doc.as_deref());
+        self.write("define script ");
+        self.write(&ident(&s.id));
+        self.args(&s.params.args);
+        self.clause("script");
+        self.indented(&s.script.exprs, ";", false, &Self::top_level_expr);
+        self.close(s.mid.end(), "end");
+    }
+
+    pub(super) fn define_pipeline(&mut self, p: &PipelineDefinitionRaw) {
+        self.docs(p.doc.as_deref());
+        self.write("define pipeline ");
+        self.write(&ident(&p.id));
+        for (keyword, ports) in [(" from ", &p.from), (" into ", &p.into)] {
+            if let Some(ports) = ports {
+                // the parser collects ports, args and with clauses in reverse
+                let ports: Vec<String> = ports.iter().rev().map(|port| ident(&port.id)).collect();
+                self.write(keyword);
+                self.write(&ports.join(", "));
+            }
+        }
+        self.args(&p.params.args);
+        self.clause("pipeline");
+        self.indent += 1;
+        self.last_line = None;
+        self.config(&p.config);
+        self.entries(&p.pipeline, ";", true, &Self::stmt);
+        self.indent -= 1;
+        self.close(p.mid.end(), "end");
+    }
+
+    /// `args` and `with` of a definition, `with` is required if there are `args`
+    pub(super) fn args_with(&mut self, params: &DefinitionalArgsWithRaw, end: Location) {
+        if !params.with.exprs.is_empty() {
+            self.args(&params.args);
+            self.with(&params.with);
+            self.close(end, "end");
+        }
+    }
+
+    pub(super) fn args(&mut self, args: &ArgsExprsRaw) {
+        if !args.0.is_empty() {
+            let args: Vec<_> = args.0.iter().rev().collect();
+            self.clause("args");
+            self.indented(&args, ",", false, &|p, &(name, default)| {
+                p.write(&ident(&name.id));
+                if let Some(default) = default {
+                    p.write(" = ");
+                    p.imut(default, BINARY);
+                }
+            });
+        }
+    }
+
+    pub(super) fn with(&mut self, with: &WithExprsRaw) {
+        if !with.exprs.is_empty() {
+            let exprs: Vec<_> = with.exprs.iter().rev().collect();
+            self.clause("with");
+            self.indented(&exprs, ",", false, &|p, &(name, value)| {
+                p.write(&ident(&name.id));
+                p.write(" = ");
+                p.imut(value, BINARY);
+            });
+        }
+    }
+
+    /// `create` statements, the target is omitted if it is the same as the id
+    pub(super) fn create(
+        &mut self,
+        kind: &str,
+        id: &str,
+        target: &NodeId,
+        params: &CreationalWithRaw,
+        end: Location,
+    ) {
+        self.write("create ");
+        self.write(kind);
+        self.write(" ");
+        self.write(&ident(id));
+        self.target(id, target);
+        if !params.with.exprs.is_empty() {
+            self.with(&params.with);
+            self.close(end, "end");
+        }
+    }
+
+    /// the `from` of an instance if it differs from its id
+    pub(super) fn target(&mut self, id: &str, target: &NodeId) {
+        if !target.module.is_empty() || target.id != id {
+            self.write(" from ");
+            self.node_id(target);
+        }
+    }
+
+    /// a select on a single line if it fits and contains no comments, otherwise one clause per line
+    fn select(&mut self, s: &SelectRaw) {
+        if !self.flat && !self.comments_within(s.mid.end()) {
+            let mut p = self.flat_printer();
+            p.select_clauses(s);
+            if self.fits(&p.out) {
+                self.write(&p.out);
+                return;
+            }
+        }
+        self.select_clauses(s);
+    }
+
+    fn select_clauses(&mut self, s: &SelectRaw) {
+        let clause = |p: &mut Self, keyword: &str, start: Location| {
+            if p.flat {
+                p.write(" ");
+            } else {
+                p.comments_before(start.absolute());
+                p.clause("");
+            }
+            p.write(keyword);
+        };
+        self.write("select ");
+        self.imut(&s.target, COMPLEX);
+        clause(self, "from ", s.from.0.s());
+        self.port(&s.from);
+        if let Some(windows) = &s.windows {
+            let windows: Vec<String> = windows
+                .iter()
+                .map(|w| {
+                    let mut p = self.flat_printer();
+                    p.node_id(&w.id);
+                    p.out
+                })
+                .collect();
+            self.write("[");
+            self.write(&windows.join(", "));
+            self.write("]");
+        }
+        if let Some(maybe_where) = &s.maybe_where {
+            clause(self, "where ", maybe_where.s());
+            self.imut(maybe_where, COMPLEX);
+        }
+        if let Some(group_by) = &s.maybe_group_by {
+            clause(self, "group by ", group_by_meta(group_by));
+            self.group_by(group_by);
+        }
+        clause(self, "into ", s.into.0.s());
+        self.port(&s.into);
+        if let Some(having) = &s.maybe_having {
+            clause(self, "having ", having.s());
+            self.imut(having, COMPLEX);
+        }
+    }
+
+    fn port(&mut self, (op, port): &(IdentRaw, Option<IdentRaw>)) {
+        self.write(&ident(&op.id));
+        if let Some(port) = port {
+            self.write("/");
+            self.write(&ident(&port.id));
+        }
+    }
+
+    fn group_by(&mut self, group_by: &GroupByRaw) {
+        match group_by {
+            GroupByRaw::Expr { expr, .. } => self.imut(expr, BINARY),
+            GroupByRaw::Set { items, .. } => {
+                self.write("set(");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    self.group_by(item);
+                }
+                self.write(")");
+            }
+            GroupByRaw::Each { expr, .. } => {
+                self.write("each(");
+                self.imut(expr, BINARY);
+                self.write(")");
+            }
+        }
+    }
+}
+
+fn group_by_meta(group_by: &GroupByRaw) -> Location {
+    match group_by {
+        GroupByRaw::Expr { mid, .. }
+        | GroupByRaw::Set { mid, .. }
+        | GroupByRaw::Each { mid, .. } => mid.start(),
+    }
+}
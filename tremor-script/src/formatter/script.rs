@@ -0,0 +1,821 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Printing of tremor-script and module files
+
+use super::{
+    doc_lines, ident, ident_path, layout, Body, Layout, Printer, BINARY, COMPLEX, NOT, PRESENCE,
+    SIGN, SIMPLE,
+};
+use crate::{
+    ast::{
+        base_expr::Ranged,
+        module::{ModuleRaw, ModuleStmtRaw},
+        raw::{
+            AnyFnRaw, ArrayPredicatePatternRaw, BytesPartRaw, BytesRaw, ComprehensionCaseRaw,
+            ComprehensionRaw, ConstRaw, ExprRaw, ExpressionRaw, FieldRaw, FnDefnRaw, ImutExprRaw,
+            InvokeRaw, MatchRaw, PatchOperationRaw, PathRaw, PatternRaw, PredicateClauseRaw,
+            PredicatePatternRaw, RecordPatternRaw, ReservedPathRaw, ScriptRaw, SegmentRaw,
+            StrLitElementRaw, StringLitRaw, TestExprRaw, TopLevelExprRaw, TuplePatternRaw, UseRaw,
+            FN_RES_NAME,
+        },
+        upable::Upable,
+        BinOpKind, Expression, NodeId, UnaryOpKind,
+    },
+    pos::Location,
+    prelude::*,
+    NodeMeta, Value,
+};
+use simd_json::StaticNode;
+
+layout!(ExprRaw, BytesPartRaw);
+
+impl Layout for TopLevelExprRaw<'_> {
+    fn bounds(&self) -> (Location, Location) {
+        (self.s(), self.e())
+    }
+    fn doc_lines(&self) -> usize {
+        match self {
+            TopLevelExprRaw::Const(c) => doc_lines(&c.comment),
+            TopLevelExprRaw::FnDefn(f) => fn_doc_lines(f),
+            TopLevelExprRaw::Use(_) | TopLevelExprRaw::Expr(_) => 0,
+        }
+    }
+}
+
+impl Layout for ModuleStmtRaw<'_> {
+    fn bounds(&self) -> (Location, Location) {
+        (self.s(), self.e())
+    }
+    fn doc_lines(&self) -> usize {
+        match self {
+            ModuleStmtRaw::Const(c) => doc_lines(&c.comment),
+            ModuleStmtRaw::FnDefn(f) => fn_doc_lines(f),
+            ModuleStmtRaw::Pipeline(p) => doc_lines(&p.doc),
+            ModuleStmtRaw::Window(w) => doc_lines(&w.doc),
+            ModuleStmtRaw::Operator(o) => doc_lines(&o.doc),
+            ModuleStmtRaw::Script(s) => doc_lines(&s.doc),
+            // the docs of connectors and flows are covered by their meta
+            ModuleStmtRaw::Flow(_) | ModuleStmtRaw::Connector(_) | ModuleStmtRaw::Use(_) => 0,
+        }
+    }
+}
+
+impl Body for ExprRaw<'_> {
+    const SEP: &'static str = ";";
+    fn print(&self, p: &mut Printer) {
+        p.expr(self);
+    }
+}
+
+impl Layout for FieldRaw<'_> {
+    fn bounds(&self) -> (Location, Location) {
+        (self.mid.start(), self.mid.end())
+    }
+}
+
+impl Layout for PatchOperationRaw<'_> {
+    fn bounds(&self) -> (Location, Location) {
+        let mid = patch_meta(self);
+        (mid.start(), mid.end())
+    }
+}
+
+impl<'script, Ex> Layout for PredicateClauseRaw<'script, Ex>
+where
+    Ex: ExpressionRaw<'script> + 'script,
+    <Ex as Upable<'script>>::Target: Expression + 'script,
+{
+    fn bounds(&self) -> (Location, Location) {
+        (self.mid.start(), self.mid.end())
+    }
+}
+
+impl<'script, Ex> Layout for ComprehensionCaseRaw<'script, Ex>
+where
+    Ex: Body + ExpressionRaw<'script> + 'script,
+    <Ex as Upable<'script>>::Target: Expression + 'script,
+{
+    fn bounds(&self) -> (Location, Location) {
+        // the meta of a case only covers its `(key, value)`
+        let end = self.exprs.last().map_or(self.mid.end(), |e| e.bounds().1);
+        (self.mid.start(), end)
+    }
+}
+
+fn fn_doc_lines(f: &AnyFnRaw) -> usize {
+    match f {
+        AnyFnRaw::Normal(f) => doc_lines(&f.doc),
+        AnyFnRaw::Match(f) => doc_lines(&f.doc),
+    }
+}
+
+fn patch_meta<'a>(op: &'a PatchOperationRaw) -> &'a NodeMeta {
+    match op {
+        PatchOperationRaw::Insert { mid, .. }
+        | PatchOperationRaw::Upsert { mid, .. }
+        | PatchOperationRaw::Update { mid, .. }
+        | PatchOperationRaw::Erase { mid, .. }
+        | PatchOperationRaw::Copy { mid, .. }
+        | PatchOperationRaw::Move { mid, .. }
+        | PatchOperationRaw::Merge { mid, .. }
+        | PatchOperationRaw::MergeRecord { mid, .. }
+        | PatchOperationRaw::Default { mid, .. }
+        | PatchOperationRaw::DefaultRecord { mid, .. } => mid,
+    }
+}
+
+/// the binding power of an expression, it needs parentheses where a higher one is required
+fn binding(e: &ImutExprRaw) -> u8 {
+    match e {
+        ImutExprRaw::Match(_) | ImutExprRaw::Comprehension(_) => COMPLEX,
+        ImutExprRaw::Binary(b) => binary_binding(b.kind),
+        ImutExprRaw::Unary(u) => match (u.kind, &u.expr) {
+            (UnaryOpKind::Not, ImutExprRaw::Present { .. }) => PRESENCE,
+            (UnaryOpKind::Plus | UnaryOpKind::Minus, _) => SIGN,
+            (UnaryOpKind::Not | UnaryOpKind::BitNot, _) => NOT,
+        },
+        ImutExprRaw::Present { .. } => PRESENCE,
+        _ => SIMPLE,
+    }
+}
+
+fn binary_binding(kind: BinOpKind) -> u8 {
+    match kind {
+        BinOpKind::Or => BINARY,
+        BinOpKind::Xor => 2,
+        BinOpKind::And => 3,
+        BinOpKind::BitXor => 4,
+        BinOpKind::BitAnd => 5,
+        BinOpKind::Eq | BinOpKind::NotEq => 6,
+        BinOpKind::Gte | BinOpKind::Gt | BinOpKind::Lte | BinOpKind::Lt => 7,
+        BinOpKind::RBitShiftSigned | BinOpKind::RBitShiftUnsigned | BinOpKind::LBitShift => 8,
+        BinOpKind::Add | BinOpKind::Sub => 9,
+        BinOpKind::Mul | BinOpKind::Div | BinOpKind::Mod => 10,
+    }
+}
+
+/// the name of a `.name` path segment
+fn field_name<'a>(segment: &'a SegmentRaw) -> Option<&'a str> {
+    if let SegmentRaw::Element(e) = segment {
+        if let ImutExprRaw::Literal(l) = &e.expr {
+            return l.value.as_str();
+        }
+    }
+    None
+}
+
+/// escapes the content of a string, `last` marks the end of the string
+fn escape(s: &str, heredoc: bool, last: bool, out: &mut String) {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let next = chars.peek().copied();
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '#' if next == Some('{') => out.push_str("\\#"),
+            // a heredoc only ends with three quotes
+            '"' if !heredoc || next == Some('"') || (next.is_none() && last) => {
+                out.push_str("\\\"");
+            }
+            '\n' | '\t' | '\r' if heredoc => out.push(c),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+}
+
+impl<'src> Printer<'src> {
+    pub(super) fn script(&mut self, script: &ScriptRaw) {
+        self.mod_doc(script.doc.as_deref());
+        self.entries(&script.exprs, ";", false, &Self::top_level_expr);
+    }
+
+    pub(super) fn module(&mut self, module: &ModuleRaw) {
+        self.mod_doc(module.doc.as_deref());
+        self.entries(&module.stmts, ";", true, &Self::module_stmt);
+    }
+
+    fn module_stmt(&mut self, stmt: &ModuleStmtRaw) {
+        match stmt {
+            ModuleStmtRaw::Flow(f) => self.define_flow(f),
+            ModuleStmtRaw::Connector(c) => self.define_connector(c),
+            ModuleStmtRaw::Const(c) => self.constant(c),
+            ModuleStmtRaw::FnDefn(f) => self.function(f),
+            ModuleStmtRaw::Pipeline(p) => self.define_pipeline(p),
+            ModuleStmtRaw::Use(u) => self.use_stmt(u),
+            ModuleStmtRaw::Window(w) => self.define_window(w),
+            ModuleStmtRaw::Operator(o) => self.define_operator(o),
+            ModuleStmtRaw::Script(s) => self.define_script(s),
+        }
+    }
+
+    pub(super) fn top_level_expr(&mut self, e: &TopLevelExprRaw) {
+        match e {
+            TopLevelExprRaw::Const(c) => self.constant(c),
+            TopLevelExprRaw::FnDefn(f) => self.function(f),
+            TopLevelExprRaw::Use(u) => self.use_stmt(u),
+            TopLevelExprRaw::Expr(e) => self.expr(e),
+        }
+    }
+
+    pub(super) fn use_stmt(&mut self, u: &UseRaw) {
+        self.write("use ");
+        self.node_id(&u.module);
+        if let Some(alias) = &u.alias {
+            self.write(" as ");
+            self.write(&ident(alias));
+        }
+    }
+
+    pub(super) fn node_id(&mut self, id: &NodeId) {
+        let path = id.module.iter().chain(std::iter::once(&id.id));
+        self.write(&ident_path(path.map(String::as_str)));
+    }
+
+    fn constant(&mut self, c: &ConstRaw) {
+        self.docs(c.comment.as_deref());
+        self.write("const ");
+        self.write(&ident(&c.name));
+        self.write(" = ");
+        self.imut(&c.expr, COMPLEX);
+    }
+
+    fn function(&mut self, f: &AnyFnRaw) {
+        match f {
+            AnyFnRaw::Normal(f) if f.inline => self.intrinsic(f),
+            AnyFnRaw::Normal(f) => {
+                self.docs(f.doc.as_deref());
+                self.write("fn ");
+                self.write(&ident(&f.name.id));
+                self.fn_args(&f.args, f.open);
+                self.write(" with");
+                self.indented(&f.body, ";", false, &Self::expr);
+                self.close(f.mid.end(), "end");
+            }
+            AnyFnRaw::Match(f) => {
+                self.docs(f.doc.as_deref());
+                self.write("fn ");
+                self.write(&ident(&f.name.id));
+                self.fn_args(&f.args, f.open);
+                self.write(" of");
+                self.indented(&f.cases, "", false, &Self::case);
+                self.close(f.mid.end(), "end");
+            }
+        }
+    }
+
+    fn fn_args(&mut self, args: &[crate::ast::raw::IdentRaw], open: bool) {
+        let mut args: Vec<String> = args.iter().map(|a| ident(&a.id)).collect();
+        if open {
+            args.push("...".to_string());
+        }
+        self.write("(");
+        self.write(&args.join(", "));
+        self.write(")");
+    }
+
+    fn intrinsic(&mut self, f: &FnDefnRaw) {
+        self.docs(f.doc.as_deref());
+        self.write("intrinsic fn ");
+        self.write(&ident(&f.name.id));
+        self.fn_args(&f.args, f.open);
+        if let Some(ExprRaw::Imut(ImutExprRaw::Invoke(invoke))) = f.body.first() {
+            // intrinsics are looked up in the `core` module
+            let module = invoke.module.iter().skip(1);
+            let path = module.chain(std::iter::once(&invoke.fun));
+            self.write(" as ");
+            self.write(&ident_path(path.map(String::as_str)));
+        }
+    }
+
+    pub(super) fn expr(&mut self, e: &ExprRaw) {
+        match e {
+            ExprRaw::MatchExpr(m) => self.match_expr(m),
+            ExprRaw::Assign(a) => {
+                self.write("let ");
+                self.path(&a.path);
+                self.write(" = ");
+                self.expr(&a.expr);
+            }
+            ExprRaw::Comprehension(c) => self.comprehension(c),
+            ExprRaw::Drop { .. } => self.write("drop"),
+            ExprRaw::Emit(e) => {
+                self.write("emit");
+                let event = matches!(&e.expr, ImutExprRaw::Path(PathRaw::Event(p)) if p.segments.is_empty());
+                if !event {
+                    self.write(" ");
+                    self.imut(&e.expr, COMPLEX);
+                }
+                if let Some(port) = &e.port {
+                    self.write(" => ");
+                    self.imut(port, COMPLEX);
+                }
+            }
+            ExprRaw::Imut(e) => self.imut(e, BINARY),
+        }
+    }
+
+    /// prints `e`, in parentheses if it binds less than `min`
+    pub(super) fn imut(&mut self, e: &ImutExprRaw, min: u8) {
+        if binding(e) < min {
+            self.write("(");
+            self.imut_inner(e);
+            self.write(")");
+        } else {
+            self.imut_inner(e);
+        }
+    }
+
+    fn imut_inner(&mut self, e: &ImutExprRaw) {
+        match e {
+            ImutExprRaw::Record(r) => self.group(
+                r.mid.end(),
+                &|p| p.write("{"),
+                &r.fields,
+                ",",
+                "}",
+                false,
+                &|p, f| {
+                    p.string(&f.name);
+                    p.write(": ");
+                    p.imut(&f.value, COMPLEX);
+                },
+            ),
+            ImutExprRaw::List(l) => self.group(
+                l.mid.end(),
+                &|p| p.write("["),
+                &l.exprs,
+                ",",
+                "]",
+                false,
+                &|p, e| p.imut(e, COMPLEX),
+            ),
+            ImutExprRaw::Patch(patch) => self.group(
+                patch.mid.end(),
+                &|p| {
+                    p.write("patch ");
+                    p.imut(&patch.target, COMPLEX);
+                    p.write(" of");
+                },
+                &patch.operations,
+                ";",
+                "end",
+                true,
+                &Self::patch_operation,
+            ),
+            ImutExprRaw::Merge(merge) => self.group(
+                merge.mid.end(),
+                &|p| {
+                    p.write("merge ");
+                    p.imut(&merge.target, COMPLEX);
+                    p.write(" of");
+                },
+                std::slice::from_ref(&merge.expr),
+                "",
+                "end",
+                true,
+                &|p, e| p.imut(e, COMPLEX),
+            ),
+            ImutExprRaw::Match(m) => self.match_expr(m),
+            ImutExprRaw::Comprehension(c) => self.comprehension(c),
+            ImutExprRaw::Path(path) => self.path(path),
+            ImutExprRaw::Binary(b) => {
+                let binding = binary_binding(b.kind);
+                self.imut(&b.lhs, binding);
+                self.write(" ");
+                self.write(&b.kind.to_string());
+                self.write(" ");
+                self.imut(&b.rhs, binding + 1);
+            }
+            ImutExprRaw::Unary(u) => match (u.kind, &u.expr) {
+                (UnaryOpKind::Not, ImutExprRaw::Present { path, .. }) => {
+                    self.write("absent ");
+                    self.path(path);
+                }
+                (UnaryOpKind::Plus, e) => {
+                    self.write("+");
+                    self.imut(e, SIGN);
+                }
+                (UnaryOpKind::Minus, e) => {
+                    self.write("-");
+                    self.imut(e, SIGN);
+                }
+                (UnaryOpKind::Not, e) => {
+                    self.write("not ");
+                    self.imut(e, NOT);
+                }
+                (UnaryOpKind::BitNot, e) => {
+                    self.write("!");
+                    self.imut(e, NOT);
+                }
+            },
+            ImutExprRaw::Literal(l) => self.literal(&l.value),
+            ImutExprRaw::Invoke(i) => self.invoke(i),
+            ImutExprRaw::Present { path, .. } => {
+                self.write("present ");
+                self.path(path);
+            }
+            ImutExprRaw::String(s) => self.string(s),
+            ImutExprRaw::Recur(r) => self.group(
+                r.mid.end(),
+                &|p| p.write("recur("),
+                &r.exprs,
+                ",",
+                ")",
+                false,
+                &|p, e| p.imut(e, COMPLEX),
+            ),
+            ImutExprRaw::Bytes(b) => self.bytes(b),
+        }
+    }
+
+    fn invoke(&mut self, invoke: &InvokeRaw) {
+        let path = invoke.module.iter().chain(std::iter::once(&invoke.fun));
+        let name = ident_path(path.map(String::as_str));
+        self.group(
+            invoke.mid.end(),
+            &|p| {
+                p.write(&name);
+                p.write("(");
+            },
+            &invoke.args,
+            ",",
+            ")",
+            false,
+            &|p, e| p.imut(e, COMPLEX),
+        );
+    }
+
+    fn bytes(&mut self, bytes: &BytesRaw) {
+        self.group(
+            bytes.mid.end(),
+            &|p| p.write("<<"),
+            &bytes.bytes,
+            ",",
+            ">>",
+            false,
+            &|p, part| {
+                p.imut(&part.data, SIMPLE);
+                if let Some(bits) = part.bits {
+                    p.write(&format!(":{}", bits));
+                }
+                if !part.data_type.id.is_empty() {
+                    p.write("/");
+                    p.write(&ident(&part.data_type.id));
+                }
+            },
+        );
+    }
+
+    fn literal(&mut self, value: &Value) {
+        if let Value::Static(StaticNode::F64(f)) = value {
+            let f = f.to_string();
+            self.write(&f);
+            if !f.contains('.') {
+                self.write(".0");
+            }
+        } else {
+            self.write(&value.encode());
+        }
+    }
+
+    pub(super) fn string(&mut self, s: &StringLitRaw) {
+        let start = s.mid.start().absolute();
+        let heredoc = self
+            .src
+            .get(start..)
+            .map_or(false, |src| src.starts_with(r#"""""#));
+        let mut out = String::from(if heredoc { "\"\"\"\n" } else { "\"" });
+        let mut lit = String::new();
+        // the parser collects the elements in reverse
+        for element in s.elements.iter().rev() {
+            match element {
+                StrLitElementRaw::Lit(l) => lit.push_str(l),
+                StrLitElementRaw::Expr(e) => {
+                    escape(&lit, heredoc, false, &mut out);
+                    lit.clear();
+                    let mut p = self.flat_printer();
+                    p.imut(e, BINARY);
+                    out.push_str("#{");
+                    out.push_str(&p.out);
+                    out.push('}');
+                }
+            }
+        }
+        escape(&lit, heredoc, true, &mut out);
+        out.push_str(if heredoc { r#"""""# } else { "\"" });
+        self.write(&out);
+    }
+
+    pub(super) fn path(&mut self, path: &PathRaw) {
+        match path {
+            PathRaw::Local(p) => {
+                self.write(&ident(&p.root.id));
+                self.segments(&p.segments);
+            }
+            PathRaw::Event(p) => {
+                self.write("event");
+                self.segments(&p.segments);
+            }
+            PathRaw::State(p) => {
+                self.write("state");
+                self.segments(&p.segments);
+            }
+            PathRaw::Meta(p) => {
+                self.write("$");
+                let mut segments = p.segments.as_slice();
+                if let Some((first, rest)) = segments.split_first() {
+                    if let Some(name) = field_name(first) {
+                        self.write(&ident(name));
+                        segments = rest;
+                    }
+                }
+                self.segments(segments);
+            }
+            PathRaw::Const(p) => {
+                let module = p.module.iter().chain(std::iter::once(&p.root));
+                self.write(&ident_path(module.map(|i| &*i.id)));
+                self.segments(&p.segments);
+            }
+            PathRaw::Reserved(p) => {
+                let (root, segments) = match p {
+                    ReservedPathRaw::Args { segments, .. } => ("args", segments),
+                    ReservedPathRaw::Window { segments, .. } => ("window", segments),
+                    ReservedPathRaw::Group { segments, .. } => ("group", segments),
+                };
+                self.write(root);
+                self.segments(segments);
+            }
+            PathRaw::Expr(p) => {
+                match p.expr.as_ref() {
+                    root @ (ImutExprRaw::Invoke(_)
+                    | ImutExprRaw::Record(_)
+                    | ImutExprRaw::List(_)) => {
+                        self.imut_inner(root);
+                    }
+                    root => {
+                        self.write("(");
+                        self.imut(root, COMPLEX);
+                        self.write(")");
+                    }
+                }
+                self.segments(&p.segments);
+            }
+        }
+    }
+
+    fn segments(&mut self, segments: &[SegmentRaw]) {
+        for segment in segments {
+            if let Some(name) = field_name(segment) {
+                self.write(".");
+                self.write(&ident(name));
+                continue;
+            }
+            self.write("[");
+            match segment {
+                SegmentRaw::Element(e) => self.imut(&e.expr, COMPLEX),
+                SegmentRaw::Range(r) => {
+                    self.imut(&r.range_start, COMPLEX);
+                    self.write(":");
+                    self.imut(&r.range_end, COMPLEX);
+                }
+            }
+            self.write("]");
+        }
+    }
+
+    fn patch_operation(&mut self, op: &PatchOperationRaw) {
+        let (keyword, field, expr) = match op {
+            PatchOperationRaw::Insert { ident, expr, .. } => ("insert", Some(ident), Some(expr)),
+            PatchOperationRaw::Upsert { ident, expr, .. } => ("upsert", Some(ident), Some(expr)),
+            PatchOperationRaw::Update { ident, expr, .. } => ("update", Some(ident), Some(expr)),
+            PatchOperationRaw::Erase { ident, .. } => ("erase", Some(ident), None),
+            PatchOperationRaw::Merge { ident, expr, .. } => ("merge", Some(ident), Some(expr)),
+            PatchOperationRaw::MergeRecord { expr, .. } => ("merge", None, Some(expr)),
+            PatchOperationRaw::Default { ident, expr, .. } => ("default", Some(ident), Some(expr)),
+            PatchOperationRaw::DefaultRecord { expr, .. } => ("default", None, Some(expr)),
+            PatchOperationRaw::Copy { from, to, .. } | PatchOperationRaw::Move { from, to, .. } => {
+                let keyword = if matches!(op, PatchOperationRaw::Copy { .. }) {
+                    "copy "
+                } else {
+                    "move "
+                };
+                self.write(keyword);
+                self.string(from);
+                self.write(" => ");
+                self.string(to);
+                return;
+            }
+        };
+        self.write(keyword);
+        if let Some(field) = field {
+            self.write(" ");
+            self.string(field);
+        }
+        if let Some(expr) = expr {
+            self.write(" => ");
+            self.imut(expr, COMPLEX);
+        }
+    }
+
+    fn match_expr<'script, Ex>(&mut self, m: &MatchRaw<'script, Ex>)
+    where
+        Ex: Body + ExpressionRaw<'script> + 'script,
+        <Ex as Upable<'script>>::Target: Expression + 'script,
+    {
+        self.write("match ");
+        self.imut(&m.target, COMPLEX);
+        self.write(" of");
+        self.indented(&m.patterns, "", false, &Self::case);
+        self.close(m.mid.end(), "end");
+    }
+
+    fn case<'script, Ex>(&mut self, c: &PredicateClauseRaw<'script, Ex>)
+    where
+        Ex: Body + ExpressionRaw<'script> + 'script,
+        <Ex as Upable<'script>>::Target: Expression + 'script,
+    {
+        match &c.pattern {
+            PatternRaw::Default => self.write("default"),
+            pattern => {
+                self.write("case ");
+                self.pattern(pattern);
+            }
+        }
+        self.guard(c.guard.as_ref());
+        self.case_body(&c.exprs);
+    }
+
+    fn guard(&mut self, guard: Option<&ImutExprRaw>) {
+        if let Some(guard) = guard {
+            self.write(" when ");
+            self.imut(guard, COMPLEX);
+        }
+    }
+
+    /// prints a body after `=>`, on the same line if it is a single expression
+    fn case_body<Ex: Body>(&mut self, exprs: &[Ex]) {
+        self.write(" =>");
+        match exprs {
+            [e] if !self.pending_before(e.bounds().0.absolute()) => {
+                self.write(" ");
+                e.print(self);
+            }
+            _ => self.indented(exprs, Ex::SEP, false, &|p, e| e.print(p)),
+        }
+    }
+
+    fn comprehension<'script, Ex>(&mut self, c: &ComprehensionRaw<'script, Ex>)
+    where
+        Ex: Body + ExpressionRaw<'script> + 'script,
+        <Ex as Upable<'script>>::Target: Expression + 'script,
+    {
+        self.write("for ");
+        self.imut(&c.target, COMPLEX);
+        self.write(" of");
+        self.indented(&c.cases, "", false, &|p, case| {
+            p.write("case (");
+            p.write(&ident(&case.key_name));
+            p.write(", ");
+            p.write(&ident(&case.value_name));
+            p.write(")");
+            p.guard(case.guard.as_ref());
+            p.case_body(&case.exprs);
+        });
+        self.close(c.mid.end(), "end");
+    }
+
+    fn pattern(&mut self, pattern: &PatternRaw) {
+        match pattern {
+            PatternRaw::Record(r) => self.record_pattern(r),
+            PatternRaw::Array(a) => {
+                self.write("%[");
+                self.array_predicates(&a.exprs);
+                self.write("]");
+            }
+            PatternRaw::Tuple(t) => self.tuple_pattern(t),
+            PatternRaw::Expr(e) => self.imut(e, COMPLEX),
+            // the arguments of `fn ... of` cases are matched as a tuple
+            PatternRaw::Assign(a) if a.id == FN_RES_NAME => {
+                if let PatternRaw::Tuple(t) = a.pattern.as_ref() {
+                    self.write("(");
+                    self.array_predicates(&t.exprs);
+                    self.write(")");
+                }
+            }
+            PatternRaw::Assign(a) => {
+                self.write(&ident(&a.id));
+                self.write(" = ");
+                self.pattern(&a.pattern);
+            }
+            PatternRaw::Extract(t) => {
+                self.write("~ ");
+                self.test(t);
+            }
+            PatternRaw::DoNotCare => self.write("_"),
+            PatternRaw::Default => self.write("default"),
+        }
+    }
+
+    fn record_pattern(&mut self, r: &RecordPatternRaw) {
+        self.write("%{");
+        for (i, field) in r.fields.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            match field {
+                PredicatePatternRaw::TildeEq { assign, lhs, test } => {
+                    if assign != lhs {
+                        self.write(&ident(assign));
+                        self.write(" = ");
+                    }
+                    self.write(&ident(lhs));
+                    self.write(" ~= ");
+                    self.test(test);
+                }
+                PredicatePatternRaw::Bin { lhs, rhs, kind } => {
+                    self.write(&ident(lhs));
+                    self.write(" ");
+                    self.write(&kind.to_string());
+                    self.write(" ");
+                    self.imut(rhs, COMPLEX);
+                }
+                PredicatePatternRaw::RecordPatternEq { lhs, pattern } => {
+                    self.write(&ident(lhs));
+                    self.write(" ~= ");
+                    self.record_pattern(pattern);
+                }
+                PredicatePatternRaw::ArrayPatternEq { lhs, pattern } => {
+                    self.write(&ident(lhs));
+                    self.write(" ~= %[");
+                    self.array_predicates(&pattern.exprs);
+                    self.write("]");
+                }
+                PredicatePatternRaw::TuplePatternEq { lhs, pattern } => {
+                    self.write(&ident(lhs));
+                    self.write(" ~= ");
+                    self.tuple_pattern(pattern);
+                }
+                PredicatePatternRaw::FieldPresent { lhs } => {
+                    self.write("present ");
+                    self.write(&ident(lhs));
+                }
+                PredicatePatternRaw::FieldAbsent { lhs } => {
+                    self.write("absent ");
+                    self.write(&ident(lhs));
+                }
+            }
+        }
+        self.write("}");
+    }
+
+    fn tuple_pattern(&mut self, t: &TuplePatternRaw) {
+        self.write("%(");
+        self.array_predicates(&t.exprs);
+        if t.open {
+            self.write(if t.exprs.is_empty() { "..." } else { ", ..." });
+        }
+        self.write(")");
+    }
+
+    fn array_predicates(&mut self, predicates: &[ArrayPredicatePatternRaw]) {
+        for (i, predicate) in predicates.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            match predicate {
+                ArrayPredicatePatternRaw::Expr(e) => self.imut(e, COMPLEX),
+                ArrayPredicatePatternRaw::Tilde(t) => {
+                    self.write("~ ");
+                    self.test(t);
+                }
+                ArrayPredicatePatternRaw::Record(r) => self.record_pattern(r),
+                ArrayPredicatePatternRaw::Ignore => self.write("_"),
+            }
+        }
+    }
+
+    fn test(&mut self, t: &TestExprRaw) {
+        let test = t.test.replace('\\', "\\\\").replace('|', "\\|");
+        self.write(&ident(&t.id));
+        self.write("|");
+        self.write(&test);
+        self.write("|");
+    }
+}
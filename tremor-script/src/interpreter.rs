@@ -33,6 +33,7 @@
 /// Runtime interpreter support for expressions
 pub mod expr;
 mod imut_expr;
+pub(crate) mod instrumentation;
 
 pub use self::expr::Cont;
 use crate::{
@@ -13,8 +13,8 @@
 // limitations under the License.
 
 use super::{
-    resolve, resolve_value, set_local_shadow, test_guard, test_predicate_expr, Env, ExecOpts,
-    LocalStack, NULL,
+    instrumentation, resolve, resolve_value, set_local_shadow, test_guard, test_predicate_expr,
+    Env, ExecOpts, LocalStack, NULL,
 };
 use crate::errors::{
     err_need_obj, error_assign_array, error_assign_to_const, error_bad_key_err,
@@ -29,7 +29,7 @@ use crate::{
     },
     errors::error_oops_err,
 };
-use crate::{stry, Value};
+use crate::{coverage, debug, profile, stry, Value};
 use std::mem;
 use std::{
     borrow::{Borrow, Cow},
@@ -452,6 +452,19 @@ impl<'script> Expr<'script> {
     where
         'script: 'event,
     {
+        let _profile = if instrumentation::is_active() {
+            // immutable expressions record their own hit once they are run
+            if coverage::is_enabled() && !matches!(self, Expr::Imut(_)) {
+                coverage::hit(self.extent());
+            }
+            // immutable expressions are stepped when they are run
+            if debug::is_attached() && !matches!(self, Expr::Imut(_)) {
+                debug::step(self.extent(), event, state, meta, local);
+            }
+            profile::is_enabled().then(|| profile::enter(profile::Frame::Expr(self.extent())))
+        } else {
+            None
+        };
         match self {
             Expr::Emit(expr) => match expr.borrow() {
                 EmitExpr {
@@ -527,6 +540,9 @@ impl<'script> Expr<'script> {
                 let r = if opts.result_needed {
                     stry!(expr.run(opts, env, event, state, meta, local))
                 } else {
+                    if instrumentation::is_active() && coverage::is_enabled() {
+                        coverage::hit(expr.extent());
+                    }
                     Cow::Borrowed(&NULL)
                 };
                 if let Cow::Borrowed(v) = r {
@@ -18,14 +18,16 @@ use crate::{
         ExprPath, ImutExpr, Invoke, InvokeAggr, Literal, LocalPath, Match, Merge, Patch, Path,
         Recur, ReservedPath, Segment, UnaryExpr,
     },
+    coverage, debug,
     errors::Kind as ErrorKind,
     errors::{
         error_bad_key, error_decreasing_range, error_invalid_unary, error_need_obj, error_need_str,
         error_no_clause_hit, error_oops, error_oops_err, Result,
     },
     interpreter::{
-        exec_binary, exec_unary, merge_values, patch_value, resolve, set_local_shadow, test_guard,
-        test_predicate_expr, value_to_index, AggrType, Env, ExecOpts, LocalStack, FALSE, TRUE,
+        exec_binary, exec_unary, instrumentation, merge_values, patch_value, resolve,
+        set_local_shadow, test_guard, test_predicate_expr, value_to_index, AggrType, Env, ExecOpts,
+        LocalStack, FALSE, TRUE,
     },
     lexer::Span,
     prelude::*,
@@ -154,6 +156,14 @@ impl<'script> ImutExpr<'script> {
     where
         'script: 'event,
     {
+        if instrumentation::is_active() {
+            if coverage::is_enabled() {
+                coverage::hit(self.extent());
+            }
+            if debug::is_attached() {
+                debug::step(self.extent(), event, state, meta, local);
+            }
+        }
         match self {
             ImutExpr::String(s) => s.run(opts, env, event, state, meta, local).map(owned_val),
             ImutExpr::Recur(Recur { exprs, argc, .. }) => {
@@ -0,0 +1,41 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A single switch in front of everything that instruments the evaluation of
+//! expressions, so the uninstrumented interpreter only checks one flag.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// number of instrumentations currently switched on
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Switches on the instrumentation with the given flag
+pub(crate) fn switch_on(flag: &AtomicBool) {
+    if !flag.swap(true, Ordering::AcqRel) {
+        ACTIVE.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Switches off the instrumentation with the given flag
+pub(crate) fn switch_off(flag: &AtomicBool) {
+    if flag.swap(false, Ordering::AcqRel) {
+        ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns true if any instrumentation is switched on
+#[inline]
+pub(crate) fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed) > 0
+}
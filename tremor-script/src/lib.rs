@@ -32,9 +32,13 @@ pub mod arena;
 /// The Tremor Script AST
 pub mod ast;
 mod compat;
+/// Expression and branch coverage
+pub mod coverage;
 /// Context struct for tremor-script
 pub mod ctx;
 mod datetime;
+/// Debugger hooks
+pub mod debug;
 /// Tremor Deploy ( troy )
 pub mod deploy;
 
@@ -42,6 +46,8 @@ pub mod deploy;
 pub mod docs;
 /// Errors
 pub mod errors;
+/// Canonical source formatter
+pub mod formatter;
 /// Grok implementation
 pub mod grok;
 /// Tremor Script highlighter
@@ -50,6 +56,8 @@ pub mod highlighter;
 pub mod interpreter;
 /// The Tremor Script Lexer
 pub mod lexer;
+/// Lint rules and their configuration
+pub mod lint;
 pub(crate) mod parser;
 /// Support for module paths
 pub mod path;
@@ -57,6 +65,8 @@ pub mod path;
 pub mod pos;
 /// Prelude module with important exports
 pub mod prelude;
+/// Profiling of scripts and pipelines
+pub mod profile;
 /// Tremor Query ( trickle )
 pub mod query;
 /// Function registry
@@ -91,7 +101,7 @@ use ast::{Consts, InvokeAggrFn};
 pub use interpreter::{AggrType, FALSE, NULL, TRUE};
 use lazy_static::lazy_static;
 use std::sync::{
-    atomic::{AtomicU32, Ordering},
+    atomic::{AtomicU32, AtomicU8, Ordering},
     RwLock,
 };
 pub use tremor_common::stry;
@@ -99,6 +109,8 @@ pub use tremor_value::{literal, KnownKey, Object, Value};
 
 /// Default recursion limit
 pub static RECURSION_LIMIT: AtomicU32 = AtomicU32::new(1024);
+/// Type checking mode, see `TypeCheck`
+static TYPE_CHECK: AtomicU8 = AtomicU8::new(TypeCheck::Off as u8);
 /// No aggregates
 pub const NO_AGGRS: [InvokeAggrFn<'static>; 0] = [];
 
@@ -108,6 +120,55 @@ pub fn recursion_limit() -> u32 {
     RECURSION_LIMIT.load(Ordering::Relaxed)
 }
 
+/// How type mismatches found by static type inference are reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TypeCheck {
+    /// No type inference is performed
+    Off = 0,
+    /// Type mismatches are reported as warnings
+    Warn = 1,
+    /// Definite type mismatches are errors, probable ones are warnings
+    Strict = 2,
+}
+
+impl Default for TypeCheck {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+impl std::str::FromStr for TypeCheck {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "strict" => Ok(Self::Strict),
+            other => Err(format!(
+                "invalid type check mode `{}`, expected one of `off`, `warn` or `strict`",
+                other
+            )),
+        }
+    }
+}
+
+/// type checking mode used when compiling scripts
+#[inline]
+#[must_use]
+pub fn type_check() -> TypeCheck {
+    match TYPE_CHECK.load(Ordering::Relaxed) {
+        1 => TypeCheck::Warn,
+        2 => TypeCheck::Strict,
+        _ => TypeCheck::Off,
+    }
+}
+
+/// sets the type checking mode used when compiling scripts
+pub fn set_type_check(mode: TypeCheck) {
+    TYPE_CHECK.store(mode as u8, Ordering::Relaxed);
+}
+
 lazy_static! {
     /// No Constants
     pub static ref NO_CONSTS: Consts<'static> = Consts::new();
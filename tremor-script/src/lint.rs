@@ -0,0 +1,416 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Named lint rules for warnings raised while compiling tremor sources
+//! and their configurable severity.
+
+use crate::ast::base_expr::Ranged;
+use crate::ast::visitors::prelude::*;
+use crate::ast::{helper::Warning, module::Content, NodeId};
+use crate::pos::Span;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+/// A lint rule, every warning is raised by exactly one rule
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// A match without a default case
+    MissingDefault,
+    /// A match case that can never be taken
+    UnreachableCase,
+    /// A pattern that tests the same thing twice
+    RedundantPattern,
+    /// A guard that is always true
+    AlwaysTrueGuard,
+    /// A `let` binding that is never read
+    UnusedLet,
+    /// A pattern or comprehension binding that hides a local of the same name
+    ShadowedLocal,
+    /// A `define` that is never created, deployed or referenced
+    UnusedDefine,
+    /// Use of a deprecated function or operator
+    Deprecated,
+    /// A value that can not, or might not, have the type an operation requires
+    TypeMismatch,
+}
+
+impl Rule {
+    /// All lint rules
+    pub const ALL: [Rule; 9] = [
+        Rule::MissingDefault,
+        Rule::UnreachableCase,
+        Rule::RedundantPattern,
+        Rule::AlwaysTrueGuard,
+        Rule::UnusedLet,
+        Rule::ShadowedLocal,
+        Rule::UnusedDefine,
+        Rule::Deprecated,
+        Rule::TypeMismatch,
+    ];
+
+    /// Name of the rule as used in configuration and reports
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::MissingDefault => "missing_default",
+            Rule::UnreachableCase => "unreachable_case",
+            Rule::RedundantPattern => "redundant_pattern",
+            Rule::AlwaysTrueGuard => "always_true_guard",
+            Rule::UnusedLet => "unused_let",
+            Rule::ShadowedLocal => "shadowed_local",
+            Rule::UnusedDefine => "unused_define",
+            Rule::Deprecated => "deprecated",
+            Rule::TypeMismatch => "type_mismatch",
+        }
+    }
+
+    /// Short description of what the rule checks for
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match self {
+            Rule::MissingDefault => "match expressions without a default case",
+            Rule::UnreachableCase => "match cases that can never be taken",
+            Rule::RedundantPattern => "patterns that test the same field more than once",
+            Rule::AlwaysTrueGuard => "case guards that are always true",
+            Rule::UnusedLet => "`let` bindings that are never read",
+            Rule::ShadowedLocal => "bindings that shadow a local variable of the same name",
+            Rule::UnusedDefine => "definitions that are never used",
+            Rule::Deprecated => "use of deprecated functions and operators",
+            Rule::TypeMismatch => "values that do not have the type an operation requires",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Rule {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|r| r.name() == s)
+            .ok_or_else(|| format!("unknown lint rule `{}`", s))
+    }
+}
+
+/// How a lint is reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The lint is ignored
+    Allow,
+    /// The lint is reported as a warning
+    Warn,
+    /// The lint is reported as an error
+    Deny,
+}
+
+impl Default for Severity {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Allow => f.write_str("allow"),
+            Severity::Warn => f.write_str("warn"),
+            Severity::Deny => f.write_str("deny"),
+        }
+    }
+}
+
+/// Lint configuration, rules that are not configured are reported as warnings
+///
+/// ```yaml
+/// rules:
+///   unused_let: deny
+///   shadowed_local: allow
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Severity per rule
+    #[serde(default)]
+    pub rules: BTreeMap<Rule, Severity>,
+}
+
+impl Config {
+    /// Severity of a rule
+    #[must_use]
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.rules.get(&rule).copied().unwrap_or_default()
+    }
+
+    /// Pairs warnings with their severity, dropping allowed ones
+    pub fn apply<'w>(
+        &self,
+        warnings: impl IntoIterator<Item = &'w Warning>,
+    ) -> Vec<(Severity, &'w Warning)> {
+        warnings
+            .into_iter()
+            .map(|w| (self.severity(w.rule), w))
+            .filter(|(s, _)| *s != Severity::Allow)
+            .collect()
+    }
+}
+
+/// Replacement for a deprecated operator, if it is one
+pub(crate) fn deprecated_operator(module: &str, operation: &str) -> Option<&'static str> {
+    match (module, operation) {
+        ("generic", "backpressure") => Some("qos::backpressure"),
+        _ => None,
+    }
+}
+
+/// Messages for the definitions in `defs` whose name is not in `used`
+pub(crate) fn unused_definitions<'a, T: BaseExpr + 'a>(
+    kind: &str,
+    defs: impl IntoIterator<Item = (&'a String, &'a T)>,
+    used: &HashSet<String>,
+) -> Vec<(Span, String)> {
+    let mut unused: Vec<(Span, String)> = defs
+        .into_iter()
+        .filter(|(id, _)| !used.contains(*id) && !id.starts_with('_'))
+        .map(|(id, d)| (d.extent(), format!("The {} `{}` is never used", kind, id)))
+        .collect();
+    unused.sort();
+    unused
+}
+
+/// Names of the definitions in the local scope `targets` refer to
+pub(crate) fn local_targets<'a>(targets: impl IntoIterator<Item = &'a NodeId>) -> HashSet<String> {
+    targets
+        .into_iter()
+        .filter(|t| t.module().is_empty())
+        .map(|t| t.id().to_string())
+        .collect()
+}
+
+/// Messages for query definitions that are never created or used as a window
+pub(crate) fn unused_query_definitions(content: &Content, stmts: &[Stmt]) -> Vec<(Span, String)> {
+    let mut created = Vec::new();
+    let mut windows = Vec::new();
+    for stmt in stmts {
+        match stmt {
+            Stmt::OperatorCreate(c) => created.push(&c.target),
+            Stmt::ScriptCreate(c) => created.push(&c.target),
+            Stmt::PipelineCreate(c) => created.push(&c.target),
+            Stmt::SelectStmt(s) => windows.extend(s.stmt.windows.iter().map(|w| &w.id)),
+            _ => (),
+        }
+    }
+    let created = local_targets(created);
+    let windows = local_targets(windows);
+    let mut unused = unused_definitions("operator", &content.operators, &created);
+    unused.append(&mut unused_definitions(
+        "script",
+        &content.scripts,
+        &created,
+    ));
+    unused.append(&mut unused_definitions(
+        "pipeline",
+        &content.pipelines,
+        &created,
+    ));
+    unused.append(&mut unused_definitions(
+        "window",
+        &content.windows,
+        &windows,
+    ));
+    unused
+}
+
+/// Finds `let` bindings that are never read
+#[derive(Default)]
+pub(crate) struct UnusedLets {
+    assigned: HashMap<usize, Span>,
+    read: HashSet<usize>,
+}
+
+impl UnusedLets {
+    /// Warns about locals of a script that are assigned but never read
+    pub(crate) fn check<'script>(
+        helper: &mut Helper<'script, '_>,
+        exprs: &mut Exprs<'script>,
+    ) -> Result<()> {
+        let mut unused = Self::default();
+        for e in exprs {
+            ExprWalker::walk_expr(&mut unused, e)?;
+        }
+        let mut found: Vec<(Span, String)> = helper
+            .locals
+            .iter()
+            .filter(|(name, _)| !name.starts_with(" __SHADOW ") && !name.starts_with('_'))
+            .filter(|(_, idx)| !unused.read.contains(idx))
+            .filter_map(|(name, idx)| {
+                let span = unused.assigned.get(idx)?;
+                Some((
+                    *span,
+                    format!("The local `{}` is assigned but never read", name),
+                ))
+            })
+            .collect();
+        found.sort();
+        for (span, msg) in found {
+            helper.warn_with_scope(Rule::UnusedLet, span, &msg);
+        }
+        Ok(())
+    }
+}
+
+impl<'script> walkers::imut_expr::Walker<'script> for UnusedLets {}
+impl<'script> visitors::imut_expr::Visitor<'script> for UnusedLets {
+    fn visit_local(&mut self, local_idx: &mut usize) -> Result<VisitRes> {
+        self.read.insert(*local_idx);
+        Ok(VisitRes::Walk)
+    }
+    fn visit_local_path(&mut self, path: &mut LocalPath<'script>) -> Result<VisitRes> {
+        self.read.insert(path.idx);
+        Ok(VisitRes::Walk)
+    }
+}
+
+impl<'script> walkers::expr::Walker<'script> for UnusedLets {}
+impl<'script> visitors::expr::Visitor<'script> for UnusedLets {
+    fn visit_expr(&mut self, e: &mut Expr<'script>) -> Result<VisitRes> {
+        let span = e.extent();
+        // the target of an assignment to a local is not a read, so we only walk the assigned value,
+        // updating a field of a local counts as using it though, so those paths are walked as usual
+        match e {
+            Expr::Assign {
+                path: Path::Local(LocalPath { idx, segments, .. }),
+                expr,
+                ..
+            } if segments.is_empty() => {
+                self.assigned.entry(*idx).or_insert(span);
+                ExprWalker::walk_expr(self, expr.as_mut())?;
+                Ok(VisitRes::Stop)
+            }
+            Expr::AssignMoveLocal {
+                path: Path::Local(LocalPath { idx, segments, .. }),
+                idx: moved,
+                ..
+            } if segments.is_empty() => {
+                self.assigned.entry(*idx).or_insert(span);
+                self.read.insert(*moved);
+                Ok(VisitRes::Stop)
+            }
+            Expr::AssignMoveLocal { idx, .. } => {
+                self.read.insert(*idx);
+                Ok(VisitRes::Walk)
+            }
+            _ => Ok(VisitRes::Walk),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::registry;
+    use crate::script::Script;
+
+    fn rules(src: &str) -> Result<Vec<(Rule, String)>> {
+        let mut reg = registry();
+        crate::std_lib::load(&mut reg);
+        let script = Script::parse(src, &reg)?;
+        Ok(script.warnings().map(|w| (w.rule, w.msg.clone())).collect())
+    }
+
+    #[test]
+    fn rule_names() {
+        for rule in Rule::ALL {
+            assert_eq!(Ok(rule), rule.name().parse());
+        }
+        assert!("snot".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn config() -> std::result::Result<(), simd_json::Error> {
+        let mut src = r#"{"rules": {"unused_let": "deny", "deprecated": "allow"}}"#.to_string();
+        let config: Config = simd_json::from_str(src.as_mut_str())?;
+        assert_eq!(Severity::Deny, config.severity(Rule::UnusedLet));
+        assert_eq!(Severity::Allow, config.severity(Rule::Deprecated));
+        assert_eq!(Severity::Warn, config.severity(Rule::ShadowedLocal));
+        let mut src = r#"{"rules": {"snot": "deny"}}"#.to_string();
+        assert!(simd_json::from_str::<Config>(src.as_mut_str()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn unused_let() -> Result<()> {
+        let w = rules(
+            "let a = 1; let b = a; let `_c` = 3; let d = {}; let d.x = 1; let e = 4; emit b",
+        )?;
+        assert_eq!(
+            vec![(
+                Rule::UnusedLet,
+                "The local `e` is assigned but never read".to_string()
+            )],
+            w.into_iter()
+                .filter(|(r, _)| *r == Rule::UnusedLet)
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn shadowed_local() -> Result<()> {
+        let w = rules(
+            r#"
+            let k = 1;
+            let x = for event of case (k, v) => v end;
+            emit [k, x]
+            "#,
+        )?;
+        assert!(w.contains(&(
+            Rule::ShadowedLocal,
+            "The binding `k` shadows a local of the same name".to_string()
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn guards_and_unreachable_cases() -> Result<()> {
+        let w = rules(
+            r#"
+            match event of
+              case 1 when true => 1
+              case 2 when false => 2
+              case _ => 3
+              case 4 => 4
+              default => 5
+            end
+            "#,
+        )?;
+        let rules: Vec<_> = w.into_iter().map(|(r, _)| r).collect();
+        assert!(rules.contains(&Rule::AlwaysTrueGuard));
+        assert_eq!(
+            3,
+            rules
+                .iter()
+                .filter(|r| **r == Rule::UnreachableCase)
+                .count()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,433 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Opt-in profiling of scripts and pipelines.
+//!
+//! While profiling is [`enable`]d the interpreter [`enter`]s a frame for every
+//! expression it evaluates and pipelines enter one for every operator an event
+//! passes through. When the returned [`Guard`] is dropped the time spent in the
+//! frame is attributed to the full stack of frames that led to it, so the
+//! samples can be rendered as [`folded`] stacks for flamegraph tooling.
+//! Every thread records its samples in its own profile, so profiled pipelines
+//! never contend on a shared lock, the profiles are summed up when read.
+//!
+//! Pipelines deployed in a flow enter a [`Frame::Flow`] first, so the samples
+//! of a single flow can be looked at with [`flow_stacks`] and [`flow_folded`].
+
+use crate::arena;
+use crate::errors::Result;
+use crate::interpreter::instrumentation;
+use crate::pos::Span;
+use lazy_static::lazy_static;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// names of the sources expressions come from
+    static ref NAMES: Mutex<HashMap<arena::Index, String>> = Mutex::new(HashMap::new());
+    /// the profiles of every thread that entered a frame, they outlive their thread
+    static ref PROFILES: Mutex<Vec<Arc<Mutex<Profile>>>> = Mutex::new(Vec::new());
+}
+
+thread_local! {
+    static STACK: RefCell<Vec<Open>> = RefCell::new(Vec::new());
+    /// the profile of the current thread, only contended while samples are read
+    static LOCAL_PROFILE: Arc<Mutex<Profile>> = {
+        let profile = Arc::default();
+        if let Ok(mut all) = PROFILES.lock() {
+            all.push(Arc::clone(&profile));
+        }
+        profile
+    };
+}
+
+/// A frame of a profiled stack
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Frame {
+    /// evaluation of the expression with the given span
+    Expr(Span),
+    /// an operator of a pipeline, named `<pipeline>/<operator>`
+    Operator(Arc<str>),
+    /// the flow with the given id a pipeline is deployed in
+    Flow(Arc<str>),
+}
+
+/// Execution count and time of a frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Sample {
+    /// how often the frame was entered
+    pub count: u64,
+    /// time spent in the frame, including its children, in nanoseconds
+    pub total_ns: u64,
+    /// time spent in the frame itself, in nanoseconds
+    pub self_ns: u64,
+}
+
+impl Sample {
+    fn add(&mut self, other: &Self) {
+        self.count += other.count;
+        self.total_ns += other.total_ns;
+        self.self_ns += other.self_ns;
+    }
+}
+
+/// A stack of frames, identified by the frame entered last and the stack it
+/// was entered from
+#[derive(Debug)]
+struct Stack {
+    parent: Option<usize>,
+    frame: Frame,
+    sample: Sample,
+}
+
+/// The stacks entered on a single thread
+#[derive(Debug, Default)]
+struct Profile {
+    stacks: Vec<Stack>,
+    /// index into `stacks` by parent stack and frame
+    index: HashMap<(Option<usize>, Frame), usize>,
+}
+
+impl Profile {
+    /// The stack entering `frame` from `parent` leads to, so every stack is
+    /// only allocated the first time it is entered
+    fn stack(&mut self, parent: Option<usize>, frame: Frame) -> usize {
+        let key = (parent, frame);
+        if let Some(stack) = self.index.get(&key) {
+            return *stack;
+        }
+        let stack = self.stacks.len();
+        self.stacks.push(Stack {
+            parent,
+            frame: key.1.clone(),
+            sample: Sample::default(),
+        });
+        self.index.insert(key, stack);
+        stack
+    }
+
+    /// The frames of a stack, starting with the outermost one
+    fn frames(&self, stack: usize) -> Vec<&Frame> {
+        let mut frames = Vec::new();
+        let mut next = Some(stack);
+        while let Some(stack) = next.and_then(|i| self.stacks.get(i)) {
+            frames.push(&stack.frame);
+            next = stack.parent;
+        }
+        frames.reverse();
+        frames
+    }
+}
+
+fn render(names: &HashMap<arena::Index, String>, frame: &Frame, f: &mut String) -> fmt::Result {
+    match frame {
+        Frame::Expr(span) => {
+            let start = span.start();
+            match names.get(&span.aid()) {
+                Some(name) => write!(f, "{}:{}:{}", name, start.line(), start.column()),
+                None => write!(f, "<{}>:{}:{}", span.aid(), start.line(), start.column()),
+            }
+        }
+        Frame::Operator(name) | Frame::Flow(name) => f.write_str(name),
+    }
+}
+
+/// A frame that is currently being executed on this thread
+struct Open {
+    stack: usize,
+    start: Instant,
+    children_ns: u64,
+}
+
+/// Records the time spent in a frame once dropped
+#[must_use]
+pub struct Guard {
+    _private: (),
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let open = STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let open = stack.pop()?;
+            let total_ns = u64::try_from(open.start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+            if let Some(parent) = stack.last_mut() {
+                parent.children_ns += total_ns;
+            }
+            Some((open, total_ns))
+        });
+        if let Some((open, total_ns)) = open {
+            LOCAL_PROFILE.with(|profile| {
+                let mut profile = profile.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Some(stack) = profile.stacks.get_mut(open.stack) {
+                    stack.sample.add(&Sample {
+                        count: 1,
+                        total_ns,
+                        self_ns: total_ns.saturating_sub(open.children_ns),
+                    });
+                }
+            });
+        }
+    }
+}
+
+/// Turns on profiling
+pub fn enable() {
+    instrumentation::switch_on(&ENABLED);
+}
+
+/// Turns off profiling, collected samples are kept
+pub fn disable() {
+    instrumentation::switch_off(&ENABLED);
+}
+
+/// Returns true if profiling is enabled
+#[inline]
+#[must_use]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Discards all collected samples
+///
+/// # Errors
+/// if a profile lock is poisoned
+pub fn reset() -> Result<()> {
+    // the stacks themselves are kept, frames might still be open on other threads
+    for profile in PROFILES.lock()?.iter() {
+        for stack in &mut profile.lock()?.stacks {
+            stack.sample = Sample::default();
+        }
+    }
+    Ok(())
+}
+
+/// Names the source with the given arena index in rendered frames
+///
+/// # Errors
+/// if the names lock is poisoned
+pub fn register_file(aid: arena::Index, name: &str) -> Result<()> {
+    NAMES.lock()?.insert(aid, name.to_string());
+    Ok(())
+}
+
+/// Enters a frame on the current thread, it is left when the returned guard
+/// is dropped
+pub fn enter(frame: Frame) -> Guard {
+    STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        let parent = stack.last().map(|open| open.stack);
+        let entered = LOCAL_PROFILE.with(|profile| {
+            profile
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .stack(parent, frame)
+        });
+        stack.push(Open {
+            stack: entered,
+            start: Instant::now(),
+            children_ns: 0,
+        });
+    });
+    Guard { _private: () }
+}
+
+/// The collected samples per rendered stack, frames are separated by `;`
+///
+/// # Errors
+/// if a profile lock is poisoned
+pub fn stacks() -> Result<BTreeMap<String, Sample>> {
+    collect(|frames| Some(frames))
+}
+
+/// The collected samples of the flow with the given id per rendered stack,
+/// the flow frame itself is left out
+///
+/// # Errors
+/// if a profile lock is poisoned
+pub fn flow_stacks(flow: &str) -> Result<BTreeMap<String, Sample>> {
+    collect(|frames| match frames.split_first() {
+        Some((Frame::Flow(id), rest)) if &**id == flow && !rest.is_empty() => Some(rest),
+        _ => None,
+    })
+}
+
+/// Renders the samples of the stacks `select` returns frames for, summed up
+/// over all threads
+fn collect<F>(select: F) -> Result<BTreeMap<String, Sample>>
+where
+    F: for<'frames> Fn(&'frames [&'frames Frame]) -> Option<&'frames [&'frames Frame]>,
+{
+    let names = NAMES.lock()?;
+    let mut res: BTreeMap<String, Sample> = BTreeMap::new();
+    for profile in PROFILES.lock()?.iter() {
+        let profile = profile.lock()?;
+        for (i, stack) in profile.stacks.iter().enumerate() {
+            if stack.sample.count == 0 {
+                continue;
+            }
+            let frames = profile.frames(i);
+            if let Some(frames) = select(&frames) {
+                let mut rendered = String::new();
+                for (i, frame) in frames.iter().enumerate() {
+                    if i > 0 {
+                        rendered.push(';');
+                    }
+                    // writing to a string can not fail
+                    let _ = render(&names, frame, &mut rendered);
+                }
+                // different threads and aids may render the same
+                res.entry(rendered).or_default().add(&stack.sample);
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// The collected samples per rendered frame, summed up over all stacks the
+/// frame is the innermost one of
+///
+/// # Errors
+/// if the profile lock is poisoned
+pub fn frames() -> Result<BTreeMap<String, Sample>> {
+    let mut res: BTreeMap<String, Sample> = BTreeMap::new();
+    for (stack, sample) in stacks()? {
+        let frame = stack.rsplit(';').next().unwrap_or_default();
+        res.entry(frame.to_string()).or_default().add(&sample);
+    }
+    Ok(res)
+}
+
+/// Renders the collected samples as folded stacks, one `<stack> <self ns>`
+/// line per stack, as consumed by `flamegraph.pl` or `inferno`
+///
+/// # Errors
+/// if the profile lock is poisoned
+pub fn folded() -> Result<String> {
+    Ok(fold(stacks()?))
+}
+
+/// Renders the collected samples of the flow with the given id as folded
+/// stacks, see [`folded`]
+///
+/// # Errors
+/// if the profile lock is poisoned
+pub fn flow_folded(flow: &str) -> Result<String> {
+    Ok(fold(flow_stacks(flow)?))
+}
+
+fn fold(stacks: BTreeMap<String, Sample>) -> String {
+    let mut out = String::new();
+    for (stack, sample) in stacks {
+        let _ = writeln!(out, "{} {}", stack, sample.self_ns);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::{registry, Script};
+
+    /// keeps profiling enabled for as long as it lives
+    struct Enabled;
+
+    impl Enabled {
+        fn new() -> Self {
+            enable();
+            Self
+        }
+    }
+
+    impl Drop for Enabled {
+        fn drop(&mut self) {
+            disable();
+        }
+    }
+
+    #[test]
+    fn nested_frames() -> Result<()> {
+        {
+            let _outer = enter(Frame::Operator("profile-test/outer".into()));
+            let _inner = enter(Frame::Operator("profile-test/inner".into()));
+        }
+        let stacks = stacks()?;
+        let outer = stacks.get("profile-test/outer").ok_or("no outer stack")?;
+        let inner = stacks
+            .get("profile-test/outer;profile-test/inner")
+            .ok_or("no inner stack")?;
+        assert_eq!(1, outer.count);
+        assert_eq!(1, inner.count);
+        assert!(outer.total_ns >= inner.total_ns);
+        assert_eq!(outer.total_ns - inner.total_ns, outer.self_ns);
+        Ok(())
+    }
+
+    #[test]
+    fn flow_frames() -> Result<()> {
+        {
+            let _flow = enter(Frame::Flow("profile-flow".into()));
+            let _op = enter(Frame::Operator("profile-flow-pipe/op".into()));
+        }
+        {
+            let _flow = enter(Frame::Flow("profile-other-flow".into()));
+            let _op = enter(Frame::Operator("profile-other-pipe/op".into()));
+        }
+        let stacks = flow_stacks("profile-flow")?;
+        assert_eq!(
+            vec!["profile-flow-pipe/op"],
+            stacks.keys().collect::<Vec<_>>()
+        );
+        assert!(flow_folded("profile-flow")?.starts_with("profile-flow-pipe/op "));
+        assert!(super::stacks()?.contains_key("profile-flow;profile-flow-pipe/op"));
+        Ok(())
+    }
+
+    #[test]
+    fn script_exprs() -> Result<()> {
+        let script = Script::parse("event + 1;\nevent + 2", &registry::registry())?;
+        register_file(script.aid, "profile.tremor")?;
+        let _enabled = Enabled::new();
+        for _ in 0..3 {
+            let mut event = Value::from(1);
+            let mut state = Value::null();
+            let mut meta = Value::object();
+            script.run(
+                &EventContext::new(0, None),
+                AggrType::Emit,
+                &mut event,
+                &mut state,
+                &mut meta,
+            )?;
+        }
+        let frames = frames()?;
+        assert_eq!(
+            Some(3),
+            frames.get("profile.tremor:1:1").map(|s| s.count),
+            "{:?}",
+            frames
+        );
+        assert!(folded()?
+            .lines()
+            .any(|l| l.starts_with("profile.tremor:2:1 ")));
+        Ok(())
+    }
+}
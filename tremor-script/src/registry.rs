@@ -299,6 +299,130 @@ impl FunctionError {
     }
 }
 
+/// A set of value types, used for function signatures and type inference
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Types(u8);
+
+impl Types {
+    /// No type at all
+    pub const NONE: Self = Self(0);
+    /// `null`
+    pub const NULL: Self = Self(1);
+    /// booleans
+    pub const BOOL: Self = Self(1 << 1);
+    /// integers
+    pub const INTEGER: Self = Self(1 << 2);
+    /// floats
+    pub const FLOAT: Self = Self(1 << 3);
+    /// strings
+    pub const STRING: Self = Self(1 << 4);
+    /// arrays
+    pub const ARRAY: Self = Self(1 << 5);
+    /// records
+    pub const RECORD: Self = Self(1 << 6);
+    /// binaries
+    pub const BYTES: Self = Self(1 << 7);
+    /// integers and floats
+    pub const NUMBER: Self = Self(Self::INTEGER.0 | Self::FLOAT.0);
+    /// any type
+    pub const ANY: Self = Self(u8::MAX);
+
+    const NAMES: [(Self, &'static str); 8] = [
+        (Self::NULL, "null"),
+        (Self::BOOL, "bool"),
+        (Self::INTEGER, "integer"),
+        (Self::FLOAT, "float"),
+        (Self::STRING, "string"),
+        (Self::ARRAY, "array"),
+        (Self::RECORD, "record"),
+        (Self::BYTES, "binary"),
+    ];
+
+    /// Types that are in either set
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Types that are in both sets
+    #[must_use]
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Tests if every type in this set is also in `other`
+    #[must_use]
+    pub const fn is_subset(self, other: Self) -> bool {
+        self.0 & other.0 == self.0
+    }
+
+    /// Tests if the set is empty
+    #[must_use]
+    pub const fn is_none(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Tests if the set contains every type, i.e. nothing is known about a value
+    #[must_use]
+    pub const fn is_any(self) -> bool {
+        self.0 == u8::MAX
+    }
+}
+
+impl fmt::Display for Types {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_any() {
+            return write!(f, "any");
+        }
+        let names: Vec<_> = Self::NAMES
+            .iter()
+            .filter(|(t, _)| t.is_subset(*self))
+            .map(|(_, n)| *n)
+            .collect();
+        match names.split_last() {
+            None => write!(f, "nothing"),
+            Some((last, [])) => write!(f, "{}", last),
+            Some((last, rest)) => write!(f, "{} or {}", rest.join(", "), last),
+        }
+    }
+}
+
+/// Type signature of a registry function
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Signature {
+    /// Types of the positional arguments
+    pub args: &'static [Types],
+    /// Type of any further arguments for variadic functions
+    pub varargs: Option<Types>,
+    /// Type of the result
+    pub result: Types,
+}
+
+impl Signature {
+    /// Creates a signature for a function with a fixed number of arguments
+    #[must_use]
+    pub const fn new(args: &'static [Types], result: Types) -> Self {
+        Self {
+            args,
+            varargs: None,
+            result,
+        }
+    }
+
+    /// Accepts any number of further arguments of the given type
+    #[must_use]
+    pub const fn with_varargs(mut self, varargs: Types) -> Self {
+        self.varargs = Some(varargs);
+        self
+    }
+
+    /// Expected type of the `n`th argument, if the function takes one
+    #[must_use]
+    pub fn arg(&self, n: usize) -> Option<Types> {
+        self.args.get(n).copied().or(self.varargs)
+    }
+}
+
 /// Wrapper around a function
 pub struct TremorFnWrapper {
     /// Name of the module the function is in
@@ -307,13 +431,39 @@ pub struct TremorFnWrapper {
     name: String,
     /// Boxed dyn of the implementaiton
     fun: Box<dyn TremorFn>,
+    /// Type signature, if one is known
+    signature: Option<Signature>,
 }
 
 impl TremorFnWrapper {
     /// Creates a new wrapper
     #[must_use]
     pub fn new(module: String, name: String, fun: Box<dyn TremorFn>) -> Self {
-        Self { module, name, fun }
+        Self {
+            module,
+            name,
+            fun,
+            signature: None,
+        }
+    }
+
+    /// Attaches a type signature to the function
+    #[must_use]
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Name of the module the function is in
+    #[must_use]
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// Name of the function
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
     }
     /// Invokes the function
     ///
@@ -343,6 +493,12 @@ impl TremorFnWrapper {
     pub fn is_const(&self) -> bool {
         self.fun.is_const()
     }
+
+    /// Returns the functions type signature, if one is known
+    #[must_use]
+    pub fn signature(&self) -> Option<Signature> {
+        self.signature
+    }
 }
 
 impl Clone for TremorFnWrapper {
@@ -351,6 +507,7 @@ impl Clone for TremorFnWrapper {
             module: self.module.clone(),
             name: self.name.clone(),
             fun: self.fun.boxed_clone(),
+            signature: self.signature,
         }
     }
 }
@@ -700,6 +857,11 @@ impl Registry {
     pub fn find_module(&self, module: &str) -> Option<&HashMap<String, TremorFnWrapper>> {
         self.functions.get(module)
     }
+
+    /// Names of all modules in the registry
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.functions.keys().map(String::as_str)
+    }
 }
 
 /// Wrapper around an aggregate function
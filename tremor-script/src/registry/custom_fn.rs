@@ -18,7 +18,7 @@ use crate::prelude::*;
 use crate::Value;
 use crate::{
     ast::{visitors::IsConstFn, Expr, Exprs, FnDefn, ImutExpr, ImutExprs, NodeMeta},
-    NO_AGGRS,
+    debug, NO_AGGRS,
 };
 use beef::Cow;
 //use std::mem;
@@ -133,6 +133,7 @@ impl<'script> CustomFn<'script> {
     where
         'script: 'event,
     {
+        let _call = debug::is_attached().then(debug::Call::enter);
         let args_const = Value::from(
             args.iter()
                 .skip(self.locals)
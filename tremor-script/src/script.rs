@@ -30,6 +30,7 @@ use crate::{
     registry::{Aggr as AggrRegistry, Registry},
     Value,
 };
+use halfbrown::HashMap;
 use serde::Serialize;
 use std::io;
 
@@ -172,4 +173,25 @@ impl Script {
     {
         self.script.run(context, aggr, event, state, meta)
     }
+
+    /// Runs an event through this script, seeding the scripts top level locals
+    /// from `locals` and writing their values back after the run
+    ///
+    /// # Errors
+    /// if the script fails to run for the given context, event state and metadata
+    pub fn run_with_locals<'run, 'event>(
+        &self,
+        context: &'run EventContext,
+        aggr: AggrType,
+        event: &'run mut Value<'event>,
+        state: &'run mut Value<'static>,
+        meta: &'run mut Value<'event>,
+        locals: &'run mut HashMap<String, Value<'event>>,
+    ) -> Result<Return<'event>>
+    where
+        'event: 'run,
+    {
+        self.script
+            .run_with_locals(context, aggr, event, state, meta, locals)
+    }
 }
@@ -118,6 +118,34 @@ impl EventPayload {
         })
     }
 
+    /// Like `try_new`, but hands back the raw data alongside the error
+    /// if the conversion function fails.
+    ///
+    /// The conversion function might have mutated the data in place (e.g. when parsing JSON)
+    /// before it failed, it is handed back as the function left it.
+    ///
+    /// # Errors
+    /// errors if the conversion function fails
+    pub fn try_new_or_raw<E, F>(raw: Vec<u8>, f: F) -> std::result::Result<Self, (E, Vec<u8>)>
+    where
+        F: for<'head> FnOnce(&'head mut [u8]) -> std::result::Result<ValueAndMeta<'head>, E>,
+    {
+        let mut raw = Pin::new(raw);
+        let structured = f(raw.as_mut().get_mut()).map(|data| {
+            // ALLOW: this is sound since we implement a self referential struct, see `try_new`
+            unsafe { mem::transmute::<ValueAndMeta<'_>, ValueAndMeta<'static>>(data) }
+        });
+        let structured = match structured {
+            Ok(structured) => structured,
+            Err(e) => return Err((e, Pin::into_inner(raw))),
+        };
+        let raw = vec![Arc::new(raw)];
+        Ok(Self {
+            raw,
+            data: structured,
+        })
+    }
+
     /// Named after the original rental struct for easy rewriting.
     ///
     /// Borrows the borrowed (liftimed) part of the self referential struct
@@ -378,3 +406,31 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn try_new_or_raw_hands_back_data() {
+        let raw = br#"{"snot": "badger", "badger": }"#.to_vec();
+        let res = EventPayload::try_new_or_raw(raw.clone(), |data| {
+            tremor_value::parse_to_value(data).map(ValueAndMeta::from)
+        });
+        match res {
+            Ok(_) => panic!("invalid json was decoded"),
+            Err((_, data)) => assert_eq!(raw, data),
+        }
+
+        let res =
+            EventPayload::try_new_or_raw::<(), _>(br#"{"snot": "badger"}"#.to_vec(), |data| {
+                tremor_value::parse_to_value(data)
+                    .map(ValueAndMeta::from)
+                    .map_err(|_| ())
+            });
+        match res {
+            Ok(event) => assert_eq!(Some("badger"), event.suffix().value().get_str("snot")),
+            Err(_) => panic!("valid json was not decoded"),
+        }
+    }
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use crate::prelude::*;
-use crate::registry::Registry;
+use crate::registry::{Registry, Signature, Types};
 use crate::tremor_const_fn;
 use crate::Value;
 
@@ -23,13 +23,13 @@ pub fn load(registry: &mut Registry) {
             let mut output = _input.clone();
             output.sort();
             Ok(Value::from(output))
-        }))
+        }).with_signature(Signature::new(&[Types::ARRAY], Types::ARRAY)))
         .insert(tremor_const_fn! (array|len(_context, _input: Array) {
             Ok(Value::from(_input.len() as i64))
-        }))
+        }).with_signature(Signature::new(&[Types::ARRAY], Types::INTEGER)))
         .insert(tremor_const_fn! (array|is_empty(_context, _input: Array) {
             Ok(Value::from(_input.is_empty()))
-        }))
+        }).with_signature(Signature::new(&[Types::ARRAY], Types::BOOL)))
         .insert(tremor_const_fn! (array|contains(_context, _input, _contains) {
             _input.as_array().map_or_else(
                 ||Err(FunctionError::BadType{mfa: mfa("array", "contains", 2)}),
@@ -37,7 +37,7 @@ pub fn load(registry: &mut Registry) {
                     Ok(Value::from(input.contains(_contains)))
                 }
             )
-        }))
+        }).with_signature(Signature::new(&[Types::ARRAY, Types::ANY], Types::BOOL)))
         .insert(tremor_const_fn! (array|push(_context, _input, _value) {
             _input.as_array().map_or_else(
                 ||Err(FunctionError::BadType{mfa: mfa("array", "push", 2)}),
@@ -50,7 +50,7 @@ pub fn load(registry: &mut Registry) {
                     Ok(Value::from(output))
                 }
             )
-        }))
+        }).with_signature(Signature::new(&[Types::ARRAY, Types::ANY], Types::ARRAY)))
         .insert(tremor_const_fn! (array|unzip(_context, _input: Array) {
                 let r: FResult<Vec<(Value, Value)>> = _input.iter().map(|a| if let Some(a) = a.as_array() {
                     if let [ first, second] = a.as_slice() {
@@ -63,7 +63,7 @@ pub fn load(registry: &mut Registry) {
                 }).collect();
                 let (l, r): (Vec<_>, Vec<_>) = r?.into_iter().unzip();
                 Ok(Value::from(vec![l, r]))
-        }))
+        }).with_signature(Signature::new(&[Types::ARRAY], Types::ARRAY)))
         .insert(tremor_const_fn!(array|zip(_context, _left: Array, _right: Array) {
             if _left.len() != _right.len() {
                 return Err(FunctionError::RuntimeError{mfa: this_mfa(), error: format!("Zipping two arrays requires them to have the same length, but the first array provided has {} elements while the second one has {} elements", _left.len(), _right.len())});
@@ -73,16 +73,16 @@ pub fn load(registry: &mut Registry) {
                 .zip(_right.iter())
                 .map(|(l, r)| Value::from(vec![l.clone(), r.clone()]))
                 .collect::<Vec<_>>()))
-        }))
+        }).with_signature(Signature::new(&[Types::ARRAY, Types::ARRAY], Types::ARRAY)))
         .insert(
             tremor_const_fn!(array|flatten(_context, _input) {
                 Ok(Value::from(flatten_value(_input)))
-            }))
+            }).with_signature(Signature::new(&[Types::ARRAY], Types::ARRAY)))
         .insert(
             tremor_const_fn!(array|join(_context, _input: Array, _sep: String) {
                 let input: Vec<String> = _input.iter().map(ToString::to_string).collect();
                 Ok(Value::from(input.join(_sep)))
-            }),
+            }).with_signature(Signature::new(&[Types::ARRAY, Types::STRING], Types::STRING)),
         )
         .insert(tremor_const_fn!(array|coalesce(_context, _input: Array) {
             Ok(Value::from(_input.iter().filter_map(|v| if v.is_null()  {
@@ -90,11 +90,11 @@ pub fn load(registry: &mut Registry) {
             }else {
                 Some(v.clone())
             }).collect::<Vec<_>>()))
-        }))
+        }).with_signature(Signature::new(&[Types::ARRAY], Types::ARRAY)))
         .insert(tremor_const_fn!(array|concatenate(_context, _left: Array, _right: Array) {
             let output: Vec<Value> = [_left.as_slice(), _right.as_slice()].concat();
             Ok(Value::from(output))
-        }));
+        }).with_signature(Signature::new(&[Types::ARRAY, Types::ARRAY], Types::ARRAY)));
 }
 
 //TODO this is not very nice
@@ -12,17 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::registry::Registry;
+use crate::registry::{Registry, Signature, Types};
 use crate::{tremor_const_fn, tremor_fn_};
 
 pub fn load(registry: &mut Registry) {
     registry
         .insert(tremor_const_fn! (base64|encode(_context, _input: Bytes) {
             Ok(Value::from(base64::encode(&_input)))
-        }))
+        }).with_signature(Signature::new(&[Types::BYTES], Types::STRING)))
         .insert(tremor_const_fn! (base64|decode(_context, _input: String) {
             base64::decode(_input.as_bytes()).map(|v| Value::Bytes(v.into())).map_err(to_runtime_error)
-        }));
+        }).with_signature(Signature::new(&[Types::STRING], Types::BYTES)));
 }
 
 #[cfg(test)]
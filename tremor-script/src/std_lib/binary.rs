@@ -13,18 +13,18 @@
 // limitations under the License.
 
 use crate::prelude::*;
-use crate::registry::Registry;
+use crate::registry::{Registry, Signature, Types};
 use crate::{tremor_const_fn, tremor_fn_};
 
 pub fn load(registry: &mut Registry) {
     registry
         .insert(tremor_const_fn! (binary|len(_context, _input: Bytes) {
             Ok(Value::from(_input.len()))
-        }))
+        }).with_signature(Signature::new(&[Types::BYTES], Types::INTEGER)))
         .insert(
             tremor_const_fn! (binary|from_bytes(_context, _input: Array) {
                 _input.iter().map(|v| v.as_u8().ok_or_else(||to_runtime_error("array contains non bytes"))).collect::<FResult<Vec<u8>>>().map(beef::Cow::from).map(Value::Bytes)
-            }),
+            }).with_signature(Signature::new(&[Types::ARRAY], Types::BYTES)),
         ).insert(
             tremor_const_fn! (binary|into_bytes(_context, input) {
                 if let Value::Bytes(input) = input {
@@ -32,7 +32,7 @@ pub fn load(registry: &mut Registry) {
                 } else {
                     Err(to_runtime_error("cannot convert a non-binary into an array of bytes"))
                 }
-            }),
+            }).with_signature(Signature::new(&[Types::BYTES], Types::ARRAY)),
         );
 }
 
@@ -12,13 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::registry::Registry;
+use crate::registry::{Registry, Signature, Types};
 use crate::tremor_const_fn;
 
 pub fn load(registry: &mut Registry) {
-    registry.insert(tremor_const_fn! (float|parse(_context, _input: String) {
-        _input.parse::<f64>().map_err(to_runtime_error).map(Value::from)
-    }));
+    registry.insert(
+        tremor_const_fn! (float|parse(_context, _input: String) {
+            _input.parse::<f64>().map_err(to_runtime_error).map(Value::from)
+        })
+        .with_signature(Signature::new(&[Types::STRING], Types::FLOAT)),
+    );
 }
 
 #[cfg(test)]
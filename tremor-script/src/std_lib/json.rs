@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::registry::Registry;
+use crate::registry::{Registry, Signature, Types};
 use crate::tremor_const_fn;
 use tremor_value::parse_to_value;
 
@@ -26,13 +26,13 @@ pub fn load(registry: &mut Registry) {
             let mut bytes = s.into_bytes();
             // We need to do this since otherwise we depend on the clone of s
             parse_to_value(bytes.as_mut_slice()).map_err(to_runtime_error).map(Value::into_static)
-        }))
+        }).with_signature(Signature::new(&[Types::STRING], Types::ANY)))
         .insert(tremor_const_fn! (json|encode(_context, _input) {
             simd_json::to_string(_input).map(Value::from).map_err(to_runtime_error)
-        }))
+        }).with_signature(Signature::new(&[Types::ANY], Types::STRING)))
         .insert(tremor_const_fn! (json|encode_pretty(_context, _input) {
             simd_json::to_string_pretty(_input).map(Value::from).map_err(to_runtime_error)
-        }));
+        }).with_signature(Signature::new(&[Types::ANY], Types::STRING)));
 }
 
 #[cfg(test)]
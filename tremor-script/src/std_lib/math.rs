@@ -14,7 +14,7 @@
 #![allow(clippy::cast_precision_loss)]
 
 use crate::prelude::*;
-use crate::registry::Registry;
+use crate::registry::{Registry, Signature, Types};
 use crate::tremor_const_fn;
 use std::cmp::{max, min};
 
@@ -36,7 +36,7 @@ macro_rules! math_fn {
             } else{
                 Err(FunctionError::BadType{mfa: this_mfa()})
             }
-        })
+        }).with_signature(Signature::new(&[Types::NUMBER], Types::INTEGER))
     };
 }
 // ALLOW: Until we have u64 support in clippy
@@ -47,36 +47,48 @@ pub fn load(registry: &mut Registry) {
         .insert(math_fn!(ceil))
         .insert(math_fn!(round))
         .insert(math_fn!(trunc))
-        .insert(tremor_const_fn! (math|max(_context, a, b) {
-            if let (Some(a), Some(b)) = (a.as_u64(), b.as_u64()) {
-                Ok(Value::from(max(a, b)))
-            } else if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
-                Ok(Value::from(max(a, b)))
-            } else if let (Some(a), Some(b)) = (a.cast_f64(), b.cast_f64()) {
-                if a >= b {
-                    Ok(Value::from(a))
+        .insert(
+            tremor_const_fn! (math|max(_context, a, b) {
+                if let (Some(a), Some(b)) = (a.as_u64(), b.as_u64()) {
+                    Ok(Value::from(max(a, b)))
+                } else if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+                    Ok(Value::from(max(a, b)))
+                } else if let (Some(a), Some(b)) = (a.cast_f64(), b.cast_f64()) {
+                    if a >= b {
+                        Ok(Value::from(a))
+                    } else {
+                        Ok(Value::from(b))
+                    }
                 } else {
-                    Ok(Value::from(b))
+                    Err(FunctionError::BadType{mfa: this_mfa()})
                 }
-            } else {
-                Err(FunctionError::BadType{mfa: this_mfa()})
-            }
-        }))
-        .insert(tremor_const_fn! (math|min(_context, a, b) {
-            if let (Some(a), Some(b)) = (a.as_u64(), b.as_u64()) {
-                Ok(Value::from(min(a, b)))
-            } else if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
-                Ok(Value::from(min(a, b)))
-            } else if let (Some(a), Some(b)) = (a.cast_f64(), b.cast_f64()) {
-                if a <= b {
-                    Ok(Value::from(a))
+            })
+            .with_signature(Signature::new(
+                &[Types::NUMBER, Types::NUMBER],
+                Types::NUMBER,
+            )),
+        )
+        .insert(
+            tremor_const_fn! (math|min(_context, a, b) {
+                if let (Some(a), Some(b)) = (a.as_u64(), b.as_u64()) {
+                    Ok(Value::from(min(a, b)))
+                } else if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+                    Ok(Value::from(min(a, b)))
+                } else if let (Some(a), Some(b)) = (a.cast_f64(), b.cast_f64()) {
+                    if a <= b {
+                        Ok(Value::from(a))
+                    } else {
+                        Ok(Value::from(b))
+                    }
                 } else {
-                    Ok(Value::from(b))
+                    Err(FunctionError::BadType{mfa: this_mfa()})
                 }
-            } else {
-                Err(FunctionError::BadType{mfa: this_mfa()})
-            }
-        }));
+            })
+            .with_signature(Signature::new(
+                &[Types::NUMBER, Types::NUMBER],
+                Types::NUMBER,
+            )),
+        );
 }
 
 #[cfg(test)]
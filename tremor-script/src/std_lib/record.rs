@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use crate::prelude::*;
-use crate::registry::Registry;
+use crate::registry::{Registry, Signature, Types};
 use crate::tremor_const_fn;
 use crate::Object;
 
@@ -21,29 +21,29 @@ pub fn load(registry: &mut Registry) {
     registry
         .insert(tremor_const_fn! (record|len(_context, _input: Object) {
             Ok(Value::from(_input.len() as i64))
-        }))
+        }).with_signature(Signature::new(&[Types::RECORD], Types::INTEGER)))
         .insert(tremor_const_fn! (record|is_empty(_context, _input: Object) {
             Ok(Value::from(_input.is_empty()))
-        }))
+        }).with_signature(Signature::new(&[Types::RECORD], Types::BOOL)))
         .insert(
             tremor_const_fn! (record|contains(_context, _input: Object, _contains: String) {
                 Ok(Value::from(_input.get(_contains).is_some()))
-            }),
+            }).with_signature(Signature::new(&[Types::RECORD, Types::STRING], Types::BOOL)),
         )
         .insert(tremor_const_fn! (record|keys(_context, _input: Object) {
             Ok(Value::from(_input.keys().map(|k| Value::from(k.to_string())).collect::<Vec<_>>()))
-        }))
+        }).with_signature(Signature::new(&[Types::RECORD], Types::ARRAY)))
         .insert(tremor_const_fn! (record|values(_context, _input: Object) {
             Ok(Value::from(_input.values().cloned().map(Value::from).collect::<Vec<_>>()))
 
-        }))
+        }).with_signature(Signature::new(&[Types::RECORD], Types::ARRAY)))
         .insert(tremor_const_fn! (record|to_array(_context, _input: Object) {
             Ok(Value::from(
                 _input.iter()
                     .map(|(k, v)| Value::from(vec![Value::from(k.clone()), v.clone()]))
                     .collect::<Vec<_>>(),
             ))
-        }))
+        }).with_signature(Signature::new(&[Types::RECORD], Types::ARRAY)))
         .insert(tremor_const_fn! (record|from_array(_context, _input: Array) {
         let r: FResult<Object> = _input.iter().map(|a| match a {
             Value::Array(a) => if a.len() == 2 {
@@ -61,7 +61,7 @@ pub fn load(registry: &mut Registry) {
             other => Err(to_runtime_error(format!("Onlay arrays that consist of tuples (arrays of two elements) can be turned into records but this array contained: {:?}", other)))
         }).collect();
         Ok(Value::from(r?))
-        })).insert(tremor_const_fn!(record|extract(_context, _input: Object, _keys: Array) {
+        }).with_signature(Signature::new(&[Types::ARRAY], Types::RECORD))).insert(tremor_const_fn!(record|extract(_context, _input: Object, _keys: Array) {
         let keys: Vec<_> = _keys.iter().filter_map(ValueAccess::as_str).collect();
         let r: Object =_input.iter().filter_map(|(k, v)| {
             let k: &str = k;
@@ -72,16 +72,16 @@ pub fn load(registry: &mut Registry) {
             }
         }).collect();
         Ok(Value::from(r))
-    }))
+    }).with_signature(Signature::new(&[Types::RECORD, Types::ARRAY], Types::RECORD)))
         .insert(tremor_const_fn!(record|combine(_context, _left: Object, _right: Object) {
         Ok(Value::from(_left.iter().chain(_right.iter()).map(|(k, v)| (k.clone(), v.clone())).collect::<Object>()))
-        })).insert(tremor_const_fn!(record|rename(_context, _target: Object, _renameings: Object) {
+        }).with_signature(Signature::new(&[Types::RECORD, Types::RECORD], Types::RECORD))).insert(tremor_const_fn!(record|rename(_context, _target: Object, _renameings: Object) {
             Ok(Value::from(_target.iter().map(|(k, v)| if let Some(Value::String(k1)) = _renameings.get(k) {
                 (k1.clone(), v.clone())
             } else {
                 (k.clone(), v.clone())
             }).collect::<Object>()))
-        }));
+        }).with_signature(Signature::new(&[Types::RECORD, Types::RECORD], Types::RECORD)));
 }
 
 #[cfg(test)]
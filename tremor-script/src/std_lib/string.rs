@@ -13,7 +13,9 @@
 // limitations under the License.
 
 use crate::prelude::*;
-use crate::registry::{mfa, FResult, FunctionError, Registry, TremorFn, TremorFnWrapper};
+use crate::registry::{
+    mfa, FResult, FunctionError, Registry, Signature, TremorFn, TremorFnWrapper, Types,
+};
 use crate::EventContext;
 use crate::Value;
 use crate::{tremor_const_fn, tremor_fn_};
@@ -118,33 +120,33 @@ pub fn load(registry: &mut Registry) {
                 let from: &str = _from;
                 let to: &str = _to;
                 Ok(Value::from(_input.replace(from, to)))
-            }),
+            }).with_signature(Signature::new(&[Types::STRING, Types::STRING, Types::STRING], Types::STRING)),
         )
-        .insert(map_function!(is_empty))
+        .insert(map_function!(is_empty).with_signature(Signature::new(&[Types::STRING], Types::BOOL)))
         .insert(tremor_const_fn! (string|len(_context, _input: String) {
             Ok(Value::from(_input.chars().count() as i64))
-        }))
+        }).with_signature(Signature::new(&[Types::STRING], Types::INTEGER)))
         .insert(tremor_const_fn! (string|bytes(_context, _input: String) {
             Ok(Value::from(_input.len() as i64))
-        }))
+        }).with_signature(Signature::new(&[Types::STRING], Types::INTEGER)))
         .insert(tremor_const_fn! (string|trim(_context, _input: String) {
             Ok(Value::from(_input.trim().to_string()))
-        }))
+        }).with_signature(Signature::new(&[Types::STRING], Types::STRING)))
         .insert(tremor_const_fn! (string|trim_start(_context, _input: String) {
             Ok(Value::from(_input.trim_start().to_string()))
-        }))
+        }).with_signature(Signature::new(&[Types::STRING], Types::STRING)))
         .insert(tremor_const_fn! (string|trim_end(_context, _input: String) {
             Ok(Value::from(_input.trim_end().to_string()))
-        }))
-        .insert(map_function!(lowercase, to_lowercase))
-        .insert(map_function!(uppercase, to_uppercase))
+        }).with_signature(Signature::new(&[Types::STRING], Types::STRING)))
+        .insert(map_function!(lowercase, to_lowercase).with_signature(Signature::new(&[Types::STRING], Types::STRING)))
+        .insert(map_function!(uppercase, to_uppercase).with_signature(Signature::new(&[Types::STRING], Types::STRING)))
         .insert(tremor_const_fn!(string|capitalize(_context, _input: String) {
             let mut c = _input.chars();
             Ok(match c.next() {
                 None => Value::from(""),
                 Some(f) => Value::from(f.to_uppercase().collect::<String>() + c.as_str()),
             })
-        }))
+        }).with_signature(Signature::new(&[Types::STRING], Types::STRING)))
         .insert(tremor_const_fn!(string|substr(_context, _input, _start, _end) {
                 let ((input, start), end) = _input.as_str().zip(_start.as_usize()).zip(_end.as_usize()).ok_or_else(||FunctionError::BadType{mfa: this_mfa()})?;
                 // Since rust doesn't handle UTF8 indexes we have to translate this
@@ -155,29 +157,29 @@ pub fn load(registry: &mut Registry) {
                 let start = input.char_indices().nth(start).map_or_else(|| 0, |v| v.0);
                 let end = input.char_indices().nth(end).map_or_else(|| input.len(), |v| v.0);
                 Ok(Value::from(input.get(start..end).unwrap_or(input).to_string()))
-            }),
+            }).with_signature(Signature::new(&[Types::STRING, Types::INTEGER, Types::INTEGER], Types::STRING)),
         )
         .insert(tremor_const_fn! (string|split(_context, _input: String, _sep: String) {
                 let sep: &str = _sep;
                 Ok(Value::from(_input.split(sep).map(|v| Value::from(v.to_string())).collect::<Vec<_>>()))
-            }),
+            }).with_signature(Signature::new(&[Types::STRING, Types::STRING], Types::ARRAY)),
         )
         .insert(tremor_const_fn! (string|from_utf8_lossy(_context, _bytes: Bytes) {
                 Ok(Value::from(String::from_utf8_lossy(_bytes).to_string()))
-            }),
+            }).with_signature(Signature::new(&[Types::BYTES], Types::STRING)),
         ).insert(tremor_const_fn! (string|contains(_context, _input: String, _contains: String) {
                 use std::borrow::Borrow;
                 let s: &str = _contains.borrow();
                 Ok(Value::from(_input.contains(s)))
-            }),
+            }).with_signature(Signature::new(&[Types::STRING, Types::STRING], Types::BOOL)),
         ).insert(tremor_const_fn! (string|into_binary(_context, _input: String) {
                 Ok(Value::Bytes(_input.as_bytes().to_vec().into()))
-            }),
+            }).with_signature(Signature::new(&[Types::STRING], Types::BYTES)),
         ).insert(TremorFnWrapper::new(
             "string".to_string(),
             "format".to_string(),
             Box::new(StringFormat::default()),
-        ));
+        ).with_signature(Signature::new(&[Types::STRING], Types::STRING).with_varargs(Types::ANY)));
 }
 
 #[cfg(test)]
@@ -13,19 +13,19 @@
 // limitations under the License.
 
 use crate::prelude::*;
-use crate::registry::Registry;
+use crate::registry::{Registry, Signature, Types};
 use crate::{tremor_const_fn, tremor_fn_};
 
 macro_rules! map_function {
     ($name:ident, $fun:ident) => {
         tremor_const_fn! (type|$name(_context, _input) {
             Ok(Value::from(_input.$fun()))
-        })
+        }).with_signature(Signature::new(&[Types::ANY], Types::BOOL))
     };
         ($fun:ident) => {
             tremor_const_fn!(type|$fun(_context, _input) {
                 Ok(Value::from(_input.$fun()))
-            })
+            }).with_signature(Signature::new(&[Types::ANY], Types::BOOL))
         }
     }
 
@@ -38,30 +38,39 @@ pub fn load(registry: &mut Registry) {
         .insert(map_function!(is_string, is_str))
         .insert(map_function!(is_array))
         .insert(map_function!(is_record, is_object))
-        .insert(tremor_const_fn! (type|as_string(_context, _input) {
-            Ok(match _input.value_type() {
-                ValueType::Null => Value::from("null"),
-                ValueType::Bool => Value::from("bool"),
-                ValueType::U64 | ValueType::I64 => Value::from("integer"),
-                ValueType::F64 => Value::from("float"),
-                ValueType::String => Value::from("string"),
-                ValueType::Array => Value::from("array"),
-                ValueType::Object => Value::from("record"),
-                ValueType::Custom(c) => Value::from(c),
+        .insert(
+            tremor_const_fn! (type|as_string(_context, _input) {
+                Ok(match _input.value_type() {
+                    ValueType::Null => Value::from("null"),
+                    ValueType::Bool => Value::from("bool"),
+                    ValueType::U64 | ValueType::I64 => Value::from("integer"),
+                    ValueType::F64 => Value::from("float"),
+                    ValueType::String => Value::from("string"),
+                    ValueType::Array => Value::from("array"),
+                    ValueType::Object => Value::from("record"),
+                    ValueType::Custom(c) => Value::from(c),
+                })
             })
-        }))
-        .insert(tremor_const_fn! (type|is_number(_context, _input) {
-            Ok(match _input.value_type() {
-                ValueType::I64 | ValueType::F64 | ValueType::U64 => Value::from(true),
-                _ => Value::from(false),
+            .with_signature(Signature::new(&[Types::ANY], Types::STRING)),
+        )
+        .insert(
+            tremor_const_fn! (type|is_number(_context, _input) {
+                Ok(match _input.value_type() {
+                    ValueType::I64 | ValueType::F64 | ValueType::U64 => Value::from(true),
+                    _ => Value::from(false),
+                })
             })
-        }))
-        .insert(tremor_const_fn! (type|is_binary(_context, _input) {
-            Ok(match _input.value_type() {
-                ValueType::Custom("bytes") => Value::from(true),
-                _ => Value::from(false),
+            .with_signature(Signature::new(&[Types::ANY], Types::BOOL)),
+        )
+        .insert(
+            tremor_const_fn! (type|is_binary(_context, _input) {
+                Ok(match _input.value_type() {
+                    ValueType::Custom("bytes") => Value::from(true),
+                    _ => Value::from(false),
+                })
             })
-        }));
+            .with_signature(Signature::new(&[Types::ANY], Types::BOOL)),
+        );
 }
 
 #[cfg(test)]